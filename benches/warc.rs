@@ -0,0 +1,117 @@
+use std::io::{self, BufReader};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libflate::gzip::MultiDecoder;
+
+use warc::bench::{Corpus, Framing};
+use warc::parser;
+use warc::{MemberCompression, WarcReader, WarcWriter};
+
+/// Corpora of about 16 MiB each, from many small records to few large ones.
+fn corpora() -> Vec<(&'static str, Corpus)> {
+    vec![
+        ("small", Corpus::new().records(16_384).body_len(512, 1536)),
+        ("medium", Corpus::new().records(1024).body_len(8192, 24_576)),
+        (
+            "large",
+            Corpus::new().records(16).body_len(1 << 20, 1 << 20),
+        ),
+    ]
+}
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for (name, corpus) in corpora() {
+        let data = corpus.generate();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("iter_records", name), &data, |b, data| {
+            b.iter(|| WarcReader::new(&data[..]).iter_records().count())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("stream_records", name),
+            &data,
+            |b, data| {
+                b.iter(|| {
+                    let mut reader = WarcReader::new(&data[..]);
+                    let mut records = reader.stream_records();
+                    let mut count = 0;
+                    while let Some(record) = records.next_item() {
+                        let (_, mut body) = record.unwrap().into_parts();
+                        io::copy(&mut body, &mut io::sink()).unwrap();
+                        count += 1;
+                    }
+                    count
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("count_records", name), &data, |b, data| {
+            b.iter(|| WarcReader::new(&data[..]).count_records().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn read_gzip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_gzip");
+    for (name, corpus) in corpora() {
+        for &(framing_name, framing) in &[
+            ("per_record", Framing::GzipPerRecord),
+            ("whole", Framing::GzipWhole),
+        ] {
+            let corpus = corpus.clone().framing(framing);
+            let data = corpus.generate();
+            group.throughput(Throughput::Bytes(data.len() as u64));
+            group.bench_with_input(BenchmarkId::new(framing_name, name), &data, |b, data| {
+                b.iter(|| {
+                    let decoder = MultiDecoder::new(&data[..]).unwrap();
+                    WarcReader::new(BufReader::new(decoder))
+                        .iter_records()
+                        .count()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    for (name, corpus) in corpora() {
+        for &(framing_name, framing) in &[
+            ("plain", Framing::Plain),
+            ("gzip_per_record", Framing::GzipPerRecord),
+        ] {
+            let corpus = corpus.clone().framing(framing);
+            let records: Vec<_> = corpus.iter_records().collect();
+            group.throughput(Throughput::Bytes(
+                records.iter().map(|r| r.content_length()).sum(),
+            ));
+            group.bench_with_input(
+                BenchmarkId::new(framing_name, name),
+                &records,
+                |b, records| {
+                    b.iter(|| {
+                        let mut writer = WarcWriter::new(io::sink());
+                        if framing == Framing::GzipPerRecord {
+                            writer.set_member_compression(|_, _| MemberCompression::Compressed);
+                        }
+                        for record in records {
+                            writer.write(record).unwrap();
+                        }
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn parse_headers(c: &mut Criterion) {
+    let data = Corpus::new().records(1).body_len(0, 0).generate();
+    c.bench_function("parse_header_block", |b| {
+        b.iter(|| parser::parse_header_block(&data[..]).unwrap().1 .1.len())
+    });
+}
+
+criterion_group!(benches, read, read_gzip, write, parse_headers);
+criterion_main!(benches);
@@ -0,0 +1,427 @@
+//! Reading of ARC files, the format written by Internet Archive crawls before WARC, and their
+//! conversion to WARC records.
+//!
+//! An ARC file starts with a `filedesc://` record describing the file, followed by a record for
+//! each capture. A record is a single header line of fields separated by spaces, a body of the
+//! length given by the last field, and a newline. Version 1 header lines have five fields:
+//!
+//! ```text
+//! URL IP-address Archive-date Content-type Archive-length
+//! ```
+//!
+//! Version 2 header lines add the HTTP result code, a checksum, a redirect location, an offset
+//! and a filename before the length. These are read past, as the body holds all of them but the
+//! offset and filename.
+//!
+//! The `filedesc://` record is converted to a `warcinfo` record, whose body keeps the file's
+//! version block and any metadata which follows it. Every other ARC record is converted to a
+//! `response` record if it holds an HTTP response, and to a `resource` record otherwise:
+//!
+//! ```
+//! use warc::arc::ArcReader;
+//! use warc::RecordType;
+//!
+//! let input = b"filedesc://example.arc 0.0.0.0 20050614070159 text/plain 77\n\
+//!     1 0 InternetArchive\n\
+//!     URL IP-address Archive-date Content-type Archive-length\n\n\
+//!     http://example.com/ 93.184.216.34 20050614070200 text/html 19\n\
+//!     HTTP/1.0 200 OK\r\n\r\n\n";
+//! let records: Vec<_> = ArcReader::new(&input[..])
+//!     .into_warc_records()
+//!     .map(|record| record.unwrap())
+//!     .collect();
+//! assert_eq!(records[0].warc_type(), &RecordType::WarcInfo);
+//! assert_eq!(records[1].warc_type(), &RecordType::Response);
+//! ```
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+
+use crate::header::WarcHeader;
+use crate::recompress::Codec;
+use crate::{BufferedBody, Error, Record, RecordBuilder, RecordType, WarcWriter};
+
+/// A record read from an ARC file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArcRecord {
+    pub url: String,
+    /// The address of the server the capture was made from, or `0.0.0.0` if there was none.
+    pub ip_address: String,
+    pub date: DateTime<Utc>,
+    /// The media type of the capture as given by the server, or `no-type` if none was given.
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl ArcRecord {
+    /// Return true if this is the `filedesc://` record describing the file.
+    pub fn is_filedesc(&self) -> bool {
+        self.url.starts_with("filedesc:")
+    }
+
+    /// Return true if this record holds an HTTP response.
+    pub fn is_http_response(&self) -> bool {
+        (self.url.starts_with("http:") || self.url.starts_with("https:"))
+            && self.body.starts_with(b"HTTP/")
+    }
+
+    /// Convert this record to an equivalent WARC/1.0 record.
+    ///
+    /// The `filedesc://` record becomes a `warcinfo` record with a WARC-Filename of the name in
+    /// its URL and a Content-Type of `text/plain`. An HTTP response becomes a `response` record
+    /// with a Content-Type of `application/http;msgtype=response`. Any other record becomes a
+    /// `resource` record with the content type of the capture, or `application/octet-stream` if
+    /// it has none.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the URL is not a well-formed WARC-Target-URI.
+    pub fn to_warc(&self) -> Result<Record<BufferedBody>, Error> {
+        if self.is_filedesc() {
+            let filename = self.url["filedesc:".len()..].trim_start_matches('/');
+            return RecordBuilder::default()
+                .version("1.0".to_owned())
+                .warc_type(RecordType::WarcInfo)
+                .date(self.date)
+                .header(WarcHeader::Filename, filename)
+                .header(WarcHeader::ContentType, "text/plain")
+                .body(self.body.clone())
+                .build();
+        }
+
+        let (warc_type, content_type) = if self.is_http_response() {
+            (RecordType::Response, "application/http;msgtype=response")
+        } else {
+            let content_type = match self.content_type.as_str() {
+                "" | "-" | "no-type" | "unk" => "application/octet-stream",
+                content_type => content_type,
+            };
+            (RecordType::Resource, content_type)
+        };
+
+        let mut builder = RecordBuilder::default()
+            .version("1.0".to_owned())
+            .warc_type(warc_type)
+            .date(self.date)
+            .header(WarcHeader::TargetURI, self.url.as_str())
+            .header(WarcHeader::ContentType, content_type);
+        if !matches!(self.ip_address.as_str(), "" | "-" | "0.0.0.0") {
+            builder = builder.header(WarcHeader::IPAddress, self.ip_address.as_str());
+        }
+
+        builder.body(self.body.clone()).build()
+    }
+}
+
+/// A reader which iteratively parses records from an uncompressed ARC file.
+pub struct ArcReader<R> {
+    reader: R,
+    fields: usize,
+}
+
+impl<R: BufRead> ArcReader<R> {
+    /// Create a new reader.
+    pub fn new(reader: R) -> Self {
+        ArcReader { reader, fields: 5 }
+    }
+
+    /// Return an iterator over the records of the file converted to WARC records, as by
+    /// `ArcRecord::to_warc`.
+    pub fn into_warc_records(self) -> impl Iterator<Item = Result<Record<BufferedBody>, Error>> {
+        self.map(|record| record.and_then(|record| record.to_warc()))
+    }
+
+    /// Read the header line of the next record, skipping the blank lines between records.
+    ///
+    /// `None` is returned at the end of the input.
+    fn read_header_line(&mut self) -> Result<Option<String>, Error> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let len = self
+                .reader
+                .read_until(b'\n', &mut line)
+                .map_err(|_| Error::ReadData)?;
+            if len == 0 {
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if !line.trim().is_empty() {
+                return Ok(Some(line.to_owned()));
+            }
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<ArcRecord>, Error> {
+        let line = match self.read_header_line()? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        // the URL is taken as everything before the other fields, as some contain spaces
+        let mut fields: Vec<&str> = line.rsplitn(self.fields, ' ').collect();
+        if fields.len() != self.fields {
+            return Err(Error::ParseHeaders);
+        }
+        fields.reverse();
+        let length: u64 = fields[self.fields - 1]
+            .parse()
+            .map_err(|_| Error::MalformedHeader(WarcHeader::ContentLength, line.clone()))?;
+        let date = parse_date(fields[2])
+            .ok_or_else(|| Error::MalformedHeader(WarcHeader::Date, fields[2].to_owned()))?;
+
+        let mut body = Vec::new();
+        (&mut self.reader)
+            .take(length)
+            .read_to_end(&mut body)
+            .map_err(|_| Error::ReadData)?;
+        if (body.len() as u64) < length {
+            return Err(Error::UnexpectedEOB);
+        }
+
+        let record = ArcRecord {
+            url: fields[0].to_owned(),
+            ip_address: fields[1].to_owned(),
+            date,
+            content_type: fields[3].to_owned(),
+            body,
+        };
+        if record.is_filedesc() && record.body.starts_with(b"2 ") {
+            self.fields = 10;
+        }
+
+        Ok(Some(record))
+    }
+}
+
+impl<'r> ArcReader<Box<dyn BufRead + 'r>> {
+    /// Create a new reader which decompresses its input if it is compressed, as `.arc.gz` files
+    /// are.
+    pub fn detect<R: BufRead + 'r>(mut reader: R) -> io::Result<Self> {
+        let codec = Codec::detect(reader.fill_buf()?);
+        Ok(ArcReader::new(codec.decoder(reader)?))
+    }
+
+    /// Create a new reader which reads from a file, compressed or not, as for `detect`.
+    pub fn from_path_detect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        ArcReader::detect(BufReader::new(file))
+    }
+}
+
+impl<R: BufRead> Iterator for ArcReader<R> {
+    type Item = Result<ArcRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Convert every record of an ARC file to a WARC record, and write it.
+///
+/// Returns the number of records written. Conversion stops at the first record which cannot be
+/// read or converted.
+pub fn convert<R: BufRead, W: Write>(
+    reader: ArcReader<R>,
+    writer: &mut WarcWriter<W>,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    for record in reader.into_warc_records() {
+        writer.write(&record?).map_err(|_| Error::WriteData)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Parse an ARC date, given to the second as `YYYYMMDDhhmmss`, or to the day or minute by some
+/// early crawls.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    if !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let padded = match date.len() {
+        8 => format!("{}000000", date),
+        12 => format!("{}00", date),
+        14 => date.to_owned(),
+        _ => return None,
+    };
+
+    NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{convert, ArcReader};
+    use crate::header::WarcHeader;
+    use crate::{Error, RecordType, WarcReader, WarcWriter};
+
+    const ARC_V1: &[u8] = b"filedesc://IA-001102.arc 0.0.0.0 19960923142103 text/plain 76\n\
+        1 0 Alexa Internet\n\
+        URL IP-address Archive-date Content-type Archive-length\n\
+        \n\
+        http://www.dryswamp.edu:80/index.html 127.10.100.2 19961104142103 text/html 57\n\
+        HTTP/1.0 200 OK\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <html></html>\n\
+        \n\
+        dns:www.dryswamp.edu 127.0.0.53 19961104142102 text/dns 32\n\
+        19961104142102\n\
+        www.dryswamp.edu\n";
+
+    #[test]
+    fn read_and_convert() {
+        let records: Vec<_> = ArcReader::new(ARC_V1)
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].is_filedesc());
+        assert!(records[1].is_http_response());
+        assert_eq!(
+            records[1].date,
+            Utc.with_ymd_and_hms(1996, 11, 4, 14, 21, 3).unwrap()
+        );
+        assert!(records[1].body.ends_with(b"<html></html>"));
+
+        let response = records[1].to_warc().unwrap();
+        assert_eq!(response.warc_type(), &RecordType::Response);
+        assert_eq!(
+            response.header(WarcHeader::ContentType).unwrap(),
+            "application/http;msgtype=response"
+        );
+        assert_eq!(
+            response.header(WarcHeader::IPAddress).unwrap(),
+            "127.10.100.2"
+        );
+        let dns = records[2].to_warc().unwrap();
+        assert_eq!(dns.warc_type(), &RecordType::Resource);
+        assert_eq!(dns.header(WarcHeader::ContentType).unwrap(), "text/dns");
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        assert_eq!(convert(ArcReader::new(ARC_V1), &mut writer).unwrap(), 3);
+        drop(writer);
+        let targets: Vec<_> = WarcReader::new(BufReader::new(&buffer[..]))
+            .iter_records()
+            .map(|record| {
+                record
+                    .unwrap()
+                    .header(WarcHeader::TargetURI)
+                    .map(|uri| uri.into_owned())
+            })
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                None,
+                Some("http://www.dryswamp.edu:80/index.html".to_owned()),
+                Some("dns:www.dryswamp.edu".to_owned())
+            ]
+        );
+
+        let truncated = &ARC_V1[..ARC_V1.len() - 10];
+        let last = ArcReader::new(truncated).last().unwrap();
+        assert_eq!(last, Err(Error::UnexpectedEOB));
+    }
+
+    #[test]
+    fn header_versions() {
+        let version_2 = b"filedesc://a.arc 0.0.0.0 20080101000000 text/plain 14\n\
+            2 0 Heritrix\n\
+            \n\
+            http://example.com/a b 1.2.3.4 20080101000001 text/plain 200 - - 0 a.arc 2\n\
+            hi\n";
+        let records: Vec<_> = ArcReader::new(&version_2[..])
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].url, "http://example.com/a b");
+        assert_eq!(records[1].ip_address, "1.2.3.4");
+        assert_eq!(
+            records[1].date,
+            Utc.with_ymd_and_hms(2008, 1, 1, 0, 0, 1).unwrap()
+        );
+        assert_eq!(records[1].content_type, "text/plain");
+        assert_eq!(records[1].body, b"hi");
+
+        // read as a version 1 line, the last five fields are taken, so the date field is "0"
+        let version_1 = b"filedesc://a.arc 0.0.0.0 20080101000000 text/plain 14\n\
+            1 0 Heritrix\n\
+            \n\
+            http://example.com/a 1.2.3.4 20080101000001 text/plain 200 - - 0 a.arc 2\n\
+            hi\n";
+        let second = ArcReader::new(&version_1[..]).nth(1).unwrap();
+        assert_eq!(
+            second,
+            Err(Error::MalformedHeader(WarcHeader::Date, "0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn filedesc_to_warcinfo() {
+        let filedesc = ArcReader::new(ARC_V1).next().unwrap().unwrap();
+        let warcinfo = filedesc.to_warc().unwrap();
+        assert_eq!(warcinfo.warc_type(), &RecordType::WarcInfo);
+        assert_eq!(
+            warcinfo.date(),
+            &Utc.with_ymd_and_hms(1996, 9, 23, 14, 21, 3).unwrap()
+        );
+        assert_eq!(
+            warcinfo.header(WarcHeader::Filename).unwrap(),
+            "IA-001102.arc"
+        );
+        assert_eq!(
+            warcinfo.header(WarcHeader::ContentType).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(warcinfo.header(WarcHeader::TargetURI), None);
+        assert_eq!(warcinfo.header(WarcHeader::IPAddress), None);
+        assert_eq!(warcinfo.body(), &filedesc.body[..]);
+    }
+
+    #[test]
+    fn malformed_length() {
+        let input = b"http://example.com/ 1.2.3.4 20080101000001 text/plain 2x\nhi\n";
+        let mut reader = ArcReader::new(&input[..]);
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::MalformedHeader(WarcHeader::ContentLength, _)))
+        ));
+
+        let input = b"http://example.com/ 1.2.3.4 20080101000001 text/plain -2\nhi\n";
+        assert!(matches!(
+            ArcReader::new(&input[..]).next(),
+            Some(Err(Error::MalformedHeader(WarcHeader::ContentLength, _)))
+        ));
+
+        let input =
+            b"http://example.com/ 1.2.3.4 20080101000001 text/plain 99999999999999999999\nhi\n";
+        assert!(matches!(
+            ArcReader::new(&input[..]).next(),
+            Some(Err(Error::MalformedHeader(WarcHeader::ContentLength, _)))
+        ));
+
+        let input = b"http://example.com/ 20080101000001 text/plain 2\nhi\n";
+        assert_eq!(
+            ArcReader::new(&input[..]).next(),
+            Some(Err(Error::ParseHeaders))
+        );
+
+        let input = b"http://example.com/ 1.2.3.4 20080101000001 text/plain 99\nhi\n";
+        assert_eq!(
+            ArcReader::new(&input[..]).next(),
+            Some(Err(Error::UnexpectedEOB))
+        );
+    }
+}
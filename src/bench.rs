@@ -0,0 +1,240 @@
+//! Synthetic corpora for benchmarking readers, writers and storage.
+//!
+//! A `Corpus` generates a WARC file of response records with bodies of varying sizes and
+//! compressibility, in any framing. Corpora are deterministic: the same settings always generate
+//! the same bytes, so that measurements taken before and after a change are comparable. The
+//! crate's own benchmarks, run with `cargo bench --features bench`, are built on these corpora.
+
+use std::io::{self, Write};
+
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Compatibility, Record, RecordType, WarcWriter};
+
+#[cfg(feature = "gzip")]
+use crate::warc_writer::CountingWriter;
+#[cfg(feature = "gzip")]
+use crate::MemberCompression;
+#[cfg(feature = "gzip")]
+use libflate::gzip::Encoder as GzipWriter;
+
+/// Text repeated to fill the compressible part of a body.
+const TEXT: &[u8] = b"<p>Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+tempor incididunt ut labore et dolore magna aliqua.</p>\n";
+
+/// How the records of a corpus are framed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Framing {
+    /// Uncompressed records.
+    Plain,
+    /// One gzip member per record, as written by crawlers.
+    #[cfg(feature = "gzip")]
+    GzipPerRecord,
+    /// The whole file in a single gzip member, as written by `gzip`.
+    #[cfg(feature = "gzip")]
+    GzipWhole,
+}
+
+/// A generator of synthetic WARC files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Corpus {
+    records: usize,
+    body_len: (usize, usize),
+    entropy: f64,
+    framing: Framing,
+    seed: u64,
+}
+
+impl Default for Corpus {
+    fn default() -> Self {
+        Corpus {
+            records: 1000,
+            body_len: (1024, 16_384),
+            entropy: 0.5,
+            framing: Framing::Plain,
+            seed: 1,
+        }
+    }
+}
+
+impl Corpus {
+    /// Create a corpus of 1000 uncompressed records with bodies of 1 to 16 KiB, half of each
+    /// body random.
+    pub fn new() -> Self {
+        Corpus::default()
+    }
+
+    /// Set the number of records.
+    pub fn records(mut self, records: usize) -> Self {
+        self.records = records;
+
+        self
+    }
+
+    /// Set the range of body lengths, inclusive. Lengths are spread evenly over the range.
+    pub fn body_len(mut self, min: usize, max: usize) -> Self {
+        self.body_len = (min.min(max), max.max(min));
+
+        self
+    }
+
+    /// Set the fraction of each body, from 0 to 1, filled with random bytes rather than text. Text
+    /// compresses well and random bytes not at all, like the markup and media of a real crawl.
+    pub fn entropy(mut self, entropy: f64) -> Self {
+        self.entropy = entropy.clamp(0.0, 1.0);
+
+        self
+    }
+
+    /// Set how the records are framed.
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+
+        self
+    }
+
+    /// Set the seed of the random parts of the corpus, to generate a different corpus with the
+    /// same settings.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    /// Return the records of the corpus, in order.
+    pub fn iter_records(&self) -> impl Iterator<Item = Record<BufferedBody>> + '_ {
+        let mut rng = XorShift::new(self.seed);
+        (0..self.records).map(move |i| self.record(i, &mut rng))
+    }
+
+    /// Generate the corpus.
+    pub fn generate(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write_to(&mut data)
+            .expect("writing to a vector cannot fail");
+
+        data
+    }
+
+    /// Write the corpus to the output, returning the number of bytes written.
+    pub fn write_to<W: Write>(&self, mut output: W) -> io::Result<u64> {
+        match self.framing {
+            Framing::Plain => self.write_records(WarcWriter::new(&mut output)),
+            #[cfg(feature = "gzip")]
+            Framing::GzipPerRecord => {
+                let mut writer = WarcWriter::new(&mut output);
+                writer.set_member_compression(|_, _| MemberCompression::Compressed);
+                self.write_records(writer)
+            }
+            #[cfg(feature = "gzip")]
+            Framing::GzipWhole => {
+                let mut counter = CountingWriter(&mut output, 0);
+                let mut encoder = GzipWriter::new(&mut counter)?;
+                self.write_records(WarcWriter::new(&mut encoder))?;
+                encoder.finish().into_result()?;
+
+                Ok(counter.1 as u64)
+            }
+        }
+    }
+
+    fn write_records<W: Write>(&self, mut writer: WarcWriter<W>) -> io::Result<u64> {
+        // headers are otherwise written in no particular order
+        writer.set_compatibility(Compatibility::Wget);
+        let mut written = 0;
+        for record in self.iter_records() {
+            written += writer.write(&record)? as u64;
+        }
+        writer.flush()?;
+
+        Ok(written)
+    }
+
+    fn record(&self, i: usize, rng: &mut XorShift) -> Record<BufferedBody> {
+        let (min, max) = self.body_len;
+        let len = min + (rng.next() as usize) % (max - min + 1);
+        let random_len = (len as f64 * self.entropy) as usize;
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n",
+            len
+        );
+        let mut body = Vec::with_capacity(head.len() + len);
+        body.extend_from_slice(head.as_bytes());
+        body.extend(TEXT.iter().cycle().take(len - random_len));
+        while body.len() < head.len() + len {
+            body.extend_from_slice(&rng.next().to_le_bytes());
+        }
+        body.truncate(head.len() + len);
+
+        let mut record = Record::<BufferedBody>::with_body(body);
+        record.set_warc_version("1.1");
+        record.set_warc_type(RecordType::Response);
+        record.set_warc_id(format!(
+            "<urn:uuid:00000000-0000-4000-8000-{:012x}>",
+            i as u64 & 0xffff_ffff_ffff
+        ));
+        record.set_date(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+        record
+            .set_header(WarcHeader::TargetURI, format!("http://example.com/{}", i))
+            .unwrap();
+        record
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+
+        record
+    }
+}
+
+/// A xorshift generator, which is fast and stable across platforms and releases.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        XorShift(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Corpus;
+    use crate::WarcReader;
+
+    #[test]
+    fn corpus() {
+        let corpus = Corpus::new().records(20).body_len(100, 200);
+        let data = corpus.generate();
+        assert_eq!(data, corpus.generate());
+        assert_ne!(data, corpus.clone().seed(2).generate());
+
+        let records = WarcReader::new(&data[..])
+            .iter_records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 20);
+        for record in &records {
+            let len = record.body().len();
+            assert!(len > 100 && len < 300, "{}", len);
+        }
+
+        #[cfg(feature = "gzip")]
+        {
+            use super::Framing;
+
+            let per_record = corpus.clone().framing(Framing::GzipPerRecord);
+            assert_eq!(
+                per_record.write_to(std::io::sink()).unwrap(),
+                per_record.generate().len() as u64
+            );
+            let whole = corpus.framing(Framing::GzipWhole).generate();
+            assert!(whole.len() < per_record.generate().len());
+        }
+    }
+}
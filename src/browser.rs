@@ -0,0 +1,219 @@
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Record, RecordType};
+
+/// The kind of an artifact produced while rendering a page in a browser.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ArtifactKind {
+    /// A full-size screenshot of the rendered page.
+    Screenshot,
+    /// A reduced-size screenshot of the rendered page.
+    Thumbnail,
+    /// The serialized DOM of the rendered page.
+    DomSnapshot,
+    /// The rendered page printed to PDF.
+    PdfRender,
+    /// The extracted text of the rendered page.
+    Text,
+    /// A JSON summary of the resources loaded by the page.
+    PageInfo,
+}
+
+impl ArtifactKind {
+    /// Return the prefix prepended to the page URI to form the artifact's target URI.
+    pub fn uri_prefix(self) -> &'static str {
+        match self {
+            ArtifactKind::Screenshot => "urn:view:",
+            ArtifactKind::Thumbnail => "urn:thumbnail:",
+            ArtifactKind::DomSnapshot => "urn:dom:",
+            ArtifactKind::PdfRender => "urn:pdf:",
+            ArtifactKind::Text => "urn:text:",
+            ArtifactKind::PageInfo => "urn:pageinfo:",
+        }
+    }
+
+    /// Return the type of record the artifact is stored in.
+    ///
+    /// Renderings of the page's content are conversions; everything else is a resource.
+    pub fn record_type(self) -> RecordType {
+        match self {
+            ArtifactKind::DomSnapshot | ArtifactKind::PdfRender => RecordType::Conversion,
+            _ => RecordType::Resource,
+        }
+    }
+
+    /// Return the Content-Type used when none is given.
+    pub fn default_content_type(self) -> &'static str {
+        match self {
+            ArtifactKind::Screenshot | ArtifactKind::Thumbnail => "image/png",
+            ArtifactKind::DomSnapshot => "text/html",
+            ArtifactKind::PdfRender => "application/pdf",
+            ArtifactKind::Text => "text/plain",
+            ArtifactKind::PageInfo => "application/json",
+        }
+    }
+
+    /// Return the kind of artifact identified by the given target URI, if any.
+    pub fn from_target_uri(uri: &str) -> Option<Self> {
+        [
+            ArtifactKind::Screenshot,
+            ArtifactKind::Thumbnail,
+            ArtifactKind::DomSnapshot,
+            ArtifactKind::PdfRender,
+            ArtifactKind::Text,
+            ArtifactKind::PageInfo,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| uri.starts_with(kind.uri_prefix()))
+    }
+}
+
+/// An artifact produced while rendering a page, such as a screenshot or DOM snapshot.
+///
+/// Artifacts are stored with a `urn:` target URI formed from the kind of artifact and the URI of
+/// the page, as produced by browser-based crawlers, and may refer to the record of the capture
+/// they were rendered from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrowserArtifact {
+    kind: ArtifactKind,
+    page_uri: String,
+    data: Vec<u8>,
+    content_type: Option<String>,
+    date: DateTime<Utc>,
+    refers_to: Option<String>,
+    warcinfo_id: Option<String>,
+}
+
+impl BrowserArtifact {
+    /// Create an artifact of the given kind for the page at the given URI, dated now.
+    pub fn new<S: Into<String>, B: Into<Vec<u8>>>(
+        kind: ArtifactKind,
+        page_uri: S,
+        data: B,
+    ) -> Self {
+        BrowserArtifact {
+            kind,
+            page_uri: page_uri.into(),
+            data: data.into(),
+            content_type: None,
+            date: Utc::now(),
+            refers_to: None,
+            warcinfo_id: None,
+        }
+    }
+
+    /// Set the Content-Type of the artifact, overriding the default for its kind.
+    pub fn content_type<S: Into<String>>(mut self, content_type: S) -> Self {
+        self.content_type = Some(content_type.into());
+
+        self
+    }
+
+    /// Set the time at which the artifact was produced.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = date;
+
+        self
+    }
+
+    /// Set the ID of the record of the capture this artifact was rendered from.
+    pub fn refers_to<S: Into<String>>(mut self, record_id: S) -> Self {
+        self.refers_to = Some(record_id.into());
+
+        self
+    }
+
+    /// Set the WARC-Warcinfo-ID of the artifact's record.
+    pub fn warcinfo_id<S: Into<String>>(mut self, warcinfo_id: S) -> Self {
+        self.warcinfo_id = Some(warcinfo_id.into());
+
+        self
+    }
+
+    /// Return the target URI of the artifact's record.
+    pub fn target_uri(&self) -> String {
+        format!("{}{}", self.kind.uri_prefix(), self.page_uri)
+    }
+
+    /// Build the record holding this artifact.
+    pub fn into_record(self) -> Record<BufferedBody> {
+        let target_uri = self.target_uri();
+        let kind = self.kind;
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(kind.record_type());
+        record.set_date(self.date);
+        record
+            .set_header(WarcHeader::TargetURI, target_uri)
+            .unwrap();
+        record
+            .set_header(
+                WarcHeader::ContentType,
+                self.content_type
+                    .unwrap_or_else(|| kind.default_content_type().to_owned()),
+            )
+            .unwrap();
+        if let Some(refers_to) = self.refers_to {
+            record.set_header(WarcHeader::RefersTo, refers_to).unwrap();
+        }
+        if let Some(warcinfo_id) = self.warcinfo_id {
+            record
+                .set_header(WarcHeader::WarcInfoID, warcinfo_id)
+                .unwrap();
+        }
+        record.replace_body(self.data);
+
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArtifactKind, BrowserArtifact};
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    #[test]
+    fn screenshot() {
+        let record = BrowserArtifact::new(
+            ArtifactKind::Screenshot,
+            "https://example.com/",
+            &b"\x89PNG"[..],
+        )
+        .refers_to("<urn:uuid:00000000-0000-4000-8000-000000000001>")
+        .into_record();
+
+        assert_eq!(record.warc_type(), &RecordType::Resource);
+        assert_eq!(
+            record.header(WarcHeader::TargetURI).unwrap(),
+            "urn:view:https://example.com/"
+        );
+        assert_eq!(record.header(WarcHeader::ContentType).unwrap(), "image/png");
+        assert_eq!(
+            record.header(WarcHeader::RefersTo).unwrap(),
+            "<urn:uuid:00000000-0000-4000-8000-000000000001>"
+        );
+        assert_eq!(record.body(), b"\x89PNG");
+    }
+
+    #[test]
+    fn conversions() {
+        let record =
+            BrowserArtifact::new(ArtifactKind::DomSnapshot, "https://example.com/", "<html>")
+                .content_type("text/html; charset=utf-8")
+                .into_record();
+
+        assert_eq!(record.warc_type(), &RecordType::Conversion);
+        assert_eq!(
+            record.header(WarcHeader::ContentType).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert!(record.header(WarcHeader::RefersTo).is_none());
+        assert_eq!(
+            ArtifactKind::from_target_uri(&record.header(WarcHeader::TargetURI).unwrap()),
+            Some(ArtifactKind::DomSnapshot)
+        );
+        assert_eq!(ArtifactKind::from_target_uri("https://example.com/"), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! Extension headers and metadata records written by browser-based crawlers.
+//!
+//! Browsertrix Crawler and pywb add headers linking each record to the page it was loaded by,
+//! write `urn:pageinfo:` records summarizing the resources of each page and `urn:text:` records
+//! holding its extracted text, and list the pages of a crawl as JSON lines, wrapped here to fit:
+//!
+//! ```text
+//! {"format":"json-pages-1.0","id":"pages","title":"All Pages"}
+//! {"id":"1db0ef709a","url":"https://example.com/","ts":"2023-01-01T00:00:00Z","title":"Example",
+//!     "seed":true,"depth":0}
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{ArtifactKind, BufferedBody, Error, Record};
+
+/// The header holding the ID of the page a record was loaded by.
+pub const PAGE_ID: &str = "WARC-Page-ID";
+/// The header holding the kind of resource, such as `document` or `script`, as reported by the
+/// browser.
+pub const RESOURCE_TYPE: &str = "WARC-Resource-Type";
+/// The header holding arbitrary JSON metadata about the record.
+pub const JSON_METADATA: &str = "WARC-JSON-Metadata";
+
+/// The extension headers of a record.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Extensions {
+    /// The ID of the page the record was loaded by.
+    pub page_id: Option<String>,
+    /// The kind of resource as reported by the browser.
+    pub resource_type: Option<String>,
+    /// The parsed value of the WARC-JSON-Metadata header.
+    pub json_metadata: Option<Value>,
+}
+
+impl Extensions {
+    /// Read the extension headers of a record.
+    ///
+    /// An error is returned if the WARC-JSON-Metadata header is not valid JSON.
+    pub fn from_record<T: BodyKind>(record: &Record<T>) -> Result<Self, Error> {
+        let header = |name: &str| {
+            record
+                .header(WarcHeader::from(name))
+                .map(|value| value.into_owned())
+        };
+        let json_metadata = match header(JSON_METADATA) {
+            Some(json) => Some(serde_json::from_str(&json).map_err(|e| {
+                Error::MalformedHeader(WarcHeader::from(JSON_METADATA), e.to_string())
+            })?),
+            None => None,
+        };
+
+        Ok(Extensions {
+            page_id: header(PAGE_ID),
+            resource_type: header(RESOURCE_TYPE),
+            json_metadata,
+        })
+    }
+}
+
+/// An entry of a crawl's page list.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Page {
+    pub id: Option<String>,
+    pub url: String,
+    pub ts: Option<String>,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    /// True if the page was one of the crawl's seeds.
+    pub seed: bool,
+    /// The number of links followed from a seed to reach the page.
+    pub depth: Option<u32>,
+}
+
+impl Page {
+    /// Parse a single line of a page list.
+    pub fn from_json(line: &str) -> Result<Self, Error> {
+        serde_json::from_str(line).map_err(|e| Error::MalformedBody(e.to_string()))
+    }
+
+    /// Parse a page list, skipping its header line and any blank lines.
+    pub fn read_list(body: &[u8]) -> Result<Vec<Self>, Error> {
+        let body = std::str::from_utf8(body)
+            .map_err(|_| Error::MalformedBody("not a UTF-8 string".to_string()))?;
+        let mut pages = Vec::new();
+        for line in body.lines().filter(|line| !line.trim().is_empty()) {
+            let value: Value =
+                serde_json::from_str(line).map_err(|e| Error::MalformedBody(e.to_string()))?;
+            if value.get("format").is_some() {
+                continue;
+            }
+            pages.push(Page::deserialize(value).map_err(|e| Error::MalformedBody(e.to_string()))?);
+        }
+
+        Ok(pages)
+    }
+}
+
+/// A resource loaded by a page, as listed in its page info record.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ResourceInfo {
+    pub status: Option<u16>,
+    pub mime: Option<String>,
+    #[serde(rename = "type")]
+    pub resource_type: Option<String>,
+}
+
+/// The summary of a page held in a `urn:pageinfo:` record.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PageInfo {
+    #[serde(alias = "pageid")]
+    pub id: Option<String>,
+    pub url: Option<String>,
+    pub ts: Option<String>,
+    /// The resources loaded by the page, by URL.
+    pub urls: BTreeMap<String, ResourceInfo>,
+}
+
+impl PageInfo {
+    /// Parse the page info held in a record.
+    ///
+    /// `None` is returned if the record is not a page info record.
+    pub fn from_record(record: &Record<BufferedBody>) -> Result<Option<Self>, Error> {
+        if artifact_kind(record) != Some(ArtifactKind::PageInfo) {
+            return Ok(None);
+        }
+
+        serde_json::from_slice(record.body())
+            .map(Some)
+            .map_err(|e| Error::MalformedBody(e.to_string()))
+    }
+}
+
+/// Return the extracted text of the page held in a `urn:text:` record.
+///
+/// `None` is returned if the record is not a text record.
+pub fn page_text(record: &Record<BufferedBody>) -> Option<String> {
+    if artifact_kind(record) != Some(ArtifactKind::Text) {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(record.body()).into_owned())
+}
+
+fn artifact_kind(record: &Record<BufferedBody>) -> Option<ArtifactKind> {
+    record
+        .header(WarcHeader::TargetURI)
+        .and_then(|uri| ArtifactKind::from_target_uri(&uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page_text, Extensions, Page, PageInfo};
+    use crate::header::WarcHeader;
+    use crate::{ArtifactKind, BrowserArtifact, BufferedBody, Record};
+    use serde_json::json;
+
+    #[test]
+    fn extension_headers() {
+        let mut record = Record::<BufferedBody>::default();
+        record
+            .set_header(WarcHeader::from("WARC-Page-ID"), "1db0ef709a")
+            .unwrap();
+        record
+            .set_header(
+                WarcHeader::from("WARC-JSON-Metadata"),
+                r#"{"cert":{"issuer":"x"}}"#,
+            )
+            .unwrap();
+
+        let extensions = Extensions::from_record(&record).unwrap();
+        assert_eq!(extensions.page_id.as_deref(), Some("1db0ef709a"));
+        assert_eq!(extensions.resource_type, None);
+        assert_eq!(
+            extensions.json_metadata,
+            Some(json!({"cert": {"issuer": "x"}}))
+        );
+
+        record
+            .set_header(WarcHeader::from("WARC-JSON-Metadata"), "{")
+            .unwrap();
+        assert!(Extensions::from_record(&record).is_err());
+    }
+
+    #[test]
+    fn metadata_records() {
+        let pages = Page::read_list(
+            b"{\"format\":\"json-pages-1.0\",\"id\":\"pages\"}\n\
+              {\"id\":\"a\",\"url\":\"https://example.com/\",\"seed\":true,\"depth\":0}\n\
+              \n\
+              {\"url\":\"https://example.com/b\",\"title\":\"B\"}\n",
+        )
+        .unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].seed);
+        assert_eq!(pages[1].title.as_deref(), Some("B"));
+
+        let info = BrowserArtifact::new(
+            ArtifactKind::PageInfo,
+            "https://example.com/",
+            concat!(
+                r#"{"pageid":"a","url":"https://example.com/","urls":{"https://example.com/":"#,
+                r#"{"status":200,"mime":"text/html","type":"document"}}}"#,
+            ),
+        )
+        .into_record();
+        let info = PageInfo::from_record(&info).unwrap().unwrap();
+        assert_eq!(info.id.as_deref(), Some("a"));
+        assert_eq!(info.urls["https://example.com/"].status, Some(200));
+        assert_eq!(
+            info.urls["https://example.com/"].resource_type.as_deref(),
+            Some("document")
+        );
+
+        let text =
+            BrowserArtifact::new(ArtifactKind::Text, "https://example.com/", "Hello").into_record();
+        assert_eq!(page_text(&text).as_deref(), Some("Hello"));
+        assert_eq!(PageInfo::from_record(&text).unwrap(), None);
+    }
+}
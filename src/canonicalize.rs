@@ -0,0 +1,272 @@
+//! Canonicalization of URLs into the keys of CDX indexes.
+//!
+//! Index tools reduce each URL to a canonical form before using it as a lookup key, so that
+//! trivially different URLs of the same resource, such as those differing only in a session ID
+//! or the order of query parameters, find the same captures. The key is then written as a SURT
+//! (Sort-friendly URI Reordering Transform), with the host reversed: `com,example)/path?query`.
+//!
+//! Tools differ in the rules they apply, and a key built by one tool is only found by another
+//! applying the same rules. A `Canonicalizer` applies a configurable sequence of rules; the
+//! `Canonicalizer::ia` preset matches the default rules of the `surt` library used by pywb and
+//! OutbackCDX.
+
+use std::fmt;
+
+#[cfg(feature = "filter")]
+use regex::Regex;
+use url::Url;
+
+type Rule = Box<dyn Fn(String) -> String + Send + Sync>;
+
+/// The query parameters holding session IDs, with the shortest value treated as a session ID.
+const SESSION_PARAMETERS: &[(&str, usize)] = &[
+    ("jsessionid", 10),
+    ("phpsessid", 16),
+    ("sid", 16),
+    ("cfid", 1),
+    ("cftoken", 1),
+];
+
+/// A sequence of rules turning URLs into canonical form.
+///
+/// Every URL is first parsed, which lowercases the scheme and host, drops a default port, and
+/// normalizes percent-encoding. The rules are then applied in the order they were added. A URL
+/// which cannot be parsed is passed to the rules as given.
+#[derive(Default)]
+pub struct Canonicalizer {
+    rules: Vec<Rule>,
+}
+
+impl fmt::Debug for Canonicalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Canonicalizer")
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+impl Canonicalizer {
+    /// Create a canonicalizer with no rules beyond parsing.
+    pub fn new() -> Self {
+        Canonicalizer::default()
+    }
+
+    /// Create a canonicalizer with the default rules of the `surt` library, as used by pywb and
+    /// OutbackCDX: the fragment and session IDs are stripped, a `www` prefix is removed from the
+    /// host, the URL is lowercased, and query parameters are sorted.
+    pub fn ia() -> Self {
+        Canonicalizer::new()
+            .strip_fragment()
+            .strip_www()
+            .strip_session_ids()
+            .lowercase()
+            .sort_query()
+    }
+
+    /// Add a rule given as a function from a URL to its replacement.
+    pub fn rule<F>(mut self, rule: F) -> Self
+    where
+        F: Fn(String) -> String + Send + Sync + 'static,
+    {
+        self.rules.push(Box::new(rule));
+
+        self
+    }
+
+    /// Add a rule replacing every match of a regular expression, as `Regex::replace_all` does.
+    #[cfg(feature = "filter")]
+    pub fn regex<S: Into<String>>(self, regex: Regex, replacement: S) -> Self {
+        let replacement = replacement.into();
+        self.rule(move |url| regex.replace_all(&url, replacement.as_str()).into_owned())
+    }
+
+    /// Lowercase the whole URL, including its path and query.
+    pub fn lowercase(self) -> Self {
+        self.rule(|url| url.to_lowercase())
+    }
+
+    /// Remove the fragment.
+    pub fn strip_fragment(self) -> Self {
+        self.rule(|url| match url.find('#') {
+            Some(pos) => url[..pos].to_owned(),
+            None => url,
+        })
+    }
+
+    /// Remove a `www` prefix from the host, optionally numbered as in `www2`.
+    pub fn strip_www(self) -> Self {
+        self.rule(|url| {
+            with_url(url, |url| {
+                let host = match url.host_str() {
+                    Some(host) => host.to_owned(),
+                    None => return,
+                };
+                let lower = host.to_ascii_lowercase();
+                let rest = match lower.strip_prefix("www") {
+                    Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_digit()),
+                    None => return,
+                };
+                if let Some(stripped) = rest.strip_prefix('.') {
+                    if stripped.contains('.') {
+                        let _ = url.set_host(Some(&host[host.len() - stripped.len()..]));
+                    }
+                }
+            })
+        })
+    }
+
+    /// Remove common session IDs: `;jsessionid=` path parameters, and the `jsessionid`,
+    /// `phpsessid`, `sid`, `aspsessionid*`, `cfid` and `cftoken` query parameters.
+    pub fn strip_session_ids(self) -> Self {
+        self.rule(|url| {
+            with_url(url, |url| {
+                let path = url.path().to_owned();
+                if let Some(pos) = path.to_ascii_lowercase().find(";jsessionid=") {
+                    url.set_path(&path[..pos]);
+                }
+                let query = match url.query() {
+                    Some(query) => query.to_owned(),
+                    None => return,
+                };
+                let kept: Vec<_> = query
+                    .split('&')
+                    .filter(|pair| !is_session_parameter(pair))
+                    .collect();
+                set_query(url, &kept);
+            })
+        })
+    }
+
+    /// Sort the query parameters, and remove an empty query.
+    pub fn sort_query(self) -> Self {
+        self.rule(|url| {
+            with_url(url, |url| {
+                let query = match url.query() {
+                    Some(query) => query.to_owned(),
+                    None => return,
+                };
+                let mut pairs: Vec<_> = query.split('&').filter(|pair| !pair.is_empty()).collect();
+                pairs.sort_unstable();
+                set_query(url, &pairs);
+            })
+        })
+    }
+
+    /// Return the canonical form of a URL.
+    pub fn canonicalize(&self, url: &str) -> String {
+        let url = url.trim();
+        let url = Url::parse(url)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| url.to_owned());
+
+        self.rules.iter().fold(url, |url, rule| rule(url))
+    }
+
+    /// Return the SURT key of a URL in canonical form, as used in CDX indexes.
+    ///
+    /// The scheme is omitted for HTTP(S) URLs, as is any user information. URLs which cannot be
+    /// parsed are returned in canonical form.
+    pub fn surt(&self, url: &str) -> String {
+        let canonical = self.canonicalize(url);
+        let url = match Url::parse(&canonical) {
+            Ok(url) => url,
+            Err(_) => return canonical,
+        };
+        let mut key = String::new();
+        if url.scheme() != "http" && url.scheme() != "https" {
+            key.push_str(url.scheme());
+            key.push_str("://(");
+        }
+        if let Some(host) = url.host_str() {
+            key.push_str(&host.split('.').rev().collect::<Vec<_>>().join(","));
+        }
+        if let Some(port) = url.port() {
+            key.push_str(&format!(":{}", port));
+        }
+        key.push(')');
+        key.push_str(url.path());
+        if let Some(query) = url.query() {
+            key.push('?');
+            key.push_str(query);
+        }
+
+        key
+    }
+}
+
+/// Apply a change to a URL, if it can be parsed.
+fn with_url<F: FnOnce(&mut Url)>(url: String, change: F) -> String {
+    match Url::parse(&url) {
+        Ok(mut parsed) => {
+            change(&mut parsed);
+            parsed.to_string()
+        }
+        Err(_) => url,
+    }
+}
+
+/// Replace the query of a URL with the given parameters, removing it if there are none.
+fn set_query(url: &mut Url, pairs: &[&str]) {
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.set_query(Some(&pairs.join("&")));
+    }
+}
+
+/// Return true if a query parameter holds a session ID.
+fn is_session_parameter(pair: &str) -> bool {
+    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+    let name = name.to_ascii_lowercase();
+    if name.starts_with("aspsessionid") {
+        return value.len() == 24;
+    }
+    SESSION_PARAMETERS
+        .iter()
+        .any(|(parameter, min_len)| name == *parameter && value.len() >= *min_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Canonicalizer;
+
+    #[test]
+    fn canonicalize() {
+        let ia = Canonicalizer::ia();
+        let url = "HTTP://WWW2.Example.COM:80/Path;jsessionid=0123456789ABCDEF0123456789ABCDEF\
+                   ?b=2&PHPSESSID=0123456789abcdef0123&a=1#top";
+        assert_eq!(ia.canonicalize(url), "http://example.com/path?a=1&b=2");
+        assert_eq!(ia.surt(url), "com,example)/path?a=1&b=2");
+        assert_eq!(
+            ia.surt("https://user@www.example.com:8443/?sid=short"),
+            "com,example:8443)/?sid=short"
+        );
+        assert_eq!(ia.surt("http://www.com/"), "com,www)/");
+
+        let plain = Canonicalizer::new();
+        assert_eq!(
+            plain.surt("http://www.Example.com/A?b=2&a=1"),
+            "com,example,www)/A?b=2&a=1"
+        );
+        assert_eq!(plain.canonicalize("not a url"), "not a url");
+
+        let custom = Canonicalizer::new()
+            .rule(|url| url.replace("/index.html", "/"))
+            .sort_query();
+        assert_eq!(
+            custom.surt("http://example.com/index.html?"),
+            "com,example)/"
+        );
+    }
+
+    #[cfg(feature = "filter")]
+    #[test]
+    fn regex() {
+        let utm = regex::Regex::new(r"[?&]utm_[a-z]+=[^&]*").unwrap();
+        let canonicalizer = Canonicalizer::new().regex(utm, "").sort_query();
+        assert_eq!(
+            canonicalizer.surt("http://example.com/?id=1&utm_source=x"),
+            "com,example)/?id=1"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Record, RecordType};
+
+/// A set of captures whose records are linked to each other consistently.
+///
+/// Each capture started with `begin` provides handles to its request, response, resource, and
+/// metadata records. The records of a capture share a WARC-Date and WARC-Target-URI, and all but
+/// the metadata record share the session's IP address. When the session is finished, the other
+/// records are given a WARC-Concurrent-To header pointing at the response record, or at the
+/// resource record if there is no response.
+#[derive(Clone, Debug, Default)]
+pub struct CaptureSession {
+    ip_address: Option<String>,
+    warcinfo_id: Option<String>,
+    captures: Vec<Capture>,
+}
+
+impl CaptureSession {
+    /// Create a new, empty session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the WARC-IP-Address given to the request and response records of each capture.
+    pub fn ip_address<S: Into<String>>(mut self, ip_address: S) -> Self {
+        self.ip_address = Some(ip_address.into());
+
+        self
+    }
+
+    /// Set the WARC-Warcinfo-ID given to every record of each capture.
+    pub fn warcinfo_id<S: Into<String>>(mut self, warcinfo_id: S) -> Self {
+        self.warcinfo_id = Some(warcinfo_id.into());
+
+        self
+    }
+
+    /// Begin a capture of the given URI, dated now.
+    pub fn begin<S: Into<String>>(&mut self, uri: S) -> &mut Capture {
+        self.begin_at(uri, Utc::now())
+    }
+
+    /// Begin a capture of the given URI with the given date.
+    pub fn begin_at<S: Into<String>>(&mut self, uri: S, date: DateTime<Utc>) -> &mut Capture {
+        self.captures.push(Capture {
+            target_uri: uri.into(),
+            date,
+            ip_address: self.ip_address.clone(),
+            warcinfo_id: self.warcinfo_id.clone(),
+            request: None,
+            response: None,
+            resource: None,
+            metadata: None,
+        });
+
+        self.captures.last_mut().unwrap()
+    }
+
+    /// Link the records of every capture and pass them to the sink in order.
+    ///
+    /// Each capture yields its request, response, resource, and metadata records, in that order,
+    /// skipping any record which was never started. The first error returned by the sink is
+    /// returned.
+    pub fn finish<F, E>(self, mut sink: F) -> Result<(), E>
+    where
+        F: FnMut(Record<BufferedBody>) -> Result<(), E>,
+    {
+        for capture in self.captures {
+            for record in capture.into_records() {
+                sink(record)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Link the records of every capture and return them in the order `finish` would pass them.
+    pub fn into_records(self) -> Vec<Record<BufferedBody>> {
+        self.captures
+            .into_iter()
+            .flat_map(Capture::into_records)
+            .collect()
+    }
+}
+
+/// The records of a single capture within a `CaptureSession`.
+///
+/// Records are created on first access with their type, ID, date, and target URI already set.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    target_uri: String,
+    date: DateTime<Utc>,
+    ip_address: Option<String>,
+    warcinfo_id: Option<String>,
+    request: Option<Record<BufferedBody>>,
+    response: Option<Record<BufferedBody>>,
+    resource: Option<Record<BufferedBody>>,
+    metadata: Option<Record<BufferedBody>>,
+}
+
+impl Capture {
+    /// Return the target URI of this capture.
+    pub fn target_uri(&self) -> &str {
+        &self.target_uri
+    }
+
+    /// Return the date shared by the records of this capture.
+    pub fn date(&self) -> &DateTime<Utc> {
+        &self.date
+    }
+
+    /// Return the request record of this capture, creating it if necessary.
+    pub fn request(&mut self) -> &mut Record<BufferedBody> {
+        if self.request.is_none() {
+            self.request = Some(self.new_record(RecordType::Request));
+        }
+
+        self.request.as_mut().unwrap()
+    }
+
+    /// Return the response record of this capture, creating it if necessary.
+    pub fn response(&mut self) -> &mut Record<BufferedBody> {
+        if self.response.is_none() {
+            self.response = Some(self.new_record(RecordType::Response));
+        }
+
+        self.response.as_mut().unwrap()
+    }
+
+    /// Return the resource record of this capture, creating it if necessary.
+    pub fn resource(&mut self) -> &mut Record<BufferedBody> {
+        if self.resource.is_none() {
+            self.resource = Some(self.new_record(RecordType::Resource));
+        }
+
+        self.resource.as_mut().unwrap()
+    }
+
+    /// Return the metadata record of this capture, creating it if necessary.
+    pub fn metadata(&mut self) -> &mut Record<BufferedBody> {
+        if self.metadata.is_none() {
+            self.metadata = Some(self.new_record(RecordType::Metadata));
+        }
+
+        self.metadata.as_mut().unwrap()
+    }
+
+    fn new_record(&self, record_type: RecordType) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(record_type.clone());
+        record.set_date(self.date);
+        record
+            .set_header(WarcHeader::TargetURI, self.target_uri.as_str())
+            .unwrap();
+        if let Some(ref warcinfo_id) = self.warcinfo_id {
+            record
+                .set_header(WarcHeader::WarcInfoID, warcinfo_id.as_str())
+                .unwrap();
+        }
+        if record_type != RecordType::Metadata {
+            if let Some(ref ip_address) = self.ip_address {
+                record
+                    .set_header(WarcHeader::IPAddress, ip_address.as_str())
+                    .unwrap();
+            }
+        }
+
+        record
+    }
+
+    fn into_records(self) -> Vec<Record<BufferedBody>> {
+        let Capture {
+            request,
+            response,
+            resource,
+            metadata,
+            ..
+        } = self;
+        let primary_id = response
+            .as_ref()
+            .or(resource.as_ref())
+            .map(|r| r.warc_id().to_owned());
+
+        let link = |mut record: Record<BufferedBody>| {
+            if let Some(ref id) = primary_id {
+                if record.warc_id() != id && record.header(WarcHeader::ConcurrentTo).is_none() {
+                    record
+                        .set_header(WarcHeader::ConcurrentTo, id.as_str())
+                        .unwrap();
+                }
+            }
+            record
+        };
+
+        let mut records = Vec::with_capacity(4);
+        records.extend(request.map(link));
+        records.extend(response);
+        records.extend(resource.map(link));
+        records.extend(metadata.map(link));
+
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptureSession;
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    use chrono::prelude::*;
+
+    #[test]
+    fn linked_records() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut session = CaptureSession::new()
+            .ip_address("127.0.0.1")
+            .warcinfo_id("<urn:test:warcinfo>");
+
+        let capture = session.begin_at("http://example.com/", date);
+        capture
+            .request()
+            .replace_body(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+        capture
+            .response()
+            .replace_body(&b"HTTP/1.1 200 OK\r\n\r\n"[..]);
+        capture.metadata().replace_body(&b"via: test"[..]);
+        let response_id = capture.response().warc_id().to_owned();
+
+        session
+            .begin_at("http://example.com/other", date)
+            .response();
+
+        let mut records = Vec::new();
+        session
+            .finish(|record| -> Result<(), ()> {
+                records.push(record);
+                Ok(())
+            })
+            .unwrap();
+
+        let types: Vec<_> = records.iter().map(|r| r.warc_type().clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                RecordType::Request,
+                RecordType::Response,
+                RecordType::Metadata,
+                RecordType::Response
+            ]
+        );
+
+        for record in &records[..3] {
+            assert_eq!(record.date(), &date);
+            assert_eq!(
+                record.header(WarcHeader::TargetURI).unwrap(),
+                "http://example.com/"
+            );
+            assert_eq!(
+                record.header(WarcHeader::WarcInfoID).unwrap(),
+                "<urn:test:warcinfo>"
+            );
+        }
+        assert_eq!(
+            records[0].header(WarcHeader::ConcurrentTo).unwrap(),
+            response_id.as_str()
+        );
+        assert!(records[1].header(WarcHeader::ConcurrentTo).is_none());
+        assert_eq!(
+            records[2].header(WarcHeader::ConcurrentTo).unwrap(),
+            response_id.as_str()
+        );
+        assert_eq!(
+            records[1].header(WarcHeader::IPAddress).unwrap(),
+            "127.0.0.1"
+        );
+        assert!(records[2].header(WarcHeader::IPAddress).is_none());
+    }
+
+    #[test]
+    fn sink_error() {
+        let mut session = CaptureSession::new();
+        session.begin("http://example.com/").request();
+        session.begin("http://example.com/").request();
+
+        let mut calls = 0;
+        let result = session.finish(|_| {
+            calls += 1;
+            Err("full")
+        });
+        assert_eq!(result, Err("full"));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,394 @@
+//! Framing of WARC records over asynchronous transports.
+//!
+//! `WarcCodec` implements the `tokio_util` codec traits, so records can be read from any
+//! `AsyncRead` with `FramedRead` and written to any `AsyncWrite` with `FramedWrite`. The body of
+//! each decoded record is a slice of the frame it was read in, and is not copied.
+//!
+//! `AsyncWarcWriter` writes records to any `AsyncWrite` with the options of a `WarcWriter`, such
+//! as one gzip member per record. Each record is serialized in memory, then written as the output
+//! accepts it, so a slow output holds back the task writing to it rather than buffering without
+//! bound.
+
+use std::error;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+
+use bytes::{BufMut, BytesMut};
+use tokio::io::AsyncWrite;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::record::BodyKind;
+use crate::warc_reader::{parse_header_block, HeaderBlock};
+use crate::warc_writer::{write_record, HeaderName};
+use crate::{
+    BytesBody, Compatibility, Error, HeaderCase, ParserConfig, RawRecordHeader, ReadPolicy, Record,
+    WarcWriter,
+};
+
+const MB: usize = 1_048_576;
+
+/// An error returned by a `WarcCodec`.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The transport failed.
+    Io(io::Error),
+    /// A record could not be decoded or encoded.
+    Warc(Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodecError::Io(ref e) => write!(f, "I/O error: {}", e),
+            CodecError::Warc(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CodecError::Io(ref e) => Some(e),
+            CodecError::Warc(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(error: io::Error) -> Self {
+        CodecError::Io(error)
+    }
+}
+
+impl From<Error> for CodecError {
+    fn from(error: Error) -> Self {
+        CodecError::Warc(error)
+    }
+}
+
+/// A codec which frames uncompressed WARC records.
+#[derive(Clone, Debug, Default)]
+pub struct WarcCodec {
+    config: ParserConfig,
+}
+
+impl WarcCodec {
+    /// Create a codec with the default strict configuration.
+    pub fn new() -> Self {
+        WarcCodec::default()
+    }
+
+    /// Set the configuration used to interpret the records decoded.
+    ///
+    /// A record which cannot be decoded ends the stream whatever the read policy, since the
+    /// position of the next record is not known.
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+
+        self
+    }
+}
+
+impl Decoder for WarcCodec {
+    type Item = Record<BytesBody>;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let header_len = match src.windows(4).position(|w| w == b"\r\n\r\n") {
+                Some(pos) => pos + 4,
+                None => return Ok(None),
+            };
+            let (block, body_len) = match parse_header_block(&src[..header_len], &self.config)? {
+                HeaderBlock::Skip(body_len) => (None, body_len),
+                HeaderBlock::Unframed(..) => return Err(Error::ParseHeaders.into()),
+                HeaderBlock::Record(headers, body_len, warnings, names) => {
+                    (Some((headers, warnings, names)), body_len)
+                }
+            };
+
+            let len = header_len + body_len + 4;
+            if src.len() < len {
+                // grow towards the declared length as data arrives, rather than all at once
+                src.reserve((len - src.len()).min(MB));
+                return Ok(None);
+            }
+            if &src[len - 4..len] != b"\r\n\r\n" {
+                return Err(Error::ReadOverflow.into());
+            }
+            let frame = src.split_to(len).freeze();
+            let (headers, mut warnings, names) = match block {
+                Some(block) => block,
+                None => continue,
+            };
+
+            let record = if self.config.read_policy == ReadPolicy::BestEffort {
+                Record::from_raw_best_effort(headers, &self.config)?
+            } else {
+                Record::from_raw_with_config(headers, &self.config)?
+            };
+            let mut record = record.add_bytes_body(frame.slice(header_len..len - 4));
+            record.append_warnings(&mut warnings);
+            record.set_stored_len(len as u64);
+            record.set_header_names(names);
+
+            return Ok(Some(record));
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(record) => Ok(Some(record)),
+            None if src.is_empty() => Ok(None),
+            None => Err(Error::UnexpectedEOB.into()),
+        }
+    }
+}
+
+impl<T: BodyKind + AsRef<[u8]>> Encoder<Record<T>> for WarcCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, record: Record<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&record, dst)
+    }
+}
+
+impl<T: BodyKind + AsRef<[u8]>> Encoder<&Record<T>> for WarcCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, record: &Record<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let headers = record.header_block(None)?;
+        dst.reserve(record.serialized_len() as usize);
+        write_record(
+            &mut dst.writer(),
+            &headers,
+            record.body(),
+            Compatibility::Standard,
+            HeaderName {
+                case: HeaderCase::Lowercase,
+                names: record.header_names(),
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+/// A writer which writes records to an asynchronous output stream.
+pub struct AsyncWarcWriter<W> {
+    writer: W,
+    encoder: WarcWriter<Vec<u8>>,
+    flush_each: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWarcWriter<W> {
+    /// Create a new writer.
+    pub fn new(writer: W) -> Self {
+        AsyncWarcWriter {
+            writer,
+            encoder: WarcWriter::new(Vec::new()),
+            flush_each: false,
+        }
+    }
+
+    /// Return the writer which serializes each record, to set its options as for a synchronous
+    /// writer, such as `set_member_compression` for one gzip member per record.
+    pub fn encoder_mut(&mut self) -> &mut WarcWriter<Vec<u8>> {
+        &mut self.encoder
+    }
+
+    /// Flush the output stream after every record, so that each record is durable once written.
+    pub fn set_flush_each(&mut self, flush_each: bool) {
+        self.flush_each = flush_each;
+    }
+
+    /// Write a single record, returning the number of bytes written once the output has accepted
+    /// all of them.
+    pub async fn write<T: BodyKind + AsRef<[u8]>>(
+        &mut self,
+        record: &Record<T>,
+    ) -> io::Result<usize> {
+        self.encoder.write(record)?;
+        self.write_encoded().await
+    }
+
+    /// Write a single raw record, as `WarcWriter::write_raw` does.
+    pub async fn write_raw<B: AsRef<[u8]>>(
+        &mut self,
+        headers: RawRecordHeader,
+        body: &B,
+    ) -> io::Result<usize> {
+        self.encoder.write_raw(headers, body)?;
+        self.write_encoded().await
+    }
+
+    /// Flush the output stream.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        poll_fn(|cx| Pin::new(&mut self.writer).poll_flush(cx)).await
+    }
+
+    /// Flush and shut down the output stream, returning it.
+    pub async fn shutdown(mut self) -> io::Result<W> {
+        poll_fn(|cx| Pin::new(&mut self.writer).poll_shutdown(cx)).await?;
+
+        Ok(self.writer)
+    }
+
+    /// Return the output stream, without flushing it.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write the records serialized by the encoder to the output.
+    async fn write_encoded(&mut self) -> io::Result<usize> {
+        let buffer = std::mem::take(self.encoder.get_mut());
+        let mut written = 0;
+        while written < buffer.len() {
+            let len =
+                poll_fn(|cx| Pin::new(&mut self.writer).poll_write(cx, &buffer[written..])).await?;
+            if len == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            written += len;
+        }
+        if self.flush_each {
+            self.flush().await?;
+        }
+        // keep the allocation for the next record
+        let mut buffer = buffer;
+        buffer.clear();
+        *self.encoder.get_mut() = buffer;
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use tokio::io::AsyncWrite;
+
+    use super::{AsyncWarcWriter, CodecError, WarcCodec};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Error, Record, WarcReader, WarcWriter};
+
+    #[test]
+    fn round_trip() {
+        let mut codec = WarcCodec::new();
+        let mut buffer = BytesMut::new();
+        let mut records = Vec::new();
+        for body in [&b"first"[..], &b"second\r\n\r\nWARC/1.0"[..]] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record
+                .set_header(WarcHeader::TargetURI, "http://example.com/")
+                .unwrap();
+            record.replace_body(body);
+            codec.encode(&record, &mut buffer).unwrap();
+            records.push(record);
+        }
+
+        let mut input = BytesMut::new();
+        let mut decoded = Vec::new();
+        for chunk in buffer.chunks(7) {
+            input.extend_from_slice(chunk);
+            while let Some(record) = codec.decode(&mut input).unwrap() {
+                decoded.push(record.into_buffered());
+            }
+        }
+        assert!(codec.decode_eof(&mut input).unwrap().is_none());
+        assert_eq!(decoded.len(), 2);
+        for (decoded, record) in decoded.iter().zip(&records) {
+            assert_eq!(decoded.warc_id(), record.warc_id());
+            assert_eq!(decoded.body(), record.body());
+        }
+
+        let mut truncated = BytesMut::from(&buffer[..buffer.len() - 3]);
+        assert!(codec.decode(&mut truncated).unwrap().is_some());
+        assert!(matches!(
+            codec.decode_eof(&mut truncated),
+            Err(CodecError::Warc(Error::UnexpectedEOB))
+        ));
+    }
+
+    /// An output accepting a few bytes at a time, and only on every other poll.
+    #[derive(Default)]
+    struct Slow {
+        data: Vec<u8>,
+        ready: bool,
+        flushes: usize,
+    }
+
+    impl AsyncWrite for Slow {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = buf.len().min(3);
+            self.data.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushes += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn async_writer() {
+        let mut record = Record::<BufferedBody>::with_body(&b"streamed"[..]);
+        record.set_warc_version("1.0");
+
+        let mut writer = AsyncWarcWriter::new(Slow::default());
+        writer.set_flush_each(true);
+        let written = block_on(writer.write(&record)).unwrap();
+        #[cfg(feature = "gzip")]
+        writer
+            .encoder_mut()
+            .set_member_compression(|_, _| crate::MemberCompression::Compressed);
+        block_on(writer.write(&record)).unwrap();
+        let output = block_on(writer.shutdown()).unwrap();
+        assert_eq!(output.flushes, 2);
+
+        let mut expected = Vec::new();
+        WarcWriter::new(&mut expected).write(&record).unwrap();
+        assert_eq!(written, expected.len());
+        assert_eq!(output.data[..written], expected[..]);
+        let records = WarcReader::detect(&output.data[written..])
+            .unwrap()
+            .iter_records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records[0].body(), b"streamed");
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{BufRead, Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::header::WarcHeader;
+use crate::{
+    BufferedBody, EmptyBody, Error, ParserConfig, RawRecordHeader, ReadPolicy, Record, RecordType,
+    WarcReader, WarcWriter,
+};
+
+/// The location and headers of a record in a collection.
+#[derive(Clone, Debug)]
+pub struct CollectionEntry {
+    /// The offset of the start of the record.
+    pub offset: u64,
+    /// The length of the record, including its separator.
+    pub length: u64,
+    /// The headers of the record.
+    pub record: Record<EmptyBody>,
+}
+
+impl CollectionEntry {
+    /// Create an entry from a record's location and raw header.
+    pub fn from_raw(offset: u64, length: u64, header: RawRecordHeader) -> Result<Self, Error> {
+        Ok(CollectionEntry {
+            offset,
+            length,
+            record: Record::<EmptyBody>::try_from(header)?,
+        })
+    }
+
+    /// Return the IDs of the records this record is concurrent to, from every WARC-Concurrent-To
+    /// header it has.
+    pub fn concurrent_to(&self) -> Vec<String> {
+        self.record
+            .headers_all(WarcHeader::ConcurrentTo)
+            .iter()
+            .flat_map(|ids| ids.split_whitespace().map(|id| id.to_owned()))
+            .collect()
+    }
+}
+
+/// An uncompressed WARC file whose records can be read by location or by ID.
+pub struct Collection<R> {
+    reader: R,
+    entries: Vec<CollectionEntry>,
+    by_id: HashMap<String, usize>,
+    /// The indexes of the entries concurrent to each ID.
+    concurrent_from: HashMap<String, Vec<usize>>,
+}
+
+impl<R: BufRead + Seek> Collection<R> {
+    /// Open a collection by reading the headers of every record from the start of the stream.
+    ///
+    /// The first malformed record is returned as an error.
+    pub fn open(mut reader: R) -> Result<Self, Error> {
+        reader
+            .seek(SeekFrom::Start(0))
+            .map_err(|_| Error::ReadData)?;
+        let mut entries = Vec::new();
+        {
+            let config = ParserConfig::new().read_policy(ReadPolicy::FailFast);
+            let mut warc_reader = WarcReader::new(&mut reader).with_config(config);
+            let mut records = warc_reader.stream_records();
+            let mut offset = 0;
+            while let Some(record) = records.next_item() {
+                let (record, _) = record?.into_parts();
+                let length = record.stored_len().unwrap_or_default();
+                entries.push(CollectionEntry {
+                    offset,
+                    length,
+                    record,
+                });
+                offset += length;
+            }
+        }
+
+        Ok(Collection::from_entries(reader, entries))
+    }
+
+    /// Create a collection from entries found by another indexer, such as `ParallelIndexer`.
+    pub fn from_entries(reader: R, mut entries: Vec<CollectionEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.offset);
+        let by_id = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.record.warc_id().to_owned(), i))
+            .collect();
+        let mut concurrent_from: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for id in entry.concurrent_to() {
+                concurrent_from.entry(id).or_default().push(i);
+            }
+        }
+
+        Collection {
+            reader,
+            entries,
+            by_id,
+            concurrent_from,
+        }
+    }
+
+    /// Return the entries of every record, ordered by offset.
+    pub fn entries(&self) -> &[CollectionEntry] {
+        &self.entries
+    }
+
+    /// Return the entry of the record with the given WARC-Record-ID.
+    pub fn get(&self, record_id: &str) -> Option<&CollectionEntry> {
+        self.by_id.get(record_id).map(|&i| &self.entries[i])
+    }
+
+    /// Return the entry of the record starting at the given offset.
+    pub fn entry_at(&self, offset: u64) -> Option<&CollectionEntry> {
+        self.entries
+            .binary_search_by_key(&offset, |entry| entry.offset)
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    /// Read the serialized record starting at the given offset, including its separator.
+    pub fn read_raw(&mut self, offset: u64) -> Result<Vec<u8>, Error> {
+        let length = self.entry_at(offset).ok_or(Error::ReadData)?.length;
+        let mut buffer = Vec::with_capacity(length.min(1_048_576) as usize);
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| (&mut self.reader).take(length).read_to_end(&mut buffer))
+            .map_err(|_| Error::ReadData)?;
+        if (buffer.len() as u64) < length {
+            return Err(Error::UnexpectedEOB);
+        }
+
+        Ok(buffer)
+    }
+
+    /// Read the record starting at the given offset.
+    pub fn read_record(&mut self, offset: u64) -> Result<Record<BufferedBody>, Error> {
+        let raw = self.read_raw(offset)?;
+        WarcReader::new(Cursor::new(raw))
+            .iter_records()
+            .next()
+            .unwrap_or(Err(Error::UnexpectedEOB))
+    }
+
+    /// Return the offsets of the record at the given offset and every record linked to it by
+    /// WARC-Concurrent-To, directly or through other records, ordered by offset.
+    ///
+    /// Warcinfo records are only included if the record at the offset is one.
+    pub fn linked(&self, offset: u64) -> Vec<u64> {
+        let start = match self.entry_at(offset) {
+            Some(entry) => entry.record.warc_id().to_owned(),
+            None => return Vec::new(),
+        };
+
+        let mut seen = HashSet::new();
+        let mut queue = vec![start.clone()];
+        while let Some(id) = queue.pop() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            if let Some(entry) = self.get(&id) {
+                queue.extend(entry.concurrent_to());
+            }
+            if let Some(from) = self.concurrent_from.get(&id) {
+                queue.extend(
+                    from.iter()
+                        .map(|&i| self.entries[i].record.warc_id().to_owned()),
+                );
+            }
+        }
+
+        let mut offsets: Vec<_> = seen
+            .iter()
+            .filter_map(|id| self.get(id))
+            .filter(|entry| {
+                entry.record.warc_id() == start || entry.record.warc_type() != &RecordType::WarcInfo
+            })
+            .map(|entry| entry.offset)
+            .collect();
+        offsets.sort_unstable();
+
+        offsets
+    }
+}
+
+/// Copy the record at the given location, and every record linked to it, from one collection to
+/// the output of a writer.
+///
+/// The WARC-Warcinfo-ID of each record is replaced by the ID of the last warcinfo record written
+/// by the writer, or removed if it has written none. Records are copied byte for byte when the
+/// writer would write them unchanged and their WARC-Warcinfo-ID is already correct. The IDs of the
+/// records copied are returned in the order they were written.
+pub fn transplant<R, W>(
+    location: u64,
+    from: &mut Collection<R>,
+    to: &mut WarcWriter<W>,
+) -> Result<Vec<String>, Error>
+where
+    R: BufRead + Seek,
+    W: Write,
+{
+    let offsets = from.linked(location);
+    if offsets.is_empty() {
+        return Err(Error::ReadData);
+    }
+    let warcinfo_id = to
+        .warcinfo_id()
+        .map(|id| String::from_utf8_lossy(id).into_owned());
+
+    let mut ids = Vec::with_capacity(offsets.len());
+    for offset in offsets {
+        let entry = from.entry_at(offset).unwrap();
+        let id = entry.record.warc_id().to_owned();
+        let verbatim = to.writes_verbatim()
+            && entry.record.warc_type() != &RecordType::WarcInfo
+            && entry.record.header(WarcHeader::WarcInfoID).as_deref() == warcinfo_id.as_deref();
+        let raw = from.read_raw(offset)?;
+
+        if verbatim {
+            to.write_verbatim(&raw).map_err(|_| Error::WriteData)?;
+        } else {
+            let (mut headers, body) = WarcReader::new(Cursor::new(raw))
+                .iter_raw_records()
+                .next()
+                .unwrap_or(Err(Error::UnexpectedEOB))?;
+            match warcinfo_id {
+                Some(ref warcinfo_id) => {
+                    headers
+                        .as_mut()
+                        .insert(WarcHeader::WarcInfoID, warcinfo_id.clone().into_bytes());
+                }
+                None => {
+                    headers.as_mut().remove(&WarcHeader::WarcInfoID);
+                }
+            }
+            to.write_raw(headers, &body).map_err(|_| Error::WriteData)?;
+        }
+        ids.push(id);
+    }
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{transplant, Collection};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, CaptureSession, Record, RecordType, WarcReader, WarcWriter};
+
+    fn source() -> Vec<u8> {
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_version("1.0");
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+
+        let mut session = CaptureSession::new().warcinfo_id(warcinfo.warc_id());
+        for uri in ["http://example.com/a", "http://example.com/b"] {
+            let capture = session.begin(uri);
+            capture.request().replace_body(&b"GET"[..]);
+            capture.response().replace_body(&b"200"[..]);
+        }
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write(&warcinfo).unwrap();
+        for mut record in session.into_records() {
+            record.set_warc_version("1.0");
+            writer.write(&record).unwrap();
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn read_by_location() {
+        let mut collection = Collection::open(Cursor::new(source())).unwrap();
+        assert_eq!(collection.entries().len(), 5);
+
+        let entry = collection.entries()[3].clone();
+        assert_eq!(
+            collection.get(entry.record.warc_id()).unwrap().offset,
+            entry.offset
+        );
+        let record = collection.read_record(entry.offset).unwrap();
+        assert_eq!(record.warc_id(), entry.record.warc_id());
+        assert_eq!(record.body(), b"GET");
+
+        let linked = collection.linked(entry.offset);
+        assert_eq!(
+            linked,
+            vec![
+                collection.entries()[3].offset,
+                collection.entries()[4].offset
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_concurrent_to() {
+        let record = |warc_type| {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(warc_type);
+            record
+        };
+        let response = record(RecordType::Response);
+        let mut request = record(RecordType::Request);
+        request
+            .set_header(WarcHeader::ConcurrentTo, response.warc_id())
+            .unwrap();
+        let resource = record(RecordType::Resource);
+        let mut metadata = record(RecordType::Metadata);
+        metadata
+            .add_header(WarcHeader::ConcurrentTo, response.warc_id())
+            .unwrap();
+        metadata
+            .add_header(WarcHeader::ConcurrentTo, resource.warc_id())
+            .unwrap();
+        let unrelated = record(RecordType::Resource);
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for record in [&response, &request, &resource, &metadata, &unrelated] {
+            writer.write(record).unwrap();
+        }
+        drop(writer);
+        let collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let entries = collection.entries();
+
+        assert_eq!(
+            entries[3].concurrent_to(),
+            vec![response.warc_id(), resource.warc_id()]
+        );
+        let linked: Vec<_> = entries[..4].iter().map(|entry| entry.offset).collect();
+        assert_eq!(collection.linked(entries[2].offset), linked);
+        assert_eq!(collection.linked(entries[0].offset), linked);
+        assert_eq!(
+            collection.linked(entries[4].offset),
+            vec![entries[4].offset]
+        );
+    }
+
+    #[test]
+    fn transplant_capture() {
+        let mut collection = Collection::open(Cursor::new(source())).unwrap();
+        let response = collection.entries()[2].offset;
+
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_version("1.0");
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write(&warcinfo).unwrap();
+        let ids = transplant(response, &mut collection, &mut writer).unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let records: Vec<_> = WarcReader::new(Cursor::new(buffer))
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        for (record, id) in records[1..].iter().zip(&ids) {
+            assert_eq!(record.warc_id(), id);
+            assert_eq!(
+                record.header(WarcHeader::WarcInfoID).unwrap(),
+                warcinfo.warc_id()
+            );
+        }
+        assert_eq!(records[2].body(), b"200");
+    }
+}
@@ -0,0 +1,432 @@
+//! A runner for checking the readers of this crate against a corpus of WARC data.
+//!
+//! The built-in corpus contains the record examples from the WARC specification (ISO 28500) and
+//! a set of known tricky cases. Additional cases can be loaded from files, such as the examples
+//! distributed with the IIPC warc-specification repository.
+//!
+//! Each case is run through every reader and through `Validator::standard`, producing a pass/fail
+//! matrix which can be exported as tab-separated values or JSON.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Cursor};
+use std::path::Path;
+
+use crate::json::JsonString;
+use crate::validate::Validator;
+use crate::{ParserConfig, WarcReader};
+
+/// The outcome a conformance case is expected to have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Expectation {
+    /// The input is valid and contains the given number of records.
+    Accept(usize),
+    /// The input contains an error which must be reported.
+    Reject,
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expectation::Accept(n) => write!(f, "accept({})", n),
+            Expectation::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+/// A reader configuration exercised by the runner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// `WarcReader::iter_raw_records`.
+    Raw,
+    /// `WarcReader::iter_records`.
+    Records,
+    /// `WarcReader::stream_records`, reading every body.
+    Streaming,
+    /// `Validator::standard`, which accepts a file if it finds no errors.
+    Validator,
+}
+
+impl Mode {
+    /// All modes, in the order of the columns of a report.
+    pub const ALL: [Mode; 4] = [Mode::Raw, Mode::Records, Mode::Streaming, Mode::Validator];
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mode::Raw => write!(f, "raw"),
+            Mode::Records => write!(f, "records"),
+            Mode::Streaming => write!(f, "streaming"),
+            Mode::Validator => write!(f, "validator"),
+        }
+    }
+}
+
+/// A single conformance test case.
+#[derive(Clone, Debug)]
+pub struct Case {
+    /// A unique name for this case.
+    pub name: String,
+    /// The raw WARC data.
+    pub input: Vec<u8>,
+    /// The expected outcome for the semantic readers.
+    pub expected: Expectation,
+    /// The expected outcome for the raw reader, which only checks well-formedness.
+    pub expected_raw: Expectation,
+    /// The expected outcome for the validator, which also checks the rules of the standard that
+    /// the readers do not enforce.
+    pub expected_valid: Expectation,
+}
+
+impl Case {
+    /// Create a case whose outcome is the same for every reader.
+    pub fn new<S: Into<String>, B: Into<Vec<u8>>>(
+        name: S,
+        input: B,
+        expected: Expectation,
+    ) -> Self {
+        Case {
+            name: name.into(),
+            input: input.into(),
+            expected,
+            expected_raw: expected,
+            expected_valid: expected,
+        }
+    }
+
+    /// Create a case by reading an uncompressed WARC file.
+    pub fn from_path<P: AsRef<Path>>(path: P, expected: Expectation) -> io::Result<Self> {
+        let name = path.as_ref().display().to_string();
+        Ok(Case::new(name, fs::read(path)?, expected))
+    }
+
+    fn expected_for(&self, mode: Mode) -> Expectation {
+        match mode {
+            Mode::Raw => self.expected_raw,
+            Mode::Records | Mode::Streaming => self.expected,
+            Mode::Validator => self.expected_valid,
+        }
+    }
+}
+
+/// The result of running one case in one mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    /// The mode the case was run in.
+    pub mode: Mode,
+    /// The outcome the case was expected to have.
+    pub expected: Expectation,
+    /// The outcome observed.
+    pub actual: Expectation,
+}
+
+impl Outcome {
+    /// Return true if the observed outcome was the expected one.
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// The pass/fail matrix of a conformance run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Each case name, and its outcome in every mode.
+    pub rows: Vec<(String, Vec<Outcome>)>,
+}
+
+impl Report {
+    /// Return true if every case passed in every mode.
+    pub fn all_passed(&self) -> bool {
+        self.rows
+            .iter()
+            .all(|(_, outcomes)| outcomes.iter().all(Outcome::passed))
+    }
+
+    /// Return the name and outcome of every failure.
+    pub fn failures(&self) -> Vec<(&str, &Outcome)> {
+        self.rows
+            .iter()
+            .flat_map(|(name, outcomes)| {
+                outcomes
+                    .iter()
+                    .filter(|outcome| !outcome.passed())
+                    .map(move |outcome| (name.as_str(), outcome))
+            })
+            .collect()
+    }
+
+    /// Format the matrix as tab-separated values, with one row per case and one column per mode.
+    pub fn to_tsv(&self) -> String {
+        let mut out = String::from("case");
+        for mode in Mode::ALL.iter() {
+            out.push('\t');
+            out.push_str(&mode.to_string());
+        }
+        out.push('\n');
+        for (name, outcomes) in &self.rows {
+            out.push_str(name);
+            for outcome in outcomes {
+                out.push('\t');
+                out.push_str(if outcome.passed() { "pass" } else { "fail" });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Format the matrix as a JSON array of objects.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|(name, outcomes)| {
+                let cells: Vec<String> = outcomes
+                    .iter()
+                    .map(|o| {
+                        format!(
+                            "\"{}\":{{\"expected\":\"{}\",\"actual\":\"{}\",\"pass\":{}}}",
+                            o.mode,
+                            o.expected,
+                            o.actual,
+                            o.passed()
+                        )
+                    })
+                    .collect();
+                format!("{{\"case\":{},{}}}", JsonString(name), cells.join(","))
+            })
+            .collect();
+        format!("[{}]", rows.join(","))
+    }
+}
+
+/// Run a single case in the given mode, returning the observed outcome.
+pub fn run_case(case: &Case, mode: Mode, config: &ParserConfig) -> Expectation {
+    let reader = WarcReader::new(BufReader::new(Cursor::new(case.input.as_slice())))
+        .with_config(config.clone());
+    let mut count = 0;
+    match mode {
+        Mode::Raw => {
+            for record in reader.iter_raw_records() {
+                if record.is_err() {
+                    return Expectation::Reject;
+                }
+                count += 1;
+            }
+        }
+        Mode::Records => {
+            for record in reader.iter_records() {
+                if record.is_err() {
+                    return Expectation::Reject;
+                }
+                count += 1;
+            }
+        }
+        Mode::Streaming => {
+            let mut reader = reader;
+            let mut iter = reader.stream_records();
+            while let Some(record) = iter.next_item() {
+                match record.map(|r| r.into_buffered()) {
+                    Ok(Ok(_)) => count += 1,
+                    _ => return Expectation::Reject,
+                }
+            }
+        }
+        Mode::Validator => {
+            let report = Validator::standard().validate(reader);
+            if !report.is_valid() {
+                return Expectation::Reject;
+            }
+            count = report.records;
+        }
+    }
+    Expectation::Accept(count)
+}
+
+/// Run every case in every mode with the default (strict) configuration.
+pub fn run(cases: &[Case]) -> Report {
+    run_with_config(cases, &ParserConfig::default())
+}
+
+/// Run every case in every mode with the given configuration.
+pub fn run_with_config(cases: &[Case], config: &ParserConfig) -> Report {
+    let rows = cases
+        .iter()
+        .map(|case| {
+            let outcomes = Mode::ALL
+                .iter()
+                .map(|&mode| Outcome {
+                    mode,
+                    expected: case.expected_for(mode),
+                    actual: run_case(case, mode, config),
+                })
+                .collect();
+            (case.name.clone(), outcomes)
+        })
+        .collect();
+    Report { rows }
+}
+
+/// Return the built-in corpus of specification examples and tricky cases.
+pub fn builtin_corpus() -> Vec<Case> {
+    const WARCINFO: &[u8] = b"\
+WARC/1.1\r\n\
+WARC-Type: warcinfo\r\n\
+WARC-Date: 2016-09-19T17:20:14Z\r\n\
+WARC-Record-ID: <urn:uuid:d7ae5c10-e6b3-4d27-967d-34780c58ba39>\r\n\
+Content-Type: application/warc-fields\r\n\
+Content-Length: 14\r\n\
+\r\n\
+software: test\r\n\
+\r\n";
+    const REQUEST: &[u8] = b"\
+WARC/1.1\r\n\
+WARC-Type: request\r\n\
+WARC-Target-URI: http://www.archive.org/images/logoc.jpg\r\n\
+WARC-Warcinfo-ID: <urn:uuid:d7ae5c10-e6b3-4d27-967d-34780c58ba39>\r\n\
+WARC-Date: 2016-09-19T17:20:24Z\r\n\
+Content-Length: 57\r\n\
+WARC-Record-ID: <urn:uuid:4885803b-eebd-4b27-a090-144450c11594>\r\n\
+Content-Type: application/http;msgtype=request\r\n\
+WARC-Concurrent-To: <urn:uuid:92283950-ef2f-4d72-b224-f54c6ec90bb0>\r\n\
+\r\n\
+GET /images/logoc.jpg HTTP/1.0\r\nHost: www.archive.org\r\n\r\n\
+\r\n\
+\r\n";
+    const RESPONSE: &[u8] = b"\
+WARC/1.1\r\n\
+WARC-Type: response\r\n\
+WARC-Target-URI: http://www.archive.org/images/logoc.jpg\r\n\
+WARC-Date: 2016-09-19T17:20:24Z\r\n\
+WARC-Block-Digest: sha1:UZY6ND6CCHXETFVJD2MSS7ZENMWF7KQ2\r\n\
+WARC-Payload-Digest: sha1:CCHXETFVJD2MUZY6ND6SS7ZENMWF7KQ2\r\n\
+WARC-IP-Address: 207.241.233.58\r\n\
+WARC-Record-ID: <urn:uuid:92283950-ef2f-4d72-b224-f54c6ec90bb0>\r\n\
+Content-Type: application/http;msgtype=response\r\n\
+WARC-Identified-Payload-Type: image/jpeg\r\n\
+Content-Length: 38\r\n\
+\r\n\
+HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n\
+\r\n\
+\r\n";
+    const REVISIT: &[u8] = b"\
+WARC/1.1\r\n\
+WARC-Type: revisit\r\n\
+WARC-Target-URI: http://www.archive.org/images/logoc.jpg\r\n\
+WARC-Date: 2016-09-19T17:20:24Z\r\n\
+WARC-Profile: http://netpreserve.org/warc/1.1/revisit/identical-payload-digest\r\n\
+WARC-Refers-To: <urn:uuid:92283950-ef2f-4d72-b224-f54c6ec90bb0>\r\n\
+WARC-Payload-Digest: sha1:CCHXETFVJD2MUZY6ND6SS7ZENMWF7KQ2\r\n\
+WARC-Record-ID: <urn:uuid:16da6da0-bcdc-49c3-927e-57494593bbbb>\r\n\
+Content-Length: 0\r\n\
+\r\n\
+\r\n\
+\r\n";
+
+    let concat = |parts: &[&[u8]]| parts.concat();
+
+    vec![
+        Case::new("spec/warcinfo", WARCINFO, Expectation::Accept(1)),
+        Case::new("spec/request", REQUEST, Expectation::Accept(1)),
+        Case::new("spec/response", RESPONSE, Expectation::Accept(1)),
+        Case::new("spec/revisit", REVISIT, Expectation::Accept(1)),
+        Case::new(
+            "spec/full-capture",
+            concat(&[WARCINFO, REQUEST, RESPONSE, REVISIT]),
+            Expectation::Accept(4),
+        ),
+        Case::new("tricky/empty-input", &b""[..], Expectation::Accept(0)),
+        Case::new(
+            "tricky/lowercase-header-names",
+            &b"WARC/1.0\r\nwarc-type: resource\r\nwarc-date: 2020-07-08T02:52:55Z\r\n\
+warc-record-id: <urn:test:lowercase>\r\nwarc-target-uri: http://example.com/\r\n\
+content-length: 2\r\n\r\nok\r\n\r\n"[..],
+            Expectation::Accept(1),
+        ),
+        Case::new(
+            "tricky/space-before-colon",
+            &b"WARC/1.0\r\nWARC-Type : resource\r\nWARC-Date : 2020-07-08T02:52:55Z\r\n\
+WARC-Record-ID : <urn:test:space>\r\nWARC-Target-URI : http://example.com/\r\n\
+Content-Length : 2\r\n\r\nok\r\n\r\n"[..],
+            Expectation::Accept(1),
+        ),
+        Case::new(
+            "tricky/truncated-body",
+            &b"WARC/1.0\r\nWARC-Type: resource\r\nWARC-Date: 2020-07-08T02:52:55Z\r\n\
+WARC-Record-ID: <urn:test:truncated>\r\nContent-Length: 20\r\n\r\nshort\r\n\r\n"[..],
+            Expectation::Reject,
+        ),
+        Case::new(
+            "tricky/non-numeric-length",
+            &b"WARC/1.0\r\nWARC-Type: resource\r\nWARC-Date: 2020-07-08T02:52:55Z\r\n\
+WARC-Record-ID: <urn:test:length>\r\nContent-Length: two\r\n\r\nok\r\n\r\n"[..],
+            Expectation::Reject,
+        ),
+        Case {
+            name: "tricky/duplicate-record-id".to_string(),
+            input: concat(&[REQUEST, REQUEST]),
+            expected: Expectation::Accept(2),
+            expected_raw: Expectation::Accept(2),
+            expected_valid: Expectation::Reject,
+        },
+        Case {
+            name: "tricky/missing-record-id".to_string(),
+            input: b"WARC/1.0\r\nWARC-Type: resource\r\nWARC-Date: 2020-07-08T02:52:55Z\r\n\
+Content-Length: 2\r\n\r\nok\r\n\r\n"
+                .to_vec(),
+            expected: Expectation::Reject,
+            expected_raw: Expectation::Accept(1),
+            expected_valid: Expectation::Reject,
+        },
+        Case {
+            name: "tricky/malformed-date".to_string(),
+            input: b"WARC/1.0\r\nWARC-Type: resource\r\nWARC-Date: yesterday\r\n\
+WARC-Record-ID: <urn:test:date>\r\nContent-Length: 2\r\n\r\nok\r\n\r\n"
+                .to_vec(),
+            expected: Expectation::Reject,
+            expected_raw: Expectation::Accept(1),
+            expected_valid: Expectation::Reject,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin_corpus, run, Case, Expectation, Mode};
+
+    #[test]
+    fn builtin_corpus_passes() {
+        let report = run(&builtin_corpus());
+        assert!(report.all_passed(), "{:?}", report.failures());
+    }
+
+    #[test]
+    fn report_formats() {
+        let report = run(&builtin_corpus()[..1]);
+        assert_eq!(
+            report.to_tsv(),
+            "case\traw\trecords\tstreaming\tvalidator\nspec/warcinfo\tpass\tpass\tpass\tpass\n"
+        );
+        assert!(report
+            .to_json()
+            .starts_with("[{\"case\":\"spec/warcinfo\","));
+        assert_eq!(report.rows[0].1[0].mode, Mode::Raw);
+        assert_eq!(report.rows[0].1[0].actual, Expectation::Accept(1));
+
+        let cases: Vec<_> = builtin_corpus()
+            .into_iter()
+            .filter(|case| case.name == "tricky/duplicate-record-id")
+            .collect();
+        let report = run(&cases);
+        let outcome = &report.rows[0].1[3];
+        assert_eq!(outcome.mode, Mode::Validator);
+        assert_eq!(outcome.actual, Expectation::Reject);
+        assert_eq!(report.rows[0].1[1].actual, Expectation::Accept(2));
+
+        let report = run(&[Case::new("a \"b\"\t", &b""[..], Expectation::Reject)]);
+        assert!(report.to_json().starts_with(
+            "[{\"case\":\"a \\\"b\\\"\\t\",\
+             \"raw\":{\"expected\":\"reject\",\"actual\":\"accept(0)\",\"pass\":false},"
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::SecondsFormat;
+
+/// The precision used when formatting the WARC-Date header of a record.
+///
+/// WARC/1.0 only allows whole seconds; finer precision requires WARC/1.1 or later.
+///
+/// The precision of a record is reduced to `Seconds` whenever its version does not allow finer.
+/// A WARC/1.0 record read with fractional seconds in its WARC-Date therefore loses the fraction
+/// when it is written again, unless its version is first changed to WARC/1.1.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DatePrecision {
+    /// Whole seconds, such as `2020-01-02T03:04:05Z`. This is the only precision WARC/1.0
+    /// allows.
+    #[default]
+    Seconds,
+    /// Milliseconds, such as `2020-01-02T03:04:05.123Z`.
+    Millis,
+    /// Microseconds, such as `2020-01-02T03:04:05.123456Z`.
+    Micros,
+    /// Nanoseconds, such as `2020-01-02T03:04:05.123456789Z`.
+    Nanos,
+}
+
+impl DatePrecision {
+    /// Return the smallest precision which can represent the given number of fractional second
+    /// digits without loss.
+    pub fn from_fraction_digits(digits: usize) -> Self {
+        match digits {
+            0 => DatePrecision::Seconds,
+            1..=3 => DatePrecision::Millis,
+            4..=6 => DatePrecision::Micros,
+            _ => DatePrecision::Nanos,
+        }
+    }
+
+    /// Return true if this precision includes fractional seconds.
+    pub fn is_subsecond(self) -> bool {
+        self != DatePrecision::Seconds
+    }
+}
+
+impl From<DatePrecision> for SecondsFormat {
+    fn from(precision: DatePrecision) -> Self {
+        match precision {
+            DatePrecision::Seconds => SecondsFormat::Secs,
+            DatePrecision::Millis => SecondsFormat::Millis,
+            DatePrecision::Micros => SecondsFormat::Micros,
+            DatePrecision::Nanos => SecondsFormat::Nanos,
+        }
+    }
+}
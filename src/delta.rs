@@ -0,0 +1,422 @@
+//! Incremental crawls, which store only the payloads that changed since an earlier crawl.
+//!
+//! A `DeltaWriter` looks up the payload digest of each response and resource record in a
+//! `DedupStore` index before writing it. A record whose payload was captured before is written as
+//! a revisit record with the identical-payload-digest profile, holding only the HTTP head, and
+//! referring to the earlier capture. Other records are written in full, and added to the index.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Seek, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::digest::{Algorithm, Digest};
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{Collection, Record, RecordType, RefersTo, RevisitProfile, WarcWriter};
+
+/// A capture of a payload which later records may refer to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriorCapture {
+    pub record_id: String,
+    pub target_uri: String,
+    pub date: DateTime<Utc>,
+}
+
+impl From<PriorCapture> for RefersTo {
+    fn from(capture: PriorCapture) -> Self {
+        RefersTo::record_id(capture.record_id).capture(capture.target_uri, capture.date)
+    }
+}
+
+/// An index of captured payloads by digest.
+///
+/// Implement this over a persistent store to deduplicate against captures of earlier crawls
+/// without holding them in memory.
+pub trait DedupStore {
+    /// Return a capture of a payload with the given digest, preferring a capture of the given
+    /// target URI.
+    fn lookup(&self, digest: &Digest, target_uri: &str) -> Option<PriorCapture>;
+
+    /// Add a capture of a payload with the given digest.
+    fn insert(&mut self, digest: Digest, capture: PriorCapture);
+}
+
+/// An in-memory `DedupStore` index.
+#[derive(Clone, Debug, Default)]
+pub struct DigestIndex {
+    captures: HashMap<Digest, Vec<PriorCapture>>,
+}
+
+impl DigestIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        DigestIndex::default()
+    }
+
+    /// Create an index of the response and resource records of a collection which have a
+    /// WARC-Target-URI and a WARC-Payload-Digest which can be parsed.
+    pub fn from_collection<R: BufRead + Seek>(collection: &Collection<R>) -> Self {
+        let mut index = DigestIndex::new();
+        for entry in collection.entries() {
+            let record = &entry.record;
+            if !matches!(
+                record.warc_type(),
+                RecordType::Response | RecordType::Resource
+            ) {
+                continue;
+            }
+            let digest = record
+                .header(WarcHeader::PayloadDigest)
+                .and_then(|digest| Digest::parse(&digest));
+            if let (Some(digest), Some(target_uri)) = (digest, record.header(WarcHeader::TargetURI))
+            {
+                index.insert(
+                    digest,
+                    PriorCapture {
+                        record_id: record.warc_id().to_owned(),
+                        target_uri: target_uri.into_owned(),
+                        date: *record.date(),
+                    },
+                );
+            }
+        }
+
+        index
+    }
+
+    /// Return the number of distinct payloads in the index.
+    pub fn len(&self) -> usize {
+        self.captures.len()
+    }
+
+    /// Return true if the index holds no payloads.
+    pub fn is_empty(&self) -> bool {
+        self.captures.is_empty()
+    }
+}
+
+impl DedupStore for DigestIndex {
+    fn lookup(&self, digest: &Digest, target_uri: &str) -> Option<PriorCapture> {
+        let captures = self.captures.get(digest)?;
+        captures
+            .iter()
+            .find(|capture| capture.target_uri == target_uri)
+            .or_else(|| captures.first())
+            .cloned()
+    }
+
+    fn insert(&mut self, digest: Digest, capture: PriorCapture) {
+        self.captures.entry(digest).or_default().push(capture);
+    }
+}
+
+/// How a `DeltaWriter` wrote a record.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delta {
+    /// The record was written in full.
+    Full,
+    /// A revisit record referring to the given capture was written in place of the record.
+    Revisit(PriorCapture),
+}
+
+/// A writer which deduplicates payloads against a `DedupStore` index.
+pub struct DeltaWriter<W: Write, D = DigestIndex> {
+    writer: WarcWriter<W>,
+    index: D,
+    algorithm: Algorithm,
+}
+
+impl<W: Write, D: DedupStore> DeltaWriter<W, D> {
+    /// Create a writer which looks up payloads in the index, typically built from the collection
+    /// of the previous crawl.
+    pub fn new(writer: WarcWriter<W>, index: D) -> Self {
+        DeltaWriter {
+            writer,
+            index,
+            algorithm: Algorithm::Sha1,
+        }
+    }
+
+    /// Set the algorithm used to digest payloads of records without a WARC-Payload-Digest header.
+    ///
+    /// Digests only match digests computed by the same algorithm, so this should be the algorithm
+    /// used by the index. The default is SHA-1.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+
+        self
+    }
+
+    /// Write a record, or a revisit record in its place if its payload is in the index.
+    ///
+    /// Only response and resource records with a WARC-Target-URI and a non-empty payload are
+    /// deduplicated. Those written in full are given a WARC-Payload-Digest header if they have
+    /// none, so that a later crawl can be deduplicated against them, and are added to the index,
+    /// so that payloads repeated within the crawl are also deduplicated.
+    pub fn write<T: BodyKind + AsRef<[u8]>>(&mut self, record: &Record<T>) -> io::Result<Delta> {
+        let target_uri = record.header(WarcHeader::TargetURI);
+        let payload = match record.warc_type() {
+            RecordType::Response | RecordType::Resource => record.payload(),
+            _ => None,
+        };
+        let (target_uri, payload) = match (target_uri, payload) {
+            (Some(target_uri), Some(payload)) if !payload.is_empty() => (target_uri, payload),
+            _ => {
+                self.writer.write(record)?;
+                return Ok(Delta::Full);
+            }
+        };
+        let existing = record
+            .header(WarcHeader::PayloadDigest)
+            .and_then(|digest| Digest::parse(&digest));
+        let digest = existing
+            .clone()
+            .unwrap_or_else(|| self.algorithm.compute(payload));
+
+        let prior = match self.index.lookup(&digest, &target_uri) {
+            Some(prior) => prior,
+            None => {
+                if existing.is_some() {
+                    self.writer.write(record)?;
+                } else {
+                    let mut headers = record
+                        .header_block(None)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                    headers
+                        .as_mut()
+                        .insert(WarcHeader::PayloadDigest, digest.to_string().into_bytes());
+                    self.writer.write_raw(headers, &record.body())?;
+                }
+                let capture = PriorCapture {
+                    record_id: record.warc_id().to_owned(),
+                    target_uri: target_uri.into_owned(),
+                    date: *record.date(),
+                };
+                self.index.insert(digest, capture);
+                return Ok(Delta::Full);
+            }
+        };
+
+        let head = &record.body()[..record.body().len() - payload.len()];
+        let mut headers = record
+            .header_block(None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let version = headers.version.clone();
+        let map = headers.as_mut();
+        let block_digest = map
+            .remove(&WarcHeader::BlockDigest)
+            .map(|_| self.algorithm.compute(head).to_string());
+        map.remove(&WarcHeader::Truncated);
+        let revisit = vec![
+            (WarcHeader::WarcType, RecordType::Revisit.to_string()),
+            (
+                WarcHeader::Profile,
+                RevisitProfile::IdenticalPayloadDigest.uri(&version),
+            ),
+            (WarcHeader::PayloadDigest, digest.to_string()),
+            (WarcHeader::RefersTo, prior.record_id.clone()),
+            (WarcHeader::RefersToTargetURI, prior.target_uri.clone()),
+            (
+                WarcHeader::RefersToDate,
+                prior.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+            (WarcHeader::ContentLength, head.len().to_string()),
+        ];
+        for (header, value) in revisit
+            .into_iter()
+            .chain(block_digest.map(|digest| (WarcHeader::BlockDigest, digest)))
+        {
+            map.insert(header, value.into_bytes());
+        }
+        self.writer.write_raw(headers, &head)?;
+
+        Ok(Delta::Revisit(prior))
+    }
+
+    /// Return the index.
+    pub fn index(&self) -> &D {
+        &self.index
+    }
+
+    /// Return the underlying writer and the index.
+    pub fn into_inner(self) -> (WarcWriter<W>, D) {
+        (self.writer, self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{DedupStore, Delta, DeltaWriter, DigestIndex, PriorCapture};
+    use crate::digest::Algorithm;
+    use crate::header::WarcHeader;
+    use crate::{
+        BufferedBody, Collection, Record, RecordType, RevisitProfile, WarcReader, WarcWriter,
+    };
+
+    fn response(uri: &str, payload: &str) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record.set_warc_type(RecordType::Response);
+        record.set_header(WarcHeader::TargetURI, uri).unwrap();
+        record
+            .set_header(
+                WarcHeader::ContentType,
+                "application/http; msgtype=response",
+            )
+            .unwrap();
+        record.replace_body(format!("HTTP/1.1 200 OK\r\n\r\n{}", payload));
+        record
+    }
+
+    #[test]
+    fn incremental_crawl() {
+        let mut previous = Vec::new();
+        let mut writer = DeltaWriter::new(WarcWriter::new(&mut previous), DigestIndex::new());
+        let first = response("http://example.com/", "unchanged");
+        assert_eq!(writer.write(&first).unwrap(), Delta::Full);
+        writer
+            .write(&response("http://example.com/a", "old"))
+            .unwrap();
+        drop(writer);
+
+        let collection = Collection::open(Cursor::new(previous)).unwrap();
+        assert_eq!(collection.entries().len(), 2);
+        let index = DigestIndex::from_collection(&collection);
+        assert_eq!(index.len(), 2);
+
+        let mut buffer = Vec::new();
+        let mut writer = DeltaWriter::new(WarcWriter::new(&mut buffer), index);
+        match writer
+            .write(&response("http://example.com/", "unchanged"))
+            .unwrap()
+        {
+            Delta::Revisit(prior) => assert_eq!(prior.record_id, first.warc_id()),
+            Delta::Full => panic!("unchanged payload written in full"),
+        }
+        let changed = response("http://example.com/a", "new");
+        assert_eq!(writer.write(&changed).unwrap(), Delta::Full);
+        assert!(matches!(
+            writer
+                .write(&response("http://example.com/b", "new"))
+                .unwrap(),
+            Delta::Revisit(_)
+        ));
+        drop(writer);
+
+        let records: Vec<_> = WarcReader::new(buffer.as_slice())
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        let revisit = &records[0];
+        assert_eq!(revisit.warc_type(), &RecordType::Revisit);
+        assert_eq!(
+            revisit.revisit_profile(),
+            Some(RevisitProfile::IdenticalPayloadDigest)
+        );
+        assert_eq!(revisit.body(), b"HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(
+            revisit.header(WarcHeader::RefersTo).as_deref(),
+            Some(first.warc_id())
+        );
+        assert_eq!(
+            revisit.header(WarcHeader::RefersToTargetURI).as_deref(),
+            Some("http://example.com/")
+        );
+        assert_eq!(
+            records[2].header(WarcHeader::RefersTo).as_deref(),
+            Some(changed.warc_id())
+        );
+    }
+
+    #[test]
+    fn miss_then_insert() {
+        let digest = Algorithm::Sha1.compute(b"payload");
+        let capture = |uri: &str| PriorCapture {
+            record_id: format!("<urn:test:{}>", uri),
+            target_uri: uri.to_owned(),
+            date: chrono::Utc::now(),
+        };
+
+        let mut index = DigestIndex::new();
+        assert!(index.is_empty());
+        assert_eq!(index.lookup(&digest, "http://example.com/"), None);
+        index.insert(digest.clone(), capture("http://example.com/a"));
+        index.insert(digest.clone(), capture("http://example.com/b"));
+        assert_eq!(index.len(), 1);
+
+        let found = |uri| index.lookup(&digest, uri).unwrap().target_uri;
+        assert_eq!(found("http://example.com/b"), "http://example.com/b");
+        assert_eq!(found("http://example.com/a"), "http://example.com/a");
+        assert_eq!(found("http://example.com/c"), "http://example.com/a");
+        assert_eq!(
+            index.lookup(&Algorithm::Sha1.compute(b"other"), "http://example.com/a"),
+            None
+        );
+    }
+
+    #[test]
+    fn prefer_target_uri() {
+        let mut buffer = Vec::new();
+        let mut writer = DeltaWriter::new(WarcWriter::new(&mut buffer), DigestIndex::new());
+        let a = response("http://example.com/a", "same");
+        let b = response("http://example.com/b", "same");
+        assert_eq!(writer.write(&a).unwrap(), Delta::Full);
+        match writer.write(&b).unwrap() {
+            Delta::Revisit(prior) => assert_eq!(prior.record_id, a.warc_id()),
+            Delta::Full => panic!("repeated payload written in full"),
+        }
+        // only records written in full are added to the index
+        assert_eq!(writer.index().len(), 1);
+
+        let mut index = writer.into_inner().1;
+        index.insert(
+            Algorithm::Sha1.compute(b"same"),
+            PriorCapture {
+                record_id: b.warc_id().to_owned(),
+                target_uri: "http://example.com/b".to_owned(),
+                date: *b.date(),
+            },
+        );
+        let mut buffer = Vec::new();
+        let mut writer = DeltaWriter::new(WarcWriter::new(&mut buffer), index);
+        match writer
+            .write(&response("http://example.com/b", "same"))
+            .unwrap()
+        {
+            Delta::Revisit(prior) => {
+                assert_eq!(prior.record_id, b.warc_id());
+                assert_eq!(prior.target_uri, "http://example.com/b");
+            }
+            Delta::Full => panic!("repeated payload written in full"),
+        }
+    }
+
+    #[test]
+    fn other_record_types() {
+        let mut buffer = Vec::new();
+        let mut writer = DeltaWriter::new(WarcWriter::new(&mut buffer), DigestIndex::new());
+        let mut request = response("http://example.com/", "body");
+        request.set_warc_type(RecordType::Request);
+        let mut metadata = response("http://example.com/", "body");
+        metadata.set_warc_type(RecordType::Metadata);
+        for record in [&request, &request, &metadata] {
+            assert_eq!(writer.write(record).unwrap(), Delta::Full);
+        }
+        assert!(writer.index().is_empty());
+        drop(writer);
+
+        let records: Vec<_> = WarcReader::new(buffer.as_slice())
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].warc_type(), &RecordType::Request);
+        assert_eq!(records[1].body(), request.body());
+        assert_eq!(records[1].header(WarcHeader::PayloadDigest), None);
+        assert_eq!(records[2].warc_type(), &RecordType::Metadata);
+    }
+}
@@ -0,0 +1,548 @@
+//! Computation and checking of the digests in WARC-Block-Digest and WARC-Payload-Digest headers.
+//!
+//! A digest header has the form `algorithm:value`. Values are written in base32, as recommended
+//! by the standard, and read in base32 or hexadecimal.
+//!
+//! `Redigest` rewrites whole files, replacing the digests of every record with digests computed
+//! by another algorithm, such as when moving a collection off SHA-1.
+//!
+//! Index files, such as CDX or CDXJ files, can be protected by a sidecar checksum file written
+//! next to them, so that a corrupted index is refused rather than silently serving the wrong
+//! records. Sidecars use the format of `sha256sum` and `sha1sum`, and can be checked with those
+//! tools too.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use data_encoding::{BASE32, HEXLOWER, HEXLOWER_PERMISSIVE};
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256};
+
+use crate::header::WarcHeader;
+use crate::{http, Error, RawRecordHeader, RecordType, WarcReader, WarcWriter, WarcinfoBuilder};
+
+/// A digest algorithm.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Return the label of this algorithm in a digest header.
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Return the algorithm with the given label, matched case-insensitively.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "sha1" | "sha-1" => Some(Algorithm::Sha1),
+            "sha256" | "sha-256" => Some(Algorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Return the length of the digests computed by this algorithm, in bytes.
+    pub fn output_len(&self) -> usize {
+        match *self {
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 => 32,
+        }
+    }
+
+    /// Compute the digest of the given data.
+    pub fn compute(&self, data: &[u8]) -> Digest {
+        let bytes = match *self {
+            Algorithm::Sha1 => Sha1::digest(data).to_vec(),
+            Algorithm::Sha256 => Sha256::digest(data).to_vec(),
+        };
+
+        Digest {
+            algorithm: *self,
+            bytes,
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// The value of a digest header.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Digest {
+    pub algorithm: Algorithm,
+    pub bytes: Vec<u8>,
+}
+
+impl Digest {
+    /// Parse the value of a digest header.
+    ///
+    /// `None` is returned if the algorithm is not supported, or the value is not a digest of the
+    /// algorithm's length in base32 or hexadecimal.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (label, encoded) = value.trim().split_once(':')?;
+        let algorithm = Algorithm::from_label(label)?;
+        let encoded = encoded.trim();
+        let bytes = BASE32
+            .decode(encoded.to_ascii_uppercase().as_bytes())
+            .ok()
+            .filter(|bytes| bytes.len() == algorithm.output_len())
+            .or_else(|| {
+                HEXLOWER_PERMISSIVE
+                    .decode(encoded.as_bytes())
+                    .ok()
+                    .filter(|bytes| bytes.len() == algorithm.output_len())
+            })?;
+
+        Some(Digest { algorithm, bytes })
+    }
+
+    /// Return true if this is the digest of the given data.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.algorithm.compute(data) == *self
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, BASE32.encode(&self.bytes))
+    }
+}
+
+/// The outcome of a `Redigest` pass.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RedigestReport {
+    /// The number of records copied, not counting the warcinfo record describing the pass.
+    pub records: usize,
+    /// The number of records whose digest headers were replaced or added.
+    pub rewritten: usize,
+    /// The number of records copied unchanged because an existing digest did not match their
+    /// content, or could not be parsed.
+    pub mismatched: usize,
+    /// The number of revisit records whose payload digest was kept because the record it refers
+    /// to was not copied earlier in the same pass.
+    pub unresolved: usize,
+}
+
+/// A rewrite pass which recomputes the digests of records with another algorithm.
+///
+/// Each existing digest is checked against the content of its record before being replaced, so
+/// that a damaged record is never given a fresh digest which hides the damage. Bodies are copied
+/// as read. The payload digest of a revisit record covers the payload of the record it refers to,
+/// and is replaced by the digest computed for that record earlier in the pass.
+#[derive(Clone, Debug)]
+pub struct Redigest {
+    algorithm: Algorithm,
+    add_missing: bool,
+    warcinfo: WarcinfoBuilder,
+}
+
+impl Redigest {
+    /// Create a pass which replaces existing digests with digests computed by the given algorithm.
+    pub fn new(algorithm: Algorithm) -> Self {
+        Redigest {
+            algorithm,
+            add_missing: false,
+            warcinfo: WarcinfoBuilder::new(),
+        }
+    }
+
+    /// Also add digests to records which have none.
+    ///
+    /// A payload digest is only added to records with a payload, and never to revisit records.
+    pub fn add_missing(mut self, add_missing: bool) -> Self {
+        self.add_missing = add_missing;
+
+        self
+    }
+
+    /// Set the fields of the warcinfo record written before the records copied. A
+    /// `digest-algorithm` field naming the new algorithm is added to them.
+    pub fn warcinfo(mut self, warcinfo: WarcinfoBuilder) -> Self {
+        self.warcinfo = warcinfo;
+
+        self
+    }
+
+    /// Copy every record from the reader to the writer, recomputing their digests.
+    ///
+    /// A warcinfo record recording the migration is written first.
+    pub fn run<R: BufRead, W: Write>(
+        &self,
+        reader: WarcReader<R>,
+        writer: &mut WarcWriter<W>,
+    ) -> Result<RedigestReport, Error> {
+        let warcinfo = self
+            .warcinfo
+            .clone()
+            .field("digest-algorithm", self.algorithm.label());
+        writer
+            .write_warcinfo(&warcinfo)
+            .map_err(|_| Error::WriteData)?;
+
+        let mut report = RedigestReport::default();
+        let mut payload_digests: HashMap<Digest, Digest> = HashMap::new();
+        for record in reader.iter_raw_records() {
+            let (mut headers, body) = record?;
+            report.records += 1;
+            match self.redigest(&mut headers, &body, &mut payload_digests) {
+                Ok(Outcome::Unchanged) => {}
+                Ok(Outcome::Rewritten) => report.rewritten += 1,
+                Ok(Outcome::Unresolved) => report.unresolved += 1,
+                Err(original) => {
+                    report.mismatched += 1;
+                    headers = original;
+                }
+            }
+            writer
+                .write_raw(headers, &body)
+                .map_err(|_| Error::WriteData)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Replace the digests of one record, returning the original headers if an existing digest
+    /// does not match.
+    fn redigest(
+        &self,
+        headers: &mut RawRecordHeader,
+        body: &[u8],
+        payload_digests: &mut HashMap<Digest, Digest>,
+    ) -> Result<Outcome, RawRecordHeader> {
+        let original = headers.clone();
+        let value = |header: &WarcHeader| {
+            original
+                .as_ref()
+                .get(header)
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        let record_type = value(&WarcHeader::WarcType)
+            .map(RecordType::from)
+            .unwrap_or_else(|| RecordType::Unknown(String::new()));
+        let mut outcome = Outcome::Unchanged;
+
+        let mut content = vec![(WarcHeader::BlockDigest, Some(body))];
+        if record_type != RecordType::Revisit {
+            let payload = payload(&original, &record_type, body);
+            content.push((WarcHeader::PayloadDigest, payload));
+        }
+        for (header, data) in content {
+            let data = match data {
+                Some(data) => data,
+                None => continue,
+            };
+            let old = match value(&header) {
+                Some(old) => match Digest::parse(&old) {
+                    Some(old) if old.matches(data) => Some(old),
+                    _ => return Err(original),
+                },
+                None if self.add_missing => None,
+                None => continue,
+            };
+            if old.as_ref().map(|old| old.algorithm) == Some(self.algorithm) {
+                continue;
+            }
+
+            let new = self.algorithm.compute(data);
+            if header == WarcHeader::PayloadDigest {
+                if let Some(old) = old {
+                    payload_digests.insert(old, new.clone());
+                }
+            }
+            headers
+                .as_mut()
+                .insert(header, new.to_string().into_bytes());
+            outcome = Outcome::Rewritten;
+        }
+
+        if record_type == RecordType::Revisit {
+            if let Some(old) = value(&WarcHeader::PayloadDigest) {
+                match Digest::parse(&old) {
+                    Some(old) if old.algorithm == self.algorithm => {}
+                    Some(old) if payload_digests.contains_key(&old) => {
+                        let new = payload_digests[&old].to_string();
+                        headers
+                            .as_mut()
+                            .insert(WarcHeader::PayloadDigest, new.into_bytes());
+                        outcome = Outcome::Rewritten;
+                    }
+                    _ => return Ok(Outcome::Unresolved),
+                }
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+/// Return the line of a sidecar checksum file for an index with the given contents and file
+/// name.
+pub fn index_checksum(index: &[u8], name: &str, algorithm: Algorithm) -> String {
+    format!(
+        "{}  {}\n",
+        HEXLOWER.encode(&algorithm.compute(index).bytes),
+        name
+    )
+}
+
+/// Check the contents of an index against a sidecar checksum file.
+///
+/// The algorithm is chosen by the length of the checksum, and the file name is ignored.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidData` if the sidecar is malformed, or the index does not
+/// match it.
+pub fn check_index_checksum(index: &[u8], sidecar: &str) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    let encoded = sidecar
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| invalid("empty index checksum"))?;
+    let bytes = HEXLOWER_PERMISSIVE
+        .decode(encoded.as_bytes())
+        .map_err(|_| invalid("malformed index checksum"))?;
+    let algorithm = [Algorithm::Sha256, Algorithm::Sha1]
+        .iter()
+        .find(|algorithm| algorithm.output_len() == bytes.len())
+        .ok_or_else(|| invalid("unknown index checksum algorithm"))?;
+    if !(Digest {
+        algorithm: *algorithm,
+        bytes,
+    })
+    .matches(index)
+    {
+        return Err(invalid("index does not match its checksum"));
+    }
+
+    Ok(())
+}
+
+/// Return the path of the sidecar checksum file of an index: the path of the index with the
+/// label of the algorithm appended, such as `index.cdxj.sha256`.
+pub fn index_checksum_path<P: AsRef<Path>>(index: P, algorithm: Algorithm) -> PathBuf {
+    let mut path = index.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(algorithm.label());
+
+    PathBuf::from(path)
+}
+
+/// Write the sidecar checksum file of an index file, returning its path.
+pub fn write_index_checksum<P: AsRef<Path>>(index: P, algorithm: Algorithm) -> io::Result<PathBuf> {
+    let index = index.as_ref();
+    let name = index
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let sidecar = index_checksum(&fs::read(index)?, &name, algorithm);
+    let path = index_checksum_path(index, algorithm);
+    fs::write(&path, sidecar)?;
+
+    Ok(path)
+}
+
+/// Read an index file, verifying it against its sidecar checksum file.
+///
+/// A SHA-256 sidecar is preferred to a SHA-1 sidecar when both exist.
+///
+/// # Errors
+///
+/// Returns an error of kind `NotFound` if the index has no sidecar, or of kind `InvalidData` if
+/// the index does not match it.
+pub fn read_verified_index<P: AsRef<Path>>(index: P) -> io::Result<Vec<u8>> {
+    let index = index.as_ref();
+    let sidecar = [Algorithm::Sha256, Algorithm::Sha1]
+        .iter()
+        .map(|algorithm| index_checksum_path(index, *algorithm))
+        .find(|path| path.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "index checksum not found"))?;
+    let sidecar = fs::read_to_string(sidecar)?;
+    let data = fs::read(index)?;
+    check_index_checksum(&data, &sidecar)?;
+
+    Ok(data)
+}
+
+/// The outcome of recomputing the digests of a record whose digests matched.
+enum Outcome {
+    Unchanged,
+    Rewritten,
+    Unresolved,
+}
+
+/// Return the payload of a record with the given headers and body.
+fn payload<'b>(
+    headers: &RawRecordHeader,
+    record_type: &RecordType,
+    body: &'b [u8],
+) -> Option<&'b [u8]> {
+    if !record_type.has_payload() {
+        return None;
+    }
+    let is_http = headers
+        .as_ref()
+        .get(&WarcHeader::ContentType)
+        .is_some_and(|value| http::is_http_message(&String::from_utf8_lossy(value)));
+    if is_http {
+        http::split_head(body).map(|(_, payload)| payload)
+    } else {
+        Some(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_index_checksum, index_checksum, Algorithm, Digest, Redigest, RedigestReport,
+    };
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn parse_and_compute() {
+        let digest = Algorithm::Sha1.compute(b"hello");
+        assert_eq!(digest.to_string(), "sha1:VL2MMHO4YXUKFWV63YHTWSBM3GXKSQ2N");
+        assert_eq!(
+            Digest::parse("SHA1:vl2mmho4yxukfwv63yhtwsbm3gxksq2n"),
+            Some(digest)
+        );
+        assert_eq!(
+            Digest::parse("sha-1:aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+            Some(Algorithm::Sha1.compute(b"hello"))
+        );
+        assert!(Algorithm::Sha256
+            .compute(b"hello")
+            .to_string()
+            .starts_with("sha256:FTZE3OS7WCRQ4JXI"));
+
+        assert!(Digest::parse("md5:XXXX").is_none());
+        assert!(Digest::parse("sha1:AAAA").is_none());
+        assert!(Digest::parse("sha1:VL2MMHO4YXUKFWV63YHTWSBM3GXKSQ2N")
+            .unwrap()
+            .matches(b"hello"));
+    }
+
+    #[test]
+    fn index_checksums() {
+        let index = b"com,example)/ 20200101000000 {\"url\": \"http://example.com/\"}\n";
+        let sidecar = index_checksum(index, "index.cdxj", Algorithm::Sha256);
+        assert!(sidecar.ends_with("  index.cdxj\n"));
+        assert_eq!(sidecar.len(), 64 + 2 + 10 + 1);
+        assert!(check_index_checksum(index, &sidecar).is_ok());
+        let sidecar = index_checksum(index, "index.cdxj", Algorithm::Sha1);
+        assert!(check_index_checksum(index, &sidecar).is_ok());
+
+        let mut corrupted = index.to_vec();
+        corrupted[4] = b'X';
+        let error = check_index_checksum(&corrupted, &sidecar).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(check_index_checksum(index, "zz  index.cdxj\n").is_err());
+    }
+
+    #[test]
+    fn redigest() {
+        let mut records = Vec::new();
+        for (record_type, body) in [
+            (RecordType::Response, &b"HTTP/1.1 200 OK\r\n\r\nhello"[..]),
+            (RecordType::Revisit, &b"HTTP/1.1 200 OK\r\n\r\n"[..]),
+            (RecordType::Resource, &b"damaged"[..]),
+            (RecordType::Resource, &b"bare"[..]),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(record_type);
+            record
+                .set_header(
+                    WarcHeader::ContentType,
+                    "application/http; msgtype=response",
+                )
+                .unwrap();
+            record.replace_body(body);
+            records.push(record);
+        }
+        let sha1 = |data: &[u8]| Algorithm::Sha1.compute(data).to_string();
+        let block_digest = sha1(records[0].body());
+        records[0]
+            .set_header(WarcHeader::BlockDigest, block_digest)
+            .unwrap();
+        records[0]
+            .set_header(WarcHeader::PayloadDigest, sha1(b"hello"))
+            .unwrap();
+        records[1]
+            .set_header(WarcHeader::PayloadDigest, sha1(b"hello"))
+            .unwrap();
+        records[2]
+            .set_header(WarcHeader::BlockDigest, sha1(b"intact"))
+            .unwrap();
+        records[3]
+            .set_header(WarcHeader::ContentType, "text/plain")
+            .unwrap();
+
+        let mut input = Vec::new();
+        let mut writer = WarcWriter::new(&mut input);
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+
+        let mut output = Vec::new();
+        let report = Redigest::new(Algorithm::Sha256)
+            .add_missing(true)
+            .run(
+                WarcReader::new(&input[..]),
+                &mut WarcWriter::new(&mut output),
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            RedigestReport {
+                records: 4,
+                rewritten: 3,
+                mismatched: 1,
+                unresolved: 0,
+            }
+        );
+
+        let copied: Vec<_> = WarcReader::new(&output[..])
+            .iter_records()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(copied.len(), 5);
+        assert_eq!(copied[0].warc_type(), &RecordType::WarcInfo);
+        assert!(String::from_utf8_lossy(copied[0].body()).contains("digest-algorithm: sha256"));
+
+        let sha256 = |data: &[u8]| Algorithm::Sha256.compute(data).to_string();
+        assert_eq!(
+            copied[1].header(WarcHeader::BlockDigest).unwrap(),
+            sha256(records[0].body())
+        );
+        assert_eq!(
+            copied[1].header(WarcHeader::PayloadDigest).unwrap(),
+            sha256(b"hello")
+        );
+        assert_eq!(
+            copied[2].header(WarcHeader::PayloadDigest).unwrap(),
+            sha256(b"hello")
+        );
+        assert_eq!(
+            copied[3].header(WarcHeader::BlockDigest).unwrap(),
+            sha1(b"intact")
+        );
+        assert_eq!(
+            copied[4].header(WarcHeader::PayloadDigest).unwrap(),
+            sha256(b"bare")
+        );
+        assert_eq!(copied[4].body(), b"bare");
+    }
+}
@@ -0,0 +1,508 @@
+//! Encryption of records at rest.
+//!
+//! A sealed record is a conversion record whose block is another record, serialized and then
+//! encrypted. The envelope keeps the WARC-Date and WARC-Target-URI of the original, so that an
+//! archive can still be indexed without its keys, and refers to the original by WARC-Refers-To. The
+//! WARC-Encryption-Scheme and WARC-Encryption-Key-ID headers name the cipher and the key which
+//! encrypted it. The headers set by `seal` are authenticated along with the block, so that an
+//! envelope cannot be given another identity, target or key without `open` failing.
+//!
+//! Records are sealed one at a time, so an archive can encrypt only its embargoed or sensitive
+//! records. `open` restores a sealed record using a `KeyProvider`, and `decrypt` does so for every
+//! record read, passing records which are not sealed through unchanged.
+//!
+//! Ciphers are pluggable. `StaticKeys` provides a fixed set of keys by ID; the `aes-gcm` feature
+//! adds `AesGcmKey`, for keys shared between writer and reader, and the `age` feature adds
+//! `AgeKey`, which lets a writer encrypt to recipients without holding the keys to decrypt.
+
+use std::collections::HashMap;
+#[cfg(feature = "age")]
+use std::io::{Read, Write};
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{BufferedBody, Error, Record, RecordType, WarcReader, WarcWriter};
+
+const SCHEME: &str = "WARC-Encryption-Scheme";
+const KEY_ID: &str = "WARC-Encryption-Key-ID";
+
+/// A key, and the scheme by which it encrypts and decrypts data.
+pub trait Cipher {
+    /// Return the ID of the key, which is recorded in sealed records so they can be opened.
+    fn key_id(&self) -> &str;
+
+    /// Return the name of the encryption scheme.
+    fn scheme(&self) -> &str;
+
+    /// Encrypt data, authenticating it together with the associated data, which is not
+    /// encrypted.
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Decrypt data encrypted by `encrypt`, which must be given the same associated data.
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+impl<C: Cipher + ?Sized> Cipher for Box<C> {
+    fn key_id(&self) -> &str {
+        (**self).key_id()
+    }
+
+    fn scheme(&self) -> &str {
+        (**self).scheme()
+    }
+
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        (**self).encrypt(plaintext, associated_data)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        (**self).decrypt(ciphertext, associated_data)
+    }
+}
+
+/// A source of the keys which open sealed records.
+pub trait KeyProvider {
+    /// Return the cipher of the key with the given ID.
+    fn cipher(&self, key_id: &str) -> Option<&dyn Cipher>;
+}
+
+impl<C: Cipher> KeyProvider for [C] {
+    fn cipher(&self, key_id: &str) -> Option<&dyn Cipher> {
+        self.iter()
+            .find(|cipher| cipher.key_id() == key_id)
+            .map(|cipher| cipher as &dyn Cipher)
+    }
+}
+
+/// A `KeyProvider` holding a fixed set of keys.
+#[derive(Default)]
+pub struct StaticKeys {
+    keys: HashMap<String, Box<dyn Cipher>>,
+}
+
+impl StaticKeys {
+    /// Create a provider with no keys.
+    pub fn new() -> Self {
+        StaticKeys::default()
+    }
+
+    /// Add a key, replacing any key with the same ID.
+    pub fn key<C: Cipher + 'static>(mut self, cipher: C) -> Self {
+        self.keys
+            .insert(cipher.key_id().to_owned(), Box::new(cipher));
+
+        self
+    }
+}
+
+impl KeyProvider for StaticKeys {
+    fn cipher(&self, key_id: &str) -> Option<&dyn Cipher> {
+        self.keys.get(key_id).map(|cipher| &**cipher)
+    }
+}
+
+/// A 256-bit AES key, encrypting with GCM.
+///
+/// Each encryption uses a random nonce, which is stored before the ciphertext.
+#[cfg(feature = "aes-gcm")]
+pub struct AesGcmKey {
+    key_id: String,
+    cipher: aes_gcm::Aes256Gcm,
+}
+
+#[cfg(feature = "aes-gcm")]
+impl AesGcmKey {
+    /// Create a key with the given ID.
+    pub fn new<S: Into<String>>(key_id: S, key: &[u8; 32]) -> Self {
+        use aes_gcm::KeyInit;
+
+        AesGcmKey {
+            key_id: key_id.into(),
+            cipher: aes_gcm::Aes256Gcm::new(aes_gcm::Key::<aes_gcm::Aes256Gcm>::from_slice(key)),
+        }
+    }
+}
+
+#[cfg(feature = "aes-gcm")]
+impl Cipher for AesGcmKey {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn scheme(&self) -> &str {
+        "aes-256-gcm"
+    }
+
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        use aes_gcm::aead::{Aead, AeadCore, OsRng, Payload};
+
+        let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext,
+            aad: associated_data,
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|e| Error::Encrypt(e.to_string()))?;
+
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        use aes_gcm::aead::{Aead, Payload};
+
+        if ciphertext.len() < 12 {
+            return Err(Error::Decrypt("ciphertext is too short".to_owned()));
+        }
+        let (nonce, ciphertext) = ciphertext.split_at(12);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: associated_data,
+        };
+        self.cipher
+            .decrypt(aes_gcm::Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::Decrypt("ciphertext failed authentication".to_owned()))
+    }
+}
+
+/// An age key, encrypting to X25519 recipients and decrypting with their identities.
+///
+/// A writer needs only the recipients, and a reader only an identity. Since age has no associated
+/// data, it is encrypted along with the plaintext, prefixed by its length, and compared when
+/// decrypting.
+#[cfg(feature = "age")]
+pub struct AgeKey {
+    key_id: String,
+    recipients: Vec<age::x25519::Recipient>,
+    identities: Vec<age::x25519::Identity>,
+}
+
+#[cfg(feature = "age")]
+impl AgeKey {
+    /// Create a key with the given ID, and no recipients or identities.
+    pub fn new<S: Into<String>>(key_id: S) -> Self {
+        AgeKey {
+            key_id: key_id.into(),
+            recipients: Vec::new(),
+            identities: Vec::new(),
+        }
+    }
+
+    /// Encrypt to a recipient, as well as any added before.
+    pub fn recipient(mut self, recipient: age::x25519::Recipient) -> Self {
+        self.recipients.push(recipient);
+
+        self
+    }
+
+    /// Decrypt with an identity, or any added before.
+    pub fn identity(mut self, identity: age::x25519::Identity) -> Self {
+        self.identities.push(identity);
+
+        self
+    }
+}
+
+#[cfg(feature = "age")]
+impl Cipher for AgeKey {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn scheme(&self) -> &str {
+        "age"
+    }
+
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let recipients = self
+            .recipients
+            .iter()
+            .map(|recipient| recipient as &dyn age::Recipient);
+        let encryptor = age::Encryptor::with_recipients(recipients)
+            .map_err(|e| Error::Encrypt(e.to_string()))?;
+        let mut ciphertext = Vec::new();
+        let mut writer = encryptor
+            .wrap_output(&mut ciphertext)
+            .map_err(|e| Error::Encrypt(e.to_string()))?;
+        let len = associated_data.len() as u64;
+        writer
+            .write_all(&len.to_be_bytes())
+            .and_then(|_| writer.write_all(associated_data))
+            .and_then(|_| writer.write_all(plaintext))
+            .map_err(|e| Error::Encrypt(e.to_string()))?;
+        writer.finish().map_err(|e| Error::Encrypt(e.to_string()))?;
+
+        Ok(ciphertext)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let identities = self
+            .identities
+            .iter()
+            .map(|identity| identity as &dyn age::Identity);
+        let mut reader = age::Decryptor::new_buffered(ciphertext)
+            .and_then(|decryptor| decryptor.decrypt(identities))
+            .map_err(|e| Error::Decrypt(e.to_string()))?;
+        let mut plaintext = Vec::new();
+        reader
+            .read_to_end(&mut plaintext)
+            .map_err(|e| Error::Decrypt(e.to_string()))?;
+
+        let len = (associated_data.len() as u64).to_be_bytes();
+        match plaintext
+            .strip_prefix(&len[..])
+            .and_then(|rest| rest.strip_prefix(associated_data))
+        {
+            Some(rest) => Ok(rest.to_vec()),
+            None => Err(Error::Decrypt(
+                "associated data failed authentication".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Return true if the record is a sealed record.
+pub fn is_sealed<T: BodyKind>(record: &Record<T>) -> bool {
+    record.warc_type() == &RecordType::Conversion
+        && record.header(WarcHeader::from(KEY_ID)).is_some()
+}
+
+/// Encrypt a record, returning the sealed record which holds it.
+pub fn seal<T, C>(record: &Record<T>, cipher: &C) -> Result<Record<BufferedBody>, Error>
+where
+    T: BodyKind + AsRef<[u8]>,
+    C: Cipher + ?Sized,
+{
+    let mut plaintext = Vec::new();
+    WarcWriter::new(&mut plaintext)
+        .write(record)
+        .map_err(|_| Error::WriteData)?;
+
+    let mut sealed = Record::<BufferedBody>::default();
+    sealed.set_warc_version(record.warc_version());
+    sealed.set_warc_type(RecordType::Conversion);
+    sealed.set_date(*record.date());
+    sealed.set_header(WarcHeader::RefersTo, record.warc_id())?;
+    if let Some(uri) = record.header(WarcHeader::TargetURI) {
+        sealed.set_header(WarcHeader::TargetURI, uri.into_owned())?;
+    }
+    sealed.set_header(WarcHeader::ContentType, "application/octet-stream")?;
+    sealed.set_header(WarcHeader::from(SCHEME), cipher.scheme())?;
+    sealed.set_header(WarcHeader::from(KEY_ID), cipher.key_id())?;
+    sealed.replace_body(cipher.encrypt(&plaintext, &associated_data(&sealed))?);
+
+    Ok(sealed)
+}
+
+/// Serialize the headers of a sealed record which are authenticated along with its block.
+///
+/// Content-Length is left out, since it depends on the ciphertext, as are headers a writer may
+/// add to the envelope, such as WARC-Block-Digest or WARC-Warcinfo-ID.
+fn associated_data<T: BodyKind>(sealed: &Record<T>) -> Vec<u8> {
+    let mut data = format!("WARC/{}\r\n", sealed.warc_version()).into_bytes();
+    for header in [
+        WarcHeader::WarcType,
+        WarcHeader::RecordID,
+        WarcHeader::Date,
+        WarcHeader::RefersTo,
+        WarcHeader::TargetURI,
+        WarcHeader::ContentType,
+        WarcHeader::from(SCHEME),
+        WarcHeader::from(KEY_ID),
+    ] {
+        if let Some(value) = sealed.header(header.clone()) {
+            data.extend_from_slice(format!("{}: {}\r\n", header, value).as_bytes());
+        }
+    }
+
+    data
+}
+
+/// Decrypt a sealed record, returning the record it holds.
+///
+/// The key named by the record is looked up in the provider, and must be of the scheme the record
+/// names.
+pub fn open<T, P>(record: &Record<T>, keys: &P) -> Result<Record<BufferedBody>, Error>
+where
+    T: BodyKind + AsRef<[u8]>,
+    P: KeyProvider + ?Sized,
+{
+    let key_id = record
+        .header(WarcHeader::from(KEY_ID))
+        .ok_or_else(|| Error::MissingHeader(WarcHeader::from(KEY_ID)))?;
+    let cipher = keys
+        .cipher(&key_id)
+        .ok_or_else(|| Error::Decrypt(format!("no key with ID {:?}", key_id)))?;
+    if let Some(scheme) = record.header(WarcHeader::from(SCHEME)) {
+        if scheme != cipher.scheme() {
+            return Err(Error::Decrypt(format!(
+                "key {:?} is not of scheme {:?}",
+                key_id, scheme
+            )));
+        }
+    }
+
+    let plaintext = cipher.decrypt(record.body(), &associated_data(record))?;
+    WarcReader::new(&plaintext[..])
+        .iter_records()
+        .next()
+        .unwrap_or(Err(Error::UnexpectedEOB))
+}
+
+/// Open every sealed record read, passing other records through unchanged.
+pub fn decrypt<'k, I, P>(
+    records: I,
+    keys: &'k P,
+) -> impl Iterator<Item = Result<Record<BufferedBody>, Error>> + 'k
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>> + 'k,
+    P: KeyProvider + ?Sized,
+{
+    records.map(move |record| {
+        let record = record?;
+        if is_sealed(&record) {
+            open(&record, keys)
+        } else {
+            Ok(record)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, is_sealed, open, seal, Cipher, StaticKeys};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Error, Record, RecordType, WarcReader, WarcWriter};
+
+    struct Xor(&'static str, u8);
+
+    impl Cipher for Xor {
+        fn key_id(&self) -> &str {
+            self.0
+        }
+
+        fn scheme(&self) -> &str {
+            "xor"
+        }
+
+        fn encrypt(&self, plaintext: &[u8], _: &[u8]) -> Result<Vec<u8>, Error> {
+            Ok(plaintext.iter().map(|b| b ^ self.1).collect())
+        }
+
+        fn decrypt(&self, ciphertext: &[u8], _: &[u8]) -> Result<Vec<u8>, Error> {
+            self.encrypt(ciphertext, &[])
+        }
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let mut records = Vec::new();
+        for uri in &["http://example.com/public", "http://example.com/embargoed"] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Resource);
+            record.set_header(WarcHeader::TargetURI, *uri).unwrap();
+            record.replace_body(format!("content of {}", uri));
+            records.push(record);
+        }
+        let keys = [Xor("old", 0x55), Xor("current", 0xaa)];
+        let sealed = seal(&records[1], &keys[1]).unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(
+            sealed.header(WarcHeader::RefersTo).as_deref(),
+            Some(records[1].warc_id())
+        );
+        assert_eq!(
+            sealed.header(WarcHeader::TargetURI).as_deref(),
+            Some("http://example.com/embargoed")
+        );
+        assert!(!sealed.body().windows(7).any(|window| window == b"content"));
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write(&records[0]).unwrap();
+        writer.write(&sealed).unwrap();
+        drop(writer);
+
+        let opened: Vec<_> = decrypt(WarcReader::new(&buffer[..]).iter_records(), &keys[..])
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(opened.len(), 2);
+        for (opened, original) in opened.iter().zip(&records) {
+            assert_eq!(opened.warc_id(), original.warc_id());
+            assert_eq!(opened.body(), original.body());
+        }
+
+        assert!(matches!(open(&sealed, &keys[..1]), Err(Error::Decrypt(_))));
+        let keys = StaticKeys::new().key(Xor("current", 0xaa));
+        assert_eq!(open(&sealed, &keys).unwrap().body(), records[1].body());
+    }
+
+    #[cfg(any(feature = "aes-gcm", feature = "age"))]
+    fn round_trip<C: Cipher + 'static>(writer_key: C, reader_key: C) {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record.replace_body("sensitive");
+        let sealed = seal(&record, &writer_key).unwrap();
+        assert_ne!(sealed.body(), record.body());
+
+        let keys = StaticKeys::new().key(reader_key);
+        let opened = open(&sealed, &keys).unwrap();
+        assert_eq!(opened.warc_id(), record.warc_id());
+        assert_eq!(opened.body(), b"sensitive");
+
+        let mut tampered = sealed.clone();
+        let last = tampered.body().len() - 1;
+        tampered.body_mut()[last] ^= 1;
+        assert!(matches!(open(&tampered, &keys), Err(Error::Decrypt(_))));
+        assert!(open(&sealed, &StaticKeys::new()).is_err());
+
+        for (header, value) in [
+            (WarcHeader::TargetURI, "http://example.com/other"),
+            (
+                WarcHeader::RefersTo,
+                "<urn:uuid:00000000-0000-0000-0000-000000000000>",
+            ),
+        ] {
+            let mut relabeled = sealed.clone();
+            relabeled.set_header(header, value).unwrap();
+            assert!(matches!(open(&relabeled, &keys), Err(Error::Decrypt(_))));
+        }
+        let mut renamed = sealed.clone();
+        renamed.set_warc_id("<urn:uuid:00000000-0000-0000-0000-000000000000>");
+        assert!(matches!(open(&renamed, &keys), Err(Error::Decrypt(_))));
+
+        let mut buffer = Vec::new();
+        WarcWriter::new(&mut buffer).write(&sealed).unwrap();
+        let read = WarcReader::new(&buffer[..])
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(open(&read, &keys).unwrap().body(), b"sensitive");
+    }
+
+    #[cfg(feature = "aes-gcm")]
+    #[test]
+    fn aes_gcm() {
+        use super::AesGcmKey;
+
+        round_trip(
+            AesGcmKey::new("2024", &[7; 32]),
+            AesGcmKey::new("2024", &[7; 32]),
+        );
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn age() {
+        use super::AgeKey;
+
+        let identity = age::x25519::Identity::generate();
+        round_trip(
+            AgeKey::new("archivist").recipient(identity.to_public()),
+            AgeKey::new("archivist").identity(identity),
+        );
+    }
+}
@@ -15,24 +15,40 @@ pub enum Error {
     MalformedHeader(WarcHeader, String),
     /// The underlying read from the data source failed.
     ReadData,
+    /// The underlying write to the data sink failed.
+    WriteData,
     /// More data was read than expected by the header metadata. The record was well-formed, but
     /// invalid.
     ReadOverflow,
     /// The end of the record's body was found unexpectedly.
     UnexpectedEOB,
+    /// The record's body is not well-formed for its declared content type.
+    MalformedBody(String),
+    /// The record's block could not be encrypted.
+    Encrypt(String),
+    /// The record's block is encrypted, and could not be decrypted.
+    Decrypt(String),
+    /// The record's declared Content-Length exceeds the limit set on the reader, or the size of
+    /// the address space.
+    BodyTooLarge(u64),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::ParseHeaders => write!(f, "Error parsing headers."),
-            Error::MissingHeader(ref h) => write!(f, "Missing required header: {}", h.to_string()),
+            Error::MissingHeader(ref h) => write!(f, "Missing required header: {}", h),
             Error::MalformedHeader(ref h, ref r) => {
-                write!(f, "Malformed header: {}: {}", h.to_string(), r)
+                write!(f, "Malformed header: {}: {}", h, r)
             }
             Error::ReadData => write!(f, "Error reading data source."),
+            Error::WriteData => write!(f, "Error writing data sink."),
             Error::ReadOverflow => write!(f, "Read further than expected."),
             Error::UnexpectedEOB => write!(f, "Unexpected end of body."),
+            Error::MalformedBody(ref r) => write!(f, "Malformed body: {}", r),
+            Error::Encrypt(ref r) => write!(f, "Error encrypting record: {}", r),
+            Error::Decrypt(ref r) => write!(f, "Error decrypting record: {}", r),
+            Error::BodyTooLarge(len) => write!(f, "Record body too large: {} bytes", len),
         }
     }
 }
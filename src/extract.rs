@@ -0,0 +1,295 @@
+//! Extraction of selected metadata from records as compact JSON.
+//!
+//! An [`Extractor`] is built from the fields of interest, and produces one JSON object per record
+//! holding only those fields, which is suited to ad-hoc mining of HTTP headers, page titles, and
+//! links without generating full metadata records.
+//!
+//! ```
+//! use warc::extract::{Extractor, Field};
+//!
+//! let extractor = Extractor::new()
+//!     .field(Field::TargetUri)
+//!     .field(Field::HtmlTitle)
+//!     .field(Field::Outlinks);
+//! ```
+//!
+//! HTML is only extracted from uncompressed, non-chunked payloads.
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::{html, http, BufferedBody, Record};
+
+/// A field which can be extracted from a record.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Field {
+    /// The record's type, under the key `type`.
+    RecordType,
+    /// The record's WARC-Target-URI, under the key `url`.
+    TargetUri,
+    /// The record's WARC-Date, under the key `date`.
+    Date,
+    /// The named header of the record, under the key `warc:<name>`.
+    WarcHeader(WarcHeader),
+    /// The status code of the HTTP response, under the key `status`.
+    HttpStatus,
+    /// The named header of the HTTP message, under the key `http:<name>`.
+    HttpHeader(String),
+    /// The text of the HTML title, under the key `title`.
+    HtmlTitle,
+    /// The content of the HTML meta description, under the key `description`.
+    MetaDescription,
+    /// The content of the named HTML meta tag, under the key `meta:<name>`.
+    Meta(String),
+    /// The links found in the HTML, under the key `outlinks`.
+    ///
+    /// Each link is an object with the `url`, resolved against the target URI when possible, and
+    /// the `path` it was found at, such as `A@/href` or `IMG@/src`.
+    Outlinks,
+}
+
+impl Field {
+    /// Return the key the field is stored under in the extracted object.
+    pub fn key(&self) -> String {
+        match self {
+            Field::RecordType => "type".to_owned(),
+            Field::TargetUri => "url".to_owned(),
+            Field::Date => "date".to_owned(),
+            Field::WarcHeader(header) => format!("warc:{}", header),
+            Field::HttpStatus => "status".to_owned(),
+            Field::HttpHeader(name) => format!("http:{}", name.to_ascii_lowercase()),
+            Field::HtmlTitle => "title".to_owned(),
+            Field::MetaDescription => "description".to_owned(),
+            Field::Meta(name) => format!("meta:{}", name.to_ascii_lowercase()),
+            Field::Outlinks => "outlinks".to_owned(),
+        }
+    }
+
+    fn needs_html(&self) -> bool {
+        matches!(
+            self,
+            Field::HtmlTitle | Field::MetaDescription | Field::Meta(_) | Field::Outlinks
+        )
+    }
+}
+
+/// Extracts a declared set of fields from records.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Extractor {
+    fields: Vec<Field>,
+    keep_empty: bool,
+}
+
+impl Extractor {
+    /// Create an extractor with no fields.
+    pub fn new() -> Self {
+        Extractor::default()
+    }
+
+    /// Add a field to extract.
+    pub fn field(mut self, field: Field) -> Self {
+        self.fields.push(field);
+
+        self
+    }
+
+    /// Keep fields which are absent from a record as `null` instead of omitting them.
+    pub fn keep_empty(mut self, keep_empty: bool) -> Self {
+        self.keep_empty = keep_empty;
+
+        self
+    }
+
+    /// Return the fields this extractor extracts.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Extract the declared fields from the record into a JSON object.
+    pub fn extract(&self, record: &Record<BufferedBody>) -> Value {
+        let body = record.body();
+        let is_http = record
+            .header(WarcHeader::ContentType)
+            .map(|ct| http::is_http_message(&ct))
+            .unwrap_or(false);
+        let document = if self.fields.iter().any(Field::needs_html) {
+            html_payload(record, is_http)
+        } else {
+            None
+        };
+        let tags = document.as_deref().map(html::tags).unwrap_or_default();
+
+        let mut object = Map::new();
+        for field in &self.fields {
+            let value = match field {
+                Field::RecordType => Some(Value::from(record.warc_type().to_string())),
+                Field::TargetUri => record
+                    .header(WarcHeader::TargetURI)
+                    .map(|uri| Value::from(uri.into_owned())),
+                Field::Date => Some(Value::from(
+                    record.header(WarcHeader::Date).unwrap().into_owned(),
+                )),
+                Field::WarcHeader(header) => record
+                    .header(header.clone())
+                    .map(|value| Value::from(value.into_owned())),
+                Field::HttpStatus if is_http => http::status(body).map(Value::from),
+                Field::HttpHeader(name) if is_http => http::header(body, name).map(Value::from),
+                Field::HtmlTitle => document
+                    .as_deref()
+                    .and_then(|doc| html::title(doc, &tags))
+                    .map(Value::from),
+                Field::MetaDescription => html::meta(&tags, "description").map(Value::from),
+                Field::Meta(name) => html::meta(&tags, name).map(Value::from),
+                Field::Outlinks if document.is_some() => Some(outlinks(record, &tags)),
+                _ => None,
+            };
+            match value {
+                Some(value) => {
+                    object.insert(field.key(), value);
+                }
+                None if self.keep_empty => {
+                    object.insert(field.key(), Value::Null);
+                }
+                None => {}
+            }
+        }
+
+        Value::Object(object)
+    }
+
+    /// Extract the declared fields from the record as a single line of JSON.
+    pub fn extract_json(&self, record: &Record<BufferedBody>) -> String {
+        self.extract(record).to_string()
+    }
+}
+
+/// Return the HTML document held in the record, if it holds one.
+pub(crate) fn html_payload(record: &Record<BufferedBody>, is_http: bool) -> Option<String> {
+    let body = record.body();
+    let (content_type, payload) = if is_http {
+        let start = body.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+        (http::header(body, "content-type"), &body[start..])
+    } else {
+        (
+            record
+                .header(WarcHeader::ContentType)
+                .map(|ct| ct.into_owned()),
+            body,
+        )
+    };
+
+    let content_type = content_type?.to_ascii_lowercase();
+    if content_type.contains("html") {
+        Some(String::from_utf8_lossy(payload).into_owned())
+    } else {
+        None
+    }
+}
+
+/// Return the links of a document, resolved against its base, as objects holding the URL and the
+/// path of the attribute it was found in, such as `A@/href`.
+pub(crate) fn outlinks(record: &Record<BufferedBody>, tags: &[html::Tag]) -> Value {
+    let mut base = record
+        .header(WarcHeader::TargetURI)
+        .and_then(|uri| Url::parse(&uri).ok());
+
+    let mut links = Vec::new();
+    for tag in tags {
+        let attribute = match html::link_attribute(&tag.name) {
+            Some(attribute) => attribute,
+            None => continue,
+        };
+        let href = match tag.attribute(attribute) {
+            Some(href) if !href.trim().is_empty() => href.trim(),
+            _ => continue,
+        };
+        let url = match base.as_ref().and_then(|base| base.join(href).ok()) {
+            Some(url) => url.to_string(),
+            None => href.to_owned(),
+        };
+        if tag.name == "base" {
+            base = Url::parse(&url).ok().or(base);
+        }
+
+        let mut link = Map::new();
+        link.insert("url".to_owned(), Value::from(url));
+        link.insert(
+            "path".to_owned(),
+            Value::from(format!("{}@/{}", tag.name.to_ascii_uppercase(), attribute)),
+        );
+        links.push(Value::Object(link));
+    }
+
+    Value::Array(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Extractor, Field};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType};
+    use serde_json::json;
+
+    fn response() -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(RecordType::Response);
+        record
+            .set_header(WarcHeader::TargetURI, "https://example.com/dir/page")
+            .unwrap();
+        record
+            .set_header(
+                WarcHeader::ContentType,
+                "application/http; msgtype=response",
+            )
+            .unwrap();
+        record.replace_body(
+            &b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nServer: test\r\n\r\n\
+               <html><head><title>Example</title>\
+               <meta name=description content=\"An example\"></head>\
+               <body><a href=\"other\">x</a><img src=\"/logo.png\"></body></html>"[..],
+        );
+
+        record
+    }
+
+    #[test]
+    fn declared_fields() {
+        let extractor = Extractor::new()
+            .field(Field::TargetUri)
+            .field(Field::HttpStatus)
+            .field(Field::HttpHeader("Server".to_owned()))
+            .field(Field::HtmlTitle)
+            .field(Field::MetaDescription)
+            .field(Field::Outlinks);
+
+        assert_eq!(
+            extractor.extract(&response()),
+            json!({
+                "url": "https://example.com/dir/page",
+                "status": 200,
+                "http:server": "test",
+                "title": "Example",
+                "description": "An example",
+                "outlinks": [
+                    {"url": "https://example.com/dir/other", "path": "A@/href"},
+                    {"url": "https://example.com/logo.png", "path": "IMG@/src"},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn missing_fields() {
+        let mut record = Record::<BufferedBody>::default();
+        record.replace_body(&b"plain"[..]);
+        let extractor = Extractor::new()
+            .field(Field::HttpStatus)
+            .field(Field::HtmlTitle);
+
+        assert_eq!(extractor.extract_json(&record), "{}");
+        assert_eq!(
+            extractor.keep_empty(true).extract_json(&record),
+            r#"{"status":null,"title":null}"#
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Record filters parsed from a small expression language.
+//!
+//! A filter is one or more comparisons joined with `&&` and `||`, optionally negated with `!` and
+//! grouped with parentheses:
+//!
+//! ```text
+//! type=response && mime~"text/html" && status=200 && url~"example\.com"
+//! ```
+//!
+//! Each comparison names a field, an operator, and a value. The fields are:
+//!
+//! * `type` - the WARC-Type of the record
+//! * `standard` - `true` if the WARC-Type is defined by the standard, and `false` otherwise
+//! * `url` - the WARC-Target-URI of the record
+//! * `id` - the WARC-Record-ID of the record
+//! * `date` - the WARC-Date of the record
+//! * `length` - the Content-Length of the record
+//! * `mime` - the media type of the payload; for HTTP messages, the Content-Type of the message
+//! * `status` - the status code of an HTTP response
+//! * `header:<name>` - any WARC header of the record
+//!
+//! The operators are `=` and `!=` for equality, `~` and `!~` for regular expression matches, and
+//! `<`, `<=`, `>`, and `>=` for ordering, which compares numerically when both sides are numbers.
+//! A comparison against a field the record does not have is false, except for `!=` and `!~`.
+//!
+//! Values may be bare words or double-quoted strings, in which `\"` and `\\` are escapes and any
+//! other backslash is kept, so regular expressions need no double escaping.
+
+use std::error;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::header::WarcHeader;
+use crate::{http, BufferedBody, Error, Record};
+
+/// An error describing why a filter expression could not be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterError {
+    /// The byte offset in the expression at which the error was found.
+    pub position: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid filter at {}: {}", self.position, self.message)
+    }
+}
+
+impl error::Error for FilterError {}
+
+/// A field of a record which a filter can compare.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Type,
+    Standard,
+    Url,
+    Id,
+    Date,
+    Length,
+    Mime,
+    Status,
+    Header(WarcHeader),
+}
+
+/// A comparison operator of a filter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A compiled record filter.
+#[derive(Clone, Debug)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Field, Op, String),
+    Regex(Field, bool, Regex),
+}
+
+impl Filter {
+    /// Parse and compile a filter expression.
+    pub fn parse(expression: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser {
+            input: expression,
+            pos: 0,
+        };
+        let filter = parser.or()?;
+        parser.skip_space();
+        if parser.pos < expression.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+
+        Ok(filter)
+    }
+
+    /// Return true if the record is selected by this filter.
+    pub fn matches(&self, record: &Record<BufferedBody>) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(record) && b.matches(record),
+            Filter::Or(a, b) => a.matches(record) || b.matches(record),
+            Filter::Not(a) => !a.matches(record),
+            Filter::Compare(field, op, value) => match field_value(field, record) {
+                Some(actual) => compare(&actual, *op, value),
+                None => *op == Op::Ne,
+            },
+            Filter::Regex(field, negated, regex) => match field_value(field, record) {
+                Some(actual) => regex.is_match(&actual) != *negated,
+                None => *negated,
+            },
+        }
+    }
+
+    /// Wrap an iterator of records, yielding only errors and the records this filter selects.
+    pub fn apply<I>(self, records: I) -> Filtered<I>
+    where
+        I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+    {
+        Filtered {
+            records,
+            filter: self,
+        }
+    }
+}
+
+/// An iterator adapter yielding the records selected by a filter.
+pub struct Filtered<I> {
+    records: I,
+    filter: Filter,
+}
+
+impl<I> Iterator for Filtered<I>
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+{
+    type Item = Result<Record<BufferedBody>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter = &self.filter;
+        self.records.find(|item| match item {
+            Ok(record) => filter.matches(record),
+            Err(_) => true,
+        })
+    }
+}
+
+fn field_value(field: &Field, record: &Record<BufferedBody>) -> Option<String> {
+    match field {
+        Field::Type => record.header(WarcHeader::WarcType),
+        Field::Standard => return Some(record.warc_type().is_standard().to_string()),
+        Field::Url => record.header(WarcHeader::TargetURI),
+        Field::Id => record.header(WarcHeader::RecordID),
+        Field::Date => record.header(WarcHeader::Date),
+        Field::Length => return Some(record.content_length().to_string()),
+        Field::Mime => {
+            let content_type = record.header(WarcHeader::ContentType);
+            return match content_type {
+                Some(ref ct) if http::is_http_message(ct) => {
+                    http::header(record.body(), "content-type")
+                }
+                _ => record
+                    .header(WarcHeader::IdentifiedPayloadType)
+                    .or(content_type)
+                    .map(|ct| ct.into_owned()),
+            };
+        }
+        Field::Status => return http::status(record.body()).map(|s| s.to_string()),
+        Field::Header(header) => record.header(header.clone()),
+    }
+    .map(|value| value.into_owned())
+}
+
+fn compare(actual: &str, op: Op, expected: &str) -> bool {
+    let ordering = match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(actual.cmp(expected)),
+    };
+
+    match op {
+        Op::Eq => actual == expected || ordering == Some(std::cmp::Ordering::Equal),
+        Op::Ne => !(actual == expected || ordering == Some(std::cmp::Ordering::Equal)),
+        Op::Lt => ordering == Some(std::cmp::Ordering::Less),
+        Op::Le => ordering.map(|o| o.is_le()).unwrap_or(false),
+        Op::Gt => ordering == Some(std::cmp::Ordering::Greater),
+        Op::Ge => ordering.map(|o| o.is_ge()).unwrap_or(false),
+        Op::Match | Op::NotMatch => unreachable!("regex operators are compiled separately"),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<S: Into<String>>(&self, message: S) -> FilterError {
+        FilterError {
+            position: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and()?;
+        while self.eat("||") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.unary()?;
+        while self.eat("&&") {
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, FilterError> {
+        if self.eat("(") {
+            let filter = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected `)`"));
+            }
+            Ok(filter)
+        } else if self.eat("!") {
+            Ok(Filter::Not(Box::new(self.unary()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Filter, FilterError> {
+        self.skip_space();
+        let start = self.pos;
+        let field = self.field()?;
+
+        self.skip_space();
+        let op = [
+            ("!=", Op::Ne),
+            ("!~", Op::NotMatch),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("=", Op::Eq),
+            ("~", Op::Match),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ]
+        .iter()
+        .find(|(token, _)| self.rest().starts_with(token))
+        .map(|(token, op)| (token.len(), *op));
+        let op = match op {
+            Some((len, op)) => {
+                self.pos += len;
+                op
+            }
+            None => return Err(self.error("expected a comparison operator")),
+        };
+
+        let value_start = self.pos;
+        let value = self.value()?;
+        match op {
+            Op::Match | Op::NotMatch => match Regex::new(&value) {
+                Ok(regex) => Ok(Filter::Regex(field, op == Op::NotMatch, regex)),
+                Err(e) => Err(FilterError {
+                    position: value_start,
+                    message: format!("invalid regular expression: {}", e),
+                }),
+            },
+            _ => {
+                if matches!(field, Field::Status | Field::Length) && value.parse::<f64>().is_err() {
+                    return Err(FilterError {
+                        position: start,
+                        message: "expected a number".to_string(),
+                    });
+                }
+                Ok(Filter::Compare(field, op, value))
+            }
+        }
+    }
+
+    fn field(&mut self) -> Result<Field, FilterError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':'))
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        let field = match name.to_ascii_lowercase().as_str() {
+            "type" => Field::Type,
+            "standard" => Field::Standard,
+            "url" => Field::Url,
+            "id" => Field::Id,
+            "date" => Field::Date,
+            "length" => Field::Length,
+            "mime" => Field::Mime,
+            "status" => Field::Status,
+            lower => match lower.strip_prefix("header:") {
+                Some(header) if !header.is_empty() => Field::Header(WarcHeader::from(header)),
+                _ => return Err(self.error(format!("unknown field `{}`", name))),
+            },
+        };
+        self.pos += len;
+
+        Ok(field)
+    }
+
+    fn value(&mut self) -> Result<String, FilterError> {
+        self.skip_space();
+        let rest = self.rest();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += i + 2;
+                        return Ok(value);
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, next @ '"')) | Some((_, next @ '\\')) => value.push(next),
+                        Some((_, next)) => {
+                            value.push('\\');
+                            value.push(next);
+                        }
+                        None => break,
+                    },
+                    _ => value.push(c),
+                }
+            }
+            Err(self.error("unterminated string"))
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '&' | '|'))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err(self.error("expected a value"));
+            }
+            self.pos += len;
+            Ok(rest[..len].to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    fn response(url: &str, status: u16, mime: &str) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::with_body(format!(
+            "HTTP/1.1 {} OK\r\nContent-Type: {}\r\n\r\nbody",
+            status, mime
+        ));
+        record.set_warc_type(RecordType::Response);
+        record
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        record.set_header(WarcHeader::TargetURI, url).unwrap();
+        record
+    }
+
+    #[test]
+    fn evaluate() {
+        let filter = Filter::parse(
+            r#"type=response && mime~"text/html" && status=200 && url~"example\.com""#,
+        )
+        .unwrap();
+
+        assert!(filter.matches(&response(
+            "http://example.com/",
+            200,
+            "text/html; charset=utf-8"
+        )));
+        assert!(!filter.matches(&response("http://example.com/", 404, "text/html")));
+        assert!(!filter.matches(&response("http://exampleXcom/", 200, "text/html")));
+        assert!(!filter.matches(&response("http://example.com/", 200, "image/png")));
+        assert!(!filter.matches(&Record::<BufferedBody>::default()));
+
+        let filter = Filter::parse("!(status >= 400 || status < 200) && length > 10").unwrap();
+        assert!(filter.matches(&response("http://a/", 204, "text/plain")));
+        assert!(!filter.matches(&response("http://a/", 500, "text/plain")));
+
+        let filter = Filter::parse(r#"header:WARC-Target-URI != "http://a/""#).unwrap();
+        assert!(!filter.matches(&response("http://a/", 200, "text/plain")));
+        assert!(filter.matches(&Record::<BufferedBody>::default()));
+    }
+
+    #[test]
+    fn unknown_types() {
+        let raw =
+            b"WARC/1.1\r\nWARC-Type: Experimental-Capture\r\nWARC-Record-ID: <urn:test:x>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\nContent-Length: 2\r\n\r\nok\r\n\r\n\
+            WARC/1.1\r\nWARC-Type: Metadata\r\nWARC-Record-ID: <urn:test:y>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\nContent-Length: 0\r\n\r\n\r\n\r\n";
+        let filter = Filter::parse("standard=false").unwrap();
+        let records: Vec<_> = filter
+            .apply(WarcReader::new(&raw[..]).iter_records())
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].warc_type(),
+            &RecordType::Unknown("Experimental-Capture".to_owned())
+        );
+        assert!(!records[0].warc_type().is_standard());
+        assert!(Filter::parse("type=Experimental-Capture")
+            .unwrap()
+            .matches(&records[0]));
+
+        let mut buffer = Vec::new();
+        WarcWriter::new(&mut buffer).write(&records[0]).unwrap();
+        assert!(String::from_utf8(buffer)
+            .unwrap()
+            .contains("warc-type: Experimental-Capture\r\n"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Filter::parse("bogus=1").unwrap_err().position, 0);
+        assert_eq!(Filter::parse("type response").unwrap_err().position, 5);
+        assert_eq!(Filter::parse("status=ok").unwrap_err().position, 0);
+        assert!(Filter::parse("url~\"(\"").is_err());
+        assert!(Filter::parse("(type=response").is_err());
+        assert!(Filter::parse("type=\"open").is_err());
+        assert!(Filter::parse("type=response extra").is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let records = vec![
+            Ok(response("http://a/", 200, "text/html")),
+            Err(crate::Error::ReadData),
+            Ok(response("http://b/", 404, "text/html")),
+        ];
+        let filtered: Vec<_> = Filter::parse("status=200")
+            .unwrap()
+            .apply(records.into_iter())
+            .collect();
+
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered[1].is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::net::IpAddr;
+
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, CaptureSession, Record};
+
+/// The Content-Type of the metadata record holding an FTP control conversation.
+pub const CONTROL_CONVERSATION_CONTENT_TYPE: &str = "text/plain";
+
+/// A file or directory listing retrieved over FTP, convertible into records.
+///
+/// Following the conventions used by Heritrix, the retrieved data is stored in a resource record
+/// with an `ftp:` target URI, and the commands and replies exchanged on the control connection are
+/// stored in a metadata record which is concurrent to the resource record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FtpRetrieval {
+    target_uri: String,
+    data: Vec<u8>,
+    content_type: String,
+    date: DateTime<Utc>,
+    peer: Option<IpAddr>,
+    control_conversation: Option<String>,
+    warcinfo_id: Option<String>,
+}
+
+impl FtpRetrieval {
+    /// Create a retrieval of the given `ftp:` URI, dated now.
+    ///
+    /// The data is described as `application/octet-stream` unless `content_type` is set.
+    pub fn new<S: Into<String>, B: Into<Vec<u8>>>(target_uri: S, data: B) -> Self {
+        FtpRetrieval {
+            target_uri: target_uri.into(),
+            data: data.into(),
+            content_type: "application/octet-stream".to_owned(),
+            date: Utc::now(),
+            peer: None,
+            control_conversation: None,
+            warcinfo_id: None,
+        }
+    }
+
+    /// Create a retrieval of a directory listing, described as `text/plain`.
+    pub fn listing<S: Into<String>, B: Into<Vec<u8>>>(target_uri: S, listing: B) -> Self {
+        Self::new(target_uri, listing).content_type("text/plain")
+    }
+
+    /// Set the Content-Type of the retrieved data.
+    pub fn content_type<S: Into<String>>(mut self, content_type: S) -> Self {
+        self.content_type = content_type.into();
+
+        self
+    }
+
+    /// Set the time at which the retrieval started.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = date;
+
+        self
+    }
+
+    /// Set the address of the FTP server.
+    pub fn peer<A: Into<IpAddr>>(mut self, peer: A) -> Self {
+        self.peer = Some(peer.into());
+
+        self
+    }
+
+    /// Set the transcript of the control connection, such as `USER anonymous` and its reply.
+    ///
+    /// When set, it is stored in a metadata record alongside the resource record.
+    pub fn control_conversation<S: Into<String>>(mut self, conversation: S) -> Self {
+        self.control_conversation = Some(conversation.into());
+
+        self
+    }
+
+    /// Set the WARC-Warcinfo-ID given to every record.
+    pub fn warcinfo_id<S: Into<String>>(mut self, warcinfo_id: S) -> Self {
+        self.warcinfo_id = Some(warcinfo_id.into());
+
+        self
+    }
+
+    /// Convert this retrieval into its resource record and optional metadata record, in that
+    /// order.
+    pub fn into_records(self) -> Vec<Record<BufferedBody>> {
+        let mut session = CaptureSession::new();
+        if let Some(peer) = self.peer {
+            session = session.ip_address(peer.to_string());
+        }
+        if let Some(warcinfo_id) = self.warcinfo_id {
+            session = session.warcinfo_id(warcinfo_id);
+        }
+
+        let capture = session.begin_at(self.target_uri, self.date);
+        let resource = capture.resource();
+        resource.replace_body(self.data);
+        resource
+            .set_header(WarcHeader::ContentType, self.content_type)
+            .unwrap();
+        if let Some(conversation) = self.control_conversation {
+            let metadata = capture.metadata();
+            metadata.replace_body(conversation);
+            metadata
+                .set_header(WarcHeader::ContentType, CONTROL_CONVERSATION_CONTENT_TYPE)
+                .unwrap();
+        }
+
+        session.into_records()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FtpRetrieval;
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn file_with_conversation() {
+        let records = FtpRetrieval::new("ftp://ftp.example.com/pub/README", "read me")
+            .content_type("text/plain")
+            .peer(Ipv4Addr::new(192, 0, 2, 1))
+            .control_conversation("USER anonymous\r\n331 Password required\r\n")
+            .into_records();
+
+        assert_eq!(records.len(), 2);
+        let (resource, metadata) = (&records[0], &records[1]);
+        assert_eq!(resource.warc_type(), &RecordType::Resource);
+        assert_eq!(resource.body(), b"read me");
+        assert_eq!(
+            resource.header(WarcHeader::TargetURI).unwrap(),
+            "ftp://ftp.example.com/pub/README"
+        );
+        assert_eq!(resource.header(WarcHeader::IPAddress).unwrap(), "192.0.2.1");
+        assert!(resource.header(WarcHeader::ConcurrentTo).is_none());
+
+        assert_eq!(metadata.warc_type(), &RecordType::Metadata);
+        assert_eq!(metadata.date(), resource.date());
+        assert_eq!(
+            metadata.header(WarcHeader::ConcurrentTo).unwrap(),
+            resource.warc_id()
+        );
+        assert_eq!(
+            metadata.header(WarcHeader::TargetURI).unwrap(),
+            "ftp://ftp.example.com/pub/README"
+        );
+    }
+
+    #[test]
+    fn listing_without_conversation() {
+        let records =
+            FtpRetrieval::listing("ftp://ftp.example.com/pub/", "README\r\n").into_records();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].header(WarcHeader::ContentType).unwrap(),
+            "text/plain"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Accounting of the gzip members of compressed WARC files.
+//!
+//! A well-formed `.warc.gz` file holds one record per gzip member, so that an index can give the
+//! offset of a member and a reader can decompress that record alone. Files recompressed with a
+//! plain `gzip` hold the whole file in one member, and files concatenated from several of those
+//! hold records split between members; both decode to valid records, but an index of such a file
+//! gives offsets which cannot be read from.
+//!
+//! `survey` decodes every member of a file and reports how records fall across them, so that
+//! indexers can tell whether random access is available. `remember` rewrites a file into one
+//! member per record.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use libflate::gzip::Decoder as GzipReader;
+
+use crate::parser::{body_frame, parse_header_block};
+use crate::recompress::{recompress, Codec, RecompressReport};
+use crate::stats::CountingReader;
+use crate::Error;
+
+/// Where a record lies within the members of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordSpan {
+    /// The WARC-Record-ID of the record.
+    pub record_id: String,
+    /// The offset of the record within the decompressed data.
+    pub offset: u64,
+    /// The length of the record, including its separator.
+    pub length: u64,
+    /// The index of the member holding the start of the record.
+    pub first_member: u64,
+    /// The index of the member holding the end of the record.
+    pub last_member: u64,
+    /// The offset of the member holding the start of the record within the compressed file.
+    pub member_offset: u64,
+    /// True if the record starts and ends its member, so that reading the member at
+    /// `member_offset` yields this record alone.
+    pub random_access: bool,
+}
+
+/// How the records of a compressed file fall across its gzip members.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemberLayout {
+    /// The number of members.
+    pub members: u64,
+    /// The number of members holding the start of more than one record.
+    pub multi_record_members: u64,
+    /// Every record, in order.
+    pub records: Vec<RecordSpan>,
+}
+
+impl MemberLayout {
+    /// Return the number of records whose data spans more than one member.
+    pub fn spanning_records(&self) -> u64 {
+        self.records
+            .iter()
+            .filter(|record| record.first_member != record.last_member)
+            .count() as u64
+    }
+
+    /// Return true if every record has a member of its own, so that the file can be indexed for
+    /// random access.
+    pub fn is_random_access(&self) -> bool {
+        self.records.iter().all(|record| record.random_access)
+    }
+}
+
+/// A member whose data has not been wholly consumed by records.
+struct Member {
+    index: u64,
+    offset: u64,
+    start: u64,
+    end: u64,
+    records: u64,
+}
+
+/// Decode every member of a gzip-compressed WARC file, reporting where each record lies.
+///
+/// Records are framed across member boundaries, so files with several records per member, or
+/// with records split between members, are read correctly.
+///
+/// # Errors
+///
+/// Returns `Error::ReadData` if a member cannot be decoded, `Error::ParseHeaders` if the
+/// decompressed data is not a sequence of records, and `Error::UnexpectedEOB` if it ends within a
+/// record.
+pub fn survey<R: Read>(reader: R) -> Result<MemberLayout, Error> {
+    let mut layout = MemberLayout::default();
+    let mut input = CountingReader(BufReader::new(reader), 0);
+    let mut members: VecDeque<Member> = VecDeque::new();
+    let mut pending = Vec::new();
+    let mut pending_start = 0;
+    loop {
+        match input.0.fill_buf() {
+            Err(_) => return Err(Error::ReadData),
+            Ok([]) => break,
+            Ok(_) => {}
+        }
+
+        let offset = input.1;
+        let start = pending_start + pending.len() as u64;
+        GzipReader::new(&mut input)
+            .and_then(|mut member| member.read_to_end(&mut pending))
+            .map_err(|_| Error::ReadData)?;
+        members.push_back(Member {
+            index: layout.members,
+            offset,
+            start,
+            end: pending_start + pending.len() as u64,
+            records: 0,
+        });
+        layout.members += 1;
+
+        let mut consumed = 0;
+        while let Some((record_id, length)) = next_record(&pending[consumed..])? {
+            let record_start = pending_start + consumed as u64;
+            let record_end = record_start + length;
+            let first = members
+                .iter_mut()
+                .find(|member| member.end > record_start)
+                .ok_or(Error::ParseHeaders)?;
+            first.records += 1;
+            if first.records == 2 {
+                layout.multi_record_members += 1;
+            }
+            let (first_member, member_offset, member_start) =
+                (first.index, first.offset, first.start);
+            let last = members
+                .iter()
+                .find(|member| member.end >= record_end)
+                .ok_or(Error::ParseHeaders)?;
+            layout.records.push(RecordSpan {
+                record_id,
+                offset: record_start,
+                length,
+                first_member,
+                last_member: last.index,
+                member_offset,
+                random_access: first_member == last.index
+                    && member_start == record_start
+                    && last.end == record_end,
+            });
+            consumed += length as usize;
+            while members
+                .front()
+                .is_some_and(|member| member.end <= record_end)
+            {
+                members.pop_front();
+            }
+        }
+        pending.drain(..consumed);
+        pending_start += consumed as u64;
+    }
+    if !pending.is_empty() {
+        return Err(Error::UnexpectedEOB);
+    }
+
+    Ok(layout)
+}
+
+/// Frame the record at the start of the data, returning its ID and length, or `None` if the data
+/// ends before the record does.
+fn next_record(data: &[u8]) -> Result<Option<(String, u64)>, Error> {
+    let (rest, (_, headers)) = match parse_header_block(data) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Incomplete(_)) => return Ok(None),
+        Err(_) => return Err(Error::ParseHeaders),
+    };
+    let value = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+    };
+    let content_length = match value("content-length") {
+        Some(len) => len.trim().parse().map_err(|_| Error::ParseHeaders)?,
+        None => 0,
+    };
+    let rest = match body_frame(content_length)(rest) {
+        Ok((rest, _)) => rest,
+        Err(nom::Err::Incomplete(_)) => return Ok(None),
+        Err(_) => return Err(Error::ParseHeaders),
+    };
+    let record_id = value("warc-record-id").unwrap_or_default();
+
+    Ok(Some((record_id, (data.len() - rest.len()) as u64)))
+}
+
+/// Rewrite a gzip-compressed WARC file, however its records fall across members, into one member
+/// per record.
+pub fn remember<R: BufRead, W: Write>(input: R, output: W) -> Result<RecompressReport, Error> {
+    recompress(input, output, Codec::Gzip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remember, survey};
+    use crate::{BufferedBody, Record, WarcReader, WarcWriter};
+
+    use libflate::gzip::{Encoder as GzipWriter, MultiDecoder};
+    use std::io::{BufReader, Write};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipWriter::new(Vec::new()).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().into_result().unwrap()
+    }
+
+    #[test]
+    fn gzip_members() {
+        let mut plain = Vec::new();
+        let mut writer = WarcWriter::new(&mut plain);
+        let mut ids = Vec::new();
+        for body in &[&b"first"[..], b"second", b"third"] {
+            let mut record = Record::<BufferedBody>::with_body(*body);
+            record.set_warc_version("1.0");
+            ids.push(record.warc_id().to_owned());
+            writer.write(&record).unwrap();
+        }
+        drop(writer);
+
+        // the whole file in one member, as `gzip` writes it
+        let whole = gzip(&plain);
+        let layout = survey(&whole[..]).unwrap();
+        assert_eq!(layout.members, 1);
+        assert_eq!(layout.multi_record_members, 1);
+        assert_eq!(layout.records.len(), 3);
+        assert!(!layout.is_random_access());
+        assert_eq!(layout.records[1].record_id, ids[1]);
+
+        // members split in the middle of the second record
+        let split = plain.len() / 2;
+        let mut spanning = gzip(&plain[..split]);
+        spanning.extend(gzip(&plain[split..]));
+        let layout = survey(&spanning[..]).unwrap();
+        assert_eq!(layout.members, 2);
+        assert_eq!(layout.spanning_records(), 1);
+        let decoder = MultiDecoder::new(&spanning[..]).unwrap();
+        let records = WarcReader::new(BufReader::new(decoder))
+            .iter_records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].body(), b"third");
+
+        let mut rewritten = Vec::new();
+        let report = remember(&spanning[..], &mut rewritten).unwrap();
+        assert_eq!(report.written, 3);
+        let layout = survey(&rewritten[..]).unwrap();
+        assert_eq!(layout.members, 3);
+        assert!(layout.is_random_access());
+        let offsets: Vec<_> = layout.records.iter().map(|r| r.member_offset).collect();
+        assert_eq!(offsets[0], 0);
+        assert!(offsets[1] > 0 && offsets[2] > offsets[1]);
+
+        assert!(survey(&gzip(&plain[..plain.len() - 3])[..]).is_err());
+    }
+}
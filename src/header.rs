@@ -1,4 +1,6 @@
 use std::fmt::Display;
+use std::iter::FromIterator;
+use std::ops::Index;
 
 #[cfg(feature = "with_serde")]
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,7 @@ pub enum WarcHeader {
     ContentLength,
     ContentType,
     BlockDigest,
+    CipherSuite,
     ConcurrentTo,
     Date,
     Filename,
@@ -20,8 +23,11 @@ pub enum WarcHeader {
     IPAddress,
     PayloadDigest,
     Profile,
+    Protocol,
     RecordID,
     RefersTo,
+    RefersToDate,
+    RefersToTargetURI,
     SegmentNumber,
     SegmentOriginID,
     SegmentTotalLength,
@@ -32,18 +38,69 @@ pub enum WarcHeader {
     Unknown(String),
 }
 
-impl From<WarcHeader> for String {
-    fn from(header: WarcHeader) -> Self {
-        header.to_string()
+impl WarcHeader {
+    /// Return the name of the header as capitalized in the standard, such as `WARC-Record-ID`.
+    ///
+    /// Unknown headers are capitalized word by word.
+    pub fn canonical_name(&self) -> String {
+        let name = match self {
+            WarcHeader::ContentLength => "Content-Length",
+            WarcHeader::ContentType => "Content-Type",
+            WarcHeader::BlockDigest => "WARC-Block-Digest",
+            WarcHeader::CipherSuite => "WARC-Cipher-Suite",
+            WarcHeader::ConcurrentTo => "WARC-Concurrent-To",
+            WarcHeader::Date => "WARC-Date",
+            WarcHeader::Filename => "WARC-Filename",
+            WarcHeader::IdentifiedPayloadType => "WARC-Identified-Payload-Type",
+            WarcHeader::IPAddress => "WARC-IP-Address",
+            WarcHeader::PayloadDigest => "WARC-Payload-Digest",
+            WarcHeader::Profile => "WARC-Profile",
+            WarcHeader::Protocol => "WARC-Protocol",
+            WarcHeader::RecordID => "WARC-Record-ID",
+            WarcHeader::RefersTo => "WARC-Refers-To",
+            WarcHeader::RefersToDate => "WARC-Refers-To-Date",
+            WarcHeader::RefersToTargetURI => "WARC-Refers-To-Target-URI",
+            WarcHeader::SegmentNumber => "WARC-Segment-Number",
+            WarcHeader::SegmentOriginID => "WARC-Segment-Origin-ID",
+            WarcHeader::SegmentTotalLength => "WARC-Segment-Total-Length",
+            WarcHeader::TargetURI => "WARC-Target-URI",
+            WarcHeader::Truncated => "WARC-Truncated",
+            WarcHeader::WarcType => "WARC-Type",
+            WarcHeader::WarcInfoID => "WARC-Warcinfo-ID",
+            WarcHeader::Unknown(ref string) => {
+                return string
+                    .split('-')
+                    .map(|word| match word {
+                        "warc" => "WARC".to_owned(),
+                        _ => {
+                            let mut chars = word.chars();
+                            chars
+                                .next()
+                                .map(|c| c.to_uppercase().chain(chars).collect())
+                                .unwrap_or_default()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("-")
+            }
+        };
+
+        name.to_owned()
     }
-}
 
-impl Display for WarcHeader {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let stringified = match self {
+    /// Return true if the header may appear more than once in a record, as WARC-Concurrent-To
+    /// and WARC-Protocol may.
+    pub fn is_repeatable(&self) -> bool {
+        matches!(*self, WarcHeader::ConcurrentTo | WarcHeader::Protocol)
+    }
+
+    /// Return the name of the header in lowercase.
+    pub(crate) fn name(&self) -> &str {
+        match self {
             WarcHeader::ContentLength => "content-length",
             WarcHeader::ContentType => "content-type",
             WarcHeader::BlockDigest => "warc-block-digest",
+            WarcHeader::CipherSuite => "warc-cipher-suite",
             WarcHeader::ConcurrentTo => "warc-concurrent-to",
             WarcHeader::Date => "warc-date",
             WarcHeader::Filename => "warc-filename",
@@ -51,8 +108,11 @@ impl Display for WarcHeader {
             WarcHeader::IPAddress => "warc-ip-address",
             WarcHeader::PayloadDigest => "warc-payload-digest",
             WarcHeader::Profile => "warc-profile",
+            WarcHeader::Protocol => "warc-protocol",
             WarcHeader::RecordID => "warc-record-id",
             WarcHeader::RefersTo => "warc-refers-to",
+            WarcHeader::RefersToDate => "warc-refers-to-date",
+            WarcHeader::RefersToTargetURI => "warc-refers-to-target-uri",
             WarcHeader::SegmentNumber => "warc-segment-number",
             WarcHeader::SegmentOriginID => "warc-segment-origin-id",
             WarcHeader::SegmentTotalLength => "warc-segment-total-length",
@@ -61,8 +121,19 @@ impl Display for WarcHeader {
             WarcHeader::WarcType => "warc-type",
             WarcHeader::WarcInfoID => "warc-warcinfo-id",
             WarcHeader::Unknown(ref string) => string,
-        };
-        write!(f, "{}", stringified)
+        }
+    }
+}
+
+impl From<WarcHeader> for String {
+    fn from(header: WarcHeader) -> Self {
+        header.to_string()
+    }
+}
+
+impl Display for WarcHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
@@ -73,6 +144,7 @@ impl<S: AsRef<str>> From<S> for WarcHeader {
             "content-length" => WarcHeader::ContentLength,
             "content-type" => WarcHeader::ContentType,
             "warc-block-digest" => WarcHeader::BlockDigest,
+            "warc-cipher-suite" => WarcHeader::CipherSuite,
             "warc-concurrent-to" => WarcHeader::ConcurrentTo,
             "warc-date" => WarcHeader::Date,
             "warc-filename" => WarcHeader::Filename,
@@ -80,8 +152,11 @@ impl<S: AsRef<str>> From<S> for WarcHeader {
             "warc-ip-address" => WarcHeader::IPAddress,
             "warc-payload-digest" => WarcHeader::PayloadDigest,
             "warc-profile" => WarcHeader::Profile,
+            "warc-protocol" => WarcHeader::Protocol,
             "warc-record-id" => WarcHeader::RecordID,
             "warc-refers-to" => WarcHeader::RefersTo,
+            "warc-refers-to-date" => WarcHeader::RefersToDate,
+            "warc-refers-to-target-uri" => WarcHeader::RefersToTargetURI,
             "warc-segment-number" => WarcHeader::SegmentNumber,
             "warc-segment-origin-id" => WarcHeader::SegmentOriginID,
             "warc-segment-total-length" => WarcHeader::SegmentTotalLength,
@@ -93,3 +168,362 @@ impl<S: AsRef<str>> From<S> for WarcHeader {
         }
     }
 }
+
+/// The headers of a record, in the order they were read or first inserted.
+///
+/// A header may hold several values, as repeatable headers such as WARC-Concurrent-To do. `get`,
+/// `insert` and `remove` treat a header as having a single value: `get` returns the first,
+/// `insert` replaces every value, and `remove` removes every value. `get_all` and `append` give
+/// access to every value.
+#[derive(Clone, Debug, Default)]
+pub struct HeaderMap {
+    entries: Vec<(WarcHeader, Vec<u8>)>,
+}
+
+impl HeaderMap {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        HeaderMap::default()
+    }
+
+    /// Return the number of values in the map, counting each value of a repeated header.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if the map holds no headers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the first value of a header.
+    pub fn get(&self, header: &WarcHeader) -> Option<&Vec<u8>> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == header)
+            .map(|(_, value)| value)
+    }
+
+    /// Return the first value of a header, to be changed in place.
+    pub fn get_mut(&mut self, header: &WarcHeader) -> Option<&mut Vec<u8>> {
+        self.entries
+            .iter_mut()
+            .find(|(key, _)| key == header)
+            .map(|(_, value)| value)
+    }
+
+    /// Return every value of a header, in order.
+    pub fn get_all(&self, header: &WarcHeader) -> impl Iterator<Item = &Vec<u8>> {
+        let header = header.clone();
+        self.entries
+            .iter()
+            .filter(move |(key, _)| *key == header)
+            .map(|(_, value)| value)
+    }
+
+    /// Return true if the map holds a value of the header.
+    pub fn contains_key(&self, header: &WarcHeader) -> bool {
+        self.entries.iter().any(|(key, _)| key == header)
+    }
+
+    /// Set the value of a header, returning its previous first value.
+    ///
+    /// The value takes the place of the first previous value, and any others are removed. A new
+    /// header is added at the end.
+    pub fn insert(&mut self, header: WarcHeader, value: Vec<u8>) -> Option<Vec<u8>> {
+        match self.entries.iter().position(|(key, _)| *key == header) {
+            Some(i) => {
+                let previous = std::mem::replace(&mut self.entries[i].1, value);
+                let mut later = self.entries.split_off(i + 1);
+                later.retain(|(key, _)| *key != header);
+                self.entries.append(&mut later);
+                Some(previous)
+            }
+            None => {
+                self.entries.push((header, value));
+                None
+            }
+        }
+    }
+
+    /// Add a value of a header after any it already has.
+    pub fn append(&mut self, header: WarcHeader, value: Vec<u8>) {
+        self.entries.push((header, value));
+    }
+
+    /// Remove every value of a header, returning the first.
+    pub fn remove(&mut self, header: &WarcHeader) -> Option<Vec<u8>> {
+        let i = self.entries.iter().position(|(key, _)| key == header)?;
+        let first = self.entries.remove(i).1;
+        self.entries.retain(|(key, _)| key != header);
+
+        Some(first)
+    }
+
+    /// Keep only the values for which the predicate returns true.
+    pub fn retain<F: FnMut(&WarcHeader, &mut Vec<u8>) -> bool>(&mut self, mut f: F) {
+        self.entries.retain_mut(|(key, value)| f(key, value));
+    }
+
+    /// Return every header and value, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&WarcHeader, &Vec<u8>)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Return the header of every value, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &WarcHeader> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    /// Return every value, in order.
+    pub fn values(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    /// Move values into the order given, where a header listed n times places its first n
+    /// values. Values not placed by the list are moved after those which are, in their present
+    /// order.
+    pub(crate) fn sort_by_order(&mut self, order: &[WarcHeader]) {
+        let mut seen: Vec<&WarcHeader> = Vec::new();
+        let positions: Vec<usize> = self
+            .entries
+            .iter()
+            .map(|(key, _)| {
+                let nth = seen.iter().filter(|header| **header == key).count();
+                seen.push(key);
+                order
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, header)| *header == key)
+                    .nth(nth)
+                    .map_or(order.len(), |(position, _)| position)
+            })
+            .collect();
+        let mut entries: Vec<_> = positions.into_iter().zip(self.entries.drain(..)).collect();
+        entries.sort_by_key(|(position, _)| *position);
+        self.entries = entries.into_iter().map(|(_, entry)| entry).collect();
+    }
+}
+
+/// Maps are equal if each header has the same values in the same order; the order of different
+/// headers is not compared.
+impl PartialEq for HeaderMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .keys()
+                .all(|header| self.get_all(header).eq(other.get_all(header)))
+    }
+}
+
+impl Eq for HeaderMap {}
+
+impl Index<&WarcHeader> for HeaderMap {
+    type Output = Vec<u8>;
+
+    /// Return the first value of a header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the header is not present.
+    fn index(&self, header: &WarcHeader) -> &Vec<u8> {
+        self.get(header).expect("header not present")
+    }
+}
+
+/// Collect headers in order, keeping every value of a repeated header.
+impl FromIterator<(WarcHeader, Vec<u8>)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (WarcHeader, Vec<u8>)>>(iter: I) -> Self {
+        HeaderMap {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Set each header, as `insert` does.
+impl Extend<(WarcHeader, Vec<u8>)> for HeaderMap {
+    fn extend<I: IntoIterator<Item = (WarcHeader, Vec<u8>)>>(&mut self, iter: I) {
+        for (header, value) in iter {
+            self.insert(header, value);
+        }
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (WarcHeader, Vec<u8>);
+    type IntoIter = std::vec::IntoIter<(WarcHeader, Vec<u8>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a WarcHeader, &'a Vec<u8>);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (WarcHeader, Vec<u8>)>,
+        fn(&'a (WarcHeader, Vec<u8>)) -> (&'a WarcHeader, &'a Vec<u8>),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeaderMap, WarcHeader};
+
+    fn entries(map: &HeaderMap) -> Vec<(String, &str)> {
+        map.iter()
+            .map(|(header, value)| (header.to_string(), std::str::from_utf8(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn ordering() {
+        let mut map = HeaderMap::new();
+        map.insert(WarcHeader::WarcType, b"response".to_vec());
+        map.insert(WarcHeader::Unknown("x-b".to_owned()), b"b".to_vec());
+        map.insert(WarcHeader::ContentLength, b"0".to_vec());
+        map.insert(WarcHeader::Unknown("x-a".to_owned()), b"a".to_vec());
+        assert_eq!(
+            entries(&map),
+            vec![
+                ("warc-type".to_owned(), "response"),
+                ("x-b".to_owned(), "b"),
+                ("content-length".to_owned(), "0"),
+                ("x-a".to_owned(), "a"),
+            ]
+        );
+
+        // replacing a value keeps its place
+        map.insert(WarcHeader::Unknown("x-b".to_owned()), b"c".to_vec());
+        assert_eq!(entries(&map)[1], ("x-b".to_owned(), "c"));
+
+        map.append(WarcHeader::ConcurrentTo, b"<urn:1>".to_vec());
+        map.append(WarcHeader::Unknown("x-a".to_owned()), b"a2".to_vec());
+        map.append(WarcHeader::ConcurrentTo, b"<urn:2>".to_vec());
+        map.sort_by_order(&[
+            WarcHeader::ContentLength,
+            WarcHeader::ConcurrentTo,
+            WarcHeader::WarcType,
+            WarcHeader::ConcurrentTo,
+        ]);
+        assert_eq!(
+            entries(&map),
+            vec![
+                ("content-length".to_owned(), "0"),
+                ("warc-concurrent-to".to_owned(), "<urn:1>"),
+                ("warc-type".to_owned(), "response"),
+                ("warc-concurrent-to".to_owned(), "<urn:2>"),
+                ("x-b".to_owned(), "c"),
+                ("x-a".to_owned(), "a"),
+                ("x-a".to_owned(), "a2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut map: HeaderMap = vec![
+            (WarcHeader::ConcurrentTo, b"<urn:1>".to_vec()),
+            (WarcHeader::WarcType, b"request".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:2>".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:3>".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(&WarcHeader::ConcurrentTo).unwrap(), b"<urn:1>");
+        assert_eq!(map[&WarcHeader::ConcurrentTo], b"<urn:1>");
+        assert_eq!(
+            map.get_all(&WarcHeader::ConcurrentTo).collect::<Vec<_>>(),
+            vec![b"<urn:1>", b"<urn:2>", b"<urn:3>"]
+        );
+
+        // insert replaces every value, in the place of the first
+        let mut replaced = map.clone();
+        assert_eq!(
+            replaced.insert(WarcHeader::ConcurrentTo, b"<urn:4>".to_vec()),
+            Some(b"<urn:1>".to_vec())
+        );
+        assert_eq!(
+            entries(&replaced),
+            vec![
+                ("warc-concurrent-to".to_owned(), "<urn:4>"),
+                ("warc-type".to_owned(), "request"),
+            ]
+        );
+
+        // remove removes every value, returning the first
+        assert_eq!(
+            map.remove(&WarcHeader::ConcurrentTo),
+            Some(b"<urn:1>".to_vec())
+        );
+        assert!(!map.contains_key(&WarcHeader::ConcurrentTo));
+        assert_eq!(map.remove(&WarcHeader::ConcurrentTo), None);
+        assert_eq!(map.len(), 1);
+
+        // extend inserts, so a repeated header keeps only its last value
+        let mut extended = HeaderMap::new();
+        extended.extend(vec![
+            (WarcHeader::WarcType, b"request".to_vec()),
+            (WarcHeader::WarcType, b"response".to_vec()),
+        ]);
+        assert_eq!(
+            entries(&extended),
+            vec![("warc-type".to_owned(), "response")]
+        );
+    }
+
+    #[test]
+    fn equality() {
+        let a: HeaderMap = vec![
+            (WarcHeader::WarcType, b"response".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:1>".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:2>".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        let b: HeaderMap = vec![
+            (WarcHeader::ConcurrentTo, b"<urn:1>".to_vec()),
+            (WarcHeader::WarcType, b"response".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:2>".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        let c: HeaderMap = vec![
+            (WarcHeader::WarcType, b"response".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:2>".to_vec()),
+            (WarcHeader::ConcurrentTo, b"<urn:1>".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let mut d = a.clone();
+        d.append(WarcHeader::ConcurrentTo, b"<urn:2>".to_vec());
+        assert_ne!(a, d);
+        assert_ne!(d, a);
+    }
+
+    #[test]
+    fn case_insensitive_lookup() {
+        let mut map = HeaderMap::new();
+        map.insert(WarcHeader::from("WARC-Record-ID"), b"<urn:1>".to_vec());
+        map.insert(WarcHeader::from("X-Crawler"), b"test".to_vec());
+        assert_eq!(WarcHeader::from("warc-record-id"), WarcHeader::RecordID);
+
+        for name in ["WARC-Record-ID", "warc-record-id", "Warc-Record-Id"] {
+            assert_eq!(map.get(&WarcHeader::from(name)).unwrap(), b"<urn:1>");
+        }
+        for name in ["X-Crawler", "x-crawler", "X-CRAWLER"] {
+            assert!(map.contains_key(&WarcHeader::from(name)));
+        }
+        map.insert(WarcHeader::from("x-CRAWLER"), b"other".to_vec());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&WarcHeader::from("X-Crawler")], b"other");
+    }
+}
@@ -0,0 +1,355 @@
+//! Minimal scanning of HTML documents stored in record payloads.
+//!
+//! This is not a conforming HTML parser; it finds start tags and their attributes, which is enough
+//! to pull out titles, meta tags, and links from typical pages.
+
+use std::ops::Range;
+
+/// A start tag and its attributes, with lowercased names.
+#[cfg(feature = "extract")]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Tag {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    /// The byte range of the raw value of each attribute in the document, in the same order.
+    pub spans: Vec<Range<usize>>,
+    /// The byte offset just past the end of the tag.
+    pub end: usize,
+}
+
+#[cfg(feature = "extract")]
+impl Tag {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Return every start tag in the document, in order.
+///
+/// Comments are skipped, as are the contents of `script` and `style` elements.
+#[cfg(feature = "extract")]
+pub(crate) fn tags(html: &str) -> Vec<Tag> {
+    let lower = html.to_ascii_lowercase();
+    let bytes = html.as_bytes();
+    let mut tags = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset + 1;
+        if lower[start..].starts_with("!--") {
+            pos = lower[start..]
+                .find("-->")
+                .map(|end| start + end + 3)
+                .unwrap_or(html.len());
+            continue;
+        }
+        let name_len = lower[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(lower.len() - start);
+        if name_len == 0 || !bytes[start].is_ascii_alphabetic() {
+            pos = start;
+            continue;
+        }
+
+        let name = lower[start..start + name_len].to_owned();
+        let (attributes, spans, end) = attributes(html, start + name_len);
+        pos = end;
+        if name == "script" || name == "style" {
+            let close = format!("</{}", name);
+            pos = lower[pos..]
+                .find(&close)
+                .map(|offset| pos + offset)
+                .unwrap_or(html.len());
+        }
+        tags.push(Tag {
+            name,
+            attributes,
+            spans,
+            end,
+        });
+    }
+
+    tags
+}
+
+/// Parse attributes from `pos` up to the end of the tag, returning them, the spans of their
+/// values, and the end offset.
+fn attributes(html: &str, mut pos: usize) -> (Vec<(String, String)>, Vec<Range<usize>>, usize) {
+    let bytes = html.as_bytes();
+    let mut attributes = Vec::new();
+    let mut spans = Vec::new();
+
+    loop {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'/') {
+            pos += 1;
+        }
+        if pos >= bytes.len() {
+            return (attributes, spans, pos);
+        }
+        if bytes[pos] == b'>' {
+            return (attributes, spans, pos + 1);
+        }
+
+        let name_start = pos;
+        while pos < bytes.len()
+            && !bytes[pos].is_ascii_whitespace()
+            && !matches!(bytes[pos], b'=' | b'>' | b'/')
+        {
+            pos += 1;
+        }
+        let name = html[name_start..pos].to_ascii_lowercase();
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let mut value = String::new();
+        let mut span = pos..pos;
+        if pos < bytes.len() && bytes[pos] == b'=' {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && (bytes[pos] == b'"' || bytes[pos] == b'\'') {
+                let quote = bytes[pos];
+                let value_start = pos + 1;
+                pos = value_start;
+                while pos < bytes.len() && bytes[pos] != quote {
+                    pos += 1;
+                }
+                value = html[value_start..pos].to_owned();
+                span = value_start..pos;
+                pos = (pos + 1).min(bytes.len());
+            } else {
+                let value_start = pos;
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                    pos += 1;
+                }
+                value = html[value_start..pos].to_owned();
+                span = value_start..pos;
+            }
+        }
+        if !name.is_empty() {
+            attributes.push((name, decode_entities(&value)));
+            spans.push(span);
+        }
+    }
+}
+
+/// Return the text of the first `title` element, with whitespace collapsed.
+#[cfg(feature = "extract")]
+pub(crate) fn title(html: &str, tags: &[Tag]) -> Option<String> {
+    let tag = tags.iter().find(|tag| tag.name == "title")?;
+    let lower = html[tag.end..].to_ascii_lowercase();
+    let end = lower.find("</title").unwrap_or(lower.len());
+    let text = html[tag.end..tag.end + end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(decode_entities(&text))
+}
+
+/// Return the content of the first `meta` tag with the given name.
+#[cfg(feature = "extract")]
+pub(crate) fn meta(tags: &[Tag], name: &str) -> Option<String> {
+    tags.iter()
+        .filter(|tag| tag.name == "meta")
+        .find(|tag| {
+            tag.attribute("name")
+                .map(|n| n.eq_ignore_ascii_case(name))
+                .unwrap_or(false)
+        })
+        .and_then(|tag| tag.attribute("content"))
+        .map(|content| content.to_owned())
+}
+
+/// Return the attribute which holds a link for the given tag name, if any.
+#[cfg(feature = "extract")]
+pub(crate) fn link_attribute(tag: &str) -> Option<&'static str> {
+    match tag {
+        "a" | "area" | "link" | "base" => Some("href"),
+        "img" | "script" | "iframe" | "frame" | "embed" | "source" | "audio" | "video"
+        | "track" => Some("src"),
+        "form" => Some("action"),
+        _ => None,
+    }
+}
+
+/// Elements whose start and end break the text into lines.
+#[cfg(feature = "wet")]
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Elements whose content is not text shown to the reader.
+#[cfg(feature = "wet")]
+const HIDDEN_ELEMENTS: &[&str] = &["noscript", "script", "style", "template"];
+
+/// Return the text of a document as lines, one for each block of text, with whitespace collapsed
+/// and blank lines left out.
+#[cfg(feature = "wet")]
+pub(crate) fn text(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let bytes = html.as_bytes();
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut end_line = |line: &mut String| {
+        let text = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            lines.push(decode_entities(&text));
+        }
+        line.clear();
+    };
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find('<') {
+        line.push_str(&html[pos..pos + offset]);
+        let start = pos + offset + 1;
+        if lower[start..].starts_with("!--") {
+            pos = lower[start..]
+                .find("-->")
+                .map(|end| start + end + 3)
+                .unwrap_or(html.len());
+            continue;
+        }
+        if lower[start..].starts_with('!') || lower[start..].starts_with('?') {
+            pos = lower[start..]
+                .find('>')
+                .map(|end| start + end + 1)
+                .unwrap_or(html.len());
+            continue;
+        }
+
+        let name_start = if lower[start..].starts_with('/') {
+            start + 1
+        } else {
+            start
+        };
+        let name_len = lower[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(lower.len() - name_start);
+        if name_len == 0 || !bytes[name_start].is_ascii_alphabetic() {
+            line.push('<');
+            pos = start;
+            continue;
+        }
+
+        let name = &lower[name_start..name_start + name_len];
+        let (_, _, end) = attributes(html, name_start + name_len);
+        pos = end;
+        if name_start == start && HIDDEN_ELEMENTS.contains(&name) {
+            pos = lower[pos..]
+                .find(&format!("</{}", name))
+                .and_then(|close| {
+                    lower[pos + close..]
+                        .find('>')
+                        .map(|end| pos + close + end + 1)
+                })
+                .unwrap_or(html.len());
+        }
+        if BLOCK_ELEMENTS.contains(&name) {
+            end_line(&mut line);
+        }
+    }
+    line.push_str(&html[pos..]);
+    end_line(&mut line);
+
+    lines.join("\n")
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_owned();
+    }
+
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "extract")]
+    #[test]
+    fn scan() {
+        use super::{meta, tags, title};
+
+        let html = r#"<!DOCTYPE html><HTML><head><!-- <a href="/hidden"> -->
+            <title>  Hello &amp;
+              World </title>
+            <meta name="Description" content='A "quoted" page'>
+            <script>var s = "<a href='/script'>";</script>
+            </head><body><a class=x href=/one>one</a><img src="/two.png"/></body></HTML>"#;
+        let tags = tags(html);
+
+        let names: Vec<_> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["html", "head", "title", "meta", "script", "body", "a", "img"]
+        );
+        assert_eq!(title(html, &tags), Some("Hello & World".to_owned()));
+        assert_eq!(
+            meta(&tags, "description"),
+            Some("A \"quoted\" page".to_owned())
+        );
+        assert_eq!(tags[6].attribute("href"), Some("/one"));
+        assert_eq!(tags[7].attribute("src"), Some("/two.png"));
+    }
+
+    #[cfg(feature = "wet")]
+    #[test]
+    fn text() {
+        use super::text;
+
+        let html = r#"<!DOCTYPE html><HTML><head><!-- <a href="/hidden"> -->
+            <title>  Hello &amp;
+              World </title>
+            <script>var s = "<a href='/script'>";</script>
+            </head><body><a class=x href=/one>one</a><img src="/two.png"/></body></HTML>"#;
+        assert_eq!(text(html), "Hello & World\none",);
+        assert_eq!(
+            text("<p>a <b>bold</b>\n  move</p><p>1 < 2<br>x &lt; y</p>"),
+            "a bold move\n1 < 2\nx < y"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Minimal inspection of HTTP messages stored in record bodies.
+
+/// The head of an HTTP/1.x message stored in a record: its start line and headers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HttpHead {
+    /// The request line, such as `GET /index.html HTTP/1.1`, or the status line, such as
+    /// `HTTP/1.1 200 OK`.
+    pub start_line: String,
+    /// The headers in the order they were sent, with folded lines joined.
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpHead {
+    /// Parse the head of the HTTP message at the start of the body.
+    ///
+    /// `None` is returned if the body does not start with a complete HTTP message head. Header
+    /// lines without a colon are dropped, and bytes which are not UTF-8 are replaced.
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let (head, _) = split_head(body)?;
+        let mut lines = head
+            .split(|b| *b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .take_while(|line| !line.is_empty())
+            .map(String::from_utf8_lossy);
+        let start_line = lines.next()?.trim().to_owned();
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_owned(), value.trim().to_owned()));
+            }
+        }
+
+        Some(HttpHead {
+            start_line,
+            headers,
+        })
+    }
+
+    /// Return the value of the first header with the given name, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return the status code, if this is the head of a response.
+    pub fn status(&self) -> Option<u16> {
+        status(self.start_line.as_bytes())
+    }
+
+    /// Return the method, if this is the head of a request.
+    pub fn method(&self) -> Option<&str> {
+        if self.start_line.starts_with("HTTP/") {
+            return None;
+        }
+
+        self.start_line.split_whitespace().next()
+    }
+}
+
+/// Return the status code of the HTTP response at the start of the body.
+pub(crate) fn status(body: &[u8]) -> Option<u16> {
+    let line = body.split(|b| *b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+
+    parts.next()?.parse().ok()
+}
+
+/// Return the value of the named header of the HTTP message at the start of the body.
+///
+/// The name is matched case-insensitively, and the first matching header is returned.
+pub(crate) fn header(body: &[u8], name: &str) -> Option<String> {
+    body.split(|b| *b == b'\n')
+        .skip(1)
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let line = String::from_utf8_lossy(line);
+            let (key, value) = line.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case(name) {
+                Some(value.trim().to_owned())
+            } else {
+                None
+            }
+        })
+}
+
+/// Split the HTTP message at the start of the body into its head, including the blank line ending
+/// it, and its payload.
+///
+/// `None` is returned if the body does not start with a complete HTTP message head.
+pub(crate) fn split_head(body: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = body
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+        .or_else(|| {
+            body.windows(2)
+                .position(|w| w == b"\n\n")
+                .map(|pos| pos + 2)
+        })?;
+
+    Some(body.split_at(end))
+}
+
+/// Return true if the Content-Type indicates the record holds an HTTP message.
+pub(crate) fn is_http_message(content_type: &str) -> bool {
+    content_type
+        .trim_start()
+        .to_ascii_lowercase()
+        .starts_with("application/http")
+}
+
+/// Return the media type of a record's payload, lowercased and without parameters.
+///
+/// The media type is taken from the WARC-Identified-Payload-Type header, or else the Content-Type
+/// header. For HTTP messages, the Content-Type of the HTTP message is used instead.
+pub(crate) fn payload_media_type(
+    identified_type: Option<String>,
+    content_type: Option<String>,
+    body: &[u8],
+) -> String {
+    let mut media_type = identified_type.or(content_type).unwrap_or_default();
+    if is_http_message(&media_type) {
+        media_type = header(body, "content-type").unwrap_or_default();
+    }
+
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header, is_http_message, payload_media_type, split_head, status, HttpHead};
+
+    #[test]
+    fn response_head() {
+        let body = b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nX-A: b: c\r\n\r\nContent-Type: nope";
+
+        assert_eq!(status(body), Some(404));
+        assert_eq!(header(body, "content-type"), Some("text/html".to_owned()));
+        assert_eq!(header(body, "x-a"), Some("b: c".to_owned()));
+        assert_eq!(header(body, "missing"), None);
+        assert_eq!(status(b"GET / HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(split_head(body).unwrap().1, b"Content-Type: nope");
+        assert_eq!(split_head(b"HTTP/1.1 200 OK\r\n"), None);
+        assert!(is_http_message("application/http; msgtype=response"));
+        assert!(!is_http_message("text/html"));
+        assert_eq!(
+            payload_media_type(None, Some("application/http".to_owned()), body),
+            "text/html"
+        );
+
+        let head = HttpHead::parse(body).unwrap();
+        assert_eq!(head.start_line, "HTTP/1.1 404 Not Found");
+        assert_eq!(head.status(), Some(404));
+        assert_eq!(head.method(), None);
+        assert_eq!(head.header("X-A"), Some("b: c"));
+        let head = HttpHead::parse(b"GET / HTTP/1.1\r\nX-Long: a\r\n  b\r\nbad\r\n\r\n").unwrap();
+        assert_eq!(head.method(), Some("GET"));
+        assert_eq!(head.headers, vec![("X-Long".to_owned(), "a b".to_owned())]);
+    }
+}
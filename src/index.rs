@@ -0,0 +1,362 @@
+//! Generation of CDXJ indexes, as read by pywb and other replay systems.
+//!
+//! A CDXJ index has one line per capture: the SURT key of the URL, the 14-digit timestamp of the
+//! capture, and a JSON object giving the URL, the media type and status of the response, the
+//! payload digest, and the offset and length of the record within its WARC file. Lines are sorted,
+//! so that a replay system can find every capture of a URL by binary search. A line, wrapped here
+//! to fit, looks like:
+//!
+//! ```text
+//! com,example)/ 20200101000000 {"url":"http://example.com/","mime":"text/html","status":"200",
+//!     "digest":"3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ","length":"345","offset":"0",
+//!     "filename":"example.warc.gz"}
+//! ```
+//!
+//! Offsets and lengths are those of the stored record. In a compressed file, they are those of the
+//! gzip member holding the record, so each record must have a member of its own; see
+//! `gzip_members` for files which do not.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+
+#[cfg(feature = "gzip")]
+use std::io::{BufReader, Read};
+
+use crate::canonicalize::Canonicalizer;
+use crate::header::WarcHeader;
+use crate::json::JsonString;
+use crate::{http, BufferedBody, Error, Record, RecordType, WarcReader};
+
+#[cfg(feature = "gzip")]
+use crate::stats::CountingReader;
+#[cfg(feature = "gzip")]
+use libflate::gzip::Decoder as GzipReader;
+
+/// A line of a CDXJ index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CdxjEntry {
+    /// The SURT key of the canonical URL.
+    pub key: String,
+    /// The date of the capture, as 14 digits.
+    pub timestamp: String,
+    /// The URL as captured.
+    pub url: String,
+    /// The media type of the payload, or `warc/revisit` for revisit records.
+    pub mime: String,
+    /// The HTTP status code of the response.
+    pub status: Option<u16>,
+    /// The payload digest, without its algorithm label.
+    pub digest: Option<String>,
+    /// The length of the stored record.
+    pub length: u64,
+    /// The offset of the stored record within its file.
+    pub offset: u64,
+    /// The name of the file holding the record.
+    pub filename: Option<String>,
+}
+
+impl fmt::Display for CdxjEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields = vec![
+            format!("\"url\":{}", JsonString(&self.url)),
+            format!("\"mime\":{}", JsonString(&self.mime)),
+        ];
+        if let Some(status) = self.status {
+            fields.push(format!("\"status\":\"{}\"", status));
+        }
+        if let Some(ref digest) = self.digest {
+            fields.push(format!("\"digest\":{}", JsonString(digest)));
+        }
+        fields.push(format!("\"length\":\"{}\"", self.length));
+        fields.push(format!("\"offset\":\"{}\"", self.offset));
+        if let Some(ref filename) = self.filename {
+            fields.push(format!("\"filename\":{}", JsonString(filename)));
+        }
+
+        write!(
+            f,
+            "{} {} {{{}}}",
+            self.key,
+            self.timestamp,
+            fields.join(",")
+        )
+    }
+}
+
+/// A builder of CDXJ entries for the captures of a WARC file.
+///
+/// Response, revisit and resource records are indexed; other records are not captures of a URL.
+#[derive(Debug)]
+pub struct CdxjIndexer {
+    canonicalizer: Canonicalizer,
+    filename: Option<String>,
+}
+
+impl Default for CdxjIndexer {
+    fn default() -> Self {
+        CdxjIndexer {
+            canonicalizer: Canonicalizer::ia(),
+            filename: None,
+        }
+    }
+}
+
+impl CdxjIndexer {
+    /// Create an indexer building keys as pywb does, with `Canonicalizer::ia`.
+    pub fn new() -> Self {
+        CdxjIndexer::default()
+    }
+
+    /// Set the canonicalizer used to build keys.
+    pub fn canonicalizer(mut self, canonicalizer: Canonicalizer) -> Self {
+        self.canonicalizer = canonicalizer;
+
+        self
+    }
+
+    /// Set the file name given in each entry, by which a replay system finds the file.
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filename = Some(filename.into());
+
+        self
+    }
+
+    /// Build the entry for a record stored at the given offset and length, or `None` if the
+    /// record is not indexed.
+    pub fn entry(
+        &self,
+        record: &Record<BufferedBody>,
+        offset: u64,
+        length: u64,
+    ) -> Option<CdxjEntry> {
+        let warc_type = record.warc_type();
+        if !matches!(
+            warc_type,
+            RecordType::Response | RecordType::Revisit | RecordType::Resource
+        ) {
+            return None;
+        }
+        let url = record.header(WarcHeader::TargetURI)?.into_owned();
+        let content_type = record
+            .header(WarcHeader::ContentType)
+            .map(|value| value.into_owned());
+        let is_http = content_type.as_deref().is_some_and(http::is_http_message);
+        let (mime, status) = match warc_type {
+            RecordType::Revisit => ("warc/revisit".to_string(), http::status(record.body())),
+            RecordType::Response if is_http => (
+                http::payload_media_type(None, content_type, record.body()),
+                http::status(record.body()),
+            ),
+            _ => (
+                http::payload_media_type(None, content_type, record.body()),
+                Some(200),
+            ),
+        };
+        let digest =
+            record
+                .header(WarcHeader::PayloadDigest)
+                .map(|digest| match digest.split_once(':') {
+                    Some((_, value)) => value.to_owned(),
+                    None => digest.into_owned(),
+                });
+
+        Some(CdxjEntry {
+            key: self.canonicalizer.surt(&url),
+            timestamp: record.date().format("%Y%m%d%H%M%S").to_string(),
+            url,
+            mime,
+            status,
+            digest,
+            length,
+            offset,
+            filename: self.filename.clone(),
+        })
+    }
+
+    /// Index an uncompressed WARC file, returning its entries in the order of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first record which cannot be read.
+    pub fn index<R: BufRead>(&self, reader: R) -> Result<Vec<CdxjEntry>, Error> {
+        let mut entries = Vec::new();
+        for record in WarcReader::new(reader).iter_positioned_records() {
+            let (position, record) = record?;
+            let length = record.stored_len().unwrap_or_default();
+            entries.extend(self.entry(&record, position.offset, length));
+        }
+
+        Ok(entries)
+    }
+
+    /// Index a gzip-compressed WARC file with one record per member, returning its entries in the
+    /// order of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ReadData` if a member cannot be decoded, `Error::MalformedBody` if a
+    /// member does not hold exactly one record, and the first record which cannot be read.
+    #[cfg(feature = "gzip")]
+    pub fn index_gzip<R: Read>(&self, reader: R) -> Result<Vec<CdxjEntry>, Error> {
+        let mut entries = Vec::new();
+        let mut input = CountingReader(BufReader::new(reader), 0);
+        loop {
+            match input.0.fill_buf() {
+                Err(_) => return Err(Error::ReadData),
+                Ok([]) => break,
+                Ok(_) => {}
+            }
+
+            let offset = input.1;
+            let mut member = Vec::new();
+            GzipReader::new(&mut input)
+                .and_then(|mut decoder| decoder.read_to_end(&mut member))
+                .map_err(|_| Error::ReadData)?;
+            let mut records = WarcReader::new(&member[..]).iter_records();
+            let record = records.next().unwrap_or(Err(Error::UnexpectedEOB))?;
+            if records.next().is_some() {
+                return Err(Error::MalformedBody(format!(
+                    "gzip member at offset {} holds more than one record",
+                    offset
+                )));
+            }
+            entries.extend(self.entry(&record, offset, input.1 - offset));
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Write entries as a CDXJ index, sorted as replay systems require.
+pub fn write_cdxj<W: Write>(entries: &[CdxjEntry], mut output: W) -> std::io::Result<()> {
+    let mut lines: Vec<_> = entries.iter().map(|entry| entry.to_string()).collect();
+    lines.sort_unstable();
+    for line in lines {
+        output.write_all(line.as_bytes())?;
+        output.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{write_cdxj, CdxjEntry, CdxjIndexer};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcWriter};
+
+    fn record(warc_type: RecordType, uri: &str, body: &[u8]) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::with_body(body);
+        record.set_warc_version("1.0");
+        record.set_warc_type(warc_type);
+        record.set_date(Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap());
+        record.set_header(WarcHeader::TargetURI, uri).unwrap();
+        record
+    }
+
+    #[test]
+    fn json_escaping() {
+        let entry = CdxjEntry {
+            key: "com,example)/a%7fb%e2%80%8b".to_owned(),
+            timestamp: "20200102030405".to_owned(),
+            url: "http://example.com/a\u{7f}b\u{200b}\\".to_owned(),
+            mime: "text/\"x\"".to_owned(),
+            status: None,
+            digest: None,
+            length: 1,
+            offset: 0,
+            filename: Some("a\tb.warc".to_owned()),
+        };
+        assert_eq!(
+            entry.to_string(),
+            "com,example)/a%7fb%e2%80%8b 20200102030405 \
+             {\"url\":\"http://example.com/a\\u007fb\u{200b}\\\\\",\"mime\":\"text/\\\"x\\\"\",\
+             \"length\":\"1\",\"offset\":\"0\",\"filename\":\"a\\tb.warc\"}"
+        );
+    }
+
+    #[test]
+    fn cdxj() {
+        let mut response = record(
+            RecordType::Response,
+            "http://www.Example.com/b?z=1&a=2",
+            b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html; charset=utf-8\r\n\r\nmissing",
+        );
+        response
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        response
+            .set_header(WarcHeader::PayloadDigest, "sha1:ABCDEF")
+            .unwrap();
+        let mut request = record(RecordType::Request, "http://example.com/", b"GET /");
+        request.set_warc_id("<urn:uuid:00000000-0000-4000-8000-000000000001>");
+        let mut resource = record(RecordType::Resource, "http://example.com/\"a\"", b"text");
+        resource
+            .set_header(WarcHeader::ContentType, "text/plain")
+            .unwrap();
+
+        let mut data = Vec::new();
+        let mut writer = WarcWriter::new(&mut data);
+        let lengths: Vec<_> = [&response, &request, &resource]
+            .iter()
+            .map(|record| writer.write(record).unwrap() as u64)
+            .collect();
+        drop(writer);
+
+        let entries = CdxjIndexer::new()
+            .filename("a.warc")
+            .index(&data[..])
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "com,example)/b?a=2&z=1");
+        assert_eq!(entries[0].offset, 0);
+        assert_eq!(entries[0].length, lengths[0]);
+        assert_eq!(
+            entries[0].to_string(),
+            format!(
+                "com,example)/b?a=2&z=1 20200102030405 \
+                 {{\"url\":\"http://www.Example.com/b?z=1&a=2\",\"mime\":\"text/html\",\
+                 \"status\":\"404\",\"digest\":\"ABCDEF\",\"length\":\"{}\",\
+                 \"offset\":\"0\",\"filename\":\"a.warc\"}}",
+                lengths[0]
+            )
+        );
+        assert_eq!(entries[1].offset, lengths[0] + lengths[1]);
+        assert_eq!(entries[1].mime, "text/plain");
+        assert_eq!(entries[1].status, Some(200));
+        assert!(entries[1]
+            .to_string()
+            .contains(r#""url":"http://example.com/\"a\"""#));
+
+        let mut index = Vec::new();
+        write_cdxj(&entries, &mut index).unwrap();
+        let index = String::from_utf8(index).unwrap();
+        let keys: Vec<_> = index
+            .lines()
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(keys, ["com,example)/%22a%22", "com,example)/b?a=2&z=1"]);
+
+        #[cfg(feature = "gzip")]
+        {
+            use crate::MemberCompression;
+
+            let mut compressed = Vec::new();
+            let mut writer = WarcWriter::new(&mut compressed);
+            writer.set_member_compression(|_, _| MemberCompression::Compressed);
+            let lengths: Vec<_> = [&response, &request, &resource]
+                .iter()
+                .map(|record| writer.write(record).unwrap() as u64)
+                .collect();
+            drop(writer);
+
+            let entries = CdxjIndexer::new().index_gzip(&compressed[..]).unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].length, lengths[0]);
+            assert_eq!(entries[1].offset, lengths[0] + lengths[1]);
+            assert_eq!(entries[1].length, lengths[2]);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Writing of JSON strings, for the modules which produce JSON without depending on serde_json.
+
+use std::fmt;
+
+/// A string displayed as a quoted JSON string.
+///
+/// Quotation marks and backslashes are escaped with a backslash, and control characters as
+/// `\u00XX`. Every other character is written as it is, since JSON text is UTF-8.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct JsonString<'a>(pub &'a str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        let mut rest = self.0;
+        while let Some(i) = rest.find(|c: char| c == '"' || c == '\\' || c.is_control()) {
+            f.write_str(&rest[..i])?;
+            let c = rest[i..].chars().next().unwrap();
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c => write!(f, "\\u{:04x}", c as u32)?,
+            }
+            rest = &rest[i + c.len_utf8()..];
+        }
+        f.write_str(rest)?;
+        f.write_str("\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonString;
+
+    #[test]
+    fn escape() {
+        let json = |s| JsonString(s).to_string();
+        assert_eq!(json(""), r#""""#);
+        assert_eq!(json("plain"), r#""plain""#);
+        assert_eq!(json(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(json("\n\r\t\0\x1b\x7f"), r#""\n\r\t\u0000\u001b\u007f""#);
+        assert_eq!(json("\u{85}"), r#""\u0085""#);
+        assert_eq!(json("a\u{200b}b é 😀"), "\"a\u{200b}b é 😀\"");
+    }
+}
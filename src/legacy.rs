@@ -0,0 +1,77 @@
+//! Handling of records written under pre-1.0 drafts of the WARC standard.
+use crate::header::WarcHeader;
+use crate::RawRecordHeader;
+
+/// Header names used by pre-1.0 drafts, and the names which replaced them.
+const RENAMED_HEADERS: &[(&str, WarcHeader)] = &[("warc-subject-uri", WarcHeader::TargetURI)];
+
+/// Return true if the given version string is a pre-1.0 draft, such as 0.17 or 0.18.
+pub(crate) fn is_legacy_version(version: &str) -> bool {
+    let version = version.trim_start_matches("WARC/");
+    match version.split_once('.') {
+        Some((major, minor)) => major == "0" && minor.parse::<u32>().is_ok(),
+        None => false,
+    }
+}
+
+/// Rename the headers of a pre-1.0 record to their current names.
+///
+/// A header is only renamed if the record does not also have the current header.
+pub(crate) fn rename_headers(headers: &mut RawRecordHeader) {
+    if !is_legacy_version(&headers.version) {
+        return;
+    }
+
+    for (legacy, current) in RENAMED_HEADERS {
+        let legacy = WarcHeader::Unknown((*legacy).to_owned());
+        if headers.as_ref().contains_key(current) {
+            continue;
+        }
+        if let Some(value) = headers.as_mut().remove(&legacy) {
+            headers.as_mut().insert(current.clone(), value);
+        }
+    }
+}
+
+/// Return the warc-fields line noting that records were upgraded from the given version.
+pub(crate) fn upgrade_note(from: &str) -> String {
+    format!(
+        "upgraded-from: WARC/{}\r\n",
+        from.trim_start_matches("WARC/")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_legacy_version, rename_headers};
+    use crate::header::WarcHeader;
+    use crate::RawRecordHeader;
+
+    #[test]
+    fn legacy_headers() {
+        assert!(is_legacy_version("0.17"));
+        assert!(is_legacy_version("WARC/0.18"));
+        assert!(!is_legacy_version("1.0"));
+        assert!(!is_legacy_version("0"));
+
+        let mut headers = RawRecordHeader {
+            version: "0.17".to_owned(),
+            headers: vec![(
+                WarcHeader::from("WARC-Subject-URI"),
+                b"http://example.com/".to_vec(),
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let mut current = headers.clone();
+        current.version = "1.0".to_owned();
+
+        rename_headers(&mut current);
+        assert!(current.as_ref().get(&WarcHeader::TargetURI).is_none());
+        rename_headers(&mut headers);
+        assert_eq!(
+            headers.as_ref().get(&WarcHeader::TargetURI).unwrap(),
+            b"http://example.com/"
+        );
+    }
+}
@@ -1,22 +1,183 @@
 //! A WARC (Web ARChive) library
 
+pub mod arc;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
+mod browser;
+pub use browser::{ArtifactKind, BrowserArtifact};
+
+#[cfg(feature = "browsertrix")]
+pub mod browsertrix;
+
+pub mod canonicalize;
+
+mod capture;
+pub use capture::{Capture, CaptureSession};
+
+mod collection;
+pub use collection::{transplant, Collection, CollectionEntry};
+
+#[cfg(feature = "codec")]
+pub mod codec;
+
+#[cfg(feature = "conformance")]
+pub mod conformance;
+
+mod date_precision;
+pub use date_precision::DatePrecision;
+
+#[cfg(feature = "digest")]
+pub mod delta;
+
+#[cfg(feature = "digest")]
+pub mod digest;
+
+pub mod envelope;
+
 mod error;
 pub use error::Error;
 
+#[cfg(feature = "extract")]
+pub mod extract;
+
+#[cfg(feature = "filter")]
+pub mod filter;
+
+mod ftp;
+pub use ftp::FtpRetrieval;
+
+#[cfg(feature = "gzip")]
+pub mod gzip_members;
+
 mod warc_reader;
-pub use warc_reader::WarcReader;
+pub use warc_reader::{RecordPosition, ScanSummary, SkipReason, SkippedRange, WarcReader};
 mod warc_writer;
-pub use warc_writer::WarcWriter;
+#[cfg(feature = "gzip")]
+pub use warc_writer::{store_compressed_media, MemberCompression};
+pub use warc_writer::{Compatibility, HeaderCase, WarcWriter, WriteEvent};
 
 pub mod header;
 
+pub mod index;
+
+#[cfg(any(feature = "extract", feature = "wet"))]
+mod html;
+
+mod json;
+
+mod legacy;
+
+mod metadata;
+pub use metadata::MetadataRecordBuilder;
+
+mod http;
+pub use http::HttpHead;
+
+#[cfg(feature = "parallel_index")]
+pub mod parallel_index;
+
+#[cfg(feature = "pcap")]
+pub mod pcap;
+
+mod post_request;
+pub use post_request::{FormFilter, PostRequest, Redaction};
+
 pub mod parser;
+mod parser_config;
+pub use parser_config::{
+    HeaderNormalizer, HeaderPolicy, ParseMode, ParserConfig, ReadPolicy, SeparatorPolicy,
+};
+
+pub mod provenance;
+
+pub mod recompress;
+
+pub mod record_ids;
+
+pub mod redirects;
+
+pub mod replay;
+
+pub mod retention;
+
+#[cfg(feature = "extract")]
+pub mod rewrite;
 
 mod record;
-pub use record::{BufferedBody, EmptyBody, RawRecordHeader, Record, RecordBuilder, StreamingBody};
+#[cfg(feature = "bytes")]
+pub use record::BytesBody;
+pub use record::{
+    BufferedBody, EmptyBody, RawRecordHeader, Record, RecordBuilder, SharedBody, StreamingBody,
+};
+mod record_ref;
+pub use record_ref::{RecordRef, RecordRefIter};
+
+pub mod stats;
+
+pub mod service;
+
+mod segment;
+pub use segment::{
+    join_segments, segment_total_length, split_segments, verify_segments, Reassemble, Segment,
+    SegmentError,
+};
+
+mod refers_to;
+pub use refers_to::RefersTo;
+
+mod revisit_profile;
+pub use revisit_profile::RevisitProfile;
+
+pub mod sitemap;
+
+pub mod sniff;
+
+pub mod robots;
+
+mod schema;
+pub use schema::{Schema, SchemaViolation};
+
+mod rotating_writer;
+pub use rotating_writer::RotatingWarcWriter;
 
 mod record_type;
 pub use record_type::RecordType;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
+mod text_resource;
+pub use text_resource::TextResource;
+
+mod transcript;
+pub use transcript::Transcript;
+
 mod truncated_type;
 pub use truncated_type::TruncatedType;
+
+pub mod typed_header;
+pub use typed_header::TypedHeader;
+
+pub mod validate;
+
+mod warcinfo;
+pub use warcinfo::{WarcinfoBuilder, WarcinfoPreset};
+
+mod warc_version;
+pub use warc_version::WarcVersion;
+mod warning;
+pub use warning::Warning;
+
+mod wget;
+pub use wget::WgetMetadata;
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+#[cfg(feature = "wat")]
+pub mod wat;
+
+#[cfg(feature = "wet")]
+pub mod wet;
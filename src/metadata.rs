@@ -0,0 +1,251 @@
+use chrono::prelude::*;
+
+#[cfg(feature = "digest")]
+use crate::digest::Algorithm;
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{BufferedBody, Error, Record, RecordType};
+
+/// A builder for metadata records holding `application/warc-fields`, such as the outlinks and
+/// fetch timings a crawler attaches to each capture.
+///
+/// Fields are written in the order they were added, and a name may be repeated. The record
+/// describes a target record, whose ID it carries in WARC-Concurrent-To and whose
+/// WARC-Target-URI it shares.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetadataRecordBuilder {
+    warc_version: Option<String>,
+    date: Option<DateTime<Utc>>,
+    target_uri: Option<String>,
+    concurrent_to: Vec<String>,
+    fields: Vec<(String, String)>,
+    #[cfg(feature = "digest")]
+    digest: Option<Algorithm>,
+}
+
+impl MetadataRecordBuilder {
+    /// Create a builder with no fields.
+    pub fn new() -> Self {
+        MetadataRecordBuilder::default()
+    }
+
+    /// Describe the given record: link to it with WARC-Concurrent-To, and copy its
+    /// WARC-Target-URI, WARC-Date and WARC version unless they are set explicitly.
+    pub fn target<T: BodyKind>(mut self, record: &Record<T>) -> Self {
+        self.concurrent_to.push(record.warc_id().to_owned());
+        if self.target_uri.is_none() {
+            self.target_uri = record
+                .header(WarcHeader::TargetURI)
+                .map(|uri| uri.into_owned());
+        }
+        self.date.get_or_insert(*record.date());
+        self.warc_version
+            .get_or_insert_with(|| record.warc_version().to_owned());
+
+        self
+    }
+
+    /// Link to a record by its ID, with WARC-Concurrent-To.
+    pub fn concurrent_to<S: Into<String>>(mut self, record_id: S) -> Self {
+        self.concurrent_to.push(record_id.into());
+
+        self
+    }
+
+    /// Set the WARC-Target-URI of the record.
+    pub fn target_uri<S: Into<String>>(mut self, target_uri: S) -> Self {
+        self.target_uri = Some(target_uri.into());
+
+        self
+    }
+
+    /// Set the WARC-Date of the record. By default, the record is dated like its target, or when
+    /// it is built if it has none.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Set the WARC version of the record. By default, the version of the target is used.
+    pub fn warc_version<S: Into<String>>(mut self, version: S) -> Self {
+        self.warc_version = Some(version.into());
+
+        self
+    }
+
+    /// Add a field, after any added before.
+    pub fn field<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.fields.push((name.into(), value.into()));
+
+        self
+    }
+
+    /// Add every field of an iterator of name and value pairs.
+    pub fn fields<I, K, V>(self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        fields
+            .into_iter()
+            .fold(self, |builder, (name, value)| builder.field(name, value))
+    }
+
+    /// Add a WARC-Block-Digest computed with the given algorithm.
+    #[cfg(feature = "digest")]
+    pub fn digest(mut self, algorithm: Algorithm) -> Self {
+        self.digest = Some(algorithm);
+
+        self
+    }
+
+    /// Render the fields as an `application/warc-fields` body.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedBody` if a name is empty or contains a character not allowed in
+    /// a field name, or if a value contains a line break.
+    pub fn to_body(&self) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+        for (name, value) in &self.fields {
+            if name.is_empty() || !name.bytes().all(is_token_byte) {
+                return Err(Error::MalformedBody(format!(
+                    "invalid field name: {:?}",
+                    name
+                )));
+            }
+            if value.contains(&['\r', '\n'][..]) {
+                return Err(Error::MalformedBody(format!(
+                    "line break in value of field {:?}",
+                    name
+                )));
+            }
+            body.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+
+        Ok(body)
+    }
+
+    /// Build the metadata record. Its Content-Length is set from the rendered fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fields cannot be rendered, as described for `to_body`.
+    pub fn build(self) -> Result<Record<BufferedBody>, Error> {
+        let body = self.to_body()?;
+        let mut record = Record::<BufferedBody>::default();
+        if let Some(version) = self.warc_version {
+            record.set_warc_version(version);
+        }
+        record.set_warc_type(RecordType::Metadata);
+        if let Some(date) = self.date {
+            record.set_date(date);
+        }
+        if let Some(target_uri) = self.target_uri {
+            record.set_header(WarcHeader::TargetURI, target_uri)?;
+        }
+        if !self.concurrent_to.is_empty() {
+            record.set_header(WarcHeader::ConcurrentTo, self.concurrent_to.join(" "))?;
+        }
+        record.set_header(WarcHeader::ContentType, "application/warc-fields")?;
+        #[cfg(feature = "digest")]
+        {
+            if let Some(algorithm) = self.digest {
+                record.set_header(
+                    WarcHeader::BlockDigest,
+                    algorithm.compute(&body).to_string(),
+                )?;
+            }
+        }
+        record.replace_body(body);
+
+        Ok(record)
+    }
+}
+
+/// Return true if the byte may appear in a field name, which is a token as defined by RFC 2616.
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetadataRecordBuilder;
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Error, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn metadata() {
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_version("1.0");
+        response.set_warc_type(RecordType::Response);
+        response
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+
+        let record = MetadataRecordBuilder::new()
+            .target(&response)
+            .field("via", "http://example.com/index")
+            .fields(vec![
+                ("outlink", "http://example.com/a L a/@href"),
+                ("outlink", "http://example.com/b L a/@href"),
+            ])
+            .field("fetchTimeMs", "12")
+            .build()
+            .unwrap();
+
+        assert_eq!(record.warc_type(), &RecordType::Metadata);
+        assert_eq!(record.warc_version(), "1.0");
+        assert_eq!(record.date(), response.date());
+        assert_eq!(
+            record.header(WarcHeader::ConcurrentTo).as_deref(),
+            Some(response.warc_id())
+        );
+        assert_eq!(
+            record.header(WarcHeader::TargetURI).as_deref(),
+            Some("http://example.com/")
+        );
+        assert_eq!(
+            record.header(WarcHeader::ContentType).as_deref(),
+            Some("application/warc-fields")
+        );
+        assert_eq!(
+            String::from_utf8(record.body().to_vec()).unwrap(),
+            "via: http://example.com/index\r\n\
+             outlink: http://example.com/a L a/@href\r\n\
+             outlink: http://example.com/b L a/@href\r\n\
+             fetchTimeMs: 12\r\n"
+        );
+        assert_eq!(record.content_length(), record.body().len() as u64);
+
+        let mut buffer = Vec::new();
+        WarcWriter::new(&mut buffer).write(&record).unwrap();
+        let read = WarcReader::new(&buffer[..])
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.body(), record.body());
+
+        let invalid = MetadataRecordBuilder::new().field("bad name", "value");
+        assert!(matches!(invalid.build(), Err(Error::MalformedBody(_))));
+        let invalid = MetadataRecordBuilder::new().field("name", "two\r\nlines");
+        assert!(matches!(invalid.build(), Err(Error::MalformedBody(_))));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn digest() {
+        use crate::digest::{Algorithm, Digest};
+
+        let record = MetadataRecordBuilder::new()
+            .field("a", "b")
+            .digest(Algorithm::Sha1)
+            .build()
+            .unwrap();
+        let digest = Digest::parse(&record.header(WarcHeader::BlockDigest).unwrap()).unwrap();
+        assert!(digest.matches(record.body()));
+    }
+}
@@ -0,0 +1,193 @@
+//! Parallel indexing of uncompressed WARC files.
+//!
+//! The file is memory-mapped and split into one chunk per thread. Each thread searches its chunk
+//! for the `\r\n\r\nWARC/` sequence which separates records, and parses the header block found at
+//! each match. The matches are then walked in order from the start of the file, so that a match
+//! inside the body of a record, which is not a real boundary, is skipped.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+
+use memchr::memmem;
+use memmap2::Mmap;
+use nom::{character::streaming::line_ending, sequence::tuple};
+
+use crate::warc_reader::raw_header;
+use crate::{parser, Error, RawRecordHeader};
+
+const BOUNDARY: &[u8] = b"\r\n\r\nWARC/";
+
+/// The location and header of a record found by a `ParallelIndexer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedRecord {
+    /// The offset of the start of the record in the file.
+    pub offset: u64,
+    /// The length of the record, including its separator.
+    pub length: u64,
+    /// The header of the record.
+    pub header: RawRecordHeader,
+}
+
+/// A header block parsed at a possible record boundary.
+type Candidate = (usize, Result<(RawRecordHeader, usize), Error>);
+
+/// An indexer which finds the records of an uncompressed WARC file using several threads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParallelIndexer {
+    threads: usize,
+}
+
+impl Default for ParallelIndexer {
+    fn default() -> Self {
+        ParallelIndexer {
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        }
+    }
+}
+
+impl ParallelIndexer {
+    /// Create an indexer using one thread per available CPU.
+    pub fn new() -> Self {
+        ParallelIndexer::default()
+    }
+
+    /// Set the number of threads used to scan the file.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+
+        self
+    }
+
+    /// Index the uncompressed WARC file at the given path, by mapping it into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, until this
+    /// returns. A change to a mapped file is undefined behavior, and truncating it may kill the
+    /// process with `SIGBUS`. Use `index_bytes` on the contents of the file where that cannot be
+    /// ensured.
+    pub unsafe fn index_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> io::Result<Vec<Result<IndexedRecord, Error>>> {
+        let file = fs::File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(Vec::new());
+        }
+        // SAFETY: the map is read-only, and the caller upholds that the file is not modified while
+        // it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(self.index_bytes(&map))
+    }
+
+    /// Index an uncompressed WARC held in memory.
+    ///
+    /// Records are returned in order. A header block which cannot be parsed where a record was
+    /// expected is returned as an error, and indexing continues at the next boundary.
+    pub fn index_bytes(&self, data: &[u8]) -> Vec<Result<IndexedRecord, Error>> {
+        let chunk_len = data.len().div_ceil(self.threads);
+        let candidates: Vec<Candidate> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.threads)
+                .map(|i| (i * chunk_len, ((i + 1) * chunk_len).min(data.len())))
+                .filter(|(start, end)| start < end)
+                .map(|(start, end)| scope.spawn(move || scan_chunk(data, start, end)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut records = Vec::new();
+        let mut expected = 0;
+        for (offset, parsed) in candidates {
+            if offset < expected {
+                continue;
+            }
+            match parsed {
+                Ok((header, length)) => {
+                    records.push(Ok(IndexedRecord {
+                        offset: offset as u64,
+                        length: length as u64,
+                        header,
+                    }));
+                    expected = offset + length;
+                }
+                Err(e) if offset == expected => records.push(Err(e)),
+                Err(_) => {}
+            }
+        }
+
+        records
+    }
+}
+
+/// Parse the header block at every possible record boundary which starts within the chunk.
+fn scan_chunk(data: &[u8], start: usize, end: usize) -> Vec<Candidate> {
+    let mut offsets = Vec::new();
+    if start == 0 && data.starts_with(b"WARC/") {
+        offsets.push(0);
+    }
+    let window_end = (end + BOUNDARY.len() - 1).min(data.len());
+    offsets
+        .extend(memmem::find_iter(&data[start..window_end], BOUNDARY).map(|pos| start + pos + 4));
+
+    offsets
+        .into_iter()
+        .map(|offset| (offset, parse_at(&data[offset..])))
+        .collect()
+}
+
+/// Parse the header block at the start of the input, returning it and the length of the record.
+fn parse_at(input: &[u8]) -> Result<(RawRecordHeader, usize), Error> {
+    let (rest, ((version, headers, body_len), _)) =
+        tuple((parser::headers, line_ending))(input).map_err(|_| Error::ParseHeaders)?;
+    let header_len = input.len() - rest.len();
+
+    Ok((raw_header(version, headers), header_len + body_len + 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use super::ParallelIndexer;
+    use crate::{BufferedBody, Error, Record, WarcReader, WarcWriter};
+
+    #[test]
+    fn index_bytes() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for body in [
+            &b"first"[..],
+            &b"looks like\r\n\r\nWARC/1.0\r\nContent-Length: 0\r\n\r\n"[..],
+            &[b'x'; 300][..],
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.replace_body(body);
+            writer.write(&record).unwrap();
+        }
+
+        let expected: Vec<_> = WarcReader::new(BufReader::new(Cursor::new(buffer.clone())))
+            .iter_records()
+            .map(|record| record.unwrap().stored_len().unwrap())
+            .collect();
+        for threads in 1..8 {
+            let index = ParallelIndexer::new().threads(threads).index_bytes(&buffer);
+            let lengths: Vec<_> = index.iter().map(|r| r.as_ref().unwrap().length).collect();
+            assert_eq!(lengths, expected);
+            assert_eq!(index[2].as_ref().unwrap().offset, expected[0] + expected[1]);
+        }
+
+        buffer.splice(0..0, b"WARC/1.0\r\nbroken\r\n\r\n".iter().copied());
+        let index = ParallelIndexer::new().threads(3).index_bytes(&buffer);
+        assert!(matches!(index[0], Err(Error::ParseHeaders)));
+        assert_eq!(index.len(), 4);
+    }
+}
@@ -1,3 +1,29 @@
+//! The grammar of WARC records, as streaming parsers over byte slices.
+//!
+//! A record is framed in three steps, each available on its own for readers which manage their
+//! own buffers: `parse_version_line` reads the `WARC/<version>` line, `parse_header_block` reads
+//! the version line and the header lines up to the blank line ending them, and `body_frame`
+//! reads a body of a known length and the two line endings following it.
+//!
+//! Every parser returns `Err(nom::Err::Incomplete(_))` when the input ends before its part of the
+//! record, so that the caller can read more and try again.
+//!
+//! ```
+//! use warc::parser::{body_frame, parse_header_block};
+//!
+//! let input = b"WARC/1.0\r\nContent-Length: 5\r\n\r\nhello\r\n\r\n";
+//! let (rest, (version, headers)) = parse_header_block(input).unwrap();
+//! assert_eq!(version, "1.0");
+//! let len = headers
+//!     .iter()
+//!     .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+//!     .and_then(|(_, value)| std::str::from_utf8(value).ok()?.parse().ok())
+//!     .unwrap_or(0);
+//! let (rest, body) = body_frame(len)(rest).unwrap();
+//! assert_eq!(body, b"hello");
+//! assert!(rest.is_empty());
+//! ```
+
 use nom::{
     bytes::streaming::{tag, take, take_while1},
     character::streaming::{line_ending, not_line_ending, space0},
@@ -23,7 +49,8 @@ fn version(input: &[u8]) -> IResult<&[u8], &str> {
 }
 
 fn is_header_token_char(chr: u8) -> bool {
-    match chr {
+    !matches!(
+        chr,
         0..=31
         | 128..=255
         | b'('
@@ -43,9 +70,8 @@ fn is_header_token_char(chr: u8) -> bool {
         | b'{'
         | b'}'
         | b' '
-        | b'\\' => false,
-        _ => true,
-    }
+        | b'\\'
+    )
 }
 
 fn header(input: &[u8]) -> IResult<&[u8], (&[u8], &[u8])> {
@@ -62,6 +88,7 @@ fn header(input: &[u8]) -> IResult<&[u8], (&[u8], &[u8])> {
 }
 
 // TODO: evaluate the use of `ErrorKind::Verify` here.
+#[allow(clippy::type_complexity)]
 pub fn headers(input: &[u8]) -> IResult<&[u8], (&str, Vec<(&str, &[u8])>, usize)> {
     let (input, version) = version(input)?;
     let (input, headers) = many1(header)(input)?;
@@ -77,7 +104,7 @@ pub fn headers(input: &[u8]) -> IResult<&[u8], (&str, Vec<(&str, &[u8])>, usize)
             Ok(token) => token,
         };
 
-        if content_length == None && token_str.to_lowercase() == "content-length" {
+        if content_length.is_none() && token_str.eq_ignore_ascii_case("content-length") {
             let value_str = match str::from_utf8(header.1) {
                 Err(_) => {
                     return Err(nom::Err::Error((input, ErrorKind::Verify)));
@@ -100,36 +127,135 @@ pub fn headers(input: &[u8]) -> IResult<&[u8], (&str, Vec<(&str, &[u8])>, usize)
 
     // TODO: Technically if we didn't find a `content-length` header, the record is invalid. Should
     // we be returning an error here instead?
-    if content_length == None {
+    if content_length.is_none() {
         content_length = Some(0);
     }
 
     Ok((input, (version, warc_headers, content_length.unwrap())))
 }
 
+/// A line of a header block, as split without rejecting malformed lines.
+#[derive(Debug, PartialEq)]
+pub(crate) enum HeaderLine<'a> {
+    /// A well-formed header.
+    Field(&'a str, &'a [u8]),
+    /// A line whose header name is empty.
+    EmptyName(&'a [u8]),
+    /// A line without a colon separating the name from the value.
+    MissingColon(&'a [u8]),
+}
+
+/// Split a complete header block into its version and header lines.
+///
+/// Unlike `headers`, lines with an empty name or without a colon are returned for the caller to
+/// judge. `None` is returned if the version line, or any header name which is present, is not
+/// well-formed.
+pub(crate) fn header_lines(block: &[u8]) -> Option<(&str, Vec<HeaderLine<'_>>)> {
+    let mut lines = block
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    let version = str::from_utf8(lines.next()?.strip_prefix(b"WARC/")?).ok()?;
+
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t';
+    let mut header_lines = Vec::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let colon = match line.iter().position(|&b| b == b':') {
+            Some(colon) => colon,
+            None => {
+                header_lines.push(HeaderLine::MissingColon(line));
+                continue;
+            }
+        };
+        let name_len = line[..colon]
+            .iter()
+            .rposition(|b| !is_space(b))
+            .map_or(0, |i| i + 1);
+        let name = &line[..name_len];
+        if name.is_empty() {
+            header_lines.push(HeaderLine::EmptyName(line));
+            continue;
+        }
+        if !name.iter().all(|&b| is_header_token_char(b)) {
+            return None;
+        }
+        let value = &line[colon + 1..];
+        let value_start = value
+            .iter()
+            .position(|b| !is_space(b))
+            .unwrap_or(value.len());
+        header_lines.push(HeaderLine::Field(
+            str::from_utf8(name).ok()?,
+            &value[value_start..],
+        ));
+    }
+    if header_lines.is_empty() {
+        return None;
+    }
+
+    Some((version, header_lines))
+}
+
+/// Parse the version line starting a record, returning the version without its `WARC/` prefix.
+pub fn parse_version_line(input: &[u8]) -> IResult<&[u8], &str> {
+    version(input)
+}
+
+/// Parse the header block of a record: its version line, then its header lines up to and
+/// including the blank line ending them. The headers are returned as they appear, with no
+/// interpretation of their names or values.
+#[allow(clippy::type_complexity)]
+pub fn parse_header_block(input: &[u8]) -> IResult<&[u8], (&str, Vec<(&str, &[u8])>)> {
+    let (input, version) = version(input)?;
+    let (input, (headers, _)) = tuple((many1(header), line_ending))(input)?;
+
+    let mut warc_headers = Vec::with_capacity(headers.len());
+    for (token, value) in headers {
+        match str::from_utf8(token) {
+            Ok(token) => warc_headers.push((token, value)),
+            Err(_) => return Err(nom::Err::Error((input, ErrorKind::Verify))),
+        }
+    }
+
+    Ok((input, (version, warc_headers)))
+}
+
+/// Return a parser for a record body of the given length, as declared by its Content-Length,
+/// which also consumes the two line endings separating the record from the next.
+pub fn body_frame(len: usize) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |input| {
+        let (input, (body, _, _)) = tuple((take(len), line_ending, line_ending))(input)?;
+
+        Ok((input, body))
+    }
+}
+
+#[allow(clippy::type_complexity)]
 pub fn record(input: &[u8]) -> IResult<&[u8], (&str, Vec<(&str, &[u8])>, &[u8])> {
     let (input, (headers, _)) = tuple((headers, line_ending))(input)?;
-    let (input, (body, _, _)) = tuple((take(headers.2), line_ending, line_ending))(input)?;
+    let (input, body) = body_frame(headers.2)(input)?;
 
     Ok((input, (headers.0, headers.1, body)))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{header, headers, record, version};
+    use super::{
+        body_frame, header, header_lines, headers, parse_header_block, parse_version_line, record,
+        version, HeaderLine,
+    };
     use nom::error::ErrorKind;
     use nom::Err;
     use nom::Needed;
 
     #[test]
     fn version_parsing() {
-        assert_eq!(version(&b"WARC/0.0\r\n"[..]), Ok((&b""[..], &"0.0"[..])));
+        assert_eq!(version(&b"WARC/0.0\r\n"[..]), Ok((&b""[..], "0.0")));
 
-        assert_eq!(version(&b"WARC/1.0\r\n"[..]), Ok((&b""[..], &"1.0"[..])));
+        assert_eq!(version(&b"WARC/1.0\r\n"[..]), Ok((&b""[..], "1.0")));
 
         assert_eq!(
             version(&b"WARC/2.0-alpha\r\n"[..]),
-            Ok((&b""[..], &"2.0-alpha"[..]))
+            Ok((&b""[..], "2.0-alpha"))
         );
     }
 
@@ -195,6 +321,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn header_line_splitting() {
+        let (version, lines) =
+            header_lines(b"WARC/1.0\r\nA :  1\r\n: 2\r\nno colon\r\nB:\r\n\r\n").unwrap();
+        assert_eq!(version, "1.0");
+        assert_eq!(
+            lines,
+            vec![
+                HeaderLine::Field("A", b"1"),
+                HeaderLine::EmptyName(b": 2"),
+                HeaderLine::MissingColon(b"no colon"),
+                HeaderLine::Field("B", b""),
+            ]
+        );
+
+        assert_eq!(header_lines(b"HTTP/1.1\r\nA: 1\r\n\r\n"), None);
+        assert_eq!(header_lines(b"WARC/1.0\r\nA(: 1\r\n\r\n"), None);
+        assert_eq!(header_lines(b"WARC/1.0\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn framing() {
+        assert_eq!(
+            parse_version_line(b"WARC/1.1\r\nrest"),
+            Ok((&b"rest"[..], "1.1"))
+        );
+
+        let raw = b"WARC/1.1\r\nWARC-Type: resource\r\nContent-Length: 3\r\n\r\nabc\r\n\r\nnext";
+        let expected_headers: Vec<(&str, &[u8])> =
+            vec![("WARC-Type", b"resource"), ("Content-Length", b"3")];
+        let (rest, (version, headers)) = parse_header_block(&raw[..]).unwrap();
+        assert_eq!(version, "1.1");
+        assert_eq!(headers, expected_headers);
+        assert_eq!(body_frame(3)(rest), Ok((&b"next"[..], &b"abc"[..])));
+
+        assert!(matches!(
+            parse_header_block(&raw[..20]),
+            Err(Err::Incomplete(_))
+        ));
+        assert!(matches!(body_frame(3)(&rest[..5]), Err(Err::Incomplete(_))));
+        assert!(matches!(body_frame(2)(rest), Err(Err::Error(_))));
+    }
+
     #[test]
     fn parse_record() {
         let raw = b"\
@@ -0,0 +1,276 @@
+use crate::header::WarcHeader;
+
+/// How a reader behaves when it encounters a malformed record.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ReadPolicy {
+    /// Return the error, and end iteration.
+    FailFast,
+    /// Return the error in place of the record, then skip ahead to the next record and continue.
+    #[default]
+    Skip,
+    /// Return as much of the record as could be read, repairing it where possible and reporting
+    /// each repair as a `Warning`. Records which cannot be repaired are skipped as with `Skip`.
+    ///
+    /// A record whose Content-Length cannot be interpreted has its body read up to the start of
+    /// the next record, except by a streaming iterator, which cannot read ahead and skips it.
+    BestEffort,
+}
+
+/// A preset of the options of a `ParserConfig` controlling how malformed records are handled,
+/// set with `ParserConfig::parse_mode`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParseMode {
+    /// Treat anything which does not conform to the standard as an error, under
+    /// `ReadPolicy::Skip`. This is the default configuration.
+    #[default]
+    Strict,
+    /// Recover from malformed records where possible, reporting each repair as a `Warning`.
+    ///
+    /// This sets `ReadPolicy::BestEffort`, `SeparatorPolicy::Lenient`, `HeaderPolicy::KeepFirst`
+    /// for empty header names, duplicate headers and missing colons, and lenient dates.
+    Lenient,
+}
+
+/// How a reader handles a header line which does not conform to the standard.
+///
+/// For lines with an empty name or without a colon, which cannot be interpreted at all,
+/// `KeepFirst` and `KeepLast` both drop the line.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HeaderPolicy {
+    /// Treat the record as malformed.
+    #[default]
+    Reject,
+    /// Keep the first of several values, and report the others as a `Warning`.
+    KeepFirst,
+    /// Keep the last of several values, and report the others as a `Warning`.
+    KeepLast,
+    /// Skip the whole record without reporting an error.
+    SkipRecord,
+}
+
+/// How a reader handles the separator following a record's body, which the standard defines as
+/// two CRLF sequences.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SeparatorPolicy {
+    /// Treat any other separator as malformed.
+    #[default]
+    Strict,
+    /// Accept two line endings of either kind, such as LF LF, and report them as a `Warning`.
+    LineEndings,
+    /// Accept up to two line endings of either kind, including none, if they are followed by the
+    /// next record or the end of the input, and report them as a `Warning`.
+    Lenient,
+}
+
+/// A normalization applied to the value of a header as it is read.
+///
+/// Normalizers are registered per header with `ParserConfig::normalize_header`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeaderNormalizer {
+    /// Remove leading and trailing whitespace.
+    Trim,
+    /// Replace each run of whitespace with a single space, and trim the value.
+    CollapseWhitespace,
+    /// Remove all whitespace, such as the spaces and tabs left in a URI broken across lines.
+    RemoveWhitespace,
+    /// Uppercase the algorithm label of a digest, such as `sha1` in `sha1:VL2MMHO4...`, and trim
+    /// the value.
+    UppercaseDigestLabel,
+}
+
+impl HeaderNormalizer {
+    /// Return the normalized form of a header value.
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            HeaderNormalizer::Trim => value.trim().to_owned(),
+            HeaderNormalizer::CollapseWhitespace => {
+                value.split_whitespace().collect::<Vec<_>>().join(" ")
+            }
+            HeaderNormalizer::RemoveWhitespace => {
+                value.chars().filter(|c| !c.is_whitespace()).collect()
+            }
+            HeaderNormalizer::UppercaseDigestLabel => {
+                let value = value.trim();
+                match value.split_once(':') {
+                    Some((label, digest)) => {
+                        format!("{}:{}", label.to_ascii_uppercase(), digest)
+                    }
+                    None => value.to_owned(),
+                }
+            }
+        }
+    }
+}
+
+/// Options controlling how strictly WARC data is interpreted while reading.
+///
+/// The default configuration is strict: anything which does not conform to the standard is an
+/// error. Under the default `ReadPolicy::Skip`, a reader returns that error in place of the
+/// malformed record and continues with the next one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParserConfig {
+    pub(crate) lenient_dates: bool,
+    pub(crate) read_policy: ReadPolicy,
+    pub(crate) skip_leading_garbage: bool,
+    pub(crate) empty_header_names: HeaderPolicy,
+    pub(crate) duplicate_headers: HeaderPolicy,
+    pub(crate) missing_colons: HeaderPolicy,
+    pub(crate) separators: SeparatorPolicy,
+    pub(crate) max_body_len: Option<u64>,
+    pub(crate) normalizers: Vec<(WarcHeader, HeaderNormalizer)>,
+    pub(crate) forensic: bool,
+    #[cfg(feature = "gzip")]
+    pub(crate) decode_blocks: bool,
+}
+
+impl ParserConfig {
+    /// Create a new strict configuration.
+    pub fn new() -> Self {
+        ParserConfig::default()
+    }
+
+    /// Accept the malformed WARC-Date values found in older archives.
+    ///
+    /// When enabled, dates missing the `Z` designator, using a space instead of `T`, carrying a
+    /// numeric UTC offset, or using ARC-style compact and two-digit year timestamps are converted
+    /// to UTC. Each conversion is reported as a `Warning` on the record.
+    pub fn lenient_dates(mut self, lenient: bool) -> Self {
+        self.lenient_dates = lenient;
+
+        self
+    }
+
+    /// Set the behavior of the reader when it encounters a malformed record.
+    ///
+    /// The default policy is `ReadPolicy::Skip`.
+    pub fn read_policy(mut self, policy: ReadPolicy) -> Self {
+        self.read_policy = policy;
+
+        self
+    }
+
+    /// Set the read policy, header policies, separator policy, and date handling together from a
+    /// preset.
+    ///
+    /// Any of them may be changed afterwards by its own method.
+    pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+        let (read_policy, headers, separators, lenient_dates) = match mode {
+            ParseMode::Strict => (
+                ReadPolicy::Skip,
+                HeaderPolicy::Reject,
+                SeparatorPolicy::Strict,
+                false,
+            ),
+            ParseMode::Lenient => (
+                ReadPolicy::BestEffort,
+                HeaderPolicy::KeepFirst,
+                SeparatorPolicy::Lenient,
+                true,
+            ),
+        };
+        self.read_policy = read_policy;
+        self.empty_header_names = headers;
+        self.duplicate_headers = headers;
+        self.missing_colons = headers;
+        self.separators = separators;
+        self.lenient_dates = lenient_dates;
+
+        self
+    }
+
+    /// Skip a byte order mark, blank lines, or other data before the first record.
+    ///
+    /// When enabled, everything before the first `WARC/` version line is discarded, and the
+    /// number of bytes skipped is reported as a `Warning` on the first record.
+    pub fn skip_leading_garbage(mut self, skip: bool) -> Self {
+        self.skip_leading_garbage = skip;
+
+        self
+    }
+
+    /// Set how a header line with an empty name, such as `: value`, is handled.
+    pub fn empty_header_names(mut self, policy: HeaderPolicy) -> Self {
+        self.empty_header_names = policy;
+
+        self
+    }
+
+    /// Set how a header which may only appear once, such as Content-Length, is handled when it
+    /// appears more than once.
+    ///
+    /// WARC-Concurrent-To and WARC-Protocol may be repeated, and are not affected.
+    pub fn duplicate_headers(mut self, policy: HeaderPolicy) -> Self {
+        self.duplicate_headers = policy;
+
+        self
+    }
+
+    /// Set how a header line without a colon separating the name from the value is handled.
+    pub fn missing_colons(mut self, policy: HeaderPolicy) -> Self {
+        self.missing_colons = policy;
+
+        self
+    }
+
+    /// Set how a separator other than two CRLF sequences after a record's body is handled.
+    ///
+    /// This only affects reading; writers always emit the standard separator. Records yielded by
+    /// `stream_records` are returned before their separator is read, so no warning is reported
+    /// for them. The `WarcCodec` always requires the standard separator.
+    pub fn separators(mut self, policy: SeparatorPolicy) -> Self {
+        self.separators = policy;
+
+        self
+    }
+
+    /// Reject records whose Content-Length declares a body longer than the given number of bytes
+    /// with `Error::BodyTooLarge`, before any of the body is read.
+    ///
+    /// Bodies are read into buffers which grow as data arrives, so a large declared length alone
+    /// does not allocate memory; the limit bounds the memory used by records which are actually
+    /// that long. Such records are handled like any other malformed record under the read policy.
+    pub fn max_body_len(mut self, len: u64) -> Self {
+        self.max_body_len = Some(len);
+
+        self
+    }
+
+    /// Normalize the value of a header as records are read.
+    ///
+    /// Several normalizers registered for the same header are applied in the order they were
+    /// registered, to every value of a repeated header. Each value changed is reported as a
+    /// `Warning::NormalizedHeader` holding the original value, which is also kept by the raw
+    /// header blocks returned by `iter_raw_records`, and by records read in forensic mode.
+    /// Content-Length is not normalized, since the length of the body is read before
+    /// normalization.
+    pub fn normalize_header<H: Into<WarcHeader>>(
+        mut self,
+        header: H,
+        normalizer: HeaderNormalizer,
+    ) -> Self {
+        self.normalizers.push((header.into(), normalizer));
+
+        self
+    }
+
+    /// Keep the header block of each record as it was read, before any header was normalized or
+    /// repaired, so that it is available from `Record::raw_headers`.
+    pub fn forensic(mut self, forensic: bool) -> Self {
+        self.forensic = forensic;
+
+        self
+    }
+
+    /// Decode blocks stored with a Content-Encoding header of `gzip`, such as those written by a
+    /// `WarcWriter` with block compression, so that records are returned with their original
+    /// blocks.
+    ///
+    /// This only affects records returned by `iter_records`; other iterators return blocks as
+    /// stored.
+    #[cfg(feature = "gzip")]
+    pub fn decode_blocks(mut self, decode: bool) -> Self {
+        self.decode_blocks = decode;
+
+        self
+    }
+}
@@ -0,0 +1,278 @@
+//! Ingestion of HTTP exchanges reassembled from packet captures.
+//!
+//! Packet capture tools such as Zeek or tshark can reassemble the TCP streams of a capture into
+//! HTTP requests and responses. Each `HttpExchange` describes one of those, along with the
+//! addresses and times observed on the wire, and `PcapIngest` turns them into linked request,
+//! response and metadata records.
+//!
+//! Messages are archived as given. If the capture tool decoded a chunked or compressed body, the
+//! headers should be adjusted to match before ingestion.
+
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Utc};
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, CaptureSession, Error, Record};
+
+/// An HTTP request or response.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HttpMessage {
+    /// The request line, such as `GET /index.html HTTP/1.1`, or the status line, such as
+    /// `HTTP/1.1 200 OK`.
+    pub start_line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpMessage {
+    /// Return the value of the first header with the given name, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Serialize the message as it was sent.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.body.len() + 256);
+        bytes.extend_from_slice(self.start_line.as_bytes());
+        bytes.extend_from_slice(b"\r\n");
+        for (name, value) in &self.headers {
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(b": ");
+            bytes.extend_from_slice(value.as_bytes());
+            bytes.extend_from_slice(b"\r\n");
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&self.body);
+
+        bytes
+    }
+}
+
+/// An HTTP request, and its response if one was captured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpExchange {
+    /// The address the request was sent from.
+    pub client: SocketAddr,
+    /// The address the request was sent to.
+    pub server: SocketAddr,
+    /// The time the first packet of the request was seen.
+    pub request_time: DateTime<Utc>,
+    /// The time the first packet of the response was seen.
+    pub response_time: Option<DateTime<Utc>>,
+    pub request: HttpMessage,
+    pub response: Option<HttpMessage>,
+}
+
+impl HttpExchange {
+    /// Return the URI the request was for.
+    ///
+    /// A request line in origin form, such as `GET /a HTTP/1.1`, is resolved against the Host
+    /// header, or else the server address. The scheme is `https` if the server port is 443.
+    pub fn target_uri(&self) -> Result<String, Error> {
+        let malformed = |reason: &str| Error::MalformedHeader(WarcHeader::TargetURI, reason.into());
+        let target = self
+            .request
+            .start_line
+            .split_whitespace()
+            .nth(1)
+            .ok_or_else(|| malformed("no request target"))?;
+        if target.contains("://") {
+            return Ok(target.to_owned());
+        }
+        if !target.starts_with('/') {
+            return Err(malformed("request target is not a path"));
+        }
+
+        let scheme = if self.server.port() == 443 {
+            "https"
+        } else {
+            "http"
+        };
+        let host = match self.request.header("host") {
+            Some(host) => host.trim().to_owned(),
+            None => match (scheme, self.server.port()) {
+                ("http", 80) | ("https", 443) => match self.server {
+                    SocketAddr::V4(ref addr) => addr.ip().to_string(),
+                    SocketAddr::V6(ref addr) => format!("[{}]", addr.ip()),
+                },
+                _ => self.server.to_string(),
+            },
+        };
+
+        Ok(format!("{}://{}{}", scheme, host, target))
+    }
+}
+
+/// A converter of HTTP exchanges to WARC records.
+#[derive(Clone, Debug, Default)]
+pub struct PcapIngest {
+    warcinfo_id: Option<String>,
+    metadata: bool,
+}
+
+impl PcapIngest {
+    /// Create a converter which writes request and response records.
+    pub fn new() -> Self {
+        PcapIngest::default()
+    }
+
+    /// Set the WARC-Warcinfo-ID given to every record.
+    pub fn warcinfo_id<S: Into<String>>(mut self, warcinfo_id: S) -> Self {
+        self.warcinfo_id = Some(warcinfo_id.into());
+
+        self
+    }
+
+    /// Also write a metadata record for each exchange, holding the client address and the delay
+    /// before the response.
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+
+        self
+    }
+
+    /// Convert exchanges to records, in order.
+    ///
+    /// Each exchange yields a request record, a response record if a response was captured, and a
+    /// metadata record if enabled. The records of an exchange are dated by the request, carry the
+    /// server address as WARC-IP-Address, and are linked by WARC-Concurrent-To.
+    pub fn records<I>(&self, exchanges: I) -> Result<Vec<Record<BufferedBody>>, Error>
+    where
+        I: IntoIterator<Item = HttpExchange>,
+    {
+        let mut records = Vec::new();
+        for exchange in exchanges {
+            let mut session = CaptureSession::new().ip_address(exchange.server.ip().to_string());
+            if let Some(ref warcinfo_id) = self.warcinfo_id {
+                session = session.warcinfo_id(warcinfo_id.as_str());
+            }
+
+            let capture = session.begin_at(exchange.target_uri()?, exchange.request_time);
+            let request = capture.request();
+            request
+                .set_header(WarcHeader::ContentType, "application/http; msgtype=request")
+                .unwrap();
+            request.replace_body(exchange.request.to_bytes());
+            if let Some(ref response_message) = exchange.response {
+                let response = capture.response();
+                response
+                    .set_header(
+                        WarcHeader::ContentType,
+                        "application/http; msgtype=response",
+                    )
+                    .unwrap();
+                response.replace_body(response_message.to_bytes());
+            }
+            if self.metadata {
+                let mut fields = format!("client-ip: {}\r\n", exchange.client);
+                if let Some(response_time) = exchange.response_time {
+                    fields.push_str(&format!(
+                        "response-delay-ms: {}\r\n",
+                        (response_time - exchange.request_time).num_milliseconds()
+                    ));
+                }
+                let metadata = capture.metadata();
+                metadata
+                    .set_header(WarcHeader::ContentType, "application/warc-fields")
+                    .unwrap();
+                metadata.replace_body(fields);
+            }
+
+            records.extend(session.into_records());
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{HttpExchange, HttpMessage, PcapIngest};
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    fn exchange() -> HttpExchange {
+        let request_time = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        HttpExchange {
+            client: "10.0.0.2:51234".parse().unwrap(),
+            server: "93.184.216.34:80".parse().unwrap(),
+            request_time,
+            response_time: Some(request_time + Duration::milliseconds(42)),
+            request: HttpMessage {
+                start_line: "GET /a?b=c HTTP/1.1".to_owned(),
+                headers: vec![("Host".to_owned(), "example.com".to_owned())],
+                body: Vec::new(),
+            },
+            response: Some(HttpMessage {
+                start_line: "HTTP/1.1 200 OK".to_owned(),
+                headers: vec![("Content-Length".to_owned(), "2".to_owned())],
+                body: b"hi".to_vec(),
+            }),
+        }
+    }
+
+    #[test]
+    fn target_uri() {
+        let mut exchange = exchange();
+        assert_eq!(exchange.target_uri().unwrap(), "http://example.com/a?b=c");
+
+        exchange.request.headers.clear();
+        exchange.server = "93.184.216.34:443".parse().unwrap();
+        assert_eq!(
+            exchange.target_uri().unwrap(),
+            "https://93.184.216.34/a?b=c"
+        );
+        exchange.server = "[::1]:8080".parse().unwrap();
+        assert_eq!(exchange.target_uri().unwrap(), "http://[::1]:8080/a?b=c");
+
+        exchange.request.start_line = "GET http://example.org/ HTTP/1.1".to_owned();
+        assert_eq!(exchange.target_uri().unwrap(), "http://example.org/");
+        exchange.request.start_line = "GET".to_owned();
+        assert!(exchange.target_uri().is_err());
+    }
+
+    #[test]
+    fn linked_records() {
+        let records = PcapIngest::new()
+            .warcinfo_id("<urn:uuid:warcinfo>")
+            .metadata(true)
+            .records(vec![exchange()])
+            .unwrap();
+        let types: Vec<_> = records.iter().map(|r| r.warc_type().clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                RecordType::Request,
+                RecordType::Response,
+                RecordType::Metadata
+            ]
+        );
+
+        let response_id = records[1].warc_id();
+        assert_eq!(
+            records[0].header(WarcHeader::ConcurrentTo).unwrap(),
+            response_id
+        );
+        assert_eq!(
+            records[1].header(WarcHeader::IPAddress).unwrap(),
+            "93.184.216.34"
+        );
+        assert_eq!(
+            records[1].body(),
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi"
+        );
+        assert_eq!(
+            records[2].body(),
+            b"client-ip: 10.0.0.2:51234\r\nresponse-delay-ms: 42\r\n"
+        );
+        assert!(records
+            .iter()
+            .all(|r| r.header(WarcHeader::WarcInfoID).unwrap() == "<urn:uuid:warcinfo>"));
+    }
+}
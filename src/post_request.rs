@@ -0,0 +1,481 @@
+use chrono::prelude::*;
+use url::form_urlencoded;
+use url::Url;
+
+#[cfg(feature = "digest")]
+use crate::digest::Algorithm;
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Error, Record, RecordType};
+
+const HTTP_REQUEST_CONTENT_TYPE: &str = "application/http;msgtype=request";
+
+/// The form fields holding credentials in common login and API forms.
+const CREDENTIAL_FIELDS: &[&str] = &[
+    "password",
+    "passwd",
+    "pass",
+    "pwd",
+    "token",
+    "access_token",
+    "refresh_token",
+    "csrf_token",
+    "csrfmiddlewaretoken",
+    "authenticity_token",
+    "api_key",
+    "apikey",
+    "secret",
+    "client_secret",
+];
+
+/// How the value of a denied form field is removed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Redaction {
+    /// Remove the field entirely.
+    Strip,
+    /// Keep the field, replacing its value with `REDACTED`.
+    Mask,
+    /// Keep the field, replacing its value with its digest, so that requests submitting the same
+    /// value can still be matched.
+    #[cfg(feature = "digest")]
+    Hash(Algorithm),
+}
+
+/// A deny-list of form fields to redact from POST bodies before they are archived.
+///
+/// Field names are matched case-insensitively, after percent-decoding. Bodies of type
+/// `application/x-www-form-urlencoded` and `multipart/form-data` are filtered; bodies of any other
+/// type are left as they are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormFilter {
+    denied: Vec<String>,
+    redaction: Redaction,
+}
+
+impl Default for FormFilter {
+    fn default() -> Self {
+        FormFilter {
+            denied: Vec::new(),
+            redaction: Redaction::Strip,
+        }
+    }
+}
+
+impl FormFilter {
+    /// Create a filter denying no fields, which strips any fields denied later.
+    pub fn new() -> Self {
+        FormFilter::default()
+    }
+
+    /// Create a filter denying the fields commonly holding passwords, tokens, and API keys.
+    pub fn credentials() -> Self {
+        CREDENTIAL_FIELDS
+            .iter()
+            .fold(FormFilter::new(), |filter, name| filter.deny(*name))
+    }
+
+    /// Deny the named field.
+    pub fn deny<S: Into<String>>(mut self, name: S) -> Self {
+        self.denied.push(name.into().to_lowercase());
+
+        self
+    }
+
+    /// Set how the values of denied fields are removed. By default, the fields are stripped.
+    pub fn redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+
+        self
+    }
+
+    /// Return true if the named field is denied.
+    pub fn is_denied(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.denied.contains(&name)
+    }
+
+    /// Filter a request body of the given Content-Type, returning the body to archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedBody` if a `multipart/form-data` body has no boundary, or is not
+    /// ended by its closing delimiter.
+    pub fn apply(&self, content_type: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut params = content_type.split(';');
+        let media_type = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/x-www-form-urlencoded" => Ok(self.apply_urlencoded(body)),
+            "multipart/form-data" => {
+                let boundary = params
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+                    .map(|(_, value)| value.trim().trim_matches('"'))
+                    .filter(|boundary| !boundary.is_empty())
+                    .ok_or_else(|| {
+                        Error::MalformedBody("multipart body without boundary".to_string())
+                    })?;
+                self.apply_multipart(boundary, body)
+            }
+            _ => Ok(body.to_vec()),
+        }
+    }
+
+    /// Return the replacement for the value of a denied field, or `None` to strip the field.
+    #[cfg_attr(not(feature = "digest"), allow(unused_variables))]
+    fn replacement(&self, value: &[u8]) -> Option<String> {
+        match self.redaction {
+            Redaction::Strip => None,
+            Redaction::Mask => Some("REDACTED".to_string()),
+            #[cfg(feature = "digest")]
+            Redaction::Hash(algorithm) => Some(algorithm.compute(value).to_string()),
+        }
+    }
+
+    fn apply_urlencoded(&self, body: &[u8]) -> Vec<u8> {
+        let mut pairs = Vec::new();
+        for pair in body.split(|b| *b == b'&') {
+            let (name, value) = match form_urlencoded::parse(pair).next() {
+                Some(decoded) => decoded,
+                None => {
+                    pairs.push(pair.to_vec());
+                    continue;
+                }
+            };
+            if !self.is_denied(&name) {
+                pairs.push(pair.to_vec());
+                continue;
+            }
+            if let Some(replacement) = self.replacement(value.as_bytes()) {
+                let encoded_name = pair.split(|b| *b == b'=').next().unwrap_or(pair);
+                let mut pair = encoded_name.to_vec();
+                pair.push(b'=');
+                pair.extend(
+                    form_urlencoded::byte_serialize(replacement.as_bytes()).flat_map(str::bytes),
+                );
+                pairs.push(pair);
+            }
+        }
+
+        pairs.join(&b'&')
+    }
+
+    fn apply_multipart(&self, boundary: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let delimiter = format!("\r\n--{}", boundary).into_bytes();
+        let mut data = b"\r\n".to_vec();
+        data.extend_from_slice(body);
+
+        let mut segments = split(&data, &delimiter);
+        let preamble = segments.next().unwrap_or(&[]);
+        let mut kept: Vec<Vec<u8>> = vec![preamble.to_vec()];
+        let mut closed = false;
+        for segment in segments {
+            if segment.starts_with(b"--") {
+                kept.push(segment.to_vec());
+                closed = true;
+                break;
+            }
+            let (head, content) = match find(segment, b"\r\n\r\n") {
+                Some(end) => segment.split_at(end + 4),
+                None => {
+                    return Err(Error::MalformedBody(
+                        "multipart part without head".to_string(),
+                    ))
+                }
+            };
+            let denied = part_name(head).is_some_and(|name| self.is_denied(&name));
+            if !denied {
+                kept.push(segment.to_vec());
+            } else if let Some(replacement) = self.replacement(content) {
+                let mut part = head.to_vec();
+                part.extend_from_slice(replacement.as_bytes());
+                kept.push(part);
+            }
+        }
+        if !closed {
+            return Err(Error::MalformedBody(
+                "multipart body without closing delimiter".to_string(),
+            ));
+        }
+
+        let filtered = kept.join(&delimiter[..]);
+        Ok(filtered[2..].to_vec())
+    }
+}
+
+/// Return the form field name given in the Content-Disposition header of a multipart part head.
+fn part_name(head: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(head);
+    let disposition = head.split("\r\n").find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("content-disposition") {
+            Some(value.to_owned())
+        } else {
+            None
+        }
+    })?;
+
+    disposition.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("name") {
+            Some(value.trim().trim_matches('"').to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Return the position of the first occurrence of the needle in the haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Split the data at every occurrence of the delimiter.
+fn split<'a>(mut data: &'a [u8], delimiter: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match find(data, delimiter) {
+            Some(pos) => {
+                let segment = &data[..pos];
+                data = &data[pos + delimiter.len()..];
+                Some(segment)
+            }
+            None => {
+                done = true;
+                Some(data)
+            }
+        }
+    })
+}
+
+/// A builder for request records archiving an HTTP POST, with its form fields filtered before the
+/// record is built.
+///
+/// The HTTP message is written with a Host header taken from the target URI, unless one is given,
+/// and a Content-Length matching the filtered body; any Content-Length given is replaced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostRequest {
+    target_uri: String,
+    content_type: String,
+    body: Vec<u8>,
+    headers: Vec<(String, String)>,
+    filter: FormFilter,
+    date: Option<DateTime<Utc>>,
+}
+
+impl PostRequest {
+    /// Create a builder for a POST of the body, of the given Content-Type, to the target URI.
+    pub fn new<S, T, B>(target_uri: S, content_type: T, body: B) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+        B: Into<Vec<u8>>,
+    {
+        PostRequest {
+            target_uri: target_uri.into(),
+            content_type: content_type.into(),
+            body: body.into(),
+            headers: Vec::new(),
+            filter: FormFilter::new(),
+            date: None,
+        }
+    }
+
+    /// Add an HTTP header to the request, after any added before.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+
+        self
+    }
+
+    /// Set the filter applied to the form fields of the body. By default, no fields are denied.
+    pub fn filter(mut self, filter: FormFilter) -> Self {
+        self.filter = filter;
+
+        self
+    }
+
+    /// Set the WARC-Date of the record. By default, the record is dated when it is built.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Render the filtered HTTP request message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedHeader` if the target URI cannot be parsed, or an error from
+    /// `FormFilter::apply` if the body cannot be filtered.
+    pub fn to_http(&self) -> Result<Vec<u8>, Error> {
+        let url = Url::parse(&self.target_uri)
+            .map_err(|e| Error::MalformedHeader(WarcHeader::TargetURI, e.to_string()))?;
+        let body = self.filter.apply(&self.content_type, &self.body)?;
+
+        let mut path = url.path().to_owned();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        let mut head = format!("POST {} HTTP/1.1\r\n", path);
+        let has_host = self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("host"));
+        if let (false, Some(host)) = (has_host, url.host_str()) {
+            match url.port() {
+                Some(port) => head.push_str(&format!("Host: {}:{}\r\n", host, port)),
+                None => head.push_str(&format!("Host: {}\r\n", host)),
+            }
+        }
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("content-type")
+            {
+                continue;
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Type: {}\r\n", self.content_type));
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+
+        let mut message = head.into_bytes();
+        message.extend_from_slice(&body);
+
+        Ok(message)
+    }
+
+    /// Build the request record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP message cannot be rendered, as described for `to_http`.
+    pub fn build(self) -> Result<Record<BufferedBody>, Error> {
+        let message = self.to_http()?;
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(RecordType::Request);
+        if let Some(date) = self.date {
+            record.set_date(date);
+        }
+        record.set_header(WarcHeader::TargetURI, self.target_uri)?;
+        record.set_header(WarcHeader::ContentType, HTTP_REQUEST_CONTENT_TYPE)?;
+        record.replace_body(message);
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FormFilter, PostRequest, Redaction};
+    use crate::header::WarcHeader;
+    use crate::{Error, RecordType};
+
+    #[test]
+    fn post_request() {
+        let record = PostRequest::new(
+            "https://example.com:8443/login?next=%2F",
+            "application/x-www-form-urlencoded",
+            &b"user=alice&Pass%77ord=hunter2&remember=1"[..],
+        )
+        .header("User-Agent", "test")
+        .header("Content-Length", "999")
+        .filter(FormFilter::credentials())
+        .build()
+        .unwrap();
+
+        assert_eq!(record.warc_type(), &RecordType::Request);
+        assert_eq!(
+            record.header(WarcHeader::ContentType).as_deref(),
+            Some("application/http;msgtype=request")
+        );
+        assert_eq!(
+            String::from_utf8(record.body().to_vec()).unwrap(),
+            "POST /login?next=%2F HTTP/1.1\r\n\
+             Host: example.com:8443\r\n\
+             User-Agent: test\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: 21\r\n\
+             \r\n\
+             user=alice&remember=1"
+        );
+        assert_eq!(record.content_length(), record.body().len() as u64);
+
+        let masked = FormFilter::new().deny("token").redaction(Redaction::Mask);
+        assert_eq!(
+            masked
+                .apply("application/x-www-form-urlencoded", b"token=abc&a=1")
+                .unwrap(),
+            b"token=REDACTED&a=1"
+        );
+        assert_eq!(
+            masked.apply("application/json", b"{\"token\":1}").unwrap(),
+            b"{\"token\":1}"
+        );
+    }
+
+    #[test]
+    fn multipart() {
+        let body = b"preamble\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"user\"\r\n\
+            \r\n\
+            alice\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"password\"\r\n\
+            \r\n\
+            hunter2\r\n\
+            --XyZ--\r\n";
+        let content_type = "multipart/form-data; boundary=\"XyZ\"";
+
+        let stripped = FormFilter::credentials().apply(content_type, body).unwrap();
+        assert_eq!(
+            String::from_utf8(stripped).unwrap(),
+            "preamble\r\n\
+             --XyZ\r\n\
+             Content-Disposition: form-data; name=\"user\"\r\n\
+             \r\n\
+             alice\r\n\
+             --XyZ--\r\n"
+        );
+
+        let masked = FormFilter::credentials()
+            .redaction(Redaction::Mask)
+            .apply(content_type, body)
+            .unwrap();
+        assert!(String::from_utf8(masked)
+            .unwrap()
+            .contains("name=\"password\"\r\n\r\nREDACTED\r\n--XyZ--"));
+
+        assert!(matches!(
+            FormFilter::new().apply("multipart/form-data", body),
+            Err(Error::MalformedBody(_))
+        ));
+        assert!(matches!(
+            FormFilter::new().apply(content_type, &body[..body.len() - 9]),
+            Err(Error::MalformedBody(_))
+        ));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn hash() {
+        use crate::digest::Algorithm;
+
+        let filter = FormFilter::new()
+            .deny("password")
+            .redaction(Redaction::Hash(Algorithm::Sha1));
+        let first = filter
+            .apply("application/x-www-form-urlencoded", b"password=hunter2")
+            .unwrap();
+        let second = filter
+            .apply("application/x-www-form-urlencoded", b"password=hunter2")
+            .unwrap();
+        assert_eq!(first, second);
+        assert!(first.starts_with(b"password=sha1%3A"));
+    }
+}
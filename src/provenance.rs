@@ -0,0 +1,420 @@
+//! The provenance graph of a capture: the records it was derived from or captured with.
+//!
+//! Records name each other by WARC-Record-ID in four headers. `provenance` starts at a record and
+//! follows every link it finds, in both directions for WARC-Concurrent-To and
+//! WARC-Segment-Origin-ID, which tie together the records of a single capture, and forward only
+//! for WARC-Refers-To and WARC-Warcinfo-ID, which point to older or more general records.
+//! `collection_graph` instead takes every record of a collection and every link between them.
+//!
+//! Either graph can be exported as GraphViz DOT or as JSON for visualization.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io::{BufRead, Seek};
+
+use crate::header::WarcHeader;
+use crate::json::JsonString;
+use crate::record::BodyKind;
+use crate::{Collection, Record};
+
+/// The header linking one record to another.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LinkKind {
+    RefersTo,
+    ConcurrentTo,
+    SegmentOrigin,
+    Warcinfo,
+}
+
+impl LinkKind {
+    /// Return the header which holds links of this kind.
+    pub fn header(&self) -> WarcHeader {
+        match self {
+            LinkKind::RefersTo => WarcHeader::RefersTo,
+            LinkKind::ConcurrentTo => WarcHeader::ConcurrentTo,
+            LinkKind::SegmentOrigin => WarcHeader::SegmentOriginID,
+            LinkKind::Warcinfo => WarcHeader::WarcInfoID,
+        }
+    }
+
+    /// Return the name of this kind of link used in exported graphs.
+    pub fn label(&self) -> &'static str {
+        match self {
+            LinkKind::RefersTo => "refers-to",
+            LinkKind::ConcurrentTo => "concurrent-to",
+            LinkKind::SegmentOrigin => "segment-origin",
+            LinkKind::Warcinfo => "warcinfo",
+        }
+    }
+
+    /// Return true if a link of this kind also ties the target to the source.
+    fn is_mutual(&self) -> bool {
+        matches!(self, LinkKind::ConcurrentTo | LinkKind::SegmentOrigin)
+    }
+}
+
+const KINDS: [LinkKind; 4] = [
+    LinkKind::RefersTo,
+    LinkKind::ConcurrentTo,
+    LinkKind::SegmentOrigin,
+    LinkKind::Warcinfo,
+];
+
+/// A link from the record with one ID to the record with another, as written in the header of the
+/// first.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Link {
+    pub from: String,
+    pub to: String,
+    pub kind: LinkKind,
+}
+
+/// A string displayed as a quoted DOT ID.
+///
+/// Quotation marks and backslashes are escaped with a backslash. Line feeds are written as `\n`,
+/// which GraphViz draws as a centered line break in a label, and carriage returns are dropped.
+struct DotId<'a>(&'a str);
+
+impl fmt::Display for DotId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => {}
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// The records reachable from a record, and the links between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProvenanceGraph {
+    /// The IDs of the records found, starting with the record the walk started at, in the order
+    /// they were reached.
+    pub records: Vec<String>,
+    /// Every link followed, in the order it was found.
+    pub links: Vec<Link>,
+    /// The IDs linked to which are not in the collection, in the order they were found.
+    pub missing: Vec<String>,
+}
+
+impl ProvenanceGraph {
+    /// Format the graph as a GraphViz DOT digraph.
+    ///
+    /// Records are labeled with their type and target URI from the collection. Records missing
+    /// from the collection are drawn dashed.
+    pub fn to_dot<R: BufRead + Seek>(&self, collection: &Collection<R>) -> String {
+        let mut lines = vec!["digraph warc {".to_owned()];
+        for id in &self.records {
+            let label = match collection.get(id) {
+                Some(entry) => match entry.record.header(WarcHeader::TargetURI) {
+                    Some(uri) => format!("{}\n{}", entry.record.warc_type(), uri),
+                    None => entry.record.warc_type().to_string(),
+                },
+                None => id.clone(),
+            };
+            lines.push(format!("  {} [label={}];", DotId(id), DotId(&label)));
+        }
+        for id in &self.missing {
+            lines.push(format!("  {} [style=dashed];", DotId(id)));
+        }
+        for link in &self.links {
+            lines.push(format!(
+                "  {} -> {} [label={}];",
+                DotId(&link.from),
+                DotId(&link.to),
+                DotId(link.kind.label())
+            ));
+        }
+        lines.push("}".to_owned());
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Format the graph as JSON, with a list of nodes and a list of links.
+    ///
+    /// Each node has the `id` of a record, and its `type`, `target_uri` and `offset` in the
+    /// collection if it is found there, or else `"missing":true`.
+    pub fn to_json<R: BufRead + Seek>(&self, collection: &Collection<R>) -> String {
+        let mut nodes = Vec::new();
+        for id in self.records.iter().chain(&self.missing) {
+            let mut fields = vec![format!("\"id\":{}", JsonString(id))];
+            match collection.get(id) {
+                Some(entry) => {
+                    fields.push(format!(
+                        "\"type\":{}",
+                        JsonString(&entry.record.warc_type().to_string())
+                    ));
+                    if let Some(uri) = entry.record.header(WarcHeader::TargetURI) {
+                        fields.push(format!("\"target_uri\":{}", JsonString(&uri)));
+                    }
+                    fields.push(format!("\"offset\":{}", entry.offset));
+                }
+                None if self.missing.contains(id) => fields.push("\"missing\":true".to_owned()),
+                None => {}
+            }
+            nodes.push(format!("{{{}}}", fields.join(",")));
+        }
+        let links: Vec<_> = self
+            .links
+            .iter()
+            .map(|link| {
+                format!(
+                    "{{\"from\":{},\"to\":{},\"kind\":{}}}",
+                    JsonString(&link.from),
+                    JsonString(&link.to),
+                    JsonString(link.kind.label())
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"nodes\":[{}],\"links\":[{}]}}",
+            nodes.join(","),
+            links.join(",")
+        )
+    }
+}
+
+/// Return the IDs a record links to by each kind of link.
+fn links_of<T: BodyKind>(record: &Record<T>) -> Vec<(LinkKind, String)> {
+    let mut links = Vec::new();
+    for kind in KINDS.iter() {
+        if let Some(value) = record.header(kind.header()) {
+            links.extend(value.split_whitespace().map(|id| (*kind, id.to_owned())));
+        }
+    }
+
+    links
+}
+
+/// Walk the links of a record through a collection, returning its provenance graph.
+///
+/// The record need not be part of the collection.
+pub fn provenance<R: BufRead + Seek, T: BodyKind>(
+    collection: &Collection<R>,
+    record: &Record<T>,
+) -> ProvenanceGraph {
+    let mut graph = ProvenanceGraph::default();
+    let mut seen_links = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    let root = record.warc_id().to_owned();
+    seen.insert(root.clone());
+    graph.records.push(root.clone());
+    queue.push_back((root, links_of(record)));
+
+    while let Some((id, outgoing)) = queue.pop_front() {
+        let mut links: Vec<_> = outgoing
+            .into_iter()
+            .map(|(kind, to)| Link {
+                from: id.clone(),
+                to,
+                kind,
+            })
+            .collect();
+        for entry in collection.entries() {
+            for (kind, to) in links_of(&entry.record) {
+                if kind.is_mutual() && to == id {
+                    links.push(Link {
+                        from: entry.record.warc_id().to_owned(),
+                        to,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        for link in links {
+            if !seen_links.insert(link.clone()) {
+                continue;
+            }
+            let next = if link.from == id {
+                link.to.clone()
+            } else {
+                link.from.clone()
+            };
+            graph.links.push(link);
+            if !seen.insert(next.clone()) {
+                continue;
+            }
+            match collection.get(&next) {
+                Some(entry) => {
+                    graph.records.push(next.clone());
+                    queue.push_back((next, links_of(&entry.record)));
+                }
+                None => graph.missing.push(next),
+            }
+        }
+    }
+
+    graph
+}
+
+/// Return the graph of every record in a collection and every link between them.
+///
+/// Links to records missing from the collection are included, and their targets listed as
+/// missing.
+pub fn collection_graph<R: BufRead + Seek>(collection: &Collection<R>) -> ProvenanceGraph {
+    let mut graph = ProvenanceGraph::default();
+    let mut missing = HashSet::new();
+    for entry in collection.entries() {
+        let id = entry.record.warc_id();
+        graph.records.push(id.to_owned());
+        for (kind, to) in links_of(&entry.record) {
+            if collection.get(&to).is_none() && missing.insert(to.clone()) {
+                graph.missing.push(to.clone());
+            }
+            graph.links.push(Link {
+                from: id.to_owned(),
+                to,
+                kind,
+            });
+        }
+    }
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{collection_graph, provenance, Link, LinkKind};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcWriter};
+
+    #[test]
+    fn walk() {
+        let mut records = Vec::new();
+        for (type_, links) in [
+            (RecordType::WarcInfo, vec![]),
+            (RecordType::Response, vec![(WarcHeader::WarcInfoID, 0)]),
+            (RecordType::Request, vec![(WarcHeader::ConcurrentTo, 1)]),
+            (
+                RecordType::Continuation,
+                vec![(WarcHeader::SegmentOriginID, 1)],
+            ),
+            (
+                RecordType::Revisit,
+                vec![(WarcHeader::RefersTo, 1), (WarcHeader::WarcInfoID, 0)],
+            ),
+            (RecordType::Metadata, vec![(WarcHeader::ConcurrentTo, 4)]),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(type_);
+            for (header, to) in links {
+                let id = records.get(to).map(Record::warc_id).unwrap().to_owned();
+                record.set_header(header, id).unwrap();
+            }
+            records.push(record);
+        }
+        let concurrent = format!("{} <urn:uuid:gone>", records[4].warc_id());
+        records[5]
+            .set_header(WarcHeader::ConcurrentTo, concurrent)
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        drop(writer);
+        let collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let id = |i: usize| records[i].warc_id().to_owned();
+
+        let graph = provenance(&collection, &records[1]);
+        assert_eq!(graph.records, vec![id(1), id(0), id(2), id(3)]);
+        assert_eq!(graph.links.len(), 3);
+        assert!(graph.missing.is_empty());
+
+        let graph = provenance(&collection, &records[4]);
+        assert_eq!(
+            graph.records,
+            vec![id(4), id(1), id(0), id(5), id(2), id(3)]
+        );
+        assert_eq!(graph.missing, vec!["<urn:uuid:gone>"]);
+        assert_eq!(
+            graph
+                .links
+                .iter()
+                .filter(|link| link.kind == LinkKind::Warcinfo)
+                .count(),
+            2
+        );
+
+        let graph = collection_graph(&collection);
+        assert_eq!(graph.records.len(), 6);
+        assert_eq!(graph.links.len(), 7);
+        assert_eq!(
+            graph.links[0],
+            Link {
+                from: id(1),
+                to: id(0),
+                kind: LinkKind::Warcinfo
+            }
+        );
+        assert_eq!(graph.missing, vec!["<urn:uuid:gone>"]);
+    }
+
+    #[test]
+    fn export() {
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_version("1.0");
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        warcinfo.set_warc_id("<urn:test:\"info\">");
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_version("1.0");
+        response.set_warc_type(RecordType::Response);
+        response.set_warc_id("<urn:test:response>");
+        response
+            .set_header(WarcHeader::TargetURI, "http://example.com/a\\b\u{7f}")
+            .unwrap();
+        response
+            .set_header(WarcHeader::WarcInfoID, warcinfo.warc_id())
+            .unwrap();
+        response
+            .set_header(WarcHeader::ConcurrentTo, "<urn:test:gone>")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        let offset = writer.write(&warcinfo).unwrap();
+        writer.write(&response).unwrap();
+        drop(writer);
+        let collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let graph = provenance(&collection, &response);
+
+        assert_eq!(
+            graph.to_dot(&collection),
+            "digraph warc {\n  \
+             \"<urn:test:response>\" [label=\"response\\nhttp://example.com/a\\\\b\u{7f}\"];\n  \
+             \"<urn:test:\\\"info\\\">\" [label=\"warcinfo\"];\n  \
+             \"<urn:test:gone>\" [style=dashed];\n  \
+             \"<urn:test:response>\" -> \"<urn:test:gone>\" [label=\"concurrent-to\"];\n  \
+             \"<urn:test:response>\" -> \"<urn:test:\\\"info\\\">\" [label=\"warcinfo\"];\n\
+             }\n"
+        );
+        assert_eq!(
+            graph.to_json(&collection),
+            format!(
+                "{{\"nodes\":[\
+                 {{\"id\":\"<urn:test:response>\",\"type\":\"response\",\
+                 \"target_uri\":\"http://example.com/a\\\\b\\u007f\",\"offset\":{}}},\
+                 {{\"id\":\"<urn:test:\\\"info\\\">\",\"type\":\"warcinfo\",\"offset\":0}},\
+                 {{\"id\":\"<urn:test:gone>\",\"missing\":true}}],\"links\":[\
+                 {{\"from\":\"<urn:test:response>\",\"to\":\"<urn:test:gone>\",\
+                 \"kind\":\"concurrent-to\"}},\
+                 {{\"from\":\"<urn:test:response>\",\"to\":\"<urn:test:\\\"info\\\">\",\
+                 \"kind\":\"warcinfo\"}}]}}",
+                offset
+            )
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Transcoding of WARC files between compression formats.
+//!
+//! A WARC file is plain, or a series of compressed members holding one record each. `recompress`
+//! reads a file in any of these framings and writes it in another, copying each record block
+//! unchanged: only the header block is read, to find where the record ends.
+//!
+//! A `Recompress` pass can compress records on several threads, and can stop before its output
+//! exceeds a quota. Its report gives the number of records consumed, from which a later pass can
+//! resume.
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::header::WarcHeader;
+use crate::Error;
+
+const BATCH_PER_THREAD: usize = 8;
+
+/// The framing of the records of a WARC file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Codec {
+    /// Uncompressed records.
+    Plain,
+    /// One gzip member per record.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// One Zstandard frame per record.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Codec {
+    /// Return the codec of the data starting with the given bytes, by its magic number.
+    ///
+    /// Data not starting with the magic number of a supported codec is assumed to be plain.
+    pub fn detect(start: &[u8]) -> Codec {
+        #[cfg(feature = "gzip")]
+        {
+            if start.starts_with(&[0x1f, 0x8b]) {
+                return Codec::Gzip;
+            }
+        }
+        #[cfg(feature = "zstd")]
+        {
+            if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+                return Codec::Zstd;
+            }
+        }
+        let _ = start;
+
+        Codec::Plain
+    }
+
+    /// Return the file name extension conventionally used for this codec.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Codec::Plain => ".warc",
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => ".warc.gz",
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => ".warc.zst",
+        }
+    }
+
+    /// Return a reader of the uncompressed records of the input.
+    pub(crate) fn decoder<'r, R: BufRead + 'r>(
+        &self,
+        input: R,
+    ) -> io::Result<Box<dyn BufRead + 'r>> {
+        Ok(match self {
+            Codec::Plain => Box::new(input),
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Box::new(io::BufReader::new(libflate::gzip::MultiDecoder::new(
+                input,
+            )?)),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Box::new(io::BufReader::new(zstd::Decoder::with_buffer(input)?)),
+        })
+    }
+
+    /// Frame one record.
+    fn encode(&self, record: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Plain => Ok(record.to_vec()),
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => {
+                let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+                encoder.write_all(record)?;
+                encoder.finish().into_result()
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::encode_all(record, 0),
+        }
+    }
+}
+
+/// The outcome of a `Recompress` pass.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecompressReport {
+    /// The number of records consumed from the input, including those skipped to resume an
+    /// earlier pass. A pass stopped by its quota resumes from this record.
+    pub records: u64,
+    /// The number of records written.
+    pub written: u64,
+    /// The uncompressed length of the records written.
+    pub bytes_in: u64,
+    /// The length of the output written.
+    pub bytes_out: u64,
+    /// True if the whole input was consumed, and false if the pass was stopped by its quota.
+    pub complete: bool,
+}
+
+/// A pass converting a WARC file from its framing to another.
+#[derive(Clone, Debug)]
+pub struct Recompress {
+    codec: Codec,
+    threads: usize,
+    quota: Option<u64>,
+    resume: u64,
+    dry_run: bool,
+}
+
+impl Recompress {
+    /// Create a pass writing records framed by the given codec.
+    pub fn new(codec: Codec) -> Self {
+        Recompress {
+            codec,
+            threads: 1,
+            quota: None,
+            resume: 0,
+            dry_run: false,
+        }
+    }
+
+    /// Compress records on the given number of threads. The default is one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+
+        self
+    }
+
+    /// Stop before the output exceeds the given number of bytes.
+    pub fn quota(mut self, bytes: u64) -> Self {
+        self.quota = Some(bytes);
+
+        self
+    }
+
+    /// Frame every record and report the result without writing anything to the output.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+
+        self
+    }
+
+    /// Skip the given number of records, as counted by the report of an earlier pass.
+    pub fn resume(mut self, records: u64) -> Self {
+        self.resume = records;
+
+        self
+    }
+
+    /// Read every record from the input, whose framing is detected, and write it to the output.
+    pub fn run<R: BufRead, W: Write>(
+        &self,
+        mut input: R,
+        mut output: W,
+    ) -> Result<RecompressReport, Error> {
+        let codec = Codec::detect(input.fill_buf().map_err(|_| Error::ReadData)?);
+        let mut input = codec.decoder(input).map_err(|_| Error::ReadData)?;
+
+        let mut report = RecompressReport::default();
+        while report.records < self.resume {
+            if next_record(&mut input)?.is_none() {
+                report.complete = true;
+                return Ok(report);
+            }
+            report.records += 1;
+        }
+
+        let mut batch = Vec::with_capacity(self.threads * BATCH_PER_THREAD);
+        loop {
+            batch.clear();
+            while batch.len() < self.threads * BATCH_PER_THREAD {
+                match next_record(&mut input)? {
+                    Some(record) => batch.push(record),
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            for (record, framed) in batch.iter().zip(self.encode(&batch)?) {
+                let bytes_out = report.bytes_out + framed.len() as u64;
+                if self.quota.is_some_and(|quota| bytes_out > quota) {
+                    output.flush().map_err(|_| Error::WriteData)?;
+                    return Ok(report);
+                }
+                if !self.dry_run {
+                    output.write_all(&framed).map_err(|_| Error::WriteData)?;
+                }
+                report.records += 1;
+                report.written += 1;
+                report.bytes_in += record.len() as u64;
+                report.bytes_out = bytes_out;
+            }
+        }
+        output.flush().map_err(|_| Error::WriteData)?;
+        report.complete = true;
+
+        Ok(report)
+    }
+
+    /// Frame a batch of records, dividing it between the threads.
+    fn encode(&self, batch: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let codec = self.codec;
+        let encode_all = |records: &[Vec<u8>]| {
+            records
+                .iter()
+                .map(|record| codec.encode(record))
+                .collect::<io::Result<Vec<_>>>()
+        };
+        if self.threads == 1 {
+            return encode_all(batch).map_err(|_| Error::WriteData);
+        }
+
+        let chunk_len = batch.len().div_ceil(self.threads);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .chunks(chunk_len)
+                .map(|chunk| scope.spawn(move || encode_all(chunk)))
+                .collect();
+            let mut framed = Vec::with_capacity(batch.len());
+            for worker in workers {
+                match worker.join() {
+                    Ok(Ok(mut chunk)) => framed.append(&mut chunk),
+                    _ => return Err(Error::WriteData),
+                }
+            }
+
+            Ok(framed)
+        })
+    }
+}
+
+/// Convert a WARC file from its framing, which is detected, to the given codec.
+pub fn recompress<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    codec: Codec,
+) -> Result<RecompressReport, Error> {
+    Recompress::new(codec).run(input, output)
+}
+
+/// Read the next serialized record, including its separator, without parsing its block.
+fn next_record(input: &mut dyn BufRead) -> Result<Option<Vec<u8>>, Error> {
+    let mut record = Vec::new();
+    let mut content_length = None;
+    loop {
+        let start = record.len();
+        match input.read_until(b'\n', &mut record) {
+            Err(_) => return Err(Error::ReadData),
+            Ok(0) if record.is_empty() => return Ok(None),
+            Ok(0) => return Err(Error::UnexpectedEOB),
+            Ok(_) => {}
+        }
+        let line = &record[start..];
+        if line == b"\r\n" {
+            break;
+        }
+        if let Some(colon) = line.iter().position(|&b| b == b':') {
+            if line[..colon].eq_ignore_ascii_case(b"content-length") {
+                let value = String::from_utf8_lossy(&line[colon + 1..]);
+                content_length = Some(value.trim().parse::<u64>().map_err(|_| {
+                    Error::MalformedHeader(WarcHeader::ContentLength, value.trim().to_owned())
+                })?);
+            }
+        }
+    }
+
+    let length = content_length.ok_or(Error::MissingHeader(WarcHeader::ContentLength))? + 4;
+    let read = input
+        .take(length)
+        .read_to_end(&mut record)
+        .map_err(|_| Error::ReadData)?;
+    if (read as u64) < length {
+        return Err(Error::UnexpectedEOB);
+    }
+    if !record.ends_with(b"\r\n\r\n") {
+        return Err(Error::ReadOverflow);
+    }
+
+    Ok(Some(record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recompress, Codec, Recompress, RecompressReport};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcWriter};
+
+    fn plain() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for i in 0..5 {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Resource);
+            record
+                .set_header(WarcHeader::TargetURI, format!("http://example.com/{}", i))
+                .unwrap();
+            record.replace_body(vec![b'a' + i as u8; 100 * i]);
+            writer.write(&record).unwrap();
+        }
+        drop(writer);
+        buffer
+    }
+
+    #[test]
+    fn round_trip() {
+        let plain = plain();
+        let mut copy = Vec::new();
+        let report = recompress(&plain[..], &mut copy, Codec::Plain).unwrap();
+        assert_eq!(copy, plain);
+        assert_eq!(report.written, 5);
+        assert!(report.complete);
+
+        #[cfg(feature = "gzip")]
+        {
+            let mut gzip = Vec::new();
+            Recompress::new(Codec::Gzip)
+                .threads(3)
+                .run(&plain[..], &mut gzip)
+                .unwrap();
+            assert_eq!(Codec::detect(&gzip), Codec::Gzip);
+
+            let mut back = Vec::new();
+            recompress(&gzip[..], &mut back, Codec::Plain).unwrap();
+            assert_eq!(back, plain);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let mut zstd = Vec::new();
+            recompress(&plain[..], &mut zstd, Codec::Zstd).unwrap();
+            assert_eq!(Codec::detect(&zstd), Codec::Zstd);
+
+            let mut back = Vec::new();
+            recompress(&zstd[..], &mut back, Codec::Plain).unwrap();
+            assert_eq!(back, plain);
+        }
+    }
+
+    #[test]
+    fn quota_and_resume() {
+        let plain = plain();
+        let mut first = Vec::new();
+        let report = Recompress::new(Codec::Plain)
+            .quota(plain.len() as u64 / 2)
+            .run(&plain[..], &mut first)
+            .unwrap();
+        assert!(!report.complete);
+        assert!(report.written > 0 && report.written < 5);
+        assert!(first.len() as u64 <= plain.len() as u64 / 2);
+
+        let mut rest = Vec::new();
+        let resumed = Recompress::new(Codec::Plain)
+            .resume(report.records)
+            .run(&plain[..], &mut rest)
+            .unwrap();
+        assert_eq!(
+            resumed,
+            RecompressReport {
+                records: 5,
+                written: 5 - report.written,
+                bytes_in: rest.len() as u64,
+                bytes_out: rest.len() as u64,
+                complete: true,
+            }
+        );
+        first.append(&mut rest);
+        assert_eq!(first, plain);
+
+        let mut output = Vec::new();
+        let dry_run = Recompress::new(Codec::Plain)
+            .resume(report.records)
+            .dry_run(true)
+            .run(&plain[..], &mut output)
+            .unwrap();
+        assert_eq!(dry_run, resumed);
+        assert!(output.is_empty());
+    }
+}
@@ -3,19 +3,32 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 use std::io::Read;
+use std::sync::Arc;
 
 use uuid::Uuid;
 
-use crate::header::WarcHeader;
+use crate::date_precision::DatePrecision;
+use crate::header::{HeaderMap, WarcHeader};
+use crate::http::{self, HttpHead};
+use crate::parser_config::ParserConfig;
 use crate::record_type::RecordType;
+use crate::refers_to::RefersTo;
+use crate::revisit_profile::RevisitProfile;
+use crate::sniff;
 use crate::truncated_type::TruncatedType;
+use crate::typed_header::TypedHeader;
+use crate::warc_version::WarcVersion;
+use crate::warning::Warning;
 use crate::Error as WarcError;
 
-use streaming_trait::BodyKind;
-pub use streaming_trait::{BufferedBody, EmptyBody, StreamingBody};
+pub(crate) use streaming_trait::BodyKind;
+#[cfg(feature = "bytes")]
+pub use streaming_trait::BytesBody;
+pub use streaming_trait::{BufferedBody, EmptyBody, SharedBody, StreamingBody};
 
 mod streaming_trait {
     use std::io::Read;
+    use std::sync::Arc;
 
     /// An associated type indicating how the body of a record is represented.
     pub trait BodyKind {
@@ -30,31 +43,73 @@ mod streaming_trait {
             self.0.len() as u64
         }
     }
+    impl AsRef<[u8]> for BufferedBody {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    /// An associated type indicating the body is buffered, and shared by every clone of the
+    /// record.
+    pub struct SharedBody(pub Arc<[u8]>);
+    impl BodyKind for SharedBody {
+        fn content_length(&self) -> u64 {
+            self.0.len() as u64
+        }
+    }
+    impl AsRef<[u8]> for SharedBody {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[derive(Clone, Debug, PartialEq)]
+    /// An associated type indicating the body is a `Bytes` buffer, which may be a slice of a
+    /// larger buffer shared with other records.
+    pub struct BytesBody(pub bytes::Bytes);
+    #[cfg(feature = "bytes")]
+    impl BodyKind for BytesBody {
+        fn content_length(&self) -> u64 {
+            self.0.len() as u64
+        }
+    }
+    #[cfg(feature = "bytes")]
+    impl AsRef<[u8]> for BytesBody {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
 
     /// An associated type indicating the body is streamed from a reader.
-    pub struct StreamingBody<'t, T: Read + 't>(&'t mut T, &'t mut u64);
+    ///
+    /// The body is read through its `Read` implementation, which ends after the number of bytes
+    /// given by the record's Content-Length.
+    pub struct StreamingBody<'t, T: Read + 't>(&'t mut T, &'t mut u64, u64);
     impl<'t, T: Read + 't> StreamingBody<'t, T> {
         pub(crate) fn new(stream: &'t mut T, max_len: &'t mut u64) -> StreamingBody<'t, T> {
-            StreamingBody(stream, max_len)
+            let len = *max_len;
+            StreamingBody(stream, max_len, len)
         }
 
-        pub(crate) fn len(&self) -> u64 {
+        /// Return the number of bytes of the body which have not been read yet.
+        pub fn remaining(&self) -> u64 {
             *self.1
         }
     }
     impl<'t, T: Read + 't> BodyKind for StreamingBody<'t, T> {
         fn content_length(&self) -> u64 {
-            *self.1
+            self.2
         }
     }
 
     impl<'t, T: Read + 't> Read for StreamingBody<'t, T> {
         fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
             let max_read = std::cmp::min(data.len(), *self.1 as usize);
-            self.0.read(&mut data[..max_read as usize]).map(|n| {
-                *self.1 -= n as u64;
-                n
-            })
+            let n = self.0.read(&mut data[..max_read])?;
+            *self.1 -= n as u64;
+            Ok(n)
         }
     }
 
@@ -77,32 +132,82 @@ mod streaming_trait {
 pub struct RawRecordHeader {
     /// The WARC standard version this record reports conformance to.
     pub version: String,
-    /// All headers that are part of this record.
-    pub headers: HashMap<WarcHeader, Vec<u8>>,
+    /// All headers that are part of this record, in the order they were read.
+    pub headers: HeaderMap,
 }
 
-impl AsRef<HashMap<WarcHeader, Vec<u8>>> for RawRecordHeader {
-    fn as_ref(&self) -> &HashMap<WarcHeader, Vec<u8>> {
+impl AsRef<HeaderMap> for RawRecordHeader {
+    fn as_ref(&self) -> &HeaderMap {
         &self.headers
     }
 }
 
-impl AsMut<HashMap<WarcHeader, Vec<u8>>> for RawRecordHeader {
-    fn as_mut(&mut self) -> &mut HashMap<WarcHeader, Vec<u8>> {
+impl AsMut<HeaderMap> for RawRecordHeader {
+    fn as_mut(&mut self) -> &mut HeaderMap {
         &mut self.headers
     }
 }
 
 impl std::convert::TryFrom<RawRecordHeader> for Record<EmptyBody> {
     type Error = WarcError;
-    fn try_from(mut headers: RawRecordHeader) -> Result<Self, WarcError> {
+    fn try_from(headers: RawRecordHeader) -> Result<Self, WarcError> {
+        Record::<EmptyBody>::from_raw_with_config(headers, &ParserConfig::default())
+    }
+}
+
+impl Record<EmptyBody> {
+    /// Create a record from a raw header block, checking it for validity according to the given
+    /// configuration.
+    ///
+    /// Any repairs allowed by the configuration are reported by the record's `warnings()`.
+    pub fn from_raw_with_config(
+        mut headers: RawRecordHeader,
+        config: &ParserConfig,
+    ) -> Result<Self, WarcError> {
+        let mut warnings = Vec::new();
+        let header_order = headers.as_ref().keys().cloned().collect();
+        let raw_headers = if config.forensic {
+            Some(headers.clone())
+        } else {
+            None
+        };
+
+        for (header, normalizer) in &config.normalizers {
+            if *header == WarcHeader::ContentLength {
+                continue;
+            }
+            headers.as_mut().retain(|name, value| {
+                if name != header {
+                    return true;
+                }
+                let original = match std::str::from_utf8(value) {
+                    Ok(original) => original.to_owned(),
+                    Err(_) => return true,
+                };
+                let normalized = normalizer.apply(&original);
+                if normalized != original {
+                    *value = normalized.clone().into_bytes();
+                    warnings.push(Warning::NormalizedHeader {
+                        header: header.clone(),
+                        original,
+                        normalized,
+                    });
+                }
+
+                true
+            });
+        }
+
         headers
             .as_mut()
             .remove(&WarcHeader::ContentLength)
             .ok_or(WarcError::MissingHeader(WarcHeader::ContentLength))
             .and_then(|vec| {
                 String::from_utf8(vec).map_err(|_| {
-                    WarcError::MalformedHeader(WarcHeader::Date, "not a UTF-8 string".to_string())
+                    WarcError::MalformedHeader(
+                        WarcHeader::ContentLength,
+                        "not a UTF-8 string".to_string(),
+                    )
                 })
             })?;
 
@@ -126,7 +231,10 @@ impl std::convert::TryFrom<RawRecordHeader> for Record<EmptyBody> {
             .ok_or(WarcError::MissingHeader(WarcHeader::RecordID))
             .and_then(|vec| {
                 String::from_utf8(vec).map_err(|_| {
-                    WarcError::MalformedHeader(WarcHeader::Date, "not a UTF-8 string".to_string())
+                    WarcError::MalformedHeader(
+                        WarcHeader::RecordID,
+                        "not a UTF-8 string".to_string(),
+                    )
                 })
             })?;
 
@@ -139,29 +247,119 @@ impl std::convert::TryFrom<RawRecordHeader> for Record<EmptyBody> {
                     WarcError::MalformedHeader(WarcHeader::Date, "not a UTF-8 string".to_string())
                 })
             })
-            .and_then(|date| Record::<BufferedBody>::parse_record_date(&date))?;
+            .and_then(|date| {
+                if config.lenient_dates {
+                    let version = WarcVersion::from(&headers.version);
+                    Record::<BufferedBody>::parse_record_date_lenient(&date, &version).map(
+                        |parsed| {
+                            if let Some(normalized) = parsed.1 {
+                                warnings.push(Warning::NormalizedHeader {
+                                    header: WarcHeader::Date,
+                                    original: date.clone(),
+                                    normalized,
+                                });
+                            }
+                            parsed.0
+                        },
+                    )
+                } else {
+                    Record::<BufferedBody>::parse_record_date(&date)
+                }
+                .map(|parsed| (parsed, Record::<BufferedBody>::parse_date_precision(&date)))
+            })?;
+        let (record_date, date_precision) = record_date;
+        let date_precision = WarcVersion::from(&headers.version).date_precision(date_precision);
 
         Ok(Record {
             headers,
             record_date,
+            date_precision,
             record_id,
             record_type,
+            warnings,
+            header_order,
+            raw_headers,
             body: EmptyBody(),
             ..Default::default()
         })
     }
+
+    /// Create a record from a raw header block, replacing any missing or invalid required
+    /// headers with default values.
+    ///
+    /// Each replacement is reported by the record's `warnings()`, and every other header is kept.
+    /// The defaults are those used by `Record::new()`, except that the WARC-Type of the record is
+    /// "unknown".
+    ///
+    /// # Errors
+    ///
+    /// The error of the header block is returned if it cannot be repaired, so that the record is
+    /// skipped as under `ReadPolicy::Skip`.
+    pub fn from_raw_best_effort(
+        mut headers: RawRecordHeader,
+        config: &ParserConfig,
+    ) -> Result<Self, WarcError> {
+        let mut warnings = Vec::new();
+        let mut repaired = Vec::new();
+        let defaults = Record::<EmptyBody>::default();
+        let original = if config.forensic {
+            Some(headers.clone())
+        } else {
+            None
+        };
+
+        loop {
+            let error = match Record::from_raw_with_config(headers.clone(), config) {
+                Ok(mut record) => {
+                    warnings.append(&mut record.warnings);
+                    record.warnings = warnings;
+                    record.raw_headers = original;
+                    return Ok(record);
+                }
+                Err(error) => error,
+            };
+            // each required header is repaired at most once
+            let header = match error {
+                WarcError::MissingHeader(ref header)
+                | WarcError::MalformedHeader(ref header, _)
+                    if !repaired.contains(header) =>
+                {
+                    header.clone()
+                }
+                _ => return Err(error),
+            };
+            if let WarcError::MissingHeader(_) = error {
+                warnings.push(Warning::MissingHeader(header.clone()));
+            }
+            repaired.push(header.clone());
+
+            let default = match header {
+                WarcHeader::ContentLength => "0".to_string(),
+                WarcHeader::WarcType => "unknown".to_string(),
+                _ => defaults
+                    .header(header.clone())
+                    .map(|value| value.into_owned())
+                    .unwrap_or_default(),
+            };
+            if let Some(original) = headers
+                .as_mut()
+                .insert(header.clone(), default.clone().into())
+            {
+                warnings.push(Warning::NormalizedHeader {
+                    header,
+                    original: String::from_utf8_lossy(&original).into_owned(),
+                    normalized: default,
+                });
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for RawRecordHeader {
     fn fmt(&self, w: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         writeln!(w, "WARC/{}", self.version)?;
         for (key, value) in self.as_ref().iter() {
-            writeln!(
-                w,
-                "{}: {}",
-                key.to_string(),
-                String::from_utf8_lossy(&value)
-            )?;
+            writeln!(w, "{}: {}", key, String::from_utf8_lossy(value))?;
         }
         writeln!(w)?;
 
@@ -175,6 +373,8 @@ pub struct RecordBuilder {
     value: Record<BufferedBody>,
     broken_headers: HashMap<WarcHeader, Vec<u8>>,
     last_error: Option<WarcError>,
+    #[cfg(feature = "digest")]
+    digests: Option<crate::digest::Algorithm>,
 }
 
 /// A single WARC record.
@@ -193,9 +393,17 @@ pub struct Record<T: BodyKind> {
     // NB: invariant: does not contain the headers stored in the struct
     headers: RawRecordHeader,
     record_date: DateTime<Utc>,
+    date_precision: DatePrecision,
     record_id: String,
     record_type: RecordType,
     truncated_type: Option<TruncatedType>,
+    warnings: Vec<Warning>,
+    stored_len: Option<u64>,
+    header_names: HashMap<WarcHeader, String>,
+    // the order of the headers as read, including those stored in the struct
+    header_order: Vec<WarcHeader>,
+    // the header block as read, kept in forensic mode
+    raw_headers: Option<RawRecordHeader>,
     body: T,
 }
 
@@ -247,7 +455,7 @@ impl<T: BodyKind> Record<T> {
     /// The current implementation generates random values based on UUID version 4.
     ///
     pub fn generate_record_id() -> String {
-        format!("<{}>", Uuid::new_v4().to_urn().to_string())
+        format!("<{}>", Uuid::new_v4().to_urn())
     }
 
     fn parse_content_length(len: &str) -> Result<u64, WarcError> {
@@ -259,7 +467,7 @@ impl<T: BodyKind> Record<T> {
         })
     }
 
-    fn parse_record_date(date: &str) -> Result<DateTime<Utc>, WarcError> {
+    pub(crate) fn parse_record_date(date: &str) -> Result<DateTime<Utc>, WarcError> {
         DateTime::parse_from_rfc3339(date)
             .map_err(|_| {
                 WarcError::MalformedHeader(
@@ -270,14 +478,104 @@ impl<T: BodyKind> Record<T> {
             .map(|date| date.into())
     }
 
+    /// Parse a WARC-Date value, accepting the malformed variants found in older archives.
+    ///
+    /// If the value needed to be normalized, the normalized form is returned alongside the date.
+    /// The normalized form is no more precise than the given WARC version allows.
+    fn parse_record_date_lenient(
+        date: &str,
+        version: &WarcVersion,
+    ) -> Result<(DateTime<Utc>, Option<String>), WarcError> {
+        if date.ends_with('Z') && date.as_bytes().get(10) == Some(&b'T') {
+            if let Ok(parsed) = Record::<T>::parse_record_date(date) {
+                let precision = Record::<T>::parse_date_precision(date);
+                let normalized = version.format_date(&parsed, precision);
+                return Ok((
+                    parsed,
+                    Some(normalized).filter(|normalized| normalized != date),
+                ));
+            }
+        }
+
+        let trimmed = date.trim();
+        let parsed = if trimmed.len() >= 12 && trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            // Twelve digits are a timestamp with a two-digit year. Longer values are timestamps
+            // with a four-digit year, truncated or with digits past the seconds, which are padded
+            // with zeros or cut to whole seconds.
+            let (digits, format) = if trimmed.len() == 12 {
+                (trimmed.to_owned(), "%y%m%d%H%M%S")
+            } else {
+                (format!("{:0<14.14}", trimmed), "%Y%m%d%H%M%S")
+            };
+            NaiveDateTime::parse_from_str(&digits, format)
+                .ok()
+                .map(|naive| Utc.from_utc_datetime(&naive))
+        } else {
+            let mut normalized = trimmed.replacen(' ', "T", 1);
+            if normalized.ends_with('z') {
+                normalized.replace_range(normalized.len() - 1.., "Z");
+            }
+            DateTime::parse_from_rfc3339(&normalized)
+                .map(|date| date.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    let naive = normalized.trim_end_matches('Z');
+                    let format = if naive.find('-') == Some(2) {
+                        "%y-%m-%dT%H:%M:%S%.f"
+                    } else {
+                        "%Y-%m-%dT%H:%M:%S%.f"
+                    };
+                    NaiveDateTime::parse_from_str(naive, format)
+                        .ok()
+                        .map(|naive| Utc.from_utc_datetime(&naive))
+                })
+        };
+
+        let parsed = parsed.ok_or_else(|| {
+            WarcError::MalformedHeader(WarcHeader::Date, "not an ISO 8601 datestamp".to_string())
+        })?;
+        let precision = Record::<T>::parse_date_precision(trimmed);
+
+        Ok((parsed, Some(version.format_date(&parsed, precision))))
+    }
+
+    fn parse_date_precision(date: &str) -> DatePrecision {
+        let digits = date
+            .find('.')
+            .map(|dot| {
+                date[dot + 1..]
+                    .bytes()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count()
+            })
+            .unwrap_or(0);
+        DatePrecision::from_fraction_digits(digits)
+    }
+
     /// Return the WARC version string of this record.
     pub fn warc_version(&self) -> &str {
         &self.headers.version
     }
 
-    /// Set the WARC version string of this record.
+    /// Return the version of the standard this record is written under, which selects the rules
+    /// it is checked against.
+    pub fn version(&self) -> WarcVersion {
+        WarcVersion::from(&self.headers.version)
+    }
+
+    /// Return true if this record was written under a pre-1.0 draft of the standard, such as
+    /// WARC/0.17 or WARC/0.18.
+    pub fn is_legacy_version(&self) -> bool {
+        crate::legacy::is_legacy_version(&self.headers.version)
+    }
+
+    /// Set the WARC version string of this record, such as `"1.1"` or `WarcVersion::V1_1`.
+    ///
+    /// If the new version does not allow sub-second dates, the date precision is reset to
+    /// `DatePrecision::Seconds`.
     pub fn set_warc_version<S: Into<String>>(&mut self, id: S) {
         self.headers.version = id.into();
+        self.date_precision = self.version().date_precision(self.date_precision);
     }
 
     /// Return the WARC-Record-ID header for this record.
@@ -312,6 +610,53 @@ impl<T: BodyKind> Record<T> {
         self.record_date = date;
     }
 
+    /// Return the precision used when formatting the WARC-Date header for this record.
+    pub fn date_precision(&self) -> DatePrecision {
+        self.date_precision
+    }
+
+    /// Set the precision used when formatting the WARC-Date header for this record.
+    ///
+    /// # Errors
+    ///
+    /// Sub-second precision is only allowed by WARC/1.1 and later. An error is returned if a
+    /// sub-second precision is requested for a record of an earlier version.
+    pub fn set_date_precision(&mut self, precision: DatePrecision) -> Result<(), WarcError> {
+        if precision.is_subsecond() && !self.version().allows_subsecond_dates() {
+            return Err(WarcError::MalformedHeader(
+                WarcHeader::Date,
+                "sub-second precision requires WARC/1.1 or later".to_string(),
+            ));
+        }
+        self.date_precision = precision;
+
+        Ok(())
+    }
+
+    fn formatted_date(&self) -> String {
+        self.version()
+            .format_date(&self.record_date, self.date_precision)
+    }
+
+    /// Return the warnings reported while reading this record.
+    ///
+    /// Warnings are only reported when the record was read with a lenient `ParserConfig`.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Return true if this record was reconstructed from a revisit record and the payload it
+    /// refers to.
+    pub fn is_reconstructed(&self) -> bool {
+        self.warnings
+            .iter()
+            .any(|warning| matches!(warning, Warning::Reconstructed(_)))
+    }
+
+    pub(crate) fn append_warnings(&mut self, warnings: &mut Vec<Warning>) {
+        self.warnings.append(warnings);
+    }
+
     /// Return the WARC-Truncated header for this record.
     pub fn truncated_type(&self) -> &Option<TruncatedType> {
         &self.truncated_type
@@ -322,12 +667,165 @@ impl<T: BodyKind> Record<T> {
         self.truncated_type = Some(truncated_type);
     }
 
+    /// Return true if this record has a WARC-Truncated header.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated_type.is_some()
+    }
+
     /// Remove the WARC-Truncated header for this record.
     pub fn clear_truncated_type(&mut self) {
         self.truncated_type = None;
     }
 
+    /// Return the WARC-Identified-Payload-Type header for this record.
+    pub fn identified_payload_type(&self) -> Option<Cow<'_, str>> {
+        self.header(WarcHeader::IdentifiedPayloadType)
+    }
+
+    /// Set the WARC-Identified-Payload-Type header for this record.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if records of this type have no payload.
+    pub fn set_identified_payload_type<S: Into<String>>(
+        &mut self,
+        media_type: S,
+    ) -> Result<(), WarcError> {
+        if !self.record_type.has_payload() {
+            return Err(self.no_payload_error());
+        }
+        self.headers
+            .as_mut()
+            .insert(WarcHeader::IdentifiedPayloadType, media_type.into().into());
+
+        Ok(())
+    }
+
+    /// Return an error if this record has a WARC-Identified-Payload-Type header, but records of
+    /// its type have no payload.
+    fn check_identified_payload_type(&self) -> Result<(), WarcError> {
+        if self.record_type.has_payload()
+            || !self
+                .headers
+                .as_ref()
+                .contains_key(&WarcHeader::IdentifiedPayloadType)
+        {
+            return Ok(());
+        }
+
+        Err(self.no_payload_error())
+    }
+
+    fn no_payload_error(&self) -> WarcError {
+        WarcError::MalformedHeader(
+            WarcHeader::IdentifiedPayloadType,
+            format!("{} records have no payload", self.record_type),
+        )
+    }
+
+    /// Return the WARC-Profile header for this record, if present.
+    pub fn revisit_profile(&self) -> Option<RevisitProfile> {
+        self.header(WarcHeader::Profile).map(RevisitProfile::from)
+    }
+
+    /// Set the WARC-Profile header for this record, using the profile URI defined by the version
+    /// of this record.
+    pub fn set_revisit_profile(&mut self, profile: RevisitProfile) {
+        let uri = profile.uri(&self.headers.version);
+        self.headers
+            .as_mut()
+            .insert(WarcHeader::Profile, uri.into_bytes());
+    }
+
+    /// Return the record this record refers to, if it has any of the WARC-Refers-To,
+    /// WARC-Refers-To-Target-URI and WARC-Refers-To-Date headers.
+    ///
+    /// A WARC-Refers-To-Date which is not a well-formed date is ignored.
+    pub fn refers_to(&self) -> Option<RefersTo> {
+        let value = |header| self.header(header).map(|value| value.trim().to_owned());
+        let refers_to = RefersTo {
+            record_id: value(WarcHeader::RefersTo),
+            target_uri: value(WarcHeader::RefersToTargetURI),
+            date: value(WarcHeader::RefersToDate)
+                .and_then(|date| Record::<T>::parse_record_date(&date).ok()),
+        };
+
+        Some(refers_to).filter(|refers_to| !refers_to.is_empty())
+    }
+
+    /// Set the WARC-Refers-To, WARC-Refers-To-Target-URI and WARC-Refers-To-Date headers
+    /// together, removing any of them not given. An empty `RefersTo` removes all three.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned, and no header is changed, if a target URI or date is given but the
+    /// version of this record is earlier than WARC/1.1, which does not define those headers.
+    pub fn set_refers_to(&mut self, refers_to: RefersTo) -> Result<(), WarcError> {
+        let version = self.version();
+        let given = [
+            (
+                WarcHeader::RefersToTargetURI,
+                refers_to.target_uri.is_some(),
+            ),
+            (WarcHeader::RefersToDate, refers_to.date.is_some()),
+        ];
+        if let Some((header, _)) = given
+            .iter()
+            .find(|(header, given)| *given && !version.defines_header(header))
+        {
+            return Err(WarcError::MalformedHeader(
+                header.clone(),
+                format!("not defined by {}", version),
+            ));
+        }
+
+        let values = vec![
+            (WarcHeader::RefersTo, refers_to.record_id),
+            (WarcHeader::RefersToTargetURI, refers_to.target_uri),
+            (
+                WarcHeader::RefersToDate,
+                refers_to
+                    .date
+                    .map(|date| date.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            ),
+        ];
+        let headers = self.headers.as_mut();
+        for (header, value) in values {
+            match value {
+                Some(value) => headers.insert(header, value.into_bytes()),
+                None => headers.remove(&header),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Return an error if this is a revisit record without a profile, or without the headers its
+    /// profile requires.
+    ///
+    /// The identical-payload-digest profile requires a WARC-Payload-Digest header.
+    fn check_revisit_profile(&self) -> Result<(), WarcError> {
+        if self.record_type != RecordType::Revisit {
+            return Ok(());
+        }
+
+        let required: &[WarcHeader] = match self.revisit_profile() {
+            None => return Err(WarcError::MissingHeader(WarcHeader::Profile)),
+            Some(RevisitProfile::IdenticalPayloadDigest) => &[WarcHeader::PayloadDigest],
+            Some(_) => &[],
+        };
+        match required
+            .iter()
+            .find(|header| !self.headers.as_ref().contains_key(header))
+        {
+            Some(header) => Err(WarcError::MissingHeader(header.clone())),
+            None => Ok(()),
+        }
+    }
+
     /// Return the WARC header requested if present in this record, or `None`.
+    ///
+    /// Only the first value of a repeated header is returned; `headers_all` returns every value.
     pub fn header(&self, header: WarcHeader) -> Option<Cow<'_, str>> {
         match &header {
             WarcHeader::ContentLength => {
@@ -335,9 +833,11 @@ impl<T: BodyKind> Record<T> {
             }
             WarcHeader::RecordID => Some(Cow::Borrowed(self.warc_id())),
             WarcHeader::WarcType => Some(Cow::Owned(self.record_type.to_string())),
-            WarcHeader::Date => Some(Cow::Owned(
-                self.date().to_rfc3339_opts(SecondsFormat::Secs, true),
-            )),
+            WarcHeader::Date => Some(Cow::Owned(self.formatted_date())),
+            WarcHeader::Truncated => self
+                .truncated_type
+                .as_ref()
+                .map(|truncated_type| Cow::Owned(truncated_type.to_string())),
             _ => self
                 .headers
                 .as_ref()
@@ -346,8 +846,44 @@ impl<T: BodyKind> Record<T> {
         }
     }
 
+    /// Return every value of the WARC header requested, in order, or none if it is absent.
+    pub fn headers_all(&self, header: WarcHeader) -> Vec<Cow<'_, str>> {
+        match &header {
+            WarcHeader::ContentLength
+            | WarcHeader::RecordID
+            | WarcHeader::WarcType
+            | WarcHeader::Date
+            | WarcHeader::Truncated => self.header(header).into_iter().collect(),
+            _ => self
+                .headers
+                .as_ref()
+                .get_all(&header)
+                .map(|h| String::from_utf8_lossy(h))
+                .collect(),
+        }
+    }
+
+    /// Return the value of a header parsed as its type, such as `typed_header::TargetUri`.
+    ///
+    /// `None` is returned if the header is absent, or its value is not well-formed; the value
+    /// is then still available as a string from `header`.
+    pub fn get<H: TypedHeader>(&self) -> Option<H> {
+        self.header(H::header()).and_then(|value| H::parse(&value))
+    }
+
+    /// Set a header from its typed value.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as for `set_header`.
+    pub fn set<H: TypedHeader>(&mut self, value: &H) -> Result<(), WarcError> {
+        self.set_header(H::header(), value.format()).map(|_| ())
+    }
+
     /// Set a WARC header in this record, returning the previous value if present.
     ///
+    /// Every value of a repeated header is replaced; `add_header` adds a value instead.
+    ///
     /// # Errors
     ///
     /// If setting a header whose value has a well-formedness test, an error is returned if the
@@ -363,13 +899,12 @@ impl<T: BodyKind> Record<T> {
         let value = value.into();
         match &header {
             WarcHeader::Date => {
-                let old_date = std::mem::replace(
-                    &mut self.record_date,
-                    Record::<T>::parse_record_date(&value)?,
-                );
-                Ok(Some(Cow::Owned(
-                    old_date.to_rfc3339_opts(SecondsFormat::Secs, true),
-                )))
+                let old_date = self.formatted_date();
+                self.record_date = Record::<T>::parse_record_date(&value)?;
+                self.date_precision = self
+                    .version()
+                    .date_precision(Record::<T>::parse_date_precision(&value));
+                Ok(Some(Cow::Owned(old_date)))
             }
             WarcHeader::RecordID => {
                 let old_id = std::mem::replace(&mut self.record_id, value);
@@ -382,7 +917,7 @@ impl<T: BodyKind> Record<T> {
             WarcHeader::Truncated => {
                 let old_type = self.truncated_type.take();
                 self.truncated_type = Some(TruncatedType::from(&value));
-                Ok(old_type.map(|old| (Cow::Owned(old.to_string()))))
+                Ok(old_type.map(|old| Cow::Owned(old.to_string())))
             }
             WarcHeader::ContentLength => {
                 if Record::<T>::parse_content_length(&value)? != self.body.content_length() {
@@ -402,34 +937,207 @@ impl<T: BodyKind> Record<T> {
         }
     }
 
+    /// Add a value of a repeatable WARC header, such as WARC-Concurrent-To, after any it
+    /// already has.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the header may only appear once.
+    pub fn add_header<V>(&mut self, header: WarcHeader, value: V) -> Result<(), WarcError>
+    where
+        V: Into<String>,
+    {
+        if !header.is_repeatable() {
+            return Err(WarcError::MalformedHeader(
+                header,
+                "header may not be repeated".to_string(),
+            ));
+        }
+        self.headers
+            .as_mut()
+            .append(header, value.into().into_bytes());
+
+        Ok(())
+    }
+
+    /// Remove a header stored in the header map, returning its value if present.
+    pub(crate) fn remove_header(&mut self, header: &WarcHeader) -> Option<Vec<u8>> {
+        self.headers.as_mut().remove(header)
+    }
+
     /// Return the Content-Length header for this record.
     ///
     /// This value is guaranteed to match the actual length of the body.
     pub fn content_length(&self) -> u64 {
         self.body.content_length()
     }
-}
 
-impl Record<EmptyBody> {
-    /// Add a known body to this record, transforming it into a buffered body record.
-    pub fn add_body<B: Into<Vec<u8>>>(self, body: B) -> Record<BufferedBody> {
-        let Self {
+    /// Return the number of bytes this record occupies when written by a `WarcWriter`.
+    ///
+    /// This includes the version line, the header block, the body, and the record separator, and
+    /// is computed without serializing the record.
+    pub fn serialized_len(&self) -> u64 {
+        // "WARC/" + version + CRLF
+        let mut len = 5 + self.headers.version.len() as u64 + 2;
+        let mut add_header = |name: &WarcHeader, value_len: usize| {
+            // name + ": " + value + CRLF
+            len += name.to_string().len() as u64 + 2 + value_len as u64 + 2;
+        };
+
+        for (name, value) in self.headers.as_ref() {
+            add_header(name, value.len());
+        }
+        add_header(
+            &WarcHeader::ContentLength,
+            self.content_length().to_string().len(),
+        );
+        add_header(&WarcHeader::WarcType, self.record_type.to_string().len());
+        add_header(&WarcHeader::RecordID, self.record_id.len());
+        add_header(&WarcHeader::Date, self.formatted_date().len());
+        if let Some(ref truncated_type) = self.truncated_type {
+            add_header(&WarcHeader::Truncated, truncated_type.to_string().len());
+        }
+
+        // CRLF + body + CRLF CRLF
+        len + 2 + self.content_length() + 4
+    }
+
+    /// Return the number of bytes this record occupied in the stream it was read from.
+    ///
+    /// This is `None` for records which were not read by a `WarcReader`. For records read from a
+    /// compressed stream, this is the length of the decompressed data.
+    pub fn stored_len(&self) -> Option<u64> {
+        self.stored_len
+    }
+
+    pub(crate) fn set_stored_len(&mut self, len: u64) {
+        self.stored_len = Some(len);
+    }
+
+    /// Return the spellings of the header names in the stream this record was read from, where
+    /// they differ from the lowercase names.
+    pub(crate) fn header_names(&self) -> &HashMap<WarcHeader, String> {
+        &self.header_names
+    }
+
+    pub(crate) fn set_header_names(&mut self, names: HashMap<WarcHeader, String>) {
+        self.header_names = names;
+    }
+
+    /// Return the header block of this record as it was read, before any header was normalized
+    /// or repaired.
+    ///
+    /// The header block is only kept for records read with a `ParserConfig` in forensic mode.
+    pub fn raw_headers(&self) -> Option<&RawRecordHeader> {
+        self.raw_headers.as_ref()
+    }
+
+    /// Return the complete header block of this record, including the headers stored outside the
+    /// header map.
+    ///
+    /// WARC-Date is formatted with the given precision, or else the precision of the record.
+    pub(crate) fn header_block(
+        &self,
+        precision: Option<DatePrecision>,
+    ) -> Result<RawRecordHeader, WarcError> {
+        let precision = match precision {
+            Some(precision) if precision.is_subsecond() => {
+                if !self.version().allows_subsecond_dates() {
+                    return Err(WarcError::MalformedHeader(
+                        WarcHeader::Date,
+                        "sub-second precision requires WARC/1.1 or later".to_string(),
+                    ));
+                }
+                precision
+            }
+            Some(precision) => precision,
+            None => self.date_precision,
+        };
+
+        let mut headers = self.headers.clone();
+        let insert1 = headers.as_mut().insert(
+            WarcHeader::ContentLength,
+            format!("{}", self.content_length()).into(),
+        );
+        let insert2 = headers
+            .as_mut()
+            .insert(WarcHeader::WarcType, self.record_type.to_string().into());
+        let insert3 = headers
+            .as_mut()
+            .insert(WarcHeader::RecordID, self.record_id.clone().into());
+        let insert4 = if let Some(ref truncated_type) = self.truncated_type {
+            headers
+                .as_mut()
+                .insert(WarcHeader::Truncated, truncated_type.to_string().into())
+        } else {
+            None
+        };
+        let insert5 = headers.as_mut().insert(
+            WarcHeader::Date,
+            self.record_date
+                .to_rfc3339_opts(precision.into(), true)
+                .into(),
+        );
+
+        headers.as_mut().sort_by_order(&self.header_order);
+
+        debug_assert!(
+            insert1.is_none()
+                && insert2.is_none()
+                && insert3.is_none()
+                && insert4.is_none()
+                && insert5.is_none(),
+            "invariant violation: raw struct contains externally stored fields"
+        );
+
+        Ok(headers)
+    }
+
+    /// Replace the body of this record with one of a different kind.
+    fn map_body<U: BodyKind, F: FnOnce(T) -> U>(self, f: F) -> Record<U> {
+        let Record {
             headers,
             record_date,
+            date_precision,
             record_id,
             record_type,
             truncated_type,
-            body: _,
+            warnings,
+            stored_len,
+            header_names,
+            header_order,
+            raw_headers,
+            body,
         } = self;
+
         Record {
             headers,
             record_date,
+            date_precision,
             record_id,
             record_type,
             truncated_type,
-            body: BufferedBody(body.into()),
+            warnings,
+            stored_len,
+            header_names,
+            header_order,
+            raw_headers,
+            body: f(body),
         }
     }
+}
+
+impl Record<EmptyBody> {
+    /// Add a known body to this record, transforming it into a buffered body record.
+    pub fn add_body<B: Into<Vec<u8>>>(self, body: B) -> Record<BufferedBody> {
+        self.map_body(move |_| BufferedBody(body.into()))
+    }
+
+    /// Add a `Bytes` body to this record.
+    #[cfg(feature = "bytes")]
+    pub fn add_bytes_body(self, body: bytes::Bytes) -> Record<BytesBody> {
+        self.map_body(move |_| BytesBody(body))
+    }
 
     /// Add a streaming body to this record, whose expected size may not match the actual stream
     /// length.
@@ -438,50 +1146,14 @@ impl Record<EmptyBody> {
         stream: &'r mut R,
         len: &'r mut u64,
     ) -> std::io::Result<Record<StreamingBody<'r, R>>> {
-        let Record {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            ..
-        } = self;
-
-        Ok(Record {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            body: StreamingBody::new(stream, len),
-        })
+        Ok(self.map_body(move |_| StreamingBody::new(stream, len)))
     }
 }
 
 impl Record<BufferedBody> {
     /// Strip the body from this record.
     pub fn strip_body(self) -> Record<EmptyBody> {
-        let Self {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            body: _,
-        } = self;
-        Record {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            body: EmptyBody(),
-        }
-    }
-
-    /// Return the body of this record.
-    pub fn body(&self) -> &[u8] {
-        self.body.0.as_slice()
+        self.map_body(|_| EmptyBody())
     }
 
     /// Return a reference to mutate the body of this record, but without changing its length.
@@ -499,48 +1171,289 @@ impl Record<BufferedBody> {
 
     /// Transform this record into a raw record containing the same data.
     pub fn into_raw_parts(self) -> (RawRecordHeader, Vec<u8>) {
-        let Record {
-            mut headers,
-            record_date,
-            record_id,
-            record_type,
-            body,
-            ..
-        } = self;
-        let insert1 = headers.as_mut().insert(
-            WarcHeader::ContentLength,
-            format!("{}", body.0.len()).into(),
-        );
-        let insert2 = headers
-            .as_mut()
-            .insert(WarcHeader::WarcType, record_type.to_string().into());
-        let insert3 = headers
-            .as_mut()
-            .insert(WarcHeader::RecordID, record_id.into());
-        let insert4 = if let Some(ref truncated_type) = self.truncated_type {
-            headers
-                .as_mut()
-                .insert(WarcHeader::Truncated, truncated_type.to_string().into())
+        let headers = self.header_block(None).unwrap();
+
+        (headers, self.body.0)
+    }
+
+    /// Decode a block stored with a Content-Encoding header of `gzip`, replacing the body with
+    /// the decoded block and removing the header.
+    ///
+    /// True is returned if the block was decoded. Records without the header are left unchanged.
+    #[cfg(feature = "gzip")]
+    pub fn decode_block(&mut self) -> Result<bool, WarcError> {
+        let header = WarcHeader::from("Content-Encoding");
+        let encoding = match self.header(header.clone()) {
+            Some(encoding) => encoding.trim().to_ascii_lowercase(),
+            None => return Ok(false),
+        };
+        if encoding != "gzip" && encoding != "x-gzip" {
+            return Err(WarcError::MalformedHeader(header, encoding));
+        }
+
+        let mut block = Vec::new();
+        libflate::gzip::Decoder::new(self.body.0.as_slice())
+            .and_then(|mut decoder| decoder.read_to_end(&mut block))
+            .map_err(|e| WarcError::MalformedBody(e.to_string()))?;
+        self.remove_header(&header);
+        self.replace_body(block);
+
+        Ok(true)
+    }
+
+    /// Transform this record into one whose body is shared by its clones.
+    pub fn into_shared(self) -> Record<SharedBody> {
+        self.map_body(|body| SharedBody(body.0.into()))
+    }
+
+    /// Transform this record into one whose body is a `Bytes` buffer, without copying the body.
+    #[cfg(feature = "bytes")]
+    pub fn into_bytes(self) -> Record<BytesBody> {
+        self.map_body(|body| BytesBody(body.0.into()))
+    }
+}
+
+impl<T: BodyKind + AsRef<[u8]>> Record<T> {
+    /// Return the body of this record.
+    pub fn body(&self) -> &[u8] {
+        self.body.as_ref()
+    }
+
+    /// Return the payload of this record, which is the body of an HTTP message, or else the whole
+    /// block.
+    ///
+    /// `None` is returned if records of this type have no payload, or an HTTP message has no
+    /// complete head.
+    pub(crate) fn payload(&self) -> Option<&[u8]> {
+        if !self.record_type.has_payload() {
+            return None;
+        }
+        let block = self.body.as_ref();
+        let is_http = self
+            .headers
+            .as_ref()
+            .get(&WarcHeader::ContentType)
+            .is_some_and(|value| http::is_http_message(&String::from_utf8_lossy(value)));
+        if is_http {
+            http::split_head(block).map(|(_, payload)| payload)
         } else {
-            None
+            Some(block)
+        }
+    }
+
+    /// Parse the head of the HTTP message held by this record, splitting its start line and
+    /// headers.
+    ///
+    /// `None` is returned unless this is a request, response or revisit record whose Content-Type
+    /// is `application/http` and whose body starts with a complete HTTP message head.
+    pub fn http_headers(&self) -> Option<HttpHead> {
+        HttpHead::parse(self.http_message()?)
+    }
+
+    /// Return the payload of the HTTP message held by this record: the body following its head,
+    /// as stored, with any transfer and content encodings left in place.
+    ///
+    /// `None` is returned in the same cases as for `http_headers`.
+    pub fn http_payload(&self) -> Option<&[u8]> {
+        http::split_head(self.http_message()?).map(|(_, payload)| payload)
+    }
+
+    /// Start building a revisit record of this record, with the given profile.
+    ///
+    /// The builder is given the version, WARC-Target-URI, Content-Type and WARC-Payload-Digest of
+    /// this record, and refers to it by its ID; under WARC/1.1, also by its target URI and date.
+    /// With the identical-payload-digest profile, the body is the HTTP head of this record, or
+    /// empty if it holds no HTTP message; with other profiles, the body is left for the caller to
+    /// set, typically to the HTTP head of the response which prompted the revisit.
+    ///
+    /// Building fails if the profile requires a WARC-Payload-Digest and this record has none.
+    pub fn revisit(&self, profile: RevisitProfile) -> RecordBuilder {
+        let version = self.headers.version.clone();
+        let mut refers_to = RefersTo::record_id(self.record_id.clone());
+        if self
+            .version()
+            .defines_header(&WarcHeader::RefersToTargetURI)
+        {
+            if let Some(target_uri) = self.header(WarcHeader::TargetURI) {
+                refers_to = refers_to.capture(target_uri, self.record_date);
+            }
+        }
+        let head = match profile {
+            RevisitProfile::IdenticalPayloadDigest => self
+                .http_message()
+                .and_then(http::split_head)
+                .map(|(head, _)| head.to_vec())
+                .unwrap_or_default(),
+            _ => Vec::new(),
         };
-        let insert5 = headers.as_mut().insert(
-            WarcHeader::Date,
-            record_date
-                .to_rfc3339_opts(SecondsFormat::Secs, true)
-                .into(),
-        );
 
-        debug_assert!(
-            insert1.is_none()
-                && insert2.is_none()
-                && insert3.is_none()
-                && insert4.is_none()
-                && insert5.is_none(),
-            "invariant violation: raw struct contains externally stored fields"
-        );
+        let mut builder = RecordBuilder::default()
+            .version(version)
+            .warc_type(RecordType::Revisit)
+            .revisit_profile(profile)
+            .refers_to(refers_to)
+            .body(head);
+        for header in [
+            WarcHeader::TargetURI,
+            WarcHeader::ContentType,
+            WarcHeader::PayloadDigest,
+        ] {
+            if let Some(value) = self.headers.as_ref().get(&header) {
+                builder = builder.header(header, value.clone());
+            }
+        }
+
+        builder
+    }
+
+    /// Return the body of this record if it holds an HTTP message.
+    fn http_message(&self) -> Option<&[u8]> {
+        let is_http = matches!(
+            self.record_type,
+            RecordType::Request | RecordType::Response | RecordType::Revisit
+        ) && self
+            .headers
+            .as_ref()
+            .get(&WarcHeader::ContentType)
+            .is_some_and(|value| http::is_http_message(&String::from_utf8_lossy(value)));
+
+        if is_http {
+            Some(self.body.as_ref())
+        } else {
+            None
+        }
+    }
+
+    /// Check the digest headers of a truncated record against the content stored.
+    ///
+    /// The digests of a truncated record must cover the content as stored. A warning is returned
+    /// for each digest header which does not match, since it presumably covers the original
+    /// content. Digests which cannot be parsed are not checked, and records which are not
+    /// truncated return no warnings.
+    #[cfg(feature = "digest")]
+    pub fn check_truncated_digests(&self) -> Vec<Warning> {
+        use crate::digest::Digest;
+
+        if !self.is_truncated() {
+            return Vec::new();
+        }
+        let content = [
+            (WarcHeader::BlockDigest, Some(self.body.as_ref())),
+            (WarcHeader::PayloadDigest, self.payload()),
+        ];
+
+        content
+            .iter()
+            .filter_map(|(header, data)| {
+                let digest = Digest::parse(&self.header(header.clone())?)?;
+                if digest.matches((*data)?) {
+                    None
+                } else {
+                    Some(Warning::TruncatedDigest(header.clone()))
+                }
+            })
+            .collect()
+    }
+
+    /// Compute the digest of the block, and of the payload for records which have one, and set
+    /// the WARC-Block-Digest and WARC-Payload-Digest headers to them.
+    ///
+    /// The payload digest of a revisit record is that of the record it revisits, and is left
+    /// unchanged.
+    #[cfg(feature = "digest")]
+    pub fn compute_digests(&mut self, algorithm: crate::digest::Algorithm) {
+        let block_digest = algorithm.compute(self.body.as_ref()).to_string();
+        let payload_digest = self
+            .payload()
+            .map(|payload| algorithm.compute(payload).to_string());
+        let headers = self.headers.as_mut();
+        headers.insert(WarcHeader::BlockDigest, block_digest.into_bytes());
+        if let Some(digest) = payload_digest {
+            headers.insert(WarcHeader::PayloadDigest, digest.into_bytes());
+        }
+    }
+
+    /// Check the WARC-Block-Digest and WARC-Payload-Digest headers against the content of this
+    /// record, for fixity checks.
+    ///
+    /// Digest headers which are absent are not checked, nor is the payload digest of a revisit
+    /// record.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedHeader` for the first digest header which does not match, or
+    /// whose algorithm is not supported.
+    #[cfg(feature = "digest")]
+    pub fn verify_digests(&self) -> Result<(), WarcError> {
+        use crate::digest::Digest;
+
+        let content = [
+            (WarcHeader::BlockDigest, Some(self.body.as_ref())),
+            (WarcHeader::PayloadDigest, self.payload()),
+        ];
+        for (header, data) in content.iter() {
+            let (value, data) = match (self.header(header.clone()), data) {
+                (Some(value), Some(data)) => (value, data),
+                _ => continue,
+            };
+            match Digest::parse(&value) {
+                Some(digest) if digest.matches(data) => {}
+                Some(_) => {
+                    return Err(WarcError::MalformedHeader(
+                        header.clone(),
+                        "digest does not match the content".to_string(),
+                    ))
+                }
+                None => {
+                    return Err(WarcError::MalformedHeader(
+                        header.clone(),
+                        "unsupported digest".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Identify the media type of the payload of this record from its content, and set the
+    /// WARC-Identified-Payload-Type header to it.
+    ///
+    /// The payload of an HTTP message is its body. The media type is returned if one was
+    /// identified; the header is left unchanged otherwise, or if records of this type have no
+    /// payload.
+    pub fn identify_payload_type(&mut self) -> Option<&'static str> {
+        let media_type = sniff::media_type(self.payload()?)?;
+        self.headers
+            .as_mut()
+            .insert(WarcHeader::IdentifiedPayloadType, media_type.into());
+        Some(media_type)
+    }
+}
+
+impl Record<SharedBody> {
+    /// Return a new reference to the body of this record.
+    pub fn shared_body(&self) -> Arc<[u8]> {
+        Arc::clone(&self.body.0)
+    }
+
+    /// Replace the body of this record with the given body.
+    pub fn replace_body<V: Into<Arc<[u8]>>>(&mut self, new_body: V) {
+        self.body = SharedBody(new_body.into());
+    }
+
+    /// Transform this record into a raw record containing the same data.
+    ///
+    /// The body is not copied.
+    pub fn into_raw_parts(self) -> (RawRecordHeader, Arc<[u8]>) {
+        let headers = self.header_block(None).unwrap();
+
+        (headers, self.body.0)
+    }
 
-        (headers, body.0)
+    /// Transform this record into one which owns a copy of its body.
+    pub fn into_buffered(self) -> Record<BufferedBody> {
+        self.map_body(|body| BufferedBody(body.0.to_vec()))
     }
 }
 
@@ -551,32 +1464,30 @@ impl<'t, T: Read + 't> Record<StreamingBody<'t, T>> {
     ///
     /// This method can fail if the underlying stream returns an error. If this happens, the
     /// state of the stream is not guaranteed.
-    pub fn into_buffered(self) -> std::io::Result<Record<BufferedBody>> {
-        let Record {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            mut body,
-        } = self;
+    pub fn into_buffered(mut self) -> std::io::Result<Record<BufferedBody>> {
+        let mut buf = Vec::with_capacity(self.body.remaining().min(1_048_576) as usize);
+        self.body.read_to_end(&mut buf)?;
 
-        let buf = {
-            let mut body_vec = Vec::with_capacity(body.len() as usize);
-            body.read_to_end(&mut body_vec)?;
-            body_vec
-        };
+        Ok(self.map_body(move |_| BufferedBody(buf)))
+    }
 
-        let empty_record = Record {
-            headers,
-            record_date,
-            record_id,
-            record_type,
-            truncated_type,
-            ..Default::default()
-        };
+    /// Return the body, to be read in place.
+    ///
+    /// The Content-Length of the record is not changed by reading the body; use
+    /// `StreamingBody::remaining` for the number of bytes left to read.
+    pub fn body_mut(&mut self) -> &mut StreamingBody<'t, T> {
+        &mut self.body
+    }
+
+    /// Split this record into its header and its streaming body.
+    pub fn into_parts(self) -> (Record<EmptyBody>, StreamingBody<'t, T>) {
+        let mut body = None;
+        let record = self.map_body(|b| {
+            body = Some(b);
+            EmptyBody()
+        });
 
-        Ok(empty_record.add_body(buf))
+        (record, body.unwrap())
     }
 }
 
@@ -584,38 +1495,92 @@ impl Default for Record<BufferedBody> {
     fn default() -> Record<BufferedBody> {
         Record {
             headers: RawRecordHeader {
-                version: "WARC/1.0".to_string(),
-                headers: HashMap::new(),
+                version: WarcVersion::default().into(),
+                headers: HeaderMap::new(),
             },
             record_date: Utc::now(),
+            date_precision: DatePrecision::Seconds,
             record_id: Record::<BufferedBody>::generate_record_id(),
             record_type: RecordType::Resource,
             truncated_type: None,
+            warnings: Vec::new(),
+            stored_len: None,
+            header_names: HashMap::new(),
+            header_order: Vec::new(),
+            raw_headers: None,
             body: BufferedBody(vec![]),
         }
     }
 }
 
-impl Default for Record<EmptyBody> {
-    fn default() -> Record<EmptyBody> {
-        Record {
-            headers: RawRecordHeader {
-                version: "WARC/1.0".to_string(),
-                headers: HashMap::new(),
-            },
-            record_date: Utc::now(),
-            record_id: Record::<EmptyBody>::generate_record_id(),
-            record_type: RecordType::Resource,
-            truncated_type: None,
-            body: EmptyBody(),
-        }
+impl Default for Record<EmptyBody> {
+    fn default() -> Record<EmptyBody> {
+        Record {
+            headers: RawRecordHeader {
+                version: WarcVersion::default().into(),
+                headers: HeaderMap::new(),
+            },
+            record_date: Utc::now(),
+            date_precision: DatePrecision::Seconds,
+            record_id: Record::<EmptyBody>::generate_record_id(),
+            record_type: RecordType::Resource,
+            truncated_type: None,
+            warnings: Vec::new(),
+            stored_len: None,
+            header_names: HashMap::new(),
+            header_order: Vec::new(),
+            raw_headers: None,
+            body: EmptyBody(),
+        }
+    }
+}
+
+impl fmt::Display for Record<BufferedBody> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let headers = self.header_block(None).unwrap();
+        write!(f, "Record({}, {:?})", headers, self.body())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Record<BytesBody> {
+    /// Return a new reference to the body of this record.
+    pub fn bytes(&self) -> bytes::Bytes {
+        self.body.0.clone()
+    }
+
+    /// Replace the body of this record with the given body.
+    pub fn replace_body<V: Into<bytes::Bytes>>(&mut self, new_body: V) {
+        self.body = BytesBody(new_body.into());
+    }
+
+    /// Transform this record into a raw record containing the same data.
+    ///
+    /// The body is not copied.
+    pub fn into_raw_parts(self) -> (RawRecordHeader, bytes::Bytes) {
+        let headers = self.header_block(None).unwrap();
+
+        (headers, self.body.0)
+    }
+
+    /// Transform this record into one which owns its body, copying it only if it is shared.
+    pub fn into_buffered(self) -> Record<BufferedBody> {
+        self.map_body(|body| BufferedBody(body.0.into()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl fmt::Display for Record<BytesBody> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let headers = self.header_block(None).unwrap();
+        write!(f, "Record({}, {:?})", headers, self.body())
     }
 }
 
-impl fmt::Display for Record<BufferedBody> {
+impl fmt::Display for Record<SharedBody> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let (headers, body) = self.clone().into_raw_parts();
-        write!(f, "Record({}, {:?})", headers, body)
+        let headers = self.header_block(None).unwrap();
+        write!(f, "Record({}, {:?})", headers, self.body())
     }
 }
 impl fmt::Display for Record<EmptyBody> {
@@ -624,27 +1589,20 @@ impl fmt::Display for Record<EmptyBody> {
     }
 }
 
-impl Clone for Record<EmptyBody> {
-    fn clone(&self) -> Self {
-        Record {
-            headers: self.headers.clone(),
-            record_type: self.record_type.clone(),
-            record_date: self.record_date,
-            record_id: self.record_id.clone(),
-            truncated_type: self.truncated_type.clone(),
-            body: self.body,
-        }
-    }
-}
-
-impl Clone for Record<BufferedBody> {
+impl<T: BodyKind + Clone> Clone for Record<T> {
     fn clone(&self) -> Self {
         Record {
             headers: self.headers.clone(),
             record_type: self.record_type.clone(),
             record_date: self.record_date,
+            date_precision: self.date_precision,
             record_id: self.record_id.clone(),
             truncated_type: self.truncated_type.clone(),
+            warnings: self.warnings.clone(),
+            stored_len: self.stored_len,
+            header_names: self.header_names.clone(),
+            header_order: self.header_order.clone(),
+            raw_headers: self.raw_headers.clone(),
             body: self.body.clone(),
         }
     }
@@ -686,6 +1644,15 @@ impl RecordBuilder {
         self
     }
 
+    /// Set the precision of the record date header of the record under construction.
+    pub fn date_precision(mut self, precision: DatePrecision) -> Self {
+        if let Err(e) = self.value.set_date_precision(precision) {
+            self.last_error = Some(e);
+        }
+
+        self
+    }
+
     /// Set the truncated type header of the record under construction.
     pub fn truncated_type(mut self, trunc_type: TruncatedType) -> Self {
         self.value.set_truncated_type(trunc_type);
@@ -693,6 +1660,40 @@ impl RecordBuilder {
         self
     }
 
+    /// Set the profile header of the record under construction.
+    ///
+    /// Building a revisit record fails if it has no profile, or lacks the headers its profile
+    /// requires.
+    pub fn revisit_profile(mut self, profile: RevisitProfile) -> Self {
+        self.value.set_revisit_profile(profile);
+
+        self
+    }
+
+    /// Set the WARC-Refers-To, WARC-Refers-To-Target-URI and WARC-Refers-To-Date headers of the
+    /// record under construction.
+    ///
+    /// Building fails if a target URI or date is given and the version is earlier than WARC/1.1.
+    pub fn refers_to(mut self, refers_to: RefersTo) -> Self {
+        if let Err(e) = self.value.set_refers_to(refers_to) {
+            self.last_error = Some(e);
+        }
+
+        self
+    }
+
+    /// Set the identified payload type header of the record under construction.
+    ///
+    /// Building fails if records of the type built have no payload.
+    pub fn identified_payload_type<S: Into<String>>(mut self, media_type: S) -> Self {
+        self.value
+            .headers
+            .as_mut()
+            .insert(WarcHeader::IdentifiedPayloadType, media_type.into().into());
+
+        self
+    }
+
     /// Create or replace an arbitrary header of the record under construction.
     pub fn header<V: Into<Vec<u8>>>(mut self, key: WarcHeader, value: V) -> Self {
         self.broken_headers.insert(key.clone(), value.into());
@@ -723,15 +1724,33 @@ impl RecordBuilder {
         self
     }
 
+    /// Compute the block and payload digests of the record under construction when it is built,
+    /// as `Record::compute_digests` does.
+    #[cfg(feature = "digest")]
+    pub fn compute_digests(mut self, algorithm: crate::digest::Algorithm) -> Self {
+        self.digests = Some(algorithm);
+
+        self
+    }
+
     /// Build a raw record header from the data collected in this builder.
     ///
     /// A body set in this builder will be returned raw.
     pub fn build_raw(self) -> (RawRecordHeader, Vec<u8>) {
         let RecordBuilder {
-            value,
+            #[allow(unused_mut)]
+            mut value,
             broken_headers,
+            #[cfg(feature = "digest")]
+            digests,
             ..
         } = self;
+        #[cfg(feature = "digest")]
+        {
+            if let Some(algorithm) = digests {
+                value.compute_digests(algorithm);
+            }
+        }
         let (mut headers, body) = value.into_raw_parts();
         headers.as_mut().extend(broken_headers);
 
@@ -741,13 +1760,27 @@ impl RecordBuilder {
     /// Build a record from the data collected in this builder.
     pub fn build(self) -> Result<Record<BufferedBody>, WarcError> {
         let RecordBuilder {
-            value,
+            #[allow(unused_mut)]
+            mut value,
             broken_headers,
             last_error,
+            #[cfg(feature = "digest")]
+            digests,
         } = self;
+        #[cfg(feature = "digest")]
+        {
+            if let Some(algorithm) = digests {
+                value.compute_digests(algorithm);
+            }
+        }
 
         if let Some(e) = last_error {
             Err(e)
+        } else if let Err(e) = value
+            .check_identified_payload_type()
+            .and_then(|_| value.check_revisit_profile())
+        {
+            Err(e)
         } else {
             debug_assert!(
                 broken_headers.is_empty(),
@@ -761,7 +1794,7 @@ impl RecordBuilder {
 #[cfg(test)]
 mod record_tests {
     use crate::header::WarcHeader;
-    use crate::{BufferedBody, Record, RecordType};
+    use crate::{BufferedBody, DatePrecision, Record, RecordType, WarcVersion};
 
     use chrono::prelude::*;
 
@@ -773,7 +1806,8 @@ mod record_tests {
         std::thread::sleep(std::time::Duration::from_millis(10));
         let after = Utc::now();
         assert_eq!(record.content_length(), 0);
-        assert_eq!(record.warc_version(), "WARC/1.0");
+        assert_eq!(record.warc_version(), "1.0");
+        assert_eq!(record.version(), WarcVersion::V1_0);
         assert_eq!(record.warc_type(), &RecordType::Resource);
         assert!(record.date() > &before);
         assert!(record.date() < &after);
@@ -790,7 +1824,7 @@ mod record_tests {
     fn body() {
         let mut record = Record::<BufferedBody>::default();
         assert_eq!(record.content_length(), 0);
-        assert_eq!(record.body(), &[]);
+        assert_eq!(record.body(), b"");
         record.replace_body(b"hello!!".to_vec());
         assert_eq!(record.content_length(), 7);
         assert_eq!(record.body(), b"hello!!");
@@ -824,6 +1858,38 @@ mod record_tests {
         );
     }
 
+    #[test]
+    fn repeated_headers() {
+        let mut record = Record::<BufferedBody>::default();
+        assert!(record.headers_all(WarcHeader::ConcurrentTo).is_empty());
+        record
+            .add_header(WarcHeader::ConcurrentTo, "<urn:uuid:1>")
+            .unwrap();
+        record
+            .add_header(WarcHeader::ConcurrentTo, "<urn:uuid:2>")
+            .unwrap();
+        assert_eq!(
+            record.headers_all(WarcHeader::ConcurrentTo),
+            vec!["<urn:uuid:1>", "<urn:uuid:2>"]
+        );
+        assert_eq!(
+            record.header(WarcHeader::ConcurrentTo).unwrap(),
+            "<urn:uuid:1>"
+        );
+        assert_eq!(record.headers_all(WarcHeader::WarcType), vec!["resource"]);
+        assert!(record
+            .add_header(WarcHeader::TargetURI, "https://docs.rs")
+            .is_err());
+
+        record
+            .set_header(WarcHeader::ConcurrentTo, "<urn:uuid:3>")
+            .unwrap();
+        assert_eq!(
+            record.headers_all(WarcHeader::ConcurrentTo),
+            vec!["<urn:uuid:3>"]
+        );
+    }
+
     #[test]
     fn set_header_override_content_length() {
         let mut record = Record::<BufferedBody>::default();
@@ -894,21 +1960,195 @@ mod record_tests {
         );
         assert_eq!(record.header(WarcHeader::WarcType).unwrap(), "revisit");
     }
+
+    #[test]
+    fn date_precision() {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_date(
+            Utc.with_ymd_and_hms(2020, 7, 21, 22, 0, 0).unwrap()
+                + chrono::Duration::milliseconds(123),
+        );
+        assert_eq!(record.date_precision(), DatePrecision::Seconds);
+        assert!(record.set_date_precision(DatePrecision::Millis).is_err());
+        assert_eq!(
+            record.header(WarcHeader::Date).unwrap(),
+            "2020-07-21T22:00:00Z"
+        );
+
+        record.set_warc_version("1.1");
+        record.set_date_precision(DatePrecision::Millis).unwrap();
+        assert_eq!(
+            record.header(WarcHeader::Date).unwrap(),
+            "2020-07-21T22:00:00.123Z"
+        );
+
+        record.set_warc_version("1.0");
+        assert_eq!(record.date_precision(), DatePrecision::Seconds);
+    }
+
+    #[test]
+    fn serialized_len() {
+        let mut record = Record::<BufferedBody>::default();
+        record.replace_body(b"hello!!".to_vec());
+        record
+            .set_header(WarcHeader::TargetURI, "https://www.rust-lang.org")
+            .unwrap();
+        record.set_truncated_type(crate::TruncatedType::Length);
+
+        let mut buffer = Vec::new();
+        let written = crate::WarcWriter::new(&mut buffer).write(&record).unwrap();
+        assert_eq!(written, buffer.len());
+        assert_eq!(record.serialized_len(), buffer.len() as u64);
+        assert_eq!(record.stored_len(), None);
+    }
+
+    #[test]
+    fn shared_body() {
+        let mut record = Record::<BufferedBody>::default();
+        record.replace_body(b"hello!!".to_vec());
+        let display = record.to_string();
+        let mut buffered = Vec::new();
+        crate::WarcWriter::new(&mut buffered)
+            .write(&record)
+            .unwrap();
+
+        let shared = record.clone().into_shared();
+        let copy = shared.clone();
+        assert!(std::sync::Arc::ptr_eq(
+            &shared.shared_body(),
+            &copy.shared_body()
+        ));
+        assert_eq!(shared.body(), b"hello!!");
+        assert_eq!(shared.content_length(), 7);
+        assert_eq!(shared.to_string(), display);
+
+        let mut written = Vec::new();
+        crate::WarcWriter::new(&mut written).write(&shared).unwrap();
+        assert_eq!(written, buffered);
+
+        let (headers, body) = copy.into_raw_parts();
+        assert_eq!(headers.as_ref()[&WarcHeader::ContentLength], b"7");
+        assert_eq!(&body[..], b"hello!!");
+        assert_eq!(shared.into_buffered(), record);
+    }
+
+    #[test]
+    fn truncated_type_round_trip() {
+        let mut record = Record::<BufferedBody>::default();
+        assert!(!record.is_truncated());
+        record
+            .set_header(WarcHeader::Truncated, "Robots-Exclusion")
+            .unwrap();
+        assert!(record.is_truncated());
+        assert_eq!(
+            record.header(WarcHeader::Truncated).unwrap(),
+            "Robots-Exclusion"
+        );
+        record.set_header(WarcHeader::Truncated, "LENGTH").unwrap();
+        assert_eq!(record.truncated_type(), &Some(crate::TruncatedType::Length));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn truncated_digests() {
+        use crate::digest::Algorithm;
+        use crate::Warning;
+
+        let mut record = Record::<BufferedBody>::default();
+        record.replace_body(&b"hel"[..]);
+        record
+            .set_header(
+                WarcHeader::BlockDigest,
+                Algorithm::Sha1.compute(b"hel").to_string(),
+            )
+            .unwrap();
+        record
+            .set_header(
+                WarcHeader::PayloadDigest,
+                Algorithm::Sha1.compute(b"hello").to_string(),
+            )
+            .unwrap();
+        assert!(record.check_truncated_digests().is_empty());
+
+        record.set_truncated_type(crate::TruncatedType::Length);
+        assert_eq!(
+            record.check_truncated_digests(),
+            vec![Warning::TruncatedDigest(WarcHeader::PayloadDigest)]
+        );
+    }
+
+    #[test]
+    fn http_message() {
+        let mut record = Record::<BufferedBody>::with_body(
+            &b"HTTP/1.1 301 Moved\r\nLocation: /new\r\n\r\nmoved"[..],
+        );
+        record.set_warc_type(RecordType::Response);
+        assert_eq!(record.http_headers(), None);
+
+        record
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        let head = record.http_headers().unwrap();
+        assert_eq!(head.status(), Some(301));
+        assert_eq!(head.header("location"), Some("/new"));
+        assert_eq!(record.http_payload(), Some(&b"moved"[..]));
+
+        record.set_warc_type(RecordType::Resource);
+        assert_eq!(record.http_payload(), None);
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn digests() {
+        use crate::digest::Algorithm;
+        use crate::{Error, RecordBuilder};
+
+        let body = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nhello".to_vec();
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::Response)
+            .header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .body(body.clone())
+            .compute_digests(Algorithm::Sha1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::BlockDigest).unwrap(),
+            Algorithm::Sha1.compute(&body).to_string()
+        );
+        assert_eq!(
+            record.header(WarcHeader::PayloadDigest).unwrap(),
+            "sha1:VL2MMHO4YXUKFWV63YHTWSBM3GXKSQ2N"
+        );
+        assert_eq!(record.verify_digests(), Ok(()));
+
+        record.replace_body(&b"HTTP/1.1 200 OK\r\n\r\nhullo"[..]);
+        assert!(matches!(
+            record.verify_digests(),
+            Err(Error::MalformedHeader(WarcHeader::BlockDigest, _))
+        ));
+        record.compute_digests(Algorithm::Sha256);
+        assert!(record
+            .header(WarcHeader::PayloadDigest)
+            .unwrap()
+            .starts_with("sha256:"));
+        assert_eq!(record.verify_digests(), Ok(()));
+    }
 }
 
 #[cfg(test)]
 mod raw_tests {
-    use crate::header::WarcHeader;
-    use crate::{EmptyBody, RawRecordHeader, Record, RecordType};
+    use crate::header::{HeaderMap, WarcHeader};
+    use crate::{
+        DatePrecision, EmptyBody, ParserConfig, RawRecordHeader, Record, RecordType, Warning,
+    };
 
-    use std::collections::HashMap;
     use std::convert::TryFrom;
 
     #[test]
     fn create() {
         let headers = RawRecordHeader {
             version: "WARC/1.0".to_owned(),
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
         };
 
         assert_eq!(headers.as_ref().len(), 0);
@@ -949,6 +2189,184 @@ mod raw_tests {
         assert!(Record::<EmptyBody>::try_from(headers).is_ok());
     }
 
+    #[test]
+    fn verify_subsecond_date() {
+        let headers = RawRecordHeader {
+            version: "1.1".to_owned(),
+            headers: vec![
+                (WarcHeader::WarcType, b"dunno".to_vec()),
+                (WarcHeader::ContentLength, b"5".to_vec()),
+                (
+                    WarcHeader::RecordID,
+                    b"<urn:test:basic-record:record-0>".to_vec(),
+                ),
+                (WarcHeader::Date, b"2020-07-08T02:52:55.123456Z".to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let record = Record::<EmptyBody>::try_from(headers).unwrap();
+        assert_eq!(record.date_precision(), DatePrecision::Micros);
+        assert_eq!(
+            record.header(WarcHeader::Date).unwrap(),
+            "2020-07-08T02:52:55.123456Z"
+        );
+    }
+
+    #[test]
+    fn verify_best_effort() {
+        let headers = RawRecordHeader {
+            version: "1.0".to_owned(),
+            headers: vec![
+                (WarcHeader::ContentLength, b"5".to_vec()),
+                (
+                    WarcHeader::RecordID,
+                    b"<urn:test:basic-record:record-0>".to_vec(),
+                ),
+                (WarcHeader::Date, b"yesterday".to_vec()),
+                (WarcHeader::TargetURI, b"http://example.com/".to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let record = Record::from_raw_best_effort(headers, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            record.warc_type(),
+            &RecordType::Unknown("unknown".to_owned())
+        );
+        assert_eq!(record.warc_id(), "<urn:test:basic-record:record-0>");
+        assert_eq!(
+            record.header(WarcHeader::TargetURI).unwrap(),
+            "http://example.com/"
+        );
+        let warnings = record.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0], Warning::MissingHeader(WarcHeader::WarcType));
+        assert!(matches!(
+            warnings[1],
+            Warning::NormalizedHeader {
+                header: WarcHeader::Date,
+                ref original,
+                ..
+            } if original == "yesterday"
+        ));
+    }
+
+    #[test]
+    fn verify_normalizers() {
+        use crate::HeaderNormalizer;
+
+        let headers = RawRecordHeader {
+            version: "1.0".to_owned(),
+            headers: vec![
+                (WarcHeader::WarcType, b"response".to_vec()),
+                (WarcHeader::ContentLength, b"5".to_vec()),
+                (
+                    WarcHeader::RecordID,
+                    b"<urn:test:basic-record:record-0>".to_vec(),
+                ),
+                (WarcHeader::Date, b"2020-07-08T02:52:55Z".to_vec()),
+                (WarcHeader::ConcurrentTo, b" <urn:test:a> ".to_vec()),
+                (WarcHeader::ConcurrentTo, b"<urn:test:b>\t".to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let config =
+            ParserConfig::new().normalize_header(WarcHeader::ConcurrentTo, HeaderNormalizer::Trim);
+
+        let record = Record::from_raw_with_config(headers.clone(), &config).unwrap();
+        assert_eq!(
+            record.headers_all(WarcHeader::ConcurrentTo),
+            vec!["<urn:test:a>", "<urn:test:b>"]
+        );
+        assert_eq!(record.warnings().len(), 2);
+        assert_eq!(record.raw_headers(), None);
+
+        let record =
+            Record::from_raw_with_config(headers.clone(), &config.clone().forensic(true)).unwrap();
+        assert_eq!(record.raw_headers(), Some(&headers));
+
+        let mut missing_date = headers.clone();
+        missing_date.as_mut().remove(&WarcHeader::Date);
+        let record =
+            Record::from_raw_best_effort(missing_date.clone(), &config.forensic(true)).unwrap();
+        assert_eq!(record.raw_headers(), Some(&missing_date));
+    }
+
+    #[test]
+    fn verify_lenient_dates() {
+        let raw_with_date = |date: &str| RawRecordHeader {
+            version: "1.0".to_owned(),
+            headers: vec![
+                (WarcHeader::WarcType, b"dunno".to_vec()),
+                (WarcHeader::ContentLength, b"5".to_vec()),
+                (
+                    WarcHeader::RecordID,
+                    b"<urn:test:basic-record:record-0>".to_vec(),
+                ),
+                (WarcHeader::Date, date.as_bytes().to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let lenient = ParserConfig::new().lenient_dates(true);
+
+        let record =
+            Record::from_raw_with_config(raw_with_date("2020-07-08T02:52:55Z"), &lenient).unwrap();
+        assert!(record.warnings().is_empty());
+
+        for date in &[
+            "2020-07-08T02:52:55",
+            "2020-07-08 02:52:55Z",
+            "2020-07-08T04:52:55+02:00",
+            "20200708025255",
+            "200708025255",
+            "20-07-08T02:52:55Z",
+        ] {
+            let record = Record::from_raw_with_config(raw_with_date(date), &lenient).unwrap();
+            assert_eq!(
+                record.header(WarcHeader::Date).unwrap(),
+                "2020-07-08T02:52:55Z"
+            );
+            assert_eq!(record.warnings().len(), 1);
+        }
+
+        let record =
+            Record::from_raw_with_config(raw_with_date("2020070802525"), &lenient).unwrap();
+        assert_eq!(
+            record.header(WarcHeader::Date).unwrap(),
+            "2020-07-08T02:52:50Z"
+        );
+        assert_eq!(record.warnings().len(), 1);
+
+        assert!(Record::from_raw_with_config(raw_with_date("yesterday"), &lenient).is_err());
+
+        for date in &["2020-07-08T02:52:55.123Z", "2020-07-08 02:52:55.123Z"] {
+            let record = Record::from_raw_with_config(raw_with_date(date), &lenient).unwrap();
+            assert_eq!(
+                record.header(WarcHeader::Date).unwrap(),
+                "2020-07-08T02:52:55Z"
+            );
+            match record.warnings() {
+                [Warning::NormalizedHeader { normalized, .. }] => {
+                    assert_eq!(normalized, "2020-07-08T02:52:55Z")
+                }
+                warnings => panic!("unexpected warnings: {:?}", warnings),
+            }
+
+            let mut raw = raw_with_date(date);
+            raw.version = "1.1".to_owned();
+            let record = Record::from_raw_with_config(raw, &lenient).unwrap();
+            assert_eq!(
+                record.header(WarcHeader::Date).unwrap(),
+                "2020-07-08T02:52:55.123Z"
+            );
+        }
+    }
+
     #[test]
     fn verify_missing_type() {
         let headers = RawRecordHeader {
@@ -1027,15 +2445,17 @@ mod raw_tests {
 mod builder_tests {
     use crate::header::WarcHeader;
     use crate::{
-        BufferedBody, EmptyBody, RawRecordHeader, Record, RecordBuilder, RecordType, TruncatedType,
+        BufferedBody, EmptyBody, RawRecordHeader, Record, RecordBuilder, RecordType, RefersTo,
+        RevisitProfile, TruncatedType,
     };
 
+    use chrono::{TimeZone, Utc};
     use std::convert::TryFrom;
 
     #[test]
     fn default() {
         let (headers, body) = RecordBuilder::default().build_raw();
-        assert_eq!(headers.version, "WARC/1.0".to_string());
+        assert_eq!(headers.version, "1.0".to_string());
         assert_eq!(
             headers.as_ref().get(&WarcHeader::ContentLength).unwrap(),
             &b"0".to_vec()
@@ -1052,7 +2472,7 @@ mod builder_tests {
         let (headers, body) = RecordBuilder::default()
             .body(b"abcdef".to_vec())
             .build_raw();
-        assert_eq!(headers.version, "WARC/1.0".to_string());
+        assert_eq!(headers.version, "1.0".to_string());
         assert_eq!(
             headers.as_ref().get(&WarcHeader::ContentLength).unwrap(),
             &b"6".to_vec()
@@ -1368,4 +2788,207 @@ mod builder_tests {
             &b"foreign-intervention"[..]
         );
     }
+
+    #[test]
+    fn verify_build_identified_payload_type() {
+        let record = RecordBuilder::default()
+            .warc_type(RecordType::Response)
+            .identified_payload_type("image/png")
+            .build()
+            .unwrap();
+        assert_eq!(record.identified_payload_type().unwrap(), "image/png");
+
+        let result = RecordBuilder::default()
+            .identified_payload_type("image/png")
+            .warc_type(RecordType::Metadata)
+            .build();
+        assert!(matches!(
+            result,
+            Err(crate::Error::MalformedHeader(
+                WarcHeader::IdentifiedPayloadType,
+                _
+            ))
+        ));
+
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::WarcInfo)
+            .build()
+            .unwrap();
+        let before = record.clone();
+        assert!(record.set_identified_payload_type("text/plain").is_err());
+        assert_eq!(record.identified_payload_type(), None);
+        assert_eq!(record, before);
+    }
+
+    #[test]
+    fn identify_payload_type() {
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::Response)
+            .header(
+                WarcHeader::ContentType,
+                "application/http; msgtype=response",
+            )
+            .body(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nGIF89a...".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(record.identify_payload_type(), Some("image/gif"));
+        assert_eq!(record.identified_payload_type().unwrap(), "image/gif");
+
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::Metadata)
+            .body(b"GIF89a...".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(record.identify_payload_type(), None);
+        assert_eq!(record.identified_payload_type(), None);
+    }
+
+    #[test]
+    fn verify_build_revisit_profile() {
+        let builder = RecordBuilder::default().warc_type(RecordType::Revisit);
+        assert!(matches!(
+            builder.clone().build(),
+            Err(crate::Error::MissingHeader(WarcHeader::Profile))
+        ));
+
+        let builder = builder.revisit_profile(RevisitProfile::IdenticalPayloadDigest);
+        assert!(matches!(
+            builder.clone().build(),
+            Err(crate::Error::MissingHeader(WarcHeader::PayloadDigest))
+        ));
+
+        let record = builder
+            .header(WarcHeader::PayloadDigest, "sha1:AAAA")
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::Profile).unwrap(),
+            "http://netpreserve.org/warc/1.0/revisit/identical-payload-digest"
+        );
+        assert_eq!(
+            record.revisit_profile(),
+            Some(RevisitProfile::IdenticalPayloadDigest)
+        );
+
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::Revisit)
+            .version("1.1".to_string())
+            .revisit_profile(RevisitProfile::ServerNotModified)
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::Profile).unwrap(),
+            "http://netpreserve.org/warc/1.1/revisit/server-not-modified"
+        );
+        record.set_revisit_profile(RevisitProfile::from("http://example.com/profile"));
+        assert_eq!(
+            record.revisit_profile(),
+            Some(RevisitProfile::Unknown(
+                "http://example.com/profile".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn revisit() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let original = RecordBuilder::default()
+            .version("1.1".to_string())
+            .warc_type(RecordType::Response)
+            .date(date)
+            .header(WarcHeader::TargetURI, "http://example.com/")
+            .header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .header(WarcHeader::PayloadDigest, "sha1:AAAA")
+            .body(b"HTTP/1.1 200 OK\r\nServer: test\r\n\r\npayload".to_vec())
+            .build()
+            .unwrap();
+
+        let revisit = original
+            .revisit(RevisitProfile::IdenticalPayloadDigest)
+            .build()
+            .unwrap();
+        assert_eq!(revisit.warc_type(), &RecordType::Revisit);
+        assert_eq!(
+            revisit.header(WarcHeader::Profile).unwrap(),
+            "http://netpreserve.org/warc/1.1/revisit/identical-payload-digest"
+        );
+        assert_eq!(revisit.body(), b"HTTP/1.1 200 OK\r\nServer: test\r\n\r\n");
+        assert_eq!(
+            revisit.header(WarcHeader::PayloadDigest).unwrap(),
+            "sha1:AAAA"
+        );
+        assert_eq!(
+            revisit.refers_to(),
+            Some(RefersTo::record_id(original.warc_id()).capture("http://example.com/", date))
+        );
+        assert_ne!(revisit.warc_id(), original.warc_id());
+
+        let mut original = original;
+        original.set_warc_version("1.0");
+        let revisit = original
+            .revisit(RevisitProfile::ServerNotModified)
+            .build()
+            .unwrap();
+        assert_eq!(revisit.body(), b"");
+        assert_eq!(
+            revisit.refers_to(),
+            Some(RefersTo::record_id(original.warc_id()))
+        );
+
+        let resource = RecordBuilder::default()
+            .warc_type(RecordType::Resource)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            resource
+                .revisit(RevisitProfile::IdenticalPayloadDigest)
+                .build(),
+            Err(crate::Error::MissingHeader(WarcHeader::PayloadDigest))
+        ));
+        assert!(matches!(
+            RecordBuilder::default()
+                .refers_to(RefersTo::record_id("<urn:test:a>").capture("http://example.com/", date))
+                .build(),
+            Err(crate::Error::MalformedHeader(
+                WarcHeader::RefersToTargetURI,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn refers_to() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let mut record = RecordBuilder::default()
+            .warc_type(RecordType::Conversion)
+            .build()
+            .unwrap();
+        assert_eq!(record.refers_to(), None);
+
+        let refers_to = RefersTo::record_id("<urn:test:original>");
+        record.set_refers_to(refers_to.clone()).unwrap();
+        assert_eq!(record.refers_to(), Some(refers_to));
+        let capture =
+            RefersTo::record_id("<urn:test:original>").capture("http://example.com/", date);
+        assert!(matches!(
+            record.set_refers_to(capture.clone()),
+            Err(crate::Error::MalformedHeader(
+                WarcHeader::RefersToTargetURI,
+                _
+            ))
+        ));
+        assert!(record.header(WarcHeader::RefersToTargetURI).is_none());
+
+        record.set_warc_version("1.1");
+        record.set_refers_to(capture.clone()).unwrap();
+        assert_eq!(record.refers_to(), Some(capture));
+        assert_eq!(
+            record.header(WarcHeader::RefersToDate).unwrap(),
+            "2020-01-02T03:04:05Z"
+        );
+
+        record.set_refers_to(RefersTo::default()).unwrap();
+        assert_eq!(record.refers_to(), None);
+        assert!(record.header(WarcHeader::RefersTo).is_none());
+    }
 }
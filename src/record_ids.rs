@@ -0,0 +1,237 @@
+//! Detection of duplicate and malformed record IDs across the files of a collection.
+//!
+//! Every WARC-Record-ID is meant to be globally unique, and readers resolve WARC-Refers-To and
+//! WARC-Concurrent-To by ID; when two records share an ID, one of them silently wins. A
+//! `RecordIdScan` is fed the records of every file of a collection in turn, and reports each ID
+//! seen more than once along with where both records are, as well as every ID which is not a URI
+//! enclosed in angle brackets as the standard requires.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Seek};
+
+use url::Url;
+
+use crate::{Collection, Error, ParserConfig, ReadPolicy, WarcReader};
+
+/// The location of a record within a collection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordLocation {
+    /// The name of the file holding the record.
+    pub file: String,
+    /// The offset of the record within the file.
+    pub offset: u64,
+}
+
+/// A record whose ID was already given to an earlier record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateId {
+    /// The shared WARC-Record-ID.
+    pub record_id: String,
+    /// The location of the first record with the ID.
+    pub first: RecordLocation,
+    /// The location of this record.
+    pub duplicate: RecordLocation,
+}
+
+/// A record whose ID is not well-formed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MalformedId {
+    /// The WARC-Record-ID as given.
+    pub record_id: String,
+    /// The location of the record.
+    pub location: RecordLocation,
+    /// Why the ID is not well-formed.
+    pub reason: String,
+}
+
+/// The findings of a scan.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordIdReport {
+    /// The number of records scanned.
+    pub records: u64,
+    /// The records with an ID given to an earlier record, in the order they were scanned.
+    pub duplicates: Vec<DuplicateId>,
+    /// The records with a malformed ID, in the order they were scanned.
+    pub malformed: Vec<MalformedId>,
+}
+
+impl RecordIdReport {
+    /// Return true if no duplicate or malformed IDs were found.
+    pub fn is_clean(&self) -> bool {
+        self.duplicates.is_empty() && self.malformed.is_empty()
+    }
+}
+
+/// A scan over the record IDs of one or more files.
+#[derive(Debug, Default)]
+pub struct RecordIdScan {
+    seen: HashMap<String, RecordLocation>,
+    report: RecordIdReport,
+}
+
+impl RecordIdScan {
+    /// Create a scan which has seen no records.
+    pub fn new() -> Self {
+        RecordIdScan::default()
+    }
+
+    /// Check the ID of the record at the given location.
+    pub fn add<S: Into<String>>(&mut self, record_id: &str, file: S, offset: u64) {
+        let location = RecordLocation {
+            file: file.into(),
+            offset,
+        };
+        self.report.records += 1;
+        if let Err(reason) = check_record_id(record_id) {
+            self.report.malformed.push(MalformedId {
+                record_id: record_id.to_owned(),
+                location: location.clone(),
+                reason,
+            });
+        }
+        match self.seen.get(record_id) {
+            Some(first) => self.report.duplicates.push(DuplicateId {
+                record_id: record_id.to_owned(),
+                first: first.clone(),
+                duplicate: location,
+            }),
+            None => {
+                self.seen.insert(record_id.to_owned(), location);
+            }
+        }
+    }
+
+    /// Check the IDs of every record of an uncompressed WARC file, reading only their headers.
+    ///
+    /// Offsets are those within the stream read; for a compressed file, they are offsets into the
+    /// decompressed data.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first malformed record as an error.
+    pub fn scan_file<R: BufRead, S: Into<String>>(
+        &mut self,
+        file: S,
+        reader: R,
+    ) -> Result<(), Error> {
+        let file = file.into();
+        let config = ParserConfig::new().read_policy(ReadPolicy::FailFast);
+        let mut warc_reader = WarcReader::new(reader).with_config(config);
+        let mut records = warc_reader.stream_records();
+        let mut offset = 0;
+        while let Some(record) = records.next_item() {
+            let (record, _) = record?.into_parts();
+            self.add(record.warc_id(), file.as_str(), offset);
+            offset += record.stored_len().unwrap_or_default();
+        }
+
+        Ok(())
+    }
+
+    /// Check the IDs of every record of an opened collection.
+    pub fn scan_collection<R: BufRead + Seek, S: Into<String>>(
+        &mut self,
+        file: S,
+        collection: &Collection<R>,
+    ) {
+        let file = file.into();
+        for entry in collection.entries() {
+            self.add(entry.record.warc_id(), file.as_str(), entry.offset);
+        }
+    }
+
+    /// Return the findings so far.
+    pub fn report(&self) -> &RecordIdReport {
+        &self.report
+    }
+
+    /// Finish the scan, returning its findings.
+    pub fn finish(self) -> RecordIdReport {
+        self.report
+    }
+}
+
+/// Check that a record ID is an absolute URI enclosed in angle brackets.
+pub(crate) fn check_record_id(record_id: &str) -> Result<(), String> {
+    let uri = record_id
+        .strip_prefix('<')
+        .and_then(|id| id.strip_suffix('>'))
+        .ok_or_else(|| "not enclosed in angle brackets".to_string())?;
+    if uri
+        .chars()
+        .any(|c| c.is_whitespace() || c == '<' || c == '>')
+    {
+        return Err("contains whitespace or angle brackets".to_string());
+    }
+    Url::parse(uri).map_err(|e| format!("not a URI: {}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordIdScan, RecordLocation};
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcWriter};
+
+    use std::io::Cursor;
+
+    fn warc(ids: &[&str]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for id in ids {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Resource);
+            record.set_warc_id(*id);
+            writer.write(&record).unwrap();
+        }
+        drop(writer);
+
+        buffer
+    }
+
+    #[test]
+    fn record_ids() {
+        let shared = "<urn:uuid:00000000-0000-4000-8000-000000000001>";
+        let first = warc(&[shared, "<urn:uuid:00000000-0000-4000-8000-000000000002>"]);
+        let second = warc(&[
+            "<urn:uuid:00000000-0000-4000-8000-000000000003>",
+            shared,
+            "urn:uuid:no-brackets",
+        ]);
+
+        let mut scan = RecordIdScan::new();
+        scan.scan_file("a.warc", &first[..]).unwrap();
+        let collection = Collection::open(Cursor::new(second)).unwrap();
+        scan.scan_collection("b.warc", &collection);
+        let report = scan.finish();
+
+        assert_eq!(report.records, 5);
+        assert!(!report.is_clean());
+        assert_eq!(report.duplicates.len(), 1);
+        let duplicate = &report.duplicates[0];
+        assert_eq!(duplicate.record_id, shared);
+        assert_eq!(
+            duplicate.first,
+            RecordLocation {
+                file: "a.warc".to_string(),
+                offset: 0
+            }
+        );
+        assert_eq!(duplicate.duplicate.file, "b.warc");
+        assert_eq!(duplicate.duplicate.offset, collection.entries()[1].offset);
+
+        assert_eq!(report.malformed.len(), 1);
+        assert_eq!(report.malformed[0].record_id, "urn:uuid:no-brackets");
+        assert_eq!(report.malformed[0].reason, "not enclosed in angle brackets");
+
+        let mut scan = RecordIdScan::new();
+        scan.add("<not a uri>", "c.warc", 0);
+        scan.add(
+            "<urn:uuid:00000000-0000-0000-0000-000000000000>",
+            "c.warc",
+            10,
+        );
+        assert_eq!(scan.report().malformed.len(), 1);
+    }
+}
@@ -0,0 +1,298 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::str;
+
+use chrono::{DateTime, Utc};
+
+use crate::header::{HeaderMap, WarcHeader};
+use crate::parser;
+use crate::{BufferedBody, Error, RawRecordHeader, Record, RecordType, TypedHeader, WarcVersion};
+
+/// A record whose headers and body borrow from the buffer it was parsed from.
+///
+/// Parsing a `RecordRef` copies neither header values nor the body, which makes it suited to
+/// scanning uncompressed archives already in memory, such as a mapped file, where most records
+/// are passed over. A record which is kept can be copied into a `Record` with `to_record`.
+///
+/// ```
+/// use warc::{RecordRef, RecordType};
+///
+/// let input = b"WARC/1.0\r\n\
+///     WARC-Type: resource\r\n\
+///     WARC-Record-ID: <urn:uuid:1>\r\n\
+///     WARC-Date: 2020-01-01T00:00:00Z\r\n\
+///     Content-Length: 5\r\n\
+///     \r\n\
+///     hello\r\n\
+///     \r\n";
+/// for record in RecordRef::iter(&input[..]) {
+///     let record = record.unwrap();
+///     assert_eq!(record.warc_type(), &RecordType::Resource);
+///     assert_eq!(record.body(), b"hello");
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordRef<'a> {
+    version: &'a str,
+    headers: Vec<(&'a str, &'a [u8])>,
+    record_id: &'a str,
+    record_type: RecordType,
+    record_date: DateTime<Utc>,
+    body: &'a [u8],
+    stored_len: u64,
+}
+
+impl<'a> RecordRef<'a> {
+    /// Parse the record at the start of the input, returning it and the input following it.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the record is not well-formed, if the input ends before the end
+    /// of the record, or if WARC-Type, WARC-Record-ID, WARC-Date or Content-Length is missing.
+    pub fn parse(input: &'a [u8]) -> Result<(RecordRef<'a>, &'a [u8]), Error> {
+        let (rest, (version, headers, body)) = parser::record(input).map_err(|err| match err {
+            nom::Err::Incomplete(_) => Error::UnexpectedEOB,
+            _ => Error::ParseHeaders,
+        })?;
+
+        let find = |header: WarcHeader| {
+            headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(header.name()))
+                .map(|(_, value)| *value)
+                .ok_or(Error::MissingHeader(header))
+        };
+        let utf8 = |header: WarcHeader| {
+            find(header.clone()).and_then(|value| {
+                str::from_utf8(value)
+                    .map_err(|_| Error::MalformedHeader(header, "not a UTF-8 string".to_string()))
+            })
+        };
+
+        find(WarcHeader::ContentLength)?;
+        let record_id = utf8(WarcHeader::RecordID)?;
+        let record_type = RecordType::from(utf8(WarcHeader::WarcType)?);
+        let record_date = Record::<BufferedBody>::parse_record_date(utf8(WarcHeader::Date)?)?;
+
+        let record = RecordRef {
+            version,
+            headers,
+            record_id,
+            record_type,
+            record_date,
+            body,
+            stored_len: (input.len() - rest.len()) as u64,
+        };
+
+        Ok((record, rest))
+    }
+
+    /// Return an iterator over the records in the input, which must hold whole, uncompressed
+    /// records.
+    ///
+    /// The iterator ends after the first error.
+    pub fn iter(input: &'a [u8]) -> RecordRefIter<'a> {
+        RecordRefIter { input }
+    }
+
+    /// Return the WARC version string of this record.
+    pub fn warc_version(&self) -> &'a str {
+        self.version
+    }
+
+    /// Return the version of the standard this record is written under.
+    pub fn version(&self) -> WarcVersion {
+        WarcVersion::from(self.version)
+    }
+
+    /// Return the WARC-Record-ID header for this record.
+    pub fn warc_id(&self) -> &'a str {
+        self.record_id
+    }
+
+    /// Return the WARC-Type header for this record.
+    pub fn warc_type(&self) -> &RecordType {
+        &self.record_type
+    }
+
+    /// Return the WARC-Date header for this record.
+    pub fn date(&self) -> &DateTime<Utc> {
+        &self.record_date
+    }
+
+    /// Return the WARC header requested if present in this record, or `None`.
+    ///
+    /// Only the first value of a repeated header is returned; `headers_all` returns every value.
+    pub fn header(&self, header: WarcHeader) -> Option<Cow<'a, str>> {
+        self.headers_all(header).into_iter().next()
+    }
+
+    /// Return every value of the WARC header requested, in order, or none if it is absent.
+    pub fn headers_all(&self, header: WarcHeader) -> Vec<Cow<'a, str>> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(header.name()))
+            .map(|(_, value)| String::from_utf8_lossy(value))
+            .collect()
+    }
+
+    /// Return the headers of this record as they appear, with no interpretation of their names
+    /// or values.
+    pub fn raw_headers(&self) -> &[(&'a str, &'a [u8])] {
+        &self.headers
+    }
+
+    /// Return the value of a header parsed as its type, such as `typed_header::TargetUri`.
+    pub fn get<H: TypedHeader>(&self) -> Option<H> {
+        self.header(H::header()).and_then(|value| H::parse(&value))
+    }
+
+    /// Return the length of the body of this record.
+    pub fn content_length(&self) -> u64 {
+        self.body.len() as u64
+    }
+
+    /// Return the body of this record.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Return the number of bytes the record took up in the input, from its version line to the
+    /// end of the line endings following its body.
+    pub fn stored_len(&self) -> u64 {
+        self.stored_len
+    }
+
+    /// Copy this record into an owned `Record`, as a `WarcReader` would have read it.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a header is not well-formed, as when reading the record with a
+    /// `WarcReader`.
+    pub fn to_record(&self) -> Result<Record<BufferedBody>, Error> {
+        let headers = RawRecordHeader {
+            version: self.version.to_owned(),
+            headers: HeaderMap::from_iter(
+                self.headers
+                    .iter()
+                    .map(|(name, value)| (WarcHeader::from(name), value.to_vec())),
+            ),
+        };
+
+        let names = self
+            .headers
+            .iter()
+            .map(|(name, _)| (WarcHeader::from(name), *name))
+            .filter(|(header, name)| header.name() != *name)
+            .map(|(header, name)| (header, name.to_owned()))
+            .collect();
+
+        let mut record = Record::try_from(headers)?.add_body(self.body);
+        record.set_stored_len(self.stored_len);
+        record.set_header_names(names);
+
+        Ok(record)
+    }
+}
+
+/// An iterator over the records borrowed from a buffer, returned by `RecordRef::iter`.
+pub struct RecordRefIter<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Iterator for RecordRefIter<'a> {
+    type Item = Result<RecordRef<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.input.is_empty() {
+            return None;
+        }
+
+        match RecordRef::parse(self.input) {
+            Ok((record, rest)) => {
+                self.input = rest;
+                Some(Ok(record))
+            }
+            Err(err) => {
+                self.input = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::RecordRef;
+    use crate::header::WarcHeader;
+    use crate::typed_header::TargetUri;
+    use crate::{Error, RecordType, WarcReader};
+
+    #[test]
+    fn borrowed_records() {
+        let input = b"WARC/1.0\r\n\
+            WARC-Type: response\r\n\
+            WARC-Record-ID: <urn:uuid:1>\r\n\
+            WARC-Date: 2020-01-01T00:00:00Z\r\n\
+            WARC-Target-URI: http://example.com/\r\n\
+            WARC-Concurrent-To: <urn:uuid:2>\r\n\
+            WARC-Concurrent-To: <urn:uuid:3>\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+            hello\r\n\
+            \r\n\
+            WARC/1.1\r\n\
+            WARC-Type: metadata\r\n\
+            WARC-Record-ID: <urn:uuid:4>\r\n\
+            WARC-Date: 2020-01-01T00:00:00.5Z\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            \r\n\
+            \r\n";
+
+        let records: Vec<_> = RecordRef::iter(&input[..])
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+
+        let response = &records[0];
+        assert_eq!(response.warc_type(), &RecordType::Response);
+        assert_eq!(response.warc_id(), "<urn:uuid:1>");
+        assert_eq!(response.body(), b"hello");
+        assert_eq!(
+            response.get::<TargetUri>().unwrap().0.as_str(),
+            "http://example.com/"
+        );
+        assert_eq!(
+            response.headers_all(WarcHeader::ConcurrentTo),
+            vec!["<urn:uuid:2>", "<urn:uuid:3>"]
+        );
+        assert_eq!(records[1].warc_version(), "1.1");
+        assert_eq!(records[1].content_length(), 0);
+        assert_eq!(
+            records[0].stored_len() + records[1].stored_len(),
+            input.len() as u64
+        );
+
+        let owned: Vec<_> = WarcReader::new(BufReader::new(&input[..]))
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        for (record, owned) in records.iter().zip(owned) {
+            assert_eq!(record.to_record().unwrap(), owned);
+        }
+
+        let mut truncated = RecordRef::iter(&input[..40]);
+        assert_eq!(truncated.next(), Some(Err(Error::UnexpectedEOB)));
+        assert_eq!(truncated.next(), None);
+
+        let missing = b"WARC/1.0\r\nWARC-Type: resource\r\nContent-Length: 0\r\n\r\n\r\n\r\n";
+        assert_eq!(
+            RecordRef::parse(&missing[..]),
+            Err(Error::MissingHeader(WarcHeader::RecordID))
+        );
+    }
+}
@@ -1,3 +1,7 @@
+/// The WARC-Type of a record.
+///
+/// Types not defined by the standard, such as experimental types or types added by later versions,
+/// are kept verbatim as `Unknown`, so that records of those types pass through unchanged.
 #[derive(Clone, Debug, PartialEq)]
 pub enum RecordType {
     WarcInfo,
@@ -11,8 +15,8 @@ pub enum RecordType {
     Unknown(String),
 }
 
-impl ToString for RecordType {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let stringified = match *self {
             RecordType::WarcInfo => "warcinfo",
             RecordType::Response => "response",
@@ -24,14 +28,30 @@ impl ToString for RecordType {
             RecordType::Continuation => "continuation",
             RecordType::Unknown(ref val) => val.as_ref(),
         };
-        stringified.to_string()
+        write!(f, "{}", stringified)
+    }
+}
+
+impl RecordType {
+    /// Return true if this type is defined by the standard.
+    pub fn is_standard(&self) -> bool {
+        !matches!(*self, RecordType::Unknown(_))
+    }
+
+    /// Return true if records of this type have a well-defined payload.
+    ///
+    /// Records of an unknown type are assumed to have one.
+    pub fn has_payload(&self) -> bool {
+        !matches!(
+            *self,
+            RecordType::WarcInfo | RecordType::Metadata | RecordType::Revisit
+        )
     }
 }
 
 impl<S: AsRef<str>> From<S> for RecordType {
     fn from(string: S) -> Self {
-        let lower: String = string.as_ref().to_lowercase();
-        match lower.as_str() {
+        match string.as_ref().to_lowercase().as_str() {
             "warcinfo" => RecordType::WarcInfo,
             "response" => RecordType::Response,
             "resource" => RecordType::Resource,
@@ -40,7 +60,7 @@ impl<S: AsRef<str>> From<S> for RecordType {
             "revisit" => RecordType::Revisit,
             "conversion" => RecordType::Conversion,
             "continuation" => RecordType::Continuation,
-            _ => RecordType::Unknown(lower),
+            _ => RecordType::Unknown(string.as_ref().to_owned()),
         }
     }
 }
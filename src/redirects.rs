@@ -0,0 +1,260 @@
+//! Analysis of the HTTP statuses and redirect chains captured in a collection.
+//!
+//! A redirect chain starts at a captured 3xx response and follows its Location header to the
+//! capture of the target URL closest in time, until a capture which is not a redirect is reached.
+//! A chain is broken if a redirect has no Location header or its target was never captured, and
+//! is a loop if it leads back to a URL already visited.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Seek};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::replay::normalize;
+use crate::{http, Collection, Error, RecordType};
+
+/// The longest chain followed. Longer chains are reported as loops.
+const MAX_HOPS: usize = 20;
+
+/// A captured HTTP response in a redirect chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hop {
+    /// The offset of the response or revisit record.
+    pub offset: u64,
+    pub url: String,
+    pub date: DateTime<Utc>,
+    pub status: u16,
+    /// The URL the response redirects to, resolved against `url`.
+    pub location: Option<String>,
+}
+
+impl Hop {
+    /// Return true if the response is a redirect.
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.status) && self.status != 304
+    }
+}
+
+/// How a redirect chain ends.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainEnd {
+    /// The last hop is not a redirect.
+    Complete,
+    /// The last hop redirects to the given URL, which was never captured.
+    Missing(String),
+    /// The last hop is a redirect without a usable Location header.
+    NoLocation,
+    /// The last hop redirects to the given URL, which is already part of the chain.
+    Loop(String),
+}
+
+/// A chain of redirects captured in a collection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedirectChain {
+    /// The responses of the chain, starting with the first redirect.
+    pub hops: Vec<Hop>,
+    pub end: ChainEnd,
+}
+
+impl RedirectChain {
+    /// Return true if the chain does not reach a capture which is not a redirect.
+    pub fn is_broken(&self) -> bool {
+        matches!(self.end, ChainEnd::Missing(_) | ChainEnd::NoLocation)
+    }
+
+    /// Return true if the chain leads back to a URL already visited.
+    pub fn is_loop(&self) -> bool {
+        matches!(self.end, ChainEnd::Loop(_))
+    }
+}
+
+/// The statuses and redirect chains of a collection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RedirectReport {
+    /// The number of captured HTTP responses with each status code.
+    pub statuses: BTreeMap<u16, u64>,
+    /// Every redirect chain, ordered by the offset of its first redirect.
+    pub chains: Vec<RedirectChain>,
+}
+
+impl RedirectReport {
+    /// Return the chains which do not reach a capture which is not a redirect.
+    pub fn broken(&self) -> impl Iterator<Item = &RedirectChain> {
+        self.chains.iter().filter(|chain| chain.is_broken())
+    }
+
+    /// Return the chains which lead back to a URL already visited.
+    pub fn loops(&self) -> impl Iterator<Item = &RedirectChain> {
+        self.chains.iter().filter(|chain| chain.is_loop())
+    }
+}
+
+/// Analyze the HTTP responses captured in a collection.
+///
+/// Response and revisit records are read for their HTTP status line and Location header. A chain
+/// is reported for each redirect which is not the target of another redirect, and for each
+/// redirect loop not reachable from such a chain.
+pub fn analyze<R: BufRead + Seek>(collection: &mut Collection<R>) -> Result<RedirectReport, Error> {
+    let offsets: Vec<_> = collection
+        .entries()
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry.record.warc_type(),
+                RecordType::Response | RecordType::Revisit
+            )
+        })
+        .filter(|entry| entry.record.header(WarcHeader::TargetURI).is_some())
+        .map(|entry| entry.offset)
+        .collect();
+
+    let mut report = RedirectReport::default();
+    let mut hops = Vec::new();
+    for offset in offsets {
+        let record = collection.read_record(offset)?;
+        let status = match http::status(record.body()) {
+            Some(status) => status,
+            None => continue,
+        };
+        *report.statuses.entry(status).or_default() += 1;
+
+        let url = normalize(&record.header(WarcHeader::TargetURI).unwrap());
+        let location = http::header(record.body(), "location").and_then(|location| {
+            let mut target = Url::parse(&url).ok()?.join(&location).ok()?;
+            target.set_fragment(None);
+            Some(target.to_string())
+        });
+        hops.push(Hop {
+            offset,
+            url,
+            date: *record.date(),
+            status,
+            location,
+        });
+    }
+
+    let mut by_url: HashMap<&str, Vec<&Hop>> = HashMap::new();
+    for hop in &hops {
+        by_url.entry(hop.url.as_str()).or_default().push(hop);
+    }
+    let targets: HashSet<&str> = hops
+        .iter()
+        .filter(|hop| hop.is_redirect())
+        .filter_map(|hop| hop.location.as_deref())
+        .collect();
+
+    let redirects = hops.iter().filter(|hop| hop.is_redirect());
+    let (heads, rest): (Vec<&Hop>, Vec<&Hop>) =
+        redirects.partition(|hop| !targets.contains(hop.url.as_str()));
+    let mut followed = HashSet::new();
+    for start in heads.into_iter().chain(rest) {
+        if followed.contains(&start.offset) {
+            continue;
+        }
+        let chain = follow(start, &by_url);
+        followed.extend(chain.hops.iter().map(|hop| hop.offset));
+        report.chains.push(chain);
+    }
+
+    Ok(report)
+}
+
+/// Follow the redirects starting at a capture.
+fn follow(start: &Hop, by_url: &HashMap<&str, Vec<&Hop>>) -> RedirectChain {
+    let mut chain = vec![start.clone()];
+    let mut visited = HashSet::new();
+    visited.insert(start.url.as_str());
+
+    let end = loop {
+        let hop = chain.last().unwrap();
+        if !hop.is_redirect() {
+            break ChainEnd::Complete;
+        }
+        let location = match hop.location {
+            Some(ref location) => location.clone(),
+            None => break ChainEnd::NoLocation,
+        };
+        if visited.contains(location.as_str()) || chain.len() > MAX_HOPS {
+            break ChainEnd::Loop(location);
+        }
+        let next = match by_url.get(location.as_str()).and_then(|captures| {
+            captures
+                .iter()
+                .min_by_key(|next| ((next.date - hop.date).num_seconds().abs(), next.date))
+        }) {
+            Some(next) => *next,
+            None => break ChainEnd::Missing(location),
+        };
+        visited.insert(next.url.as_str());
+        chain.push(next.clone());
+    };
+
+    RedirectChain { hops: chain, end }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{analyze, ChainEnd};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcWriter};
+
+    #[test]
+    fn chains() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for (uri, head) in [
+            ("http://example.com/a", "301 Moved\r\nLocation: /b"),
+            (
+                "http://example.com/b",
+                "302 Found\r\nLocation: http://example.com/c#top",
+            ),
+            ("http://example.com/c", "200 OK"),
+            ("http://example.com/d", "301 Moved\r\nLocation: /missing"),
+            ("http://example.com/e", "302 Found"),
+            ("http://example.com/x", "302 Found\r\nLocation: /y"),
+            ("http://example.com/y", "302 Found\r\nLocation: /x"),
+            ("http://example.com/z", "404 Not Found"),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Response);
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record.replace_body(format!("HTTP/1.1 {}\r\n\r\n", head));
+            writer.write(&record).unwrap();
+        }
+
+        let mut collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let report = analyze(&mut collection).unwrap();
+        assert_eq!(report.statuses[&302], 4);
+        assert_eq!(report.statuses[&404], 1);
+
+        let ends: Vec<_> = report
+            .chains
+            .iter()
+            .map(|chain| (chain.hops[0].url.as_str(), chain.hops.len(), &chain.end))
+            .collect();
+        assert_eq!(
+            ends,
+            vec![
+                ("http://example.com/a", 3, &ChainEnd::Complete),
+                (
+                    "http://example.com/d",
+                    1,
+                    &ChainEnd::Missing("http://example.com/missing".to_owned())
+                ),
+                ("http://example.com/e", 1, &ChainEnd::NoLocation),
+                (
+                    "http://example.com/x",
+                    2,
+                    &ChainEnd::Loop("http://example.com/x".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(report.broken().count(), 2);
+        assert_eq!(report.loops().count(), 1);
+    }
+}
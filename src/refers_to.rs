@@ -0,0 +1,115 @@
+use chrono::prelude::*;
+
+/// The record a record refers to, given by its WARC-Refers-To, WARC-Refers-To-Target-URI and
+/// WARC-Refers-To-Date headers.
+///
+/// WARC/1.1 identifies the record referred to by its target URI and date, as well as or instead
+/// of its record ID; earlier versions only define WARC-Refers-To.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RefersTo {
+    /// The WARC-Record-ID of the record referred to.
+    pub record_id: Option<String>,
+    /// The WARC-Target-URI of the record referred to.
+    pub target_uri: Option<String>,
+    /// The WARC-Date of the record referred to.
+    pub date: Option<DateTime<Utc>>,
+}
+
+impl RefersTo {
+    /// Refer to a record by its ID.
+    pub fn record_id<S: Into<String>>(record_id: S) -> Self {
+        RefersTo {
+            record_id: Some(record_id.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Set the target URI and date of the record referred to.
+    pub fn capture<S: Into<String>>(mut self, target_uri: S, date: DateTime<Utc>) -> Self {
+        self.target_uri = Some(target_uri.into());
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Return true if no record is referred to.
+    pub fn is_empty(&self) -> bool {
+        self.record_id.is_none() && self.target_uri.is_none() && self.date.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+
+    use super::RefersTo;
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn construct() {
+        assert!(RefersTo::default().is_empty());
+
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let refers_to = RefersTo::record_id("<urn:test:original>");
+        assert!(!refers_to.is_empty());
+        assert_eq!(refers_to.record_id.as_deref(), Some("<urn:test:original>"));
+        assert_eq!(refers_to.target_uri, None);
+
+        let refers_to = refers_to.capture("http://example.com/", date);
+        assert_eq!(
+            refers_to,
+            RefersTo {
+                record_id: Some("<urn:test:original>".to_owned()),
+                target_uri: Some("http://example.com/".to_owned()),
+                date: Some(date),
+            }
+        );
+    }
+
+    #[test]
+    fn apply_to_record() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let refers_to =
+            RefersTo::record_id("<urn:test:original>").capture("http://example.com/", date);
+
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.1");
+        record.set_warc_type(RecordType::Revisit);
+        assert_eq!(record.refers_to(), None);
+        record.set_refers_to(refers_to.clone()).unwrap();
+        assert_eq!(record.refers_to(), Some(refers_to.clone()));
+        assert_eq!(
+            record.header(WarcHeader::RefersToDate).unwrap(),
+            "2020-01-02T03:04:05Z"
+        );
+
+        let mut buffer = Vec::new();
+        WarcWriter::new(&mut buffer).write(&record).unwrap();
+        let read = WarcReader::new(&buffer[..])
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.refers_to(), Some(refers_to.clone()));
+
+        record
+            .set_refers_to(RefersTo::record_id("<urn:test:other>"))
+            .unwrap();
+        assert_eq!(record.header(WarcHeader::RefersToTargetURI), None);
+        assert_eq!(record.header(WarcHeader::RefersToDate), None);
+        record.set_refers_to(RefersTo::default()).unwrap();
+        assert_eq!(record.refers_to(), None);
+
+        record.set_warc_version("1.0");
+        assert!(record.set_refers_to(refers_to).is_err());
+        assert_eq!(record.refers_to(), None);
+        record
+            .set_refers_to(RefersTo::record_id("<urn:test:original>"))
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::RefersTo).unwrap(),
+            "<urn:test:original>"
+        );
+    }
+}
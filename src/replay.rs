@@ -0,0 +1,591 @@
+//! Selection of archived captures for replay.
+//!
+//! A wayback-style service answers a request for a URL at a datetime with the capture of that URL
+//! closest in time, as described by the Memento protocol (RFC 7089). Deduplicated captures are
+//! stored as revisit records, whose payload must be taken from the record they refer to.
+//!
+//! Readers can do the same for every revisit record they yield with `ResolveRevisits`, so that
+//! deduplicated captures are not lost to consumers which only look at responses.
+//!
+//! A `TimeMap` lists every capture of a URL, with a URI for each in the style of the Wayback
+//! Machine: the URI prefix of the service, the 14-digit timestamp of the capture, and the URL.
+
+use std::collections::HashSet;
+use std::io::{BufRead, Seek};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::json::JsonString;
+use crate::record::BodyKind;
+use crate::warc_reader::RecordIter;
+use crate::{http, BufferedBody, Collection, CollectionEntry, Error, Record, RecordType, Warning};
+
+/// A capture selected for replay.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolved {
+    /// The offset of the response or revisit record selected.
+    pub offset: u64,
+    /// The date of the capture.
+    pub date: DateTime<Utc>,
+    /// The offset of the record holding the payload. This is the offset of the record selected
+    /// unless it was a revisit.
+    pub payload_offset: u64,
+    /// The HTTP response as captured, including its status line and headers.
+    pub response: Vec<u8>,
+}
+
+/// Normalize a URL for comparison, so that differences in the case of the scheme and host, or a
+/// default port, are ignored.
+pub(crate) fn normalize(url: &str) -> String {
+    let url = url.trim().trim_start_matches('<').trim_end_matches('>');
+    Url::parse(url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| url.to_owned())
+}
+
+fn target_uri<T: BodyKind>(record: &Record<T>) -> Option<String> {
+    record
+        .header(WarcHeader::TargetURI)
+        .map(|uri| normalize(&uri))
+}
+
+/// Return the response and revisit records capturing the URL, ordered by date.
+pub fn captures<'c, R: BufRead + Seek>(
+    collection: &'c Collection<R>,
+    url: &str,
+) -> Vec<&'c CollectionEntry> {
+    let url = normalize(url);
+    let mut captures: Vec<_> = collection
+        .entries()
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry.record.warc_type(),
+                RecordType::Response | RecordType::Revisit
+            )
+        })
+        .filter(|entry| target_uri(&entry.record).as_deref() == Some(url.as_str()))
+        .collect();
+    captures.sort_by_key(|entry| (*entry.record.date(), entry.offset));
+
+    captures
+}
+
+/// Find the capture of the URL closest in time to the datetime, and reconstruct its HTTP response.
+///
+/// Of two captures equally distant from the datetime, the earlier is chosen. Revisit records are
+/// followed to the record holding their payload, by WARC-Refers-To, by
+/// WARC-Refers-To-Target-URI and WARC-Refers-To-Date, or else by a response with the same
+/// WARC-Payload-Digest. A revisit whose payload cannot be found is passed over in favor of the
+/// next closest capture. `None` is returned if no capture of the URL can be replayed.
+pub fn resolve<R: BufRead + Seek>(
+    collection: &mut Collection<R>,
+    url: &str,
+    datetime: DateTime<Utc>,
+) -> Result<Option<Resolved>, Error> {
+    let mut candidates: Vec<_> = captures(collection, url)
+        .into_iter()
+        .map(|entry| (entry.offset, *entry.record.date()))
+        .collect();
+    candidates.sort_by_key(|(_, date)| ((*date - datetime).num_seconds().abs(), *date));
+
+    for (offset, date) in candidates {
+        if let Some(payload_offset) = payload_source(collection, offset) {
+            let record = collection.read_record(offset)?;
+            let response = if payload_offset == offset {
+                record.body().to_vec()
+            } else {
+                let original = collection.read_record(payload_offset)?;
+                reconstruct(record.body(), original.body())
+            };
+
+            return Ok(Some(Resolved {
+                offset,
+                date,
+                payload_offset,
+                response,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Return the offset of the response record holding the payload of the record at the offset,
+/// following revisits.
+fn payload_source<R: BufRead + Seek>(collection: &Collection<R>, offset: u64) -> Option<u64> {
+    let entry = collection.entry_at(offset)?;
+    if entry.record.warc_type() != &RecordType::Revisit {
+        return Some(offset);
+    }
+
+    original(collection, &entry.record).map(|entry| entry.offset)
+}
+
+/// Find the response record holding the payload of a revisit record, following revisits which
+/// refer to other revisits.
+fn original<'c, R: BufRead + Seek, T: BodyKind>(
+    collection: &'c Collection<R>,
+    revisit: &Record<T>,
+) -> Option<&'c CollectionEntry> {
+    let mut seen = HashSet::new();
+    let mut entry = refers_to(collection, revisit)?;
+    while entry.record.warc_type() == &RecordType::Revisit {
+        if !seen.insert(entry.offset) {
+            return None;
+        }
+        entry = refers_to(collection, &entry.record)?;
+    }
+
+    Some(entry)
+}
+
+/// Find the record a revisit record refers to.
+fn refers_to<'c, R: BufRead + Seek, T: BodyKind>(
+    collection: &'c Collection<R>,
+    revisit: &Record<T>,
+) -> Option<&'c CollectionEntry> {
+    let refers_to = revisit.refers_to().unwrap_or_default();
+    if let Some(id) = refers_to.record_id {
+        return collection.get(id.trim());
+    }
+
+    let responses = collection
+        .entries()
+        .iter()
+        .filter(|entry| entry.record.warc_type() == &RecordType::Response);
+    if let Some(uri) = refers_to.target_uri {
+        let uri = normalize(&uri);
+        let date = refers_to.date;
+        return responses
+            .filter(|entry| target_uri(&entry.record).as_deref() == Some(uri.as_str()))
+            .find(|entry| date.is_none() || date.as_ref() == Some(entry.record.date()));
+    }
+
+    let digest = revisit.header(WarcHeader::PayloadDigest)?;
+    let same_digest: Vec<_> = responses
+        .filter(|entry| entry.record.header(WarcHeader::PayloadDigest) == Some(digest.clone()))
+        .collect();
+    let uri = target_uri(revisit);
+    same_digest
+        .iter()
+        .find(|entry| target_uri(&entry.record) == uri)
+        .or_else(|| same_digest.first())
+        .copied()
+}
+
+/// An iterator adapter which replaces each revisit record with a response reconstructed from the
+/// record holding its payload.
+///
+/// Reconstructed records keep the ID, date and target URI of the revisit, and are marked with a
+/// `Warning::Reconstructed`. Revisit records whose payload cannot be found in the collection are
+/// yielded unchanged.
+pub struct ResolveRevisits<'c, I, R> {
+    records: I,
+    collection: &'c mut Collection<R>,
+}
+
+impl<'c, I, R> ResolveRevisits<'c, I, R>
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+    R: BufRead + Seek,
+{
+    /// Resolve the revisit records of an iterator using the records of a collection.
+    pub fn new(records: I, collection: &'c mut Collection<R>) -> Self {
+        ResolveRevisits {
+            records,
+            collection,
+        }
+    }
+
+    fn reconstruct(
+        &mut self,
+        mut record: Record<BufferedBody>,
+    ) -> Result<Record<BufferedBody>, Error> {
+        let offset = match original(self.collection, &record) {
+            Some(entry) => entry.offset,
+            None => return Ok(record),
+        };
+        let original = self.collection.read_record(offset)?;
+
+        let body = reconstruct(record.body(), original.body());
+        record.replace_body(body);
+        record.set_warc_type(RecordType::Response);
+        record.remove_header(&WarcHeader::Profile);
+        record.remove_header(&WarcHeader::BlockDigest);
+        if let Some(content_type) = original.header(WarcHeader::ContentType) {
+            record
+                .set_header(WarcHeader::ContentType, content_type.into_owned())
+                .unwrap();
+        }
+        record.append_warnings(&mut vec![Warning::Reconstructed(
+            original.warc_id().to_owned(),
+        )]);
+
+        Ok(record)
+    }
+}
+
+impl<'c, I, R> Iterator for ResolveRevisits<'c, I, R>
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+    R: BufRead + Seek,
+{
+    type Item = Result<Record<BufferedBody>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.records.next()? {
+            Ok(record) if record.warc_type() == &RecordType::Revisit => {
+                Some(self.reconstruct(record))
+            }
+            other => Some(other),
+        }
+    }
+}
+
+impl<R: BufRead> RecordIter<R> {
+    /// Replace each revisit record read with a response reconstructed from the record holding its
+    /// payload in the collection. See `ResolveRevisits`.
+    pub fn resolve_revisits<C: BufRead + Seek>(
+        self,
+        collection: &mut Collection<C>,
+    ) -> ResolveRevisits<'_, Self, C> {
+        ResolveRevisits::new(self, collection)
+    }
+}
+
+/// The list of captures of a URL, as served by a Memento TimeMap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeMap {
+    original: String,
+    prefix: String,
+    self_uri: Option<String>,
+    timegate_uri: Option<String>,
+    mementos: Vec<DateTime<Utc>>,
+}
+
+impl TimeMap {
+    /// Create the TimeMap of a URL from the captures in a collection.
+    ///
+    /// The URI of each memento is the prefix followed by the timestamp of the capture and the URL.
+    pub fn new<R: BufRead + Seek, S: Into<String>>(
+        collection: &Collection<R>,
+        url: &str,
+        prefix: S,
+    ) -> Self {
+        let mut mementos: Vec<_> = captures(collection, url)
+            .into_iter()
+            .map(|entry| *entry.record.date())
+            .collect();
+        mementos.dedup();
+
+        TimeMap {
+            original: normalize(url),
+            prefix: prefix.into(),
+            self_uri: None,
+            timegate_uri: None,
+            mementos,
+        }
+    }
+
+    /// Set the URI the TimeMap itself is served at.
+    pub fn self_uri<S: Into<String>>(mut self, uri: S) -> Self {
+        self.self_uri = Some(uri.into());
+
+        self
+    }
+
+    /// Set the URI of the TimeGate for the URL.
+    pub fn timegate_uri<S: Into<String>>(mut self, uri: S) -> Self {
+        self.timegate_uri = Some(uri.into());
+
+        self
+    }
+
+    /// Return the URL the captures are of.
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// Return the datetime of each capture, in order.
+    pub fn mementos(&self) -> &[DateTime<Utc>] {
+        &self.mementos
+    }
+
+    /// Return the URI of the capture at the datetime.
+    pub fn memento_uri(&self, datetime: &DateTime<Utc>) -> String {
+        format!(
+            "{}{}/{}",
+            self.prefix,
+            datetime.format("%Y%m%d%H%M%S"),
+            self.original
+        )
+    }
+
+    /// Format the TimeMap as `application/link-format`, as defined by RFC 7089.
+    pub fn to_link_format(&self) -> String {
+        let http_date = |datetime: &DateTime<Utc>| datetime.format("%a, %d %b %Y %H:%M:%S GMT");
+        let mut links = vec![format!("<{}>; rel=\"original\"", self.original)];
+        if let Some(ref uri) = self.self_uri {
+            let mut link = format!("<{}>; rel=\"self\"; type=\"application/link-format\"", uri);
+            if let (Some(first), Some(last)) = (self.mementos.first(), self.mementos.last()) {
+                link.push_str(&format!(
+                    "; from=\"{}\"; until=\"{}\"",
+                    http_date(first),
+                    http_date(last)
+                ));
+            }
+            links.push(link);
+        }
+        if let Some(ref uri) = self.timegate_uri {
+            links.push(format!("<{}>; rel=\"timegate\"", uri));
+        }
+        for (i, datetime) in self.mementos.iter().enumerate() {
+            let rel = match (i == 0, i + 1 == self.mementos.len()) {
+                (true, true) => "first last memento",
+                (true, false) => "first memento",
+                (false, true) => "last memento",
+                (false, false) => "memento",
+            };
+            links.push(format!(
+                "<{}>; rel=\"{}\"; datetime=\"{}\"",
+                self.memento_uri(datetime),
+                rel,
+                http_date(datetime)
+            ));
+        }
+
+        links.join(",\n") + "\n"
+    }
+
+    /// Format the TimeMap as JSON, in the form served by pywb and other Memento aggregators.
+    pub fn to_json(&self) -> String {
+        let memento = |datetime: &DateTime<Utc>| {
+            format!(
+                "{{\"datetime\":\"{}\",\"uri\":{}}}",
+                datetime.format("%Y-%m-%dT%H:%M:%SZ"),
+                JsonString(&self.memento_uri(datetime))
+            )
+        };
+        let mut fields = vec![format!("\"original_uri\":{}", JsonString(&self.original))];
+        if let Some(ref uri) = self.self_uri {
+            fields.push(format!("\"self\":{}", JsonString(uri)));
+        }
+        if let Some(ref uri) = self.timegate_uri {
+            fields.push(format!("\"timegate_uri\":{}", JsonString(uri)));
+        }
+        let mut mementos = Vec::new();
+        if let (Some(first), Some(last)) = (self.mementos.first(), self.mementos.last()) {
+            mementos.push(format!("\"first\":{}", memento(first)));
+            mementos.push(format!("\"last\":{}", memento(last)));
+        }
+        let list: Vec<_> = self.mementos.iter().map(memento).collect();
+        mementos.push(format!("\"list\":[{}]", list.join(",")));
+        fields.push(format!("\"mementos\":{{{}}}", mementos.join(",")));
+
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// Combine the HTTP head stored in a revisit record with the payload of the original response.
+///
+/// The original response is used unchanged if the revisit holds no HTTP head, or records a
+/// `304 Not Modified` response.
+fn reconstruct(revisit: &[u8], original: &[u8]) -> Vec<u8> {
+    let head = match http::split_head(revisit) {
+        Some((head, _)) if http::status(head).is_some_and(|status| status != 304) => head,
+        _ => return original.to_vec(),
+    };
+    let payload = http::split_head(original).map_or(original, |(_, payload)| payload);
+
+    [head, payload].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{captures, resolve, TimeMap};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcReader, WarcWriter, Warning};
+
+    fn archive() -> Vec<u8> {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let record = |warc_type, days, body: &[u8]| {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(warc_type);
+            record.set_date(start + Duration::days(days));
+            record
+                .set_header(WarcHeader::TargetURI, "http://Example.com:80/")
+                .unwrap();
+            record.replace_body(body);
+            record
+        };
+        let original = record(
+            RecordType::Response,
+            0,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+        );
+        let changed = record(
+            RecordType::Response,
+            10,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nchanged",
+        );
+        let mut revisit = record(
+            RecordType::Revisit,
+            20,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Revisit: yes\r\n\r\n",
+        );
+        revisit
+            .set_header(WarcHeader::RefersTo, original.warc_id())
+            .unwrap();
+        let mut dangling = record(RecordType::Revisit, 30, b"");
+        dangling
+            .set_header(WarcHeader::RefersTo, "<urn:uuid:missing>")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for record in [&original, &changed, &revisit, &dangling] {
+            writer.write(record).unwrap();
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn closest_capture() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut collection = Collection::open(Cursor::new(archive())).unwrap();
+        assert_eq!(captures(&collection, "http://example.com/").len(), 4);
+
+        let resolved = resolve(
+            &mut collection,
+            "http://example.com/",
+            start + Duration::days(4),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(resolved.date, start);
+        assert_eq!(resolved.offset, resolved.payload_offset);
+
+        let resolved = resolve(
+            &mut collection,
+            "http://example.com/",
+            start + Duration::days(40),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(resolved.date, start + Duration::days(20));
+        assert_ne!(resolved.offset, resolved.payload_offset);
+        assert_eq!(
+            resolved.response,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Revisit: yes\r\n\r\nhello"
+        );
+
+        assert_eq!(
+            resolve(&mut collection, "http://example.org/", start).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_while_reading() {
+        let buffer = archive();
+        let mut collection = Collection::open(Cursor::new(buffer.clone())).unwrap();
+        let records: Vec<_> = WarcReader::new(Cursor::new(buffer))
+            .iter_records()
+            .resolve_revisits(&mut collection)
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 4);
+
+        let reconstructed = &records[2];
+        assert_eq!(reconstructed.warc_type(), &RecordType::Response);
+        assert!(reconstructed.is_reconstructed());
+        assert_eq!(
+            reconstructed.warnings(),
+            &[Warning::Reconstructed(records[0].warc_id().to_owned())]
+        );
+        assert!(reconstructed
+            .body()
+            .ends_with(b"X-Revisit: yes\r\n\r\nhello"));
+
+        assert_eq!(records[3].warc_type(), &RecordType::Revisit);
+        assert!(!records[3].is_reconstructed());
+    }
+
+    #[test]
+    fn timemap() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for (date, uri) in [
+            (
+                Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0),
+                "http://example.com/",
+            ),
+            (
+                Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0),
+                "http://example.org/",
+            ),
+            (
+                Utc.with_ymd_and_hms(2021, 6, 2, 3, 4, 5),
+                "http://example.com/",
+            ),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Response);
+            record.set_date(date.unwrap());
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            writer.write(&record).unwrap();
+        }
+        let collection = Collection::open(Cursor::new(buffer)).unwrap();
+
+        let timemap = TimeMap::new(&collection, "http://example.com", "http://a.example/")
+            .self_uri("http://a.example/timemap/link/http://example.com/");
+        assert_eq!(timemap.mementos().len(), 2);
+        assert_eq!(
+            timemap.to_link_format(),
+            "<http://example.com/>; rel=\"original\",\n\
+             <http://a.example/timemap/link/http://example.com/>; rel=\"self\"; \
+             type=\"application/link-format\"; from=\"Wed, 01 Jan 2020 00:00:00 GMT\"; \
+             until=\"Wed, 02 Jun 2021 03:04:05 GMT\",\n\
+             <http://a.example/20200101000000/http://example.com/>; rel=\"first memento\"; \
+             datetime=\"Wed, 01 Jan 2020 00:00:00 GMT\",\n\
+             <http://a.example/20210602030405/http://example.com/>; rel=\"last memento\"; \
+             datetime=\"Wed, 02 Jun 2021 03:04:05 GMT\"\n"
+        );
+
+        let json = TimeMap::new(&collection, "http://example.org/", "/").to_json();
+        assert_eq!(
+            json,
+            "{\"original_uri\":\"http://example.org/\",\"mementos\":{\
+             \"first\":{\"datetime\":\"2020-01-01T00:00:00Z\",\"uri\":\"/20200101000000/http://example.org/\"},\
+             \"last\":{\"datetime\":\"2020-01-01T00:00:00Z\",\"uri\":\"/20200101000000/http://example.org/\"},\
+             \"list\":[{\"datetime\":\"2020-01-01T00:00:00Z\",\"uri\":\"/20200101000000/http://example.org/\"}]}}"
+        );
+
+        let json = TimeMap::new(&collection, "http://example.org/", "/\u{1}/")
+            .self_uri("http://a.example/\"timemap\"")
+            .timegate_uri("http://a.example/\u{7f}\\")
+            .to_json();
+        assert_eq!(
+            json,
+            "{\"original_uri\":\"http://example.org/\",\
+             \"self\":\"http://a.example/\\\"timemap\\\"\",\
+             \"timegate_uri\":\"http://a.example/\\u007f\\\\\",\"mementos\":{\
+             \"first\":{\"datetime\":\"2020-01-01T00:00:00Z\",\
+             \"uri\":\"/\\u0001/20200101000000/http://example.org/\"},\
+             \"last\":{\"datetime\":\"2020-01-01T00:00:00Z\",\
+             \"uri\":\"/\\u0001/20200101000000/http://example.org/\"},\
+             \"list\":[{\"datetime\":\"2020-01-01T00:00:00Z\",\
+             \"uri\":\"/\\u0001/20200101000000/http://example.org/\"}]}}"
+        );
+    }
+}
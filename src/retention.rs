@@ -0,0 +1,339 @@
+//! Enforcement of retention policies over a collection.
+//!
+//! `enforce` copies a collection to a writer, dropping the records a `RetentionPolicy` does not
+//! retain: captures older than a cutoff, or of hosts outside an allowed list. Records are never
+//! left referring to a dropped record: the records concurrent to a dropped record, revisits
+//! referring to it and continuations of it are dropped along with it. Warcinfo records are always
+//! kept.
+//!
+//! The report lists every record dropped and why, as a deletion manifest, and the new offset of
+//! every record kept, so that indexes of the collection can be updated.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Cursor, Seek, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::{Collection, CollectionEntry, Error, RecordType, WarcReader, WarcWriter};
+
+/// Which records to keep.
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    cutoff: Option<DateTime<Utc>>,
+    hosts: Option<HashSet<String>>,
+}
+
+impl RetentionPolicy {
+    /// Create a policy which keeps every record.
+    pub fn new() -> Self {
+        RetentionPolicy::default()
+    }
+
+    /// Drop records dated before the cutoff.
+    pub fn cutoff(mut self, cutoff: DateTime<Utc>) -> Self {
+        self.cutoff = Some(cutoff);
+
+        self
+    }
+
+    /// Keep only records whose WARC-Target-URI is on the given host, or another host allowed
+    /// before. Hosts are compared without regard to case, and records without a target URI are
+    /// not affected.
+    pub fn allow_host<S: AsRef<str>>(mut self, host: S) -> Self {
+        self.hosts
+            .get_or_insert_with(HashSet::new)
+            .insert(host.as_ref().to_lowercase());
+
+        self
+    }
+
+    /// Return why the record of an entry is dropped by the policy itself, if it is.
+    fn reason(&self, entry: &CollectionEntry) -> Option<DeletionReason> {
+        let record = &entry.record;
+        if record.warc_type() == &RecordType::WarcInfo {
+            return None;
+        }
+        if self.cutoff.is_some_and(|cutoff| *record.date() < cutoff) {
+            return Some(DeletionReason::Expired);
+        }
+        if let (Some(hosts), Some(uri)) = (&self.hosts, record.header(WarcHeader::TargetURI)) {
+            let host = Url::parse(&uri)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_lowercase));
+            if !host.is_some_and(|host| hosts.contains(&host)) {
+                return Some(DeletionReason::HostNotAllowed);
+            }
+        }
+
+        None
+    }
+}
+
+/// Why a record was dropped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DeletionReason {
+    /// The record is dated before the cutoff.
+    Expired,
+    /// The record captures a host which is not allowed.
+    HostNotAllowed,
+    /// The record is linked to the dropped record with the given ID.
+    Linked(String),
+}
+
+/// A record dropped by `enforce`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deletion {
+    pub record_id: String,
+    pub record_type: RecordType,
+    pub target_uri: Option<String>,
+    pub date: DateTime<Utc>,
+    /// The offset of the record in the collection.
+    pub offset: u64,
+    pub reason: DeletionReason,
+}
+
+/// A record kept by `enforce`, with its offsets in the collection and in the output.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Relocation {
+    pub record_id: String,
+    pub from: u64,
+    pub to: u64,
+}
+
+/// The outcome of `enforce`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionReport {
+    /// The records kept, in order.
+    pub kept: Vec<Relocation>,
+    /// The records dropped, in order.
+    pub deleted: Vec<Deletion>,
+}
+
+impl RetentionReport {
+    /// Format the deletions as a manifest of tab-separated values, with a header line.
+    ///
+    /// The columns are the offset, the record ID, the record type, the date, the target URI, and
+    /// the reason, which for linked records is `linked:` followed by the ID of the record linked
+    /// to.
+    pub fn to_manifest(&self) -> String {
+        let mut manifest = "offset\trecord_id\ttype\tdate\ttarget_uri\treason\n".to_owned();
+        for deletion in &self.deleted {
+            let reason = match deletion.reason {
+                DeletionReason::Expired => "expired".to_owned(),
+                DeletionReason::HostNotAllowed => "host-not-allowed".to_owned(),
+                DeletionReason::Linked(ref id) => format!("linked:{}", id),
+            };
+            manifest.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\n",
+                deletion.offset,
+                deletion.record_id,
+                deletion.record_type,
+                deletion.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+                deletion.target_uri.as_deref().unwrap_or("-"),
+                reason
+            ));
+        }
+
+        manifest
+    }
+}
+
+/// Return the IDs of the records whose removal requires the removal of a record.
+///
+/// These are the records it is concurrent to, refers to, or is a segment of.
+fn dependencies(entry: &CollectionEntry) -> Vec<String> {
+    let mut ids = entry.concurrent_to();
+    for header in [WarcHeader::RefersTo, WarcHeader::SegmentOriginID].iter() {
+        if let Some(id) = entry.record.header(header.clone()) {
+            ids.push(id.trim().to_owned());
+        }
+    }
+
+    ids
+}
+
+/// Copy the records of a collection which a policy retains to the output of a writer.
+///
+/// Records are copied byte for byte when the writer would write them unchanged.
+pub fn enforce<R, W>(
+    collection: &mut Collection<R>,
+    policy: &RetentionPolicy,
+    writer: &mut WarcWriter<W>,
+) -> Result<RetentionReport, Error>
+where
+    R: BufRead + Seek,
+    W: Write,
+{
+    let entries = collection.entries();
+    let mut dropped: HashMap<&str, DeletionReason> = entries
+        .iter()
+        .filter_map(|entry| Some((entry.record.warc_id(), policy.reason(entry)?)))
+        .collect();
+    loop {
+        let mut linked = Vec::new();
+        for entry in entries {
+            let id = entry.record.warc_id();
+            if dropped.contains_key(id) || entry.record.warc_type() == &RecordType::WarcInfo {
+                continue;
+            }
+            let dependency = dependencies(entry)
+                .into_iter()
+                .find(|dependency| dropped.contains_key(dependency.as_str()));
+            let concurrent = || {
+                entries.iter().find(|other| {
+                    dropped.contains_key(other.record.warc_id())
+                        && other.concurrent_to().iter().any(|other_id| other_id == id)
+                })
+            };
+            let link =
+                dependency.or_else(|| concurrent().map(|other| other.record.warc_id().to_owned()));
+            if let Some(link) = link {
+                linked.push((id, DeletionReason::Linked(link)));
+            }
+        }
+        if linked.is_empty() {
+            break;
+        }
+        dropped.extend(linked);
+    }
+
+    let mut report = RetentionReport::default();
+    let mut deleted = Vec::new();
+    let mut kept = Vec::new();
+    for entry in entries {
+        let id = entry.record.warc_id();
+        match dropped.get(id) {
+            Some(reason) => deleted.push(Deletion {
+                record_id: id.to_owned(),
+                record_type: entry.record.warc_type().clone(),
+                target_uri: entry
+                    .record
+                    .header(WarcHeader::TargetURI)
+                    .map(|uri| uri.into_owned()),
+                date: *entry.record.date(),
+                offset: entry.offset,
+                reason: reason.clone(),
+            }),
+            None => kept.push((id.to_owned(), entry.offset)),
+        }
+    }
+    report.deleted = deleted;
+
+    let mut position = 0;
+    for (record_id, offset) in kept {
+        let raw = collection.read_raw(offset)?;
+        let written = if writer.writes_verbatim() {
+            writer.write_verbatim(&raw)
+        } else {
+            let (headers, body) = WarcReader::new(Cursor::new(raw))
+                .iter_raw_records()
+                .next()
+                .unwrap_or(Err(Error::UnexpectedEOB))?;
+            writer.write_raw(headers, &body)
+        }
+        .map_err(|_| Error::WriteData)?;
+        report.kept.push(Relocation {
+            record_id,
+            from: offset,
+            to: position,
+        });
+        position += written as u64;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{enforce, DeletionReason, RetentionPolicy};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn retention() {
+        let record = |type_: RecordType, uri: &str, year: i32| {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(type_);
+            record.set_date(Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap());
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record
+        };
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_version("1.0");
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        warcinfo.set_date(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap());
+        let old = record(RecordType::Response, "http://example.com/", 2010);
+        let mut request = record(RecordType::Request, "http://example.com/", 2010);
+        request
+            .set_header(WarcHeader::ConcurrentTo, old.warc_id())
+            .unwrap();
+        let mut revisit = record(RecordType::Revisit, "http://example.com/", 2020);
+        revisit
+            .set_header(WarcHeader::RefersTo, old.warc_id())
+            .unwrap();
+        let current = record(RecordType::Response, "http://example.com/a", 2020);
+        let other = record(RecordType::Response, "http://other.example/", 2020);
+
+        let records = [&warcinfo, &old, &request, &revisit, &current, &other];
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for record in records.iter() {
+            writer.write(*record).unwrap();
+        }
+        drop(writer);
+        let mut collection = Collection::open(Cursor::new(buffer)).unwrap();
+
+        let policy = RetentionPolicy::new()
+            .cutoff(Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap())
+            .allow_host("EXAMPLE.com");
+        let mut output = Vec::new();
+        let report = enforce(&mut collection, &policy, &mut WarcWriter::new(&mut output)).unwrap();
+
+        let reasons: Vec<_> = report
+            .deleted
+            .iter()
+            .map(|deletion| deletion.reason.clone())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                DeletionReason::Expired,
+                DeletionReason::Expired,
+                DeletionReason::Linked(old.warc_id().to_owned()),
+                DeletionReason::HostNotAllowed,
+            ]
+        );
+        let ids: Vec<_> = WarcReader::new(&output[..])
+            .iter_records()
+            .map(|record| record.unwrap().warc_id().to_owned())
+            .collect();
+        assert_eq!(ids, vec![warcinfo.warc_id(), current.warc_id()]);
+        assert_eq!(report.kept[1].record_id, current.warc_id());
+        assert_eq!(report.kept[1].to, collection.entries()[0].length);
+
+        let mut untouched = Vec::new();
+        let mut dry_run = WarcWriter::new(&mut untouched);
+        dry_run.set_dry_run(true);
+        assert_eq!(
+            enforce(&mut collection, &policy, &mut dry_run).unwrap(),
+            report
+        );
+        drop(dry_run);
+        assert!(untouched.is_empty());
+
+        let manifest = report.to_manifest();
+        assert_eq!(manifest.lines().count(), 5);
+        assert!(manifest.contains(&format!(
+            "\t{}\trevisit\t2020-01-01T00:00:00Z\thttp://example.com/\tlinked:{}\n",
+            revisit.warc_id(),
+            old.warc_id()
+        )));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::WarcVersion;
+
+const PROFILE_PREFIX_1_0: &str = "http://netpreserve.org/warc/1.0/revisit/";
+const PROFILE_PREFIX_1_1: &str = "http://netpreserve.org/warc/1.1/revisit/";
+
+/// The profile of a revisit record, given by its WARC-Profile header.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevisitProfile {
+    /// The payload is identical to that of an earlier record with the same payload digest.
+    IdenticalPayloadDigest,
+    /// The server reported that the resource was not modified since an earlier capture.
+    ServerNotModified,
+    Unknown(String),
+}
+
+impl RevisitProfile {
+    /// Return the URI of this profile as defined by the given WARC version.
+    ///
+    /// Versions other than 1.1 use the URIs defined by WARC/1.0.
+    pub fn uri(&self, version: &str) -> String {
+        let prefix = if WarcVersion::from(version) == WarcVersion::V1_1 {
+            PROFILE_PREFIX_1_1
+        } else {
+            PROFILE_PREFIX_1_0
+        };
+        match *self {
+            RevisitProfile::IdenticalPayloadDigest => {
+                format!("{}identical-payload-digest", prefix)
+            }
+            RevisitProfile::ServerNotModified => format!("{}server-not-modified", prefix),
+            RevisitProfile::Unknown(ref uri) => uri.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for RevisitProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.uri("1.0"))
+    }
+}
+
+impl<S: AsRef<str>> From<S> for RevisitProfile {
+    fn from(string: S) -> Self {
+        let uri = string.as_ref().trim();
+        let name = uri
+            .strip_prefix(PROFILE_PREFIX_1_0)
+            .or_else(|| uri.strip_prefix(PROFILE_PREFIX_1_1));
+        match name {
+            Some("identical-payload-digest") => RevisitProfile::IdenticalPayloadDigest,
+            Some("server-not-modified") => RevisitProfile::ServerNotModified,
+            _ => RevisitProfile::Unknown(uri.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RevisitProfile;
+
+    #[test]
+    fn profile_uris() {
+        for (profile, name) in [
+            (
+                RevisitProfile::IdenticalPayloadDigest,
+                "identical-payload-digest",
+            ),
+            (RevisitProfile::ServerNotModified, "server-not-modified"),
+        ] {
+            let uri_1_0 = format!("http://netpreserve.org/warc/1.0/revisit/{}", name);
+            let uri_1_1 = format!("http://netpreserve.org/warc/1.1/revisit/{}", name);
+            assert_eq!(profile.uri("1.0"), uri_1_0);
+            assert_eq!(profile.uri("WARC/1.1"), uri_1_1);
+            assert_eq!(profile.uri("0.17"), uri_1_0);
+            assert_eq!(profile.to_string(), uri_1_0);
+            assert_eq!(RevisitProfile::from(&uri_1_0), profile);
+            assert_eq!(RevisitProfile::from(format!(" {} ", uri_1_1)), profile);
+        }
+
+        let uri = "http://example.com/revisit/custom";
+        let profile = RevisitProfile::from(uri);
+        assert_eq!(profile, RevisitProfile::Unknown(uri.to_owned()));
+        assert_eq!(profile.uri("1.0"), uri);
+        assert_eq!(profile.uri("1.1"), uri);
+        assert_eq!(RevisitProfile::from(profile.uri("1.1")), profile);
+        assert_eq!(
+            RevisitProfile::from("http://netpreserve.org/warc/1.1/revisit/other"),
+            RevisitProfile::Unknown("http://netpreserve.org/warc/1.1/revisit/other".to_owned())
+        );
+    }
+}
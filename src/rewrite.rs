@@ -0,0 +1,338 @@
+//! Rewriting of the links in archived HTML and CSS for replay.
+//!
+//! A replay service serves captured pages with every link pointing back into the archive, so that
+//! following a link replays the capture of its target rather than the live web. A `Rewriter`
+//! finds links with the same scanning used by link extraction, and by default maps each absolute
+//! HTTP(S) URL to a URL in the style of the Wayback Machine: a prefix, the 14-digit timestamp of
+//! the capture, and the URL. A custom mapping can be given instead.
+//!
+//! Rewritten payloads are returned as conversion records referring to the original capture, which
+//! can be written alongside it or served directly.
+
+use std::fmt;
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{html, http, BufferedBody, Record, RecordType};
+
+type Mapper = Box<dyn Fn(&Url, &DateTime<Utc>) -> Option<String>>;
+
+/// A rewriter of the links in HTML and CSS payloads.
+pub struct Rewriter {
+    mapper: Mapper,
+}
+
+impl fmt::Debug for Rewriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rewriter").finish_non_exhaustive()
+    }
+}
+
+impl Rewriter {
+    /// Create a rewriter mapping each URL to the given prefix, followed by the timestamp of the
+    /// capture being rewritten, a slash, and the URL.
+    pub fn new<S: Into<String>>(prefix: S) -> Self {
+        let prefix = prefix.into();
+        Rewriter::with_mapper(move |url, datetime| {
+            Some(format!(
+                "{}{}/{}",
+                prefix,
+                datetime.format("%Y%m%d%H%M%S"),
+                url
+            ))
+        })
+    }
+
+    /// Create a rewriter mapping URLs with a function, which is given each absolute HTTP(S) URL
+    /// and the date of the capture being rewritten. Links for which it returns `None` are left
+    /// unchanged.
+    pub fn with_mapper<F>(mapper: F) -> Self
+    where
+        F: Fn(&Url, &DateTime<Utc>) -> Option<String> + 'static,
+    {
+        Rewriter {
+            mapper: Box::new(mapper),
+        }
+    }
+
+    /// Return the replacement of a link found in a document at the base URL, if it is rewritten.
+    fn map(&self, base: &Url, link: &str, datetime: &DateTime<Utc>) -> Option<String> {
+        let url = base.join(link.trim()).ok()?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+
+        (self.mapper)(&url, datetime)
+    }
+
+    /// Rewrite the links of an HTML document, resolving them against the base URL.
+    ///
+    /// The attributes holding links are those reported by link extraction. A `base` element
+    /// changes the base URL of the links which follow it. Style attributes and elements are
+    /// rewritten as CSS.
+    pub fn rewrite_html(&self, document: &str, base: &Url, datetime: &DateTime<Utc>) -> String {
+        let lower = document.to_ascii_lowercase();
+        let mut base = base.clone();
+        let mut replacements = Vec::new();
+        for tag in html::tags(document) {
+            let link_attribute = html::link_attribute(&tag.name);
+            for ((name, value), span) in tag.attributes.iter().zip(&tag.spans) {
+                let replacement = if name == "style" {
+                    Some(self.rewrite_css(value, &base, datetime)).filter(|css| css != value)
+                } else if Some(name.as_str()) == link_attribute {
+                    let replacement = self.map(&base, value, datetime);
+                    if tag.name == "base" {
+                        base = base.join(value.trim()).unwrap_or(base);
+                    }
+                    replacement
+                } else {
+                    None
+                };
+                if let Some(replacement) = replacement {
+                    replacements.push((span.clone(), escape(&replacement)));
+                }
+            }
+
+            if tag.name == "style" {
+                let end = lower[tag.end..]
+                    .find("</style")
+                    .map(|end| tag.end + end)
+                    .unwrap_or(document.len());
+                let css = &document[tag.end..end];
+                replacements.push((tag.end..end, self.rewrite_css(css, &base, datetime)));
+            }
+        }
+
+        splice(document, replacements)
+    }
+
+    /// Rewrite the links of a CSS stylesheet, in `url()` values and `@import` rules, resolving
+    /// them against the base URL.
+    pub fn rewrite_css(&self, css: &str, base: &Url, datetime: &DateTime<Utc>) -> String {
+        let replacements = css_links(css)
+            .into_iter()
+            .filter_map(|span| {
+                let replacement = self.map(base, &css[span.clone()], datetime)?;
+                Some((span, replacement))
+            })
+            .collect();
+
+        splice(css, replacements)
+    }
+
+    /// Rewrite the HTML or CSS payload of a response or resource record, returning a conversion
+    /// record holding the rewritten payload.
+    ///
+    /// The conversion record refers to the original, and has its WARC-Target-URI and the media
+    /// type of its payload. Links are resolved against the target URI and mapped with the date of
+    /// the original. `None` is returned for records of other types, payloads of other media
+    /// types, payloads which are not UTF-8, and HTTP payloads with a content or transfer coding.
+    pub fn rewrite<T: BodyKind + AsRef<[u8]>>(
+        &self,
+        record: &Record<T>,
+    ) -> Option<Record<BufferedBody>> {
+        if !matches!(
+            record.warc_type(),
+            RecordType::Response | RecordType::Resource
+        ) {
+            return None;
+        }
+        let target_uri = record.header(WarcHeader::TargetURI)?;
+        let base = Url::parse(&target_uri).ok()?;
+        let declared = record.header(WarcHeader::ContentType)?;
+        let content_type = if http::is_http_message(&declared) {
+            let body = record.body();
+            let identity = |name| {
+                http::header(body, name)
+                    .map(|value| value.eq_ignore_ascii_case("identity"))
+                    .unwrap_or(true)
+            };
+            if !identity("content-encoding") || !identity("transfer-encoding") {
+                return None;
+            }
+            http::header(body, "content-type")?
+        } else {
+            declared.into_owned()
+        };
+        let payload = std::str::from_utf8(record.payload()?).ok()?;
+
+        let media_type = content_type.split(';').next().unwrap_or("");
+        let rewritten = match media_type.trim().to_ascii_lowercase().as_str() {
+            "text/html" | "application/xhtml+xml" => {
+                self.rewrite_html(payload, &base, record.date())
+            }
+            "text/css" => self.rewrite_css(payload, &base, record.date()),
+            _ => return None,
+        };
+
+        let mut conversion = Record::<BufferedBody>::with_body(rewritten);
+        conversion.set_warc_version(record.warc_version());
+        conversion.set_warc_type(RecordType::Conversion);
+        conversion
+            .set_header(WarcHeader::RefersTo, record.warc_id())
+            .ok()?;
+        conversion
+            .set_header(WarcHeader::TargetURI, target_uri.into_owned())
+            .ok()?;
+        conversion
+            .set_header(WarcHeader::ContentType, content_type)
+            .ok()?;
+
+        Some(conversion)
+    }
+}
+
+/// Return the spans of the links in a stylesheet, without their quotes.
+fn css_links(css: &str) -> Vec<Range<usize>> {
+    let lower = css.to_ascii_lowercase();
+    let bytes = css.as_bytes();
+    let skip_whitespace = |mut pos: usize| {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        pos
+    };
+    let quoted = |pos: usize| {
+        let quote = *bytes.get(pos)?;
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+        let end = css[pos + 1..].find(quote as char)?;
+        Some(pos + 1..pos + 1 + end)
+    };
+
+    let mut links = Vec::new();
+    for (start, _) in lower.match_indices("url(") {
+        let pos = skip_whitespace(start + 4);
+        match quoted(pos) {
+            Some(span) => links.push(span),
+            None => {
+                if let Some(end) = css[pos..].find(')') {
+                    let value = css[pos..pos + end].trim_end();
+                    links.push(pos..pos + value.len());
+                }
+            }
+        }
+    }
+    for (start, _) in lower.match_indices("@import") {
+        if let Some(span) = quoted(skip_whitespace(start + 7)) {
+            links.push(span);
+        }
+    }
+    links.retain(|span| !span.is_empty());
+    links.sort_by_key(|span| span.start);
+
+    links
+}
+
+/// Escape text for an HTML attribute value, whether or not it is quoted.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Replace the spans of the text, which must not overlap, with their replacements.
+fn splice(text: &str, mut replacements: Vec<(Range<usize>, String)>) -> String {
+    replacements.sort_by_key(|(span, _)| span.start);
+    let mut spliced = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (span, replacement) in replacements {
+        spliced.push_str(&text[pos..span.start]);
+        spliced.push_str(&replacement);
+        pos = span.end;
+    }
+    spliced.push_str(&text[pos..]);
+
+    spliced
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use url::Url;
+
+    use super::Rewriter;
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType};
+
+    #[test]
+    fn rewrite() {
+        let rewriter = Rewriter::new("http://archive.example/web/");
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let base = Url::parse("http://example.com/dir/page").unwrap();
+
+        let html = concat!(
+            r#"<html><head><style>body { background: url("/bg.png") }</style></head>"#,
+            r#"<body style='background: url(img/a.png)'><a href="next?a=1&amp;b=2">next</a>"#,
+            r#"<a href="mailto:me@example.com">mail</a><a href=#top>top</a>"#,
+            r#"<img src=/logo.png alt="/not-a-link"></body></html>"#,
+        );
+        assert_eq!(
+            rewriter.rewrite_html(html, &base, &date),
+            concat!(
+                r#"<html><head><style>body { background: "#,
+                r#"url("http://archive.example/web/20200102030405/http://example.com/bg.png") }"#,
+                r#"</style></head><body style='background: "#,
+                r#"url(http://archive.example/web/20200102030405/"#,
+                r#"http://example.com/dir/img/a.png)'>"#,
+                r#"<a href="http://archive.example/web/20200102030405/"#,
+                r#"http://example.com/dir/next?a=1&amp;b=2">next</a>"#,
+                r#"<a href="mailto:me@example.com">mail</a>"#,
+                r#"<a href=http://archive.example/web/20200102030405/"#,
+                r#"http://example.com/dir/page#top>top</a>"#,
+                r#"<img src=http://archive.example/web/20200102030405/"#,
+                r#"http://example.com/logo.png alt="/not-a-link"></body></html>"#,
+            )
+        );
+
+        let css = "@import 'print.css'; a { background: url( x.gif ) }";
+        let only_gifs = Rewriter::with_mapper(|url, _| {
+            Some(format!("/archive/{}", url)).filter(|_| url.path().ends_with(".gif"))
+        });
+        assert_eq!(
+            only_gifs.rewrite_css(css, &base, &date),
+            "@import 'print.css'; a { background: url( /archive/http://example.com/dir/x.gif ) }"
+        );
+
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_version("1.0");
+        response.set_warc_type(RecordType::Response);
+        response.set_date(date);
+        response
+            .set_header(WarcHeader::TargetURI, base.as_str())
+            .unwrap();
+        response
+            .set_header(
+                WarcHeader::ContentType,
+                "application/http; msgtype=response",
+            )
+            .unwrap();
+        response.replace_body(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/css; charset=utf-8\r\n\r\n\
+             a { background: url(x.gif) }",
+        );
+        let conversion = rewriter.rewrite(&response).unwrap();
+        assert_eq!(conversion.warc_type(), &RecordType::Conversion);
+        assert_eq!(
+            conversion.header(WarcHeader::RefersTo).as_deref(),
+            Some(response.warc_id())
+        );
+        assert_eq!(
+            conversion.header(WarcHeader::ContentType).as_deref(),
+            Some("text/css; charset=utf-8")
+        );
+        assert_eq!(
+            conversion.body(),
+            &b"a { background: url(http://archive.example/web/20200102030405/\
+               http://example.com/dir/x.gif) }"[..]
+        );
+
+        response.replace_body("HTTP/1.1 200 OK\r\nContent-Type: image/gif\r\n\r\nGIF89a");
+        assert!(rewriter.rewrite(&response).is_none());
+    }
+}
@@ -0,0 +1,307 @@
+//! Access to captured robots.txt files, as described by RFC 9309.
+//!
+//! Crawl-compliance audits ask whether a capture was allowed by the robots.txt in force when it
+//! was made. `governing` finds the capture of that robots.txt in a collection, and `RobotsTxt`
+//! parses it and answers whether a path was allowed for a user agent.
+
+use std::io::{BufRead, Seek};
+
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::replay::normalize;
+use crate::{http, Collection, CollectionEntry, Record, RecordType};
+
+/// An `Allow` or `Disallow` rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub allow: bool,
+    /// The path pattern, which may contain `*` wildcards and end with `$`.
+    pub path: String,
+}
+
+impl Rule {
+    /// Return true if the rule's pattern matches the path.
+    pub fn matches(&self, path: &str) -> bool {
+        let (pattern, anchored) = match self.path.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (self.path.as_str(), false),
+        };
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or("");
+        let mut rest = match path.strip_prefix(first) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let parts: Vec<_> = parts.collect();
+        for (i, part) in parts.iter().enumerate() {
+            let last = i + 1 == parts.len();
+            if last && anchored {
+                return rest.ends_with(part);
+            }
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+
+        !anchored || rest.is_empty()
+    }
+}
+
+/// The rules given to a set of user agents.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Group {
+    /// The user agent tokens the group applies to, lowercased.
+    pub user_agents: Vec<String>,
+    pub rules: Vec<Rule>,
+    /// The `Crawl-delay` of the group, in seconds.
+    pub crawl_delay: Option<f64>,
+}
+
+/// A parsed robots.txt file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RobotsTxt {
+    pub groups: Vec<Group>,
+    /// The URLs given by `Sitemap` lines.
+    pub sitemaps: Vec<String>,
+}
+
+impl RobotsTxt {
+    /// Parse the content of a robots.txt file. Lines which cannot be understood are ignored.
+    pub fn parse(text: &str) -> Self {
+        let mut robots = RobotsTxt::default();
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        robots.groups.push(Group::default());
+                        in_agents = true;
+                    }
+                    let group = robots.groups.last_mut().unwrap();
+                    group.user_agents.push(value.to_lowercase());
+                    continue;
+                }
+                "sitemap" => {
+                    robots.sitemaps.push(value.to_owned());
+                    continue;
+                }
+                _ => {}
+            }
+            in_agents = false;
+            let group = match robots.groups.last_mut() {
+                Some(group) => group,
+                None => continue,
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+                    allow: key == "allow",
+                    path: value.to_owned(),
+                }),
+                "crawl-delay" => group.crawl_delay = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        robots
+    }
+
+    /// Parse the robots.txt held by a record.
+    ///
+    /// The record must be a response with status 200, or a resource record. `None` is returned
+    /// for other records, and for bodies which are not UTF-8.
+    pub fn from_record<T: BodyKind + AsRef<[u8]>>(record: &Record<T>) -> Option<Self> {
+        let body = match record.warc_type() {
+            RecordType::Response => {
+                if http::status(record.body())? != 200 {
+                    return None;
+                }
+                http::split_head(record.body())?.1
+            }
+            RecordType::Resource => record.body(),
+            _ => return None,
+        };
+
+        std::str::from_utf8(body).ok().map(RobotsTxt::parse)
+    }
+
+    /// Return the group which applies to the user agent.
+    ///
+    /// The group naming the longest token contained in the user agent applies, or else the group
+    /// for `*`.
+    pub fn group_for(&self, user_agent: &str) -> Option<&Group> {
+        let user_agent = user_agent.to_lowercase();
+        self.groups
+            .iter()
+            .flat_map(|group| group.user_agents.iter().map(move |token| (token, group)))
+            .filter(|(token, _)| *token != "*" && user_agent.contains(token.as_str()))
+            .max_by_key(|(token, _)| token.len())
+            .map(|(_, group)| group)
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| group.user_agents.iter().any(|token| token == "*"))
+            })
+    }
+
+    /// Return true if the user agent may fetch the path, which may include a query.
+    ///
+    /// The longest matching rule decides; of two equally long, `Allow` wins. Paths matched by no
+    /// rule are allowed, as is `/robots.txt` itself.
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        let group = match self.group_for(user_agent) {
+            Some(group) => group,
+            None => return true,
+        };
+
+        group
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .max_by_key(|rule| (rule.path.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    /// Return the crawl delay which applies to the user agent, in seconds.
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<f64> {
+        self.group_for(user_agent)?.crawl_delay
+    }
+}
+
+/// Return the URL of the robots.txt governing the URL.
+pub fn robots_url(url: &str) -> Option<String> {
+    let url = Url::parse(url.trim().trim_start_matches('<').trim_end_matches('>')).ok()?;
+    let host = url.host_str()?;
+    let robots = match url.port() {
+        Some(port) => format!("{}://{}:{}/robots.txt", url.scheme(), host, port),
+        None => format!("{}://{}/robots.txt", url.scheme(), host),
+    };
+
+    Some(normalize(&robots))
+}
+
+/// Return the capture of the robots.txt governing a record: the latest capture of the robots.txt
+/// of the record's scheme, host and port made no later than the record.
+///
+/// `None` is returned if the record has no WARC-Target-URI, or no such capture exists.
+pub fn governing<'c, R: BufRead + Seek, T: BodyKind>(
+    collection: &'c Collection<R>,
+    record: &Record<T>,
+) -> Option<&'c CollectionEntry> {
+    let robots = robots_url(&record.header(WarcHeader::TargetURI)?)?;
+    collection
+        .entries()
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry.record.warc_type(),
+                RecordType::Response | RecordType::Revisit | RecordType::Resource
+            )
+        })
+        .filter(|entry| entry.record.date() <= record.date())
+        .filter(|entry| {
+            entry
+                .record
+                .header(WarcHeader::TargetURI)
+                .is_some_and(|uri| normalize(&uri) == robots)
+        })
+        .max_by_key(|entry| (*entry.record.date(), entry.offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{governing, robots_url, RobotsTxt};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcWriter};
+
+    const ROBOTS: &str = "\
+        User-agent: *\n\
+        Disallow: /private\n\
+        Allow: /private/open$\n\
+        Disallow: /*.pdf$\n\
+        \n\
+        User-agent: heritrix\n\
+        User-agent: wget\n\
+        Disallow: /\n\
+        Crawl-delay: 2.5 # seconds\n\
+        Sitemap: http://example.com/sitemap.xml\n";
+
+    #[test]
+    fn rules() {
+        let robots = RobotsTxt::parse(ROBOTS);
+        assert_eq!(robots.groups.len(), 2);
+        assert_eq!(robots.sitemaps, vec!["http://example.com/sitemap.xml"]);
+
+        let agent = "Mozilla/5.0 (compatible; archiver)";
+        assert!(robots.is_allowed(agent, "/"));
+        assert!(!robots.is_allowed(agent, "/private/x"));
+        assert!(robots.is_allowed(agent, "/private/open"));
+        assert!(!robots.is_allowed(agent, "/private/open/x"));
+        assert!(!robots.is_allowed(agent, "/a/b.pdf"));
+        assert!(robots.is_allowed(agent, "/a/b.pdf?x"));
+        assert_eq!(robots.crawl_delay(agent), None);
+
+        let agent = "Mozilla/5.0 (compatible; heritrix/3.4)";
+        assert!(!robots.is_allowed(agent, "/"));
+        assert!(robots.is_allowed(agent, "/robots.txt"));
+        assert_eq!(robots.crawl_delay(agent), Some(2.5));
+    }
+
+    #[test]
+    fn governing_capture() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        let mut records = Vec::new();
+        for (uri, day, body) in [
+            ("http://example.com/robots.txt", 1, "Disallow: /"),
+            (
+                "http://example.com/robots.txt",
+                3,
+                "User-agent: *\nDisallow: /a",
+            ),
+            (
+                "https://example.com/robots.txt",
+                4,
+                "User-agent: *\nDisallow: /",
+            ),
+            ("http://example.com/a", 5, ""),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Response);
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record.set_date(Utc.with_ymd_and_hms(2020, 1, day, 0, 0, 0).unwrap());
+            record.replace_body(format!("HTTP/1.1 200 OK\r\n\r\n{}", body));
+            writer.write(&record).unwrap();
+            records.push(record);
+        }
+
+        let mut collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let entry = governing(&collection, &records[3]).unwrap();
+        assert_eq!(entry.record.warc_id(), records[1].warc_id());
+
+        let robots = collection.read_record(entry.offset).unwrap();
+        let robots = RobotsTxt::from_record(&robots).unwrap();
+        assert!(!robots.is_allowed("wget", "/a"));
+        assert!(governing(&collection, &records[0]).is_some());
+        assert_eq!(
+            robots_url("HTTP://Example.com:8080/a?b"),
+            Some("http://example.com:8080/robots.txt".to_owned())
+        );
+    }
+}
@@ -0,0 +1,627 @@
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "gzip")]
+use crate::MemberCompression;
+
+use crate::header::WarcHeader;
+use crate::segment::continuation_headers;
+use crate::{
+    verify_segments, BufferedBody, EmptyBody, RawRecordHeader, Record, Segment, StreamingBody,
+    WarcWriter, WarcinfoBuilder, WarcinfoPreset,
+};
+
+const MB: usize = 1_048_576;
+
+/// A writer which spreads records across a sequence of outputs of bounded size.
+///
+/// A new output is opened whenever the next record would not fit within the size budget of the
+/// current one. Records larger than the budget of an entire output are either written whole to an
+/// output of their own or, when segmentation is enabled, split into a first segment and
+/// continuation records which fill consecutive outputs.
+///
+/// Each output can start with a warcinfo record describing it, which counts towards its size
+/// budget.
+///
+/// Outputs can be gzip-compressed with one member per record. The size budget always counts the
+/// bytes written to an output, which for a compressed output are compressed bytes. Since the
+/// compressed size of a record is not known until it is written, a compressed output is rotated
+/// once it reaches its budget, as Heritrix does, and so exceeds it by at most one record.
+/// Segmentation cannot know compressed sizes either: it judges whether a record is oversized, and
+/// how much of its body goes in each segment, by the record's uncompressed size against the
+/// compressed bytes left in the output, so a compressed output holding a segment ends below its
+/// budget, or above it by at most the gzip framing of an incompressible segment.
+pub struct RotatingWarcWriter<W, F> {
+    open: F,
+    max_file_size: u64,
+    segment_oversized: bool,
+    current: Option<WarcWriter<W>>,
+    current_len: u64,
+    file_index: u64,
+    warcinfo: Option<WarcinfoBuilder>,
+    warcinfo_preset: WarcinfoPreset,
+    /// The number of bytes of the warcinfo record at the start of the current output.
+    warcinfo_len: u64,
+    file_name: Option<Box<dyn Fn(u64) -> String>>,
+    #[cfg(feature = "gzip")]
+    gzip: bool,
+}
+
+impl<W: Write, F: FnMut(u64) -> io::Result<W>> RotatingWarcWriter<W, F> {
+    /// Create a new writer which opens each output by calling `open` with its index, starting at
+    /// zero, and limits each output to `max_file_size` bytes.
+    pub fn new(max_file_size: u64, open: F) -> Self {
+        RotatingWarcWriter {
+            open,
+            max_file_size,
+            segment_oversized: false,
+            current: None,
+            current_len: 0,
+            file_index: 0,
+            warcinfo: None,
+            warcinfo_preset: WarcinfoPreset::default(),
+            warcinfo_len: 0,
+            file_name: None,
+            #[cfg(feature = "gzip")]
+            gzip: false,
+        }
+    }
+
+    /// Set whether each record is written as its own gzip member.
+    #[cfg(feature = "gzip")]
+    pub fn set_gzip(&mut self, enabled: bool) {
+        self.gzip = enabled;
+    }
+
+    /// Set the fields of a warcinfo record written at the start of every output, or `None` to
+    /// write none.
+    ///
+    /// Unless the fields give a file name, the record's WARC-Filename is the name of the output,
+    /// when the writer knows it.
+    pub fn set_warcinfo(&mut self, warcinfo: Option<WarcinfoBuilder>) {
+        self.warcinfo = warcinfo;
+    }
+
+    /// Set the layout of the warcinfo records written at the start of every output.
+    ///
+    /// The default preset is `WarcinfoPreset::Heritrix`.
+    pub fn set_warcinfo_preset(&mut self, preset: WarcinfoPreset) {
+        self.warcinfo_preset = preset;
+    }
+
+    /// Set whether records larger than the size budget are split into segments.
+    pub fn set_segment_oversized(&mut self, enabled: bool) {
+        self.segment_oversized = enabled;
+    }
+
+    /// Return the index of the output currently being written.
+    pub fn file_index(&self) -> u64 {
+        self.file_index
+    }
+
+    /// Return the number of bytes written to the current output, after any compression.
+    pub fn current_len(&self) -> u64 {
+        self.current_len
+    }
+
+    /// Write a single record.
+    ///
+    /// The number of bytes written, across all outputs, is returned upon success.
+    pub fn write(&mut self, record: &Record<BufferedBody>) -> io::Result<usize> {
+        let headers = record
+            .header_block(None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.write_parts(headers, record.content_length(), &mut record.body())
+    }
+
+    /// Write a single record with a streaming body.
+    ///
+    /// The body is copied to the output as it is read, and is not held in memory.
+    pub fn write_streaming<R: Read>(
+        &mut self,
+        record: Record<StreamingBody<'_, R>>,
+    ) -> io::Result<usize> {
+        let len = record.content_length();
+        let headers = record
+            .header_block(None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let (_, mut body) = record.into_parts();
+        self.write_parts(headers, len, &mut body)
+    }
+
+    /// Flush the current output, if any.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.current {
+            Some(ref mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush and close the current output, so the next record is written to a new one.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.current.take() {
+            writer.flush()?;
+            self.file_index += 1;
+        }
+        self.current_len = 0;
+
+        Ok(())
+    }
+
+    fn write_parts(
+        &mut self,
+        headers: RawRecordHeader,
+        len: u64,
+        body: &mut dyn Read,
+    ) -> io::Result<usize> {
+        let whole_len = raw_len(&headers, len);
+
+        self.open_current()?;
+        #[allow(unused_mut)]
+        let mut fits = self.current_len + whole_len <= self.max_file_size;
+        if self.segment_oversized
+            && !fits
+            && whole_len > self.max_file_size.saturating_sub(self.warcinfo_len)
+        {
+            return self.write_segments(headers, len, body);
+        }
+        #[cfg(feature = "gzip")]
+        {
+            if self.gzip {
+                fits = self.current_len < self.max_file_size;
+            }
+        }
+
+        if !fits && self.current_len > self.warcinfo_len {
+            self.rotate()?;
+        }
+        self.write_raw(headers, body, len)
+    }
+
+    fn write_segments(
+        &mut self,
+        headers: RawRecordHeader,
+        len: u64,
+        body: &mut dyn Read,
+    ) -> io::Result<usize> {
+        let continuation = continuation_headers(&headers);
+        let mut first = headers;
+        first.as_mut().remove(&WarcHeader::BlockDigest);
+
+        let mut bytes_written = 0;
+        let mut remaining = len;
+        let mut segment_number = 1u64;
+        let mut segments = Vec::new();
+        while remaining > 0 {
+            let mut segment = if segment_number == 1 {
+                first.clone()
+            } else {
+                let mut segment = continuation.clone();
+                segment.as_mut().insert(
+                    WarcHeader::RecordID,
+                    Record::<EmptyBody>::generate_record_id().into_bytes(),
+                );
+                segment
+            };
+            segment.as_mut().insert(
+                WarcHeader::SegmentNumber,
+                segment_number.to_string().into_bytes(),
+            );
+            if segment_number > 1 {
+                segment
+                    .as_mut()
+                    .insert(WarcHeader::SegmentTotalLength, len.to_string().into_bytes());
+            }
+
+            self.open_current()?;
+            let mut chunk_len = self.chunk_len(&segment, remaining);
+            if chunk_len == 0 && self.current_len > self.warcinfo_len {
+                self.rotate()?;
+                self.open_current()?;
+                chunk_len = self.chunk_len(&segment, remaining);
+            }
+            if chunk_len == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "size budget too small to hold a segment header",
+                ));
+            }
+            if segment_number > 1 && chunk_len < remaining {
+                segment.as_mut().remove(&WarcHeader::SegmentTotalLength);
+            }
+
+            let segment = with_content_length(segment, chunk_len);
+            segments.push(Segment::from_raw(&segment));
+            bytes_written += self.write_raw(segment, body, chunk_len)?;
+
+            remaining -= chunk_len;
+            segment_number += 1;
+            if remaining > 0 {
+                self.rotate()?;
+            }
+        }
+        debug_assert_eq!(verify_segments(&segments), Ok(len));
+
+        Ok(bytes_written)
+    }
+
+    /// Return the largest body which fits in the current output alongside the given headers.
+    fn chunk_len(&self, headers: &RawRecordHeader, remaining: u64) -> u64 {
+        let budget = self.max_file_size.saturating_sub(self.current_len);
+        let overhead = raw_len(&with_content_length(headers.clone(), remaining), 0);
+
+        std::cmp::min(remaining, budget.saturating_sub(overhead))
+    }
+
+    /// Open the next output if none is open, and write its warcinfo record.
+    fn open_current(&mut self) -> io::Result<()> {
+        if self.current.is_some() {
+            return Ok(());
+        }
+        let mut writer = WarcWriter::new((self.open)(self.file_index)?);
+        #[cfg(feature = "gzip")]
+        {
+            if self.gzip {
+                writer.set_member_compression(|_, _| MemberCompression::Compressed);
+            }
+        }
+        self.warcinfo_len = 0;
+        if let Some(ref warcinfo) = self.warcinfo {
+            let mut warcinfo = warcinfo.clone();
+            if let (None, Some(file_name)) = (&warcinfo.filename, &self.file_name) {
+                warcinfo = warcinfo.filename(file_name(self.file_index));
+            }
+            writer.set_warcinfo_preset(self.warcinfo_preset);
+            self.warcinfo_len = writer.write_warcinfo(&warcinfo)? as u64;
+        }
+        self.current = Some(writer);
+        self.current_len = self.warcinfo_len;
+
+        Ok(())
+    }
+
+    /// Write a record to the current output, copying `len` bytes of its body from `body`.
+    fn write_raw(
+        &mut self,
+        headers: RawRecordHeader,
+        body: &mut dyn Read,
+        len: u64,
+    ) -> io::Result<usize> {
+        self.open_current()?;
+        let written = self
+            .current
+            .as_mut()
+            .unwrap()
+            .write_raw_streaming(headers, body, len)?;
+        self.current_len += written as u64;
+
+        Ok(written)
+    }
+}
+
+impl
+    RotatingWarcWriter<BufWriter<fs::File>, Box<dyn FnMut(u64) -> io::Result<BufWriter<fs::File>>>>
+{
+    /// Create a new writer which writes files named `<prefix>-<index>.warc` in the given directory.
+    ///
+    /// The index is zero-padded to five digits.
+    pub fn in_dir<P: AsRef<Path>, S: Into<String>>(dir: P, prefix: S, max_file_size: u64) -> Self {
+        RotatingWarcWriter::in_dir_with_extension(dir, prefix, "warc", max_file_size)
+    }
+
+    /// Create a new writer which writes gzip-compressed files named `<prefix>-<index>.warc.gz` in
+    /// the given directory, with one member per record.
+    ///
+    /// The index is zero-padded to five digits.
+    #[cfg(feature = "gzip")]
+    pub fn in_dir_gzip<P: AsRef<Path>, S: Into<String>>(
+        dir: P,
+        prefix: S,
+        max_file_size: u64,
+    ) -> Self {
+        let mut writer =
+            RotatingWarcWriter::in_dir_with_extension(dir, prefix, "warc.gz", max_file_size);
+        writer.set_gzip(true);
+
+        writer
+    }
+
+    fn in_dir_with_extension<P: AsRef<Path>, S: Into<String>>(
+        dir: P,
+        prefix: S,
+        extension: &'static str,
+        max_file_size: u64,
+    ) -> Self {
+        let dir: PathBuf = dir.as_ref().to_owned();
+        let prefix = prefix.into();
+        let name = move |index: u64| format!("{}-{:05}.{}", prefix, index, extension);
+        let file_name = name.clone();
+        let open = move |index: u64| {
+            let path = dir.join(name(index));
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)?;
+            Ok(BufWriter::with_capacity(MB, file))
+        };
+
+        let mut writer = RotatingWarcWriter::new(max_file_size, Box::new(open) as Box<_>);
+        writer.file_name = Some(Box::new(file_name));
+
+        writer
+    }
+}
+
+fn with_content_length(mut headers: RawRecordHeader, len: u64) -> RawRecordHeader {
+    headers
+        .as_mut()
+        .insert(WarcHeader::ContentLength, len.to_string().into_bytes());
+
+    headers
+}
+
+/// Return the number of bytes `WarcWriter::write_raw` writes for the given headers and body length.
+fn raw_len(headers: &RawRecordHeader, body_len: u64) -> u64 {
+    let mut len = 5 + headers.version.len() as u64 + 2;
+    for (name, value) in headers.as_ref().iter() {
+        len += name.to_string().len() as u64 + 2 + value.len() as u64 + 2;
+    }
+
+    len + 2 + body_len + 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotatingWarcWriter;
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter, WarcinfoBuilder};
+
+    use chrono::{TimeZone, Utc};
+
+    use std::cell::RefCell;
+    use std::io::{self, BufReader, Cursor, Write};
+    use std::rc::Rc;
+
+    struct Output(Files, usize);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut()[self.1].extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    type Files = Rc<RefCell<Vec<Vec<u8>>>>;
+
+    fn writer(
+        max_file_size: u64,
+    ) -> (
+        Files,
+        RotatingWarcWriter<Output, impl FnMut(u64) -> io::Result<Output>>,
+    ) {
+        let files = Rc::new(RefCell::new(Vec::new()));
+        let open_files = files.clone();
+        let writer = RotatingWarcWriter::new(max_file_size, move |index| {
+            let mut files = open_files.borrow_mut();
+            assert_eq!(files.len() as u64, index);
+            files.push(Vec::new());
+            Ok(Output(open_files.clone(), index as usize))
+        });
+
+        (files, writer)
+    }
+
+    fn read_all(file: &[u8]) -> Vec<Record<BufferedBody>> {
+        WarcReader::new(BufReader::new(Cursor::new(file)))
+            .iter_records()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotation() {
+        let record = Record::<BufferedBody>::with_body(vec![b'a'; 100]);
+        let (files, mut writer) = writer(record.serialized_len() * 2);
+        for _ in 0..5 {
+            writer.write(&record).unwrap();
+        }
+
+        let files = files.borrow();
+        assert_eq!(files.len(), 3);
+        assert_eq!(read_all(&files[0]).len(), 2);
+        assert_eq!(read_all(&files[2]).len(), 1);
+    }
+
+    #[test]
+    fn warcinfo() {
+        let warcinfo = WarcinfoBuilder::new()
+            .software("rotating-test")
+            .date(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+        let mut info = Vec::new();
+        let info_len = WarcWriter::new(&mut info)
+            .write_warcinfo(&warcinfo)
+            .unwrap() as u64;
+        let record = Record::<BufferedBody>::with_body(vec![b'a'; 100]);
+        let budget = info_len + record.serialized_len() * 2;
+
+        let (files, mut writer) = writer(budget);
+        writer.set_warcinfo(Some(warcinfo));
+        for _ in 0..5 {
+            writer.write(&record).unwrap();
+        }
+
+        let files = files.borrow();
+        assert_eq!(files.len(), 3);
+        for file in files.iter() {
+            assert!(file.len() as u64 <= budget);
+            let records = read_all(file);
+            assert_eq!(records[0].warc_type(), &RecordType::WarcInfo);
+            assert!(String::from_utf8_lossy(records[0].body()).contains("rotating-test"));
+        }
+        assert_eq!(read_all(&files[1]).len(), 3);
+        assert_eq!(read_all(&files[2]).len(), 2);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_files() {
+        let dir = std::env::temp_dir().join(format!("warc-rotating-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let record = Record::<BufferedBody>::with_body(vec![b'a'; 1000]);
+        let mut writer = RotatingWarcWriter::in_dir_gzip(&dir, "crawl", 2000);
+        writer.set_warcinfo(Some(WarcinfoBuilder::new().software("rotating-test")));
+        for _ in 0..20 {
+            writer.write(&record).unwrap();
+        }
+        let files = writer.file_index() + 1;
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert!(files > 1);
+        let mut written = 0;
+        for index in 0..files {
+            let name = format!("crawl-{:05}.warc.gz", index);
+            let path = dir.join(&name);
+            let records: Vec<_> = WarcReader::from_path_detect(&path)
+                .unwrap()
+                .iter_records()
+                .map(|r| r.unwrap())
+                .collect();
+            if index + 1 < files {
+                // rotated once the budget was reached, which takes several compressed records
+                assert!(std::fs::metadata(&path).unwrap().len() >= 2000);
+                assert!(records.len() > 2);
+            }
+            assert_eq!(records[0].header(WarcHeader::Filename).unwrap(), name);
+            assert_eq!(records[1].body(), record.body());
+            written += records.len() - 1;
+        }
+        assert_eq!(written, 20);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_whole() {
+        let small = Record::<BufferedBody>::with_body(vec![b'a'; 10]);
+        let large = Record::<BufferedBody>::with_body(vec![b'b'; 2000]);
+        let (files, mut writer) = writer(600);
+        writer.write(&small).unwrap();
+        writer.write(&large).unwrap();
+        writer.write(&small).unwrap();
+
+        let files = files.borrow();
+        assert_eq!(files.len(), 3);
+        let records = read_all(&files[1]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].warc_id(), large.warc_id());
+        assert_eq!(records[0].body(), large.body());
+    }
+
+    #[test]
+    fn segmentation() {
+        let small = Record::<BufferedBody>::with_body(vec![b'a'; 10]);
+        let body: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let mut large = Record::<BufferedBody>::with_body(body.clone());
+        large
+            .set_header(WarcHeader::TargetURI, "http://example.com/large")
+            .unwrap();
+
+        let (files, mut writer) = writer(800);
+        writer.set_segment_oversized(true);
+        writer.write(&small).unwrap();
+        writer.write(&large).unwrap();
+
+        let files = files.borrow();
+        assert!(files.len() > 3);
+        let mut segments = Vec::new();
+        for (i, file) in files.iter().enumerate() {
+            assert!(file.len() as u64 <= 800);
+            let records = read_all(file);
+            let expected_records = if i == 0 { 2 } else { 1 };
+            assert_eq!(records.len(), expected_records);
+            segments.push(records.into_iter().last().unwrap());
+        }
+
+        let first = &segments[0];
+        assert_eq!(first.warc_id(), large.warc_id());
+        assert_eq!(first.warc_type(), &RecordType::Resource);
+        assert_eq!(first.header(WarcHeader::SegmentNumber).unwrap(), "1");
+
+        let mut joined = Vec::new();
+        for (n, segment) in segments.iter().enumerate() {
+            joined.extend_from_slice(segment.body());
+            if n == 0 {
+                continue;
+            }
+            assert_eq!(segment.warc_type(), &RecordType::Continuation);
+            assert_eq!(
+                segment.header(WarcHeader::SegmentOriginID).unwrap(),
+                large.warc_id()
+            );
+            assert_eq!(
+                segment.header(WarcHeader::SegmentNumber).unwrap(),
+                (n + 1).to_string()
+            );
+            assert_eq!(
+                segment.header(WarcHeader::TargetURI).unwrap(),
+                "http://example.com/large"
+            );
+            assert_eq!(
+                segment.header(WarcHeader::SegmentTotalLength).is_some(),
+                n == segments.len() - 1
+            );
+        }
+        assert_eq!(joined, body);
+        assert_eq!(
+            segments
+                .last()
+                .unwrap()
+                .header(WarcHeader::SegmentTotalLength)
+                .unwrap(),
+            "3000"
+        );
+    }
+
+    #[test]
+    fn streaming_segmentation() {
+        let body = vec![b'z'; 1000];
+        let (files, mut writer) = writer(500);
+        writer.set_segment_oversized(true);
+
+        let mut stream = Cursor::new(body.clone());
+        let mut len = body.len() as u64;
+        let record = Record::<BufferedBody>::default()
+            .strip_body()
+            .add_fixed_stream(&mut stream, &mut len)
+            .unwrap();
+        writer.write_streaming(record).unwrap();
+
+        let joined: Vec<u8> = files
+            .borrow()
+            .iter()
+            .flat_map(|file| read_all(file).into_iter().flat_map(|r| r.body().to_vec()))
+            .collect();
+        assert_eq!(joined, body);
+    }
+
+    #[test]
+    fn streaming_declared_length() {
+        let (files, mut writer) = writer(u64::MAX);
+
+        let mut stream = Cursor::new(vec![b'z'; 10]);
+        let mut len = 1 << 40;
+        let record = Record::<BufferedBody>::default()
+            .strip_body()
+            .add_fixed_stream(&mut stream, &mut len)
+            .unwrap();
+        let err = writer.write_streaming(record).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(files.borrow()[0].ends_with(&[b'z'; 10]));
+    }
+}
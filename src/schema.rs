@@ -0,0 +1,240 @@
+use std::fmt;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{RawRecordHeader, Record, RecordType};
+
+/// A header which records of some types must carry.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    record_types: Vec<RecordType>,
+    header: WarcHeader,
+    allowed: Option<Vec<String>>,
+}
+
+/// A set of headers an institution requires beyond those required by the standard, such as an
+/// extension header naming the collection every response belongs to.
+///
+/// A schema can be checked against a record directly, or set on a `WarcWriter` so that records
+/// breaking it are refused.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    rules: Vec<Rule>,
+}
+
+/// A way in which a record breaks a `Schema`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    pub record_type: RecordType,
+    pub header: WarcHeader,
+    /// The value of the header, if it was present but not one of the values allowed.
+    pub value: Option<String>,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(ref value) => write!(
+                f,
+                "{} record has a disallowed value for {}: {:?}",
+                self.record_type, self.header, value
+            ),
+            None => write!(
+                f,
+                "{} record is missing required header: {}",
+                self.record_type, self.header
+            ),
+        }
+    }
+}
+
+impl Schema {
+    /// Create a schema with no requirements.
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// Require records of the given types to carry a header. An empty list of types applies the
+    /// requirement to records of every type.
+    pub fn require<H: Into<WarcHeader>>(mut self, record_types: &[RecordType], header: H) -> Self {
+        self.rules.push(Rule {
+            record_types: record_types.to_vec(),
+            header: header.into(),
+            allowed: None,
+        });
+
+        self
+    }
+
+    /// Require records of the given types to carry a header with one of the given values. An
+    /// empty list of types applies the requirement to records of every type.
+    pub fn require_one_of<H: Into<WarcHeader>>(
+        mut self,
+        record_types: &[RecordType],
+        header: H,
+        values: &[&str],
+    ) -> Self {
+        self.rules.push(Rule {
+            record_types: record_types.to_vec(),
+            header: header.into(),
+            allowed: Some(values.iter().map(|value| value.to_string()).collect()),
+        });
+
+        self
+    }
+
+    /// Return true if the schema has no requirements.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check a record against the schema, returning every violation found.
+    pub fn check<T: BodyKind>(&self, record: &Record<T>) -> Vec<SchemaViolation> {
+        self.check_with(record.warc_type(), |header| {
+            record
+                .header(header.clone())
+                .map(|value| value.into_owned())
+        })
+    }
+
+    /// Check a raw header block against the schema, returning every violation found.
+    pub fn check_raw(&self, headers: &RawRecordHeader) -> Vec<SchemaViolation> {
+        let value = |header: &WarcHeader| {
+            headers
+                .as_ref()
+                .get(header)
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        let record_type = value(&WarcHeader::WarcType)
+            .map(RecordType::from)
+            .unwrap_or_else(|| RecordType::Unknown(String::new()));
+
+        self.check_with(&record_type, value)
+    }
+
+    fn check_with<F>(&self, record_type: &RecordType, value: F) -> Vec<SchemaViolation>
+    where
+        F: Fn(&WarcHeader) -> Option<String>,
+    {
+        self.rules
+            .iter()
+            .filter(|rule| rule.record_types.is_empty() || rule.record_types.contains(record_type))
+            .filter_map(|rule| {
+                let violation = |value| SchemaViolation {
+                    record_type: record_type.clone(),
+                    header: rule.header.clone(),
+                    value,
+                };
+                match (value(&rule.header), &rule.allowed) {
+                    (None, _) => Some(violation(None)),
+                    (Some(value), Some(allowed)) if !allowed.contains(&value) => {
+                        Some(violation(Some(value)))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schema, SchemaViolation};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType};
+
+    fn schema() -> Schema {
+        Schema::new()
+            .require(&[RecordType::Response], "X-Crawl-Job")
+            .require_one_of(&[], "X-Access", &["open", "embargoed"])
+    }
+
+    fn record(record_type: RecordType, headers: &[(&str, &str)]) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(record_type);
+        for &(header, value) in headers {
+            record.set_header(WarcHeader::from(header), value).unwrap();
+        }
+
+        record
+    }
+
+    #[test]
+    fn passing() {
+        assert!(Schema::new().is_empty());
+        assert!(!schema().is_empty());
+
+        let response = record(
+            RecordType::Response,
+            &[("X-Crawl-Job", "weekly"), ("X-Access", "open")],
+        );
+        assert!(schema().check(&response).is_empty());
+
+        let resource = record(RecordType::Resource, &[("X-Access", "embargoed")]);
+        assert!(schema().check(&resource).is_empty());
+    }
+
+    #[test]
+    fn missing_header() {
+        let response = record(RecordType::Response, &[("X-Access", "open")]);
+        let violations = schema().check(&response);
+        assert_eq!(
+            violations,
+            vec![SchemaViolation {
+                record_type: RecordType::Response,
+                header: WarcHeader::from("X-Crawl-Job"),
+                value: None,
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "response record is missing required header: x-crawl-job"
+        );
+
+        let resource = record(RecordType::Resource, &[]);
+        assert_eq!(
+            schema().check(&resource),
+            vec![SchemaViolation {
+                record_type: RecordType::Resource,
+                header: WarcHeader::from("X-Access"),
+                value: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn disallowed_value() {
+        let resource = record(RecordType::Resource, &[("X-Access", "private")]);
+        let violations = schema().check(&resource);
+        assert_eq!(
+            violations,
+            vec![SchemaViolation {
+                record_type: RecordType::Resource,
+                header: WarcHeader::from("X-Access"),
+                value: Some("private".to_owned()),
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "resource record has a disallowed value for x-access: \"private\""
+        );
+    }
+
+    #[test]
+    fn raw_headers() {
+        let response = record(RecordType::Response, &[("X-Access", "private")]);
+        let (headers, _) = response.into_raw_parts();
+        let violations: Vec<_> = schema()
+            .check_raw(&headers)
+            .into_iter()
+            .map(|violation| (violation.header, violation.value))
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                (WarcHeader::from("X-Crawl-Job"), None),
+                (WarcHeader::from("X-Access"), Some("private".to_owned())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{BufferedBody, EmptyBody, Error, RawRecordHeader, Record, RecordType};
+
+/// The segmentation headers of one record of a segmented logical record.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Segment {
+    /// The WARC-Record-ID of the record.
+    pub record_id: String,
+    /// The WARC-Segment-Number of the record.
+    pub number: Option<u64>,
+    /// The WARC-Segment-Origin-ID of the record.
+    pub origin_id: Option<String>,
+    /// The WARC-Segment-Total-Length of the record.
+    pub total_length: Option<u64>,
+    /// The length of the record's block.
+    pub content_length: u64,
+}
+
+impl Segment {
+    /// Read the segmentation headers of a record.
+    pub fn from_record<T: BodyKind>(record: &Record<T>) -> Self {
+        let number = |header| {
+            record
+                .header(header)
+                .and_then(|value| value.trim().parse().ok())
+        };
+
+        Segment {
+            record_id: record.warc_id().to_owned(),
+            number: number(WarcHeader::SegmentNumber),
+            origin_id: record
+                .header(WarcHeader::SegmentOriginID)
+                .map(|value| value.into_owned()),
+            total_length: number(WarcHeader::SegmentTotalLength),
+            content_length: record.content_length(),
+        }
+    }
+
+    /// Read the segmentation headers of a raw record header.
+    pub fn from_raw(headers: &RawRecordHeader) -> Self {
+        let value = |header: &WarcHeader| {
+            headers
+                .as_ref()
+                .get(header)
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        let number = |header: &WarcHeader| value(header).and_then(|v| v.trim().parse().ok());
+
+        Segment {
+            record_id: value(&WarcHeader::RecordID).unwrap_or_default(),
+            number: number(&WarcHeader::SegmentNumber),
+            origin_id: value(&WarcHeader::SegmentOriginID),
+            total_length: number(&WarcHeader::SegmentTotalLength),
+            content_length: number(&WarcHeader::ContentLength).unwrap_or_default(),
+        }
+    }
+}
+
+/// A problem with the segments of a logical record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SegmentError {
+    /// No segments were given.
+    Empty,
+    /// The segment at the index has no WARC-Segment-Number.
+    MissingNumber(usize),
+    /// The segment numbers do not count up from 1.
+    Discontinuity { expected: u64, found: u64 },
+    /// The segment at the index does not name the first segment as its origin.
+    OriginMismatch(usize),
+    /// The last segment has no WARC-Segment-Total-Length.
+    MissingTotalLength,
+    /// A segment other than the last has a WARC-Segment-Total-Length.
+    UnexpectedTotalLength(usize),
+    /// The declared total length is not the sum of the segments' block lengths.
+    TotalLengthMismatch { declared: u64, actual: u64 },
+}
+
+impl fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SegmentError::Empty => write!(f, "No segments."),
+            SegmentError::MissingNumber(i) => write!(f, "Segment {} has no segment number.", i),
+            SegmentError::Discontinuity { expected, found } => {
+                write!(f, "Expected segment number {}, found {}.", expected, found)
+            }
+            SegmentError::OriginMismatch(i) => {
+                write!(f, "Segment {} does not refer to the first segment.", i)
+            }
+            SegmentError::MissingTotalLength => {
+                write!(f, "Last segment has no segment total length.")
+            }
+            SegmentError::UnexpectedTotalLength(i) => {
+                write!(f, "Segment {} has a total length but is not the last.", i)
+            }
+            SegmentError::TotalLengthMismatch { declared, actual } => write!(
+                f,
+                "Segment total length is {}, but the segments hold {} bytes.",
+                declared, actual
+            ),
+        }
+    }
+}
+
+impl error::Error for SegmentError {}
+
+/// Return the WARC-Segment-Total-Length of a logical record made of the given segments.
+///
+/// This is the sum of the segments' block lengths; the headers and record separators of the
+/// segments are not counted.
+pub fn segment_total_length(segments: &[Segment]) -> u64 {
+    segments.iter().map(|segment| segment.content_length).sum()
+}
+
+/// Check that the segments, in order, form a complete logical record.
+///
+/// The segments must be numbered consecutively from 1, each continuation must name the first
+/// segment as its origin, and only the last segment may declare the total length, which must
+/// match the segments' block lengths. The total length is returned.
+pub fn verify_segments(segments: &[Segment]) -> Result<u64, SegmentError> {
+    let first = segments.first().ok_or(SegmentError::Empty)?;
+    for (i, segment) in segments.iter().enumerate() {
+        let number = segment.number.ok_or(SegmentError::MissingNumber(i))?;
+        if number != i as u64 + 1 {
+            return Err(SegmentError::Discontinuity {
+                expected: i as u64 + 1,
+                found: number,
+            });
+        }
+        if i > 0 && segment.origin_id.as_deref() != Some(first.record_id.as_str()) {
+            return Err(SegmentError::OriginMismatch(i));
+        }
+        if i + 1 < segments.len() && segment.total_length.is_some() {
+            return Err(SegmentError::UnexpectedTotalLength(i));
+        }
+    }
+
+    let actual = segment_total_length(segments);
+    let declared = segments
+        .last()
+        .and_then(|segment| segment.total_length)
+        .ok_or(SegmentError::MissingTotalLength)?;
+    if declared != actual {
+        return Err(SegmentError::TotalLengthMismatch { declared, actual });
+    }
+
+    Ok(actual)
+}
+
+/// Return the headers shared by the continuations of a record with the given headers: the type,
+/// origin ID, date, target URI and warcinfo ID. Each continuation needs a record ID and segment
+/// number of its own.
+pub(crate) fn continuation_headers(headers: &RawRecordHeader) -> RawRecordHeader {
+    let mut continuation = RawRecordHeader {
+        version: headers.version.clone(),
+        headers: Default::default(),
+    };
+    continuation.as_mut().insert(
+        WarcHeader::WarcType,
+        RecordType::Continuation.to_string().into_bytes(),
+    );
+    let origin_id = headers
+        .as_ref()
+        .get(&WarcHeader::RecordID)
+        .cloned()
+        .unwrap_or_default();
+    continuation
+        .as_mut()
+        .insert(WarcHeader::SegmentOriginID, origin_id);
+    for header in &[
+        WarcHeader::Date,
+        WarcHeader::TargetURI,
+        WarcHeader::WarcInfoID,
+    ] {
+        if let Some(value) = headers.as_ref().get(header) {
+            continuation.as_mut().insert(header.clone(), value.clone());
+        }
+    }
+
+    continuation
+}
+
+/// Split a raw record into segments holding at most `segment_size` bytes of its block each.
+///
+/// The first segment keeps the headers of the record, except the WARC-Block-Digest, which would
+/// not match its block. A record whose block fits is returned whole, unsegmented.
+pub fn split_segments(
+    headers: RawRecordHeader,
+    body: &[u8],
+    segment_size: u64,
+) -> Vec<(RawRecordHeader, &[u8])> {
+    let segment_size = segment_size.max(1) as usize;
+    if body.len() <= segment_size {
+        return vec![(headers, body)];
+    }
+
+    let continuation = continuation_headers(&headers);
+    let mut first = headers;
+    first.as_mut().remove(&WarcHeader::BlockDigest);
+    let chunks = body.chunks(segment_size);
+    let count = chunks.len();
+    chunks
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut segment = if i == 0 {
+                first.clone()
+            } else {
+                let mut segment = continuation.clone();
+                segment.as_mut().insert(
+                    WarcHeader::RecordID,
+                    Record::<EmptyBody>::generate_record_id().into_bytes(),
+                );
+                segment
+            };
+            let map = segment.as_mut();
+            map.insert(WarcHeader::SegmentNumber, (i + 1).to_string().into_bytes());
+            map.insert(
+                WarcHeader::ContentLength,
+                chunk.len().to_string().into_bytes(),
+            );
+            if i > 0 && i + 1 == count {
+                map.insert(
+                    WarcHeader::SegmentTotalLength,
+                    body.len().to_string().into_bytes(),
+                );
+            }
+
+            (segment, chunk)
+        })
+        .collect()
+}
+
+/// Join the segments of a logical record, in any order, into the record they were split from.
+///
+/// The record has the headers of the first segment, without its WARC-Segment-Number and
+/// WARC-Block-Digest, and the blocks of every segment as its body.
+pub fn join_segments(
+    mut segments: Vec<Record<BufferedBody>>,
+) -> Result<Record<BufferedBody>, SegmentError> {
+    segments.sort_by_key(|record| Segment::from_record(record).number);
+    let info: Vec<_> = segments.iter().map(Segment::from_record).collect();
+    let total_length = verify_segments(&info)?;
+
+    let stored_len = segments
+        .iter()
+        .map(|record| record.stored_len())
+        .sum::<Option<u64>>();
+    let mut body = Vec::with_capacity(total_length as usize);
+    for record in &segments {
+        body.extend_from_slice(record.body());
+    }
+    let mut record = segments.swap_remove(0);
+    record.remove_header(&WarcHeader::SegmentNumber);
+    record.remove_header(&WarcHeader::BlockDigest);
+    record.replace_body(body);
+    if let Some(len) = stored_len {
+        record.set_stored_len(len);
+    }
+
+    Ok(record)
+}
+
+/// An iterator adapter which joins segmented records into the records they were split from.
+///
+/// Unsegmented records are passed through as read. A first segment is held until its last
+/// continuation is read, when the joined record is returned in its place. Segments whose logical
+/// record is incomplete when the input ends, as when its continuations are stored in a later
+/// file, are returned unjoined after every other record, in the order they were read.
+pub struct Reassemble<I> {
+    records: I,
+    pending: HashMap<String, Vec<Record<BufferedBody>>>,
+    order: Vec<String>,
+    unjoined: std::vec::IntoIter<Record<BufferedBody>>,
+}
+
+impl<I> Reassemble<I>
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+{
+    /// Join the segmented records of the iterator.
+    pub fn new(records: I) -> Self {
+        Reassemble {
+            records,
+            pending: HashMap::new(),
+            order: Vec::new(),
+            unjoined: Vec::new().into_iter(),
+        }
+    }
+
+    fn hold(&mut self, origin_id: String, record: Record<BufferedBody>) {
+        if !self.pending.contains_key(&origin_id) {
+            self.order.push(origin_id.clone());
+        }
+        self.pending.entry(origin_id).or_default().push(record);
+    }
+}
+
+impl<I> Iterator for Reassemble<I>
+where
+    I: Iterator<Item = Result<Record<BufferedBody>, Error>>,
+{
+    type Item = Result<Record<BufferedBody>, Error>;
+
+    /// Return the next record, or an error of kind `MalformedBody` in place of a logical record
+    /// whose segments do not fit together.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    if self.unjoined.len() == 0 {
+                        let mut unjoined = Vec::new();
+                        for origin_id in self.order.drain(..) {
+                            unjoined.extend(self.pending.remove(&origin_id).unwrap_or_default());
+                        }
+                        self.unjoined = unjoined.into_iter();
+                    }
+                    return self.unjoined.next().map(Ok);
+                }
+            };
+
+            let segment = Segment::from_record(&record);
+            let is_continuation = record.warc_type() == &RecordType::Continuation;
+            match segment.origin_id {
+                Some(origin_id) if is_continuation => {
+                    self.hold(origin_id.clone(), record);
+                    if segment.total_length.is_some() {
+                        self.order.retain(|id| *id != origin_id);
+                        let segments = self.pending.remove(&origin_id).unwrap_or_default();
+                        return Some(
+                            join_segments(segments)
+                                .map_err(|e| Error::MalformedBody(e.to_string())),
+                        );
+                    }
+                }
+                _ if !is_continuation && segment.number == Some(1) => {
+                    self.hold(segment.record_id, record);
+                }
+                _ => return Some(Ok(record)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_segments, Segment, SegmentError};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    fn segments() -> Vec<Segment> {
+        let segment = |number, content_length| Segment {
+            record_id: format!("<urn:test:{}>", number),
+            number: Some(number),
+            origin_id: Some("<urn:test:1>".to_owned()).filter(|_| number > 1),
+            total_length: None,
+            content_length,
+        };
+        let mut segments = vec![segment(1, 10), segment(2, 10), segment(3, 5)];
+        segments[2].total_length = Some(25);
+
+        segments
+    }
+
+    #[test]
+    fn verify() {
+        assert_eq!(verify_segments(&segments()), Ok(25));
+        assert_eq!(verify_segments(&[]), Err(SegmentError::Empty));
+
+        let mut gap = segments();
+        gap.remove(1);
+        assert_eq!(
+            verify_segments(&gap),
+            Err(SegmentError::Discontinuity {
+                expected: 2,
+                found: 3
+            })
+        );
+
+        let mut wrong_total = segments();
+        wrong_total[2].total_length = Some(29);
+        assert_eq!(
+            verify_segments(&wrong_total),
+            Err(SegmentError::TotalLengthMismatch {
+                declared: 29,
+                actual: 25
+            })
+        );
+
+        let mut orphan = segments();
+        orphan[1].origin_id = None;
+        assert_eq!(
+            verify_segments(&orphan),
+            Err(SegmentError::OriginMismatch(1))
+        );
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let record = |body: &[u8]| {
+            let mut record = Record::<BufferedBody>::with_body(body);
+            record.set_warc_version("1.1");
+            record.set_warc_type(RecordType::Resource);
+            record
+                .set_header(WarcHeader::TargetURI, "http://example.com/")
+                .unwrap();
+            record
+        };
+        let large = record(b"0123456789abcdefghijklmnopqrstuvwxy");
+        let small = record(b"small");
+
+        let mut data = Vec::new();
+        let mut writer = WarcWriter::new(&mut data);
+        writer.set_segment_size(Some(10));
+        writer.write(&large).unwrap();
+        writer.write(&small).unwrap();
+        drop(writer);
+
+        let records: Vec<_> = WarcReader::new(&data[..])
+            .iter_records()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].warc_id(), large.warc_id());
+        assert_eq!(records[0].body(), b"0123456789");
+        assert_eq!(records[3].warc_type(), &RecordType::Continuation);
+        assert_eq!(
+            records[3].header(WarcHeader::SegmentTotalLength).unwrap(),
+            "35"
+        );
+        let segments: Vec<_> = records[..4].iter().map(Segment::from_record).collect();
+        assert_eq!(verify_segments(&segments), Ok(35));
+
+        let joined: Vec<_> = WarcReader::new(&data[..])
+            .iter_reassembled_records()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0].warc_id(), large.warc_id());
+        assert_eq!(joined[0].body(), large.body());
+        assert_eq!(joined[0].header(WarcHeader::SegmentNumber), None);
+        assert_eq!(joined[1].body(), b"small");
+
+        // segments whose continuations are missing are returned as read
+        let mut reordered = records.clone();
+        reordered.remove(3);
+        let unjoined: Vec<_> = super::Reassemble::new(reordered.into_iter().map(Ok))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(unjoined.len(), 4);
+        assert_eq!(unjoined[0].body(), b"small");
+        assert_eq!(unjoined[1].body(), b"0123456789");
+    }
+}
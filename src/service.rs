@@ -0,0 +1,310 @@
+//! Serving the records of a collection over HTTP.
+//!
+//! These helpers build framework-independent responses, so that a thin layer over any HTTP server
+//! can expose a collection to remote workers. Records are addressed by WARC-Record-ID or by
+//! offset, and a single byte range of a record may be requested with the `Range` header.
+
+use std::error;
+use std::fmt;
+use std::io::{BufRead, Seek};
+
+use crate::{Collection, Error};
+
+/// The media type of serialized WARC records.
+pub const WARC_MEDIA_TYPE: &str = "application/warc";
+
+/// An inclusive range of bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Return the number of bytes in the range.
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Return true if the range holds no bytes. An inclusive range always holds at least one.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Return the value of the `Content-Range` header for this range of a resource of the given
+    /// length.
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// A problem with the value of a `Range` header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RangeError {
+    /// The header is not a single range of bytes.
+    Malformed(String),
+    /// The range does not overlap the resource.
+    Unsatisfiable,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RangeError::Malformed(ref value) => write!(f, "Malformed range: {:?}", value),
+            RangeError::Unsatisfiable => write!(f, "Range not satisfiable."),
+        }
+    }
+}
+
+impl error::Error for RangeError {}
+
+/// Parse the value of a `Range` header for a resource of the given length.
+///
+/// Only a single range in bytes is supported, such as `bytes=0-99`, `bytes=100-`, or the suffix
+/// range `bytes=-100`. An end beyond the resource is clamped to its last byte.
+pub fn parse_range(value: &str, total: u64) -> Result<ByteRange, RangeError> {
+    let malformed = || RangeError::Malformed(value.to_owned());
+    let spec = value
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .ok_or_else(malformed)?;
+    let (start, end) = spec.trim().split_once('-').ok_or_else(malformed)?;
+    let number = |n: &str| n.trim().parse::<u64>().map_err(|_| malformed());
+
+    let range = match (start.trim().is_empty(), end.trim().is_empty()) {
+        (true, true) => return Err(malformed()),
+        (true, false) => {
+            let suffix = number(end)?;
+            if suffix == 0 || total == 0 {
+                return Err(RangeError::Unsatisfiable);
+            }
+            ByteRange {
+                start: total.saturating_sub(suffix),
+                end: total - 1,
+            }
+        }
+        (false, open) => {
+            let start = number(start)?;
+            let end = if open { u64::MAX } else { number(end)? };
+            if end < start {
+                return Err(malformed());
+            }
+            if start >= total {
+                return Err(RangeError::Unsatisfiable);
+            }
+            ByteRange {
+                start,
+                end: end.min(total - 1),
+            }
+        }
+    };
+
+    Ok(range)
+}
+
+/// An HTTP response, ready to be converted to the response type of a server framework.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, headers: Vec<(&'static str, String)>) -> Self {
+        Response {
+            status,
+            headers,
+            body: Vec::new(),
+        }
+    }
+
+    /// Return the value of the named header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Serve the record with the given WARC-Record-ID.
+///
+/// The response is `404 Not Found` if the collection holds no such record. See `serve_offset`.
+pub fn serve_id<R: BufRead + Seek>(
+    collection: &mut Collection<R>,
+    record_id: &str,
+    range: Option<&str>,
+) -> Result<Response, Error> {
+    match collection.get(record_id) {
+        Some(entry) => {
+            let offset = entry.offset;
+            serve_offset(collection, offset, range)
+        }
+        None => Ok(Response::error(404, Vec::new())),
+    }
+}
+
+/// Serve the record starting at the given offset.
+///
+/// The whole serialized record, including its separator, is served as `application/warc`. If the
+/// value of a `Range` header is given, only that range of the record is served, with status
+/// `206 Partial Content`. The response is `404 Not Found` if no record starts at the offset,
+/// `400 Bad Request` if the range is malformed, and `416 Range Not Satisfiable` if it lies
+/// outside the record.
+pub fn serve_offset<R: BufRead + Seek>(
+    collection: &mut Collection<R>,
+    offset: u64,
+    range: Option<&str>,
+) -> Result<Response, Error> {
+    let (record_id, length) = match collection.entry_at(offset) {
+        Some(entry) => (entry.record.warc_id().to_owned(), entry.length),
+        None => return Ok(Response::error(404, Vec::new())),
+    };
+    let range = match range.map(|value| parse_range(value, length)).transpose() {
+        Ok(range) => range,
+        Err(RangeError::Malformed(_)) => return Ok(Response::error(400, Vec::new())),
+        Err(RangeError::Unsatisfiable) => {
+            return Ok(Response::error(
+                416,
+                vec![("Content-Range", format!("bytes */{}", length))],
+            ))
+        }
+    };
+
+    let mut body = collection.read_raw(offset)?;
+    let mut headers = vec![
+        ("Content-Type", WARC_MEDIA_TYPE.to_owned()),
+        ("Accept-Ranges", "bytes".to_owned()),
+        ("ETag", entity_tag(&record_id)),
+    ];
+    let status = match range {
+        Some(range) => {
+            body.truncate(range.end as usize + 1);
+            body.drain(..range.start as usize);
+            headers.push(("Content-Range", range.content_range(length)));
+            206
+        }
+        None => 200,
+    };
+    headers.push(("Content-Length", body.len().to_string()));
+
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Return the strong entity-tag of a record, its WARC-Record-ID in quotes.
+///
+/// Bytes an entity-tag may not hold, such as spaces, quotes and control characters, are
+/// percent-encoded, as is `%` itself so that distinct IDs keep distinct tags.
+fn entity_tag(record_id: &str) -> String {
+    let mut tag = String::with_capacity(record_id.len() + 2);
+    tag.push('"');
+    for c in record_id.chars() {
+        let allowed = !c.is_ascii() || (c.is_ascii_graphic() && c != '"' && c != '%');
+        if allowed {
+            tag.push(c);
+        } else {
+            tag.push_str(&format!("%{:02X}", c as u8));
+        }
+    }
+    tag.push('"');
+
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{entity_tag, parse_range, serve_id, serve_offset, ByteRange, RangeError};
+    use crate::{BufferedBody, Collection, Record, WarcWriter};
+
+    #[test]
+    fn ranges() {
+        let range = |start, end| Ok(ByteRange { start, end });
+        assert_eq!(parse_range("bytes=0-9", 100), range(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=90-200", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-200", 100), range(0, 99));
+        assert_eq!(
+            parse_range("bytes=100-", 100),
+            Err(RangeError::Unsatisfiable)
+        );
+        assert!(matches!(
+            parse_range("bytes=0-1,5-6", 100),
+            Err(RangeError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_range("items=0-1", 100),
+            Err(RangeError::Malformed(_))
+        ));
+        assert_eq!(range(90, 99).unwrap().content_range(100), "bytes 90-99/100");
+    }
+
+    #[test]
+    fn serve_records() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record.replace_body(&b"hello"[..]);
+        writer.write(&record).unwrap();
+        let mut collection = Collection::open(Cursor::new(buffer.clone())).unwrap();
+
+        let response = serve_id(&mut collection, record.warc_id(), None).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("application/warc"));
+        assert_eq!(response.body, buffer);
+        assert_eq!(
+            response.header("ETag"),
+            Some(format!("\"{}\"", record.warc_id()).as_str())
+        );
+
+        let response = serve_offset(&mut collection, 0, Some("bytes=-9")).unwrap();
+        assert_eq!(response.status, 206);
+        assert_eq!(response.body, b"hello\r\n\r\n");
+        assert_eq!(response.header("Content-Length"), Some("9"));
+        assert_eq!(
+            response.header("Content-Range"),
+            Some(
+                format!(
+                    "bytes {}-{}/{}",
+                    buffer.len() - 9,
+                    buffer.len() - 1,
+                    buffer.len()
+                )
+                .as_str()
+            )
+        );
+
+        assert_eq!(
+            serve_offset(&mut collection, 0, Some("bytes=9999-"))
+                .unwrap()
+                .status,
+            416
+        );
+        assert_eq!(serve_offset(&mut collection, 1, None).unwrap().status, 404);
+        assert_eq!(
+            serve_id(&mut collection, "<urn:uuid:missing>", None)
+                .unwrap()
+                .status,
+            404
+        );
+    }
+
+    #[test]
+    fn entity_tags() {
+        assert_eq!(
+            entity_tag("<urn:uuid:d7ae5c10-e6b3-4d27-967d-34780c58ba39>"),
+            "\"<urn:uuid:d7ae5c10-e6b3-4d27-967d-34780c58ba39>\""
+        );
+        assert_eq!(entity_tag("<a \"b\"\r\n%>"), "\"<a%20%22b%22%0D%0A%25>\"");
+        assert_eq!(entity_tag("<urn:café>"), "\"<urn:café>\"");
+    }
+}
@@ -0,0 +1,269 @@
+//! Coverage of captured sitemaps.
+//!
+//! Sites list the URLs they want crawled in sitemaps, either as XML (`<urlset>` or
+//! `<sitemapindex>`) or as plain text with one URL per line. `coverage` reads the sitemaps
+//! captured in a collection and reports, for each site, the listed URLs which were never captured.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{BufRead, Seek};
+
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::replay::normalize;
+use crate::{http, Collection, Error, Record, RecordType};
+
+/// A URL listed in a sitemap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitemapUrl {
+    pub loc: String,
+    /// The `<lastmod>` of the URL, as written.
+    pub lastmod: Option<String>,
+}
+
+/// A parsed sitemap or sitemap index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sitemap {
+    /// The URLs listed by a `<urlset>` or a text sitemap.
+    pub urls: Vec<SitemapUrl>,
+    /// The sitemaps listed by a `<sitemapindex>`.
+    pub sitemaps: Vec<String>,
+}
+
+impl Sitemap {
+    /// Parse a sitemap. Content which is not XML is read as a text sitemap.
+    pub fn parse(text: &str) -> Self {
+        let mut sitemap = Sitemap::default();
+        if !text.trim_start().starts_with('<') {
+            sitemap.urls = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+                .map(|line| SitemapUrl {
+                    loc: line.to_owned(),
+                    lastmod: None,
+                })
+                .collect();
+            return sitemap;
+        }
+
+        for entry in elements(text, "url") {
+            if let Some(loc) = elements(entry, "loc").next() {
+                sitemap.urls.push(SitemapUrl {
+                    loc: unescape(loc.trim()),
+                    lastmod: elements(entry, "lastmod")
+                        .next()
+                        .map(|lastmod| lastmod.trim().to_owned()),
+                });
+            }
+        }
+        for entry in elements(text, "sitemap") {
+            if let Some(loc) = elements(entry, "loc").next() {
+                sitemap.sitemaps.push(unescape(loc.trim()));
+            }
+        }
+
+        sitemap
+    }
+
+    /// Parse the sitemap held by a response record with status 200, or a resource record.
+    pub fn from_record<T: BodyKind + AsRef<[u8]>>(record: &Record<T>) -> Option<Self> {
+        let body = match record.warc_type() {
+            RecordType::Response => {
+                if http::status(record.body())? != 200 {
+                    return None;
+                }
+                http::split_head(record.body())?.1
+            }
+            RecordType::Resource => record.body(),
+            _ => return None,
+        };
+
+        std::str::from_utf8(body).ok().map(Sitemap::parse)
+    }
+}
+
+/// Return the content of each element with the given name, ignoring namespace prefixes.
+fn elements<'t>(text: &'t str, name: &'t str) -> impl Iterator<Item = &'t str> + 't {
+    let mut rest = text;
+    std::iter::from_fn(move || loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+        let end = rest.find('>')?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let tag_name = tag.split_whitespace().next().unwrap_or("");
+        let local = tag_name.rsplit(':').next().unwrap_or("");
+        if local != name || tag.ends_with('/') {
+            continue;
+        }
+
+        let close = format!("</{}>", tag_name);
+        let content_end = rest.find(&close)?;
+        let content = &rest[..content_end];
+        rest = &rest[content_end + close.len()..];
+        return Some(content);
+    })
+}
+
+/// Replace the predefined XML entities, and unwrap CDATA sections.
+fn unescape(text: &str) -> String {
+    let cdata = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"));
+    match cdata {
+        Some(cdata) => cdata.to_owned(),
+        None => text
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    }
+}
+
+/// The coverage of the URLs one site lists in its sitemaps.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SiteCoverage {
+    /// The number of distinct URLs listed.
+    pub listed: usize,
+    /// The number of listed URLs captured.
+    pub captured: usize,
+    /// The listed URLs which were not captured, in order.
+    pub missing: Vec<String>,
+}
+
+impl SiteCoverage {
+    /// Return the fraction of the listed URLs which were captured.
+    pub fn ratio(&self) -> f64 {
+        if self.listed == 0 {
+            1.0
+        } else {
+            self.captured as f64 / self.listed as f64
+        }
+    }
+}
+
+/// Report the coverage of the sitemaps captured in a collection, by host.
+///
+/// Sitemaps are the response and resource records whose WARC-Target-URI has a path containing
+/// `sitemap`. A listed URL is covered if a response or revisit record captures it.
+pub fn coverage<R: BufRead + Seek>(
+    collection: &mut Collection<R>,
+) -> Result<BTreeMap<String, SiteCoverage>, Error> {
+    let mut captured = HashSet::new();
+    let mut sitemaps = Vec::new();
+    for entry in collection.entries() {
+        let uri = match entry.record.header(WarcHeader::TargetURI) {
+            Some(uri) => normalize(&uri),
+            None => continue,
+        };
+        match entry.record.warc_type() {
+            RecordType::Response | RecordType::Revisit | RecordType::Resource => {}
+            _ => continue,
+        }
+        let is_sitemap = entry.record.warc_type() != &RecordType::Revisit
+            && Url::parse(&uri)
+                .map(|url| url.path().to_lowercase().contains("sitemap"))
+                .unwrap_or(false);
+        if is_sitemap {
+            sitemaps.push(entry.offset);
+        }
+        if entry.record.warc_type() != &RecordType::Resource {
+            captured.insert(uri);
+        }
+    }
+
+    let mut listed: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut order = Vec::new();
+    for offset in sitemaps {
+        let record = collection.read_record(offset)?;
+        let sitemap = match Sitemap::from_record(&record) {
+            Some(sitemap) => sitemap,
+            None => continue,
+        };
+        for url in sitemap.urls {
+            let loc = normalize(&url.loc);
+            let host = match Url::parse(&loc) {
+                Ok(url) => url.host_str().unwrap_or_default().to_lowercase(),
+                Err(_) => continue,
+            };
+            if listed.entry(host.clone()).or_default().insert(loc.clone()) {
+                order.push((host, loc));
+            }
+        }
+    }
+
+    let mut report: BTreeMap<String, SiteCoverage> = BTreeMap::new();
+    for (host, loc) in order {
+        let site = report.entry(host).or_default();
+        site.listed += 1;
+        if captured.contains(&loc) {
+            site.captured += 1;
+        } else {
+            site.missing.push(loc);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{coverage, Sitemap};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Collection, Record, RecordType, WarcWriter};
+
+    const SITEMAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>http://example.com/</loc><lastmod>2020-01-01</lastmod></url>
+  <url><loc> http://example.com/a?x=1&amp;y=2 </loc></url>
+  <url><loc>http://example.com/b</loc></url>
+  <url><loc>http://other.example/</loc></url>
+</urlset>"#;
+
+    #[test]
+    fn parse() {
+        let sitemap = Sitemap::parse(SITEMAP);
+        assert_eq!(sitemap.urls.len(), 4);
+        assert_eq!(sitemap.urls[0].lastmod.as_deref(), Some("2020-01-01"));
+        assert_eq!(sitemap.urls[1].loc, "http://example.com/a?x=1&y=2");
+
+        let index = Sitemap::parse(
+            "<sitemapindex><sitemap><loc>http://example.com/s1.xml</loc></sitemap></sitemapindex>",
+        );
+        assert_eq!(index.sitemaps, vec!["http://example.com/s1.xml"]);
+        assert!(index.urls.is_empty());
+
+        let text = Sitemap::parse("http://example.com/\n\nhttp://example.com/c\n");
+        assert_eq!(text.urls.len(), 2);
+    }
+
+    #[test]
+    fn site_coverage() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        for (uri, body) in [
+            ("http://example.com/sitemap.xml", SITEMAP),
+            ("http://example.com/", ""),
+            ("http://example.com/a?x=1&y=2", ""),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(RecordType::Response);
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record.replace_body(format!("HTTP/1.1 200 OK\r\n\r\n{}", body));
+            writer.write(&record).unwrap();
+        }
+
+        let mut collection = Collection::open(Cursor::new(buffer)).unwrap();
+        let report = coverage(&mut collection).unwrap();
+        let site = &report["example.com"];
+        assert_eq!((site.listed, site.captured), (3, 2));
+        assert_eq!(site.missing, vec!["http://example.com/b"]);
+        assert_eq!(report["other.example"].ratio(), 0.0);
+    }
+}
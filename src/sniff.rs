@@ -0,0 +1,242 @@
+//! Identification of media types from the content of payloads.
+//!
+//! Only formats with a distinctive signature are recognized. The result is suited to the
+//! WARC-Identified-Payload-Type header, which records the type found by inspecting a payload
+//! rather than the type declared by the server.
+//!
+//! `check` compares the type identified in a payload with the type declared for it, and
+//! `rewrite` copies a file while reporting mismatches and filling in the
+//! WARC-Identified-Payload-Type header.
+
+use std::io::{BufRead, Write};
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{http, Error, Record, WarcReader, WarcWriter};
+
+/// Signatures found at the start of a payload, and the media types they identify.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x00asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Return the media type identified from the start of a payload, if any.
+pub fn media_type(payload: &[u8]) -> Option<&'static str> {
+    if let Some((_, media_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| payload.starts_with(signature))
+    {
+        return Some(media_type);
+    }
+    if payload.len() >= 12 && payload.starts_with(b"RIFF") && &payload[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+
+    let start = payload
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(payload.len());
+    let text = &payload[start..payload.len().min(start + 14)];
+    let starts_with = |prefix: &[u8]| {
+        text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    if starts_with(b"<!doctype html") || starts_with(b"<html") {
+        Some("text/html")
+    } else if starts_with(b"<?xml") {
+        Some("application/xml")
+    } else {
+        None
+    }
+}
+
+/// Return true if a payload identified as one type may properly be declared as another.
+///
+/// Both types are lowercase and without parameters.
+fn compatible(declared: &str, identified: &str) -> bool {
+    if declared == identified {
+        return true;
+    }
+    match identified {
+        "application/xml" => declared == "text/xml" || declared.ends_with("+xml"),
+        "text/html" => declared == "application/xhtml+xml",
+        "image/jpeg" => declared == "image/jpg" || declared == "image/pjpeg",
+        "application/gzip" => declared == "application/x-gzip",
+        "application/zip" => {
+            declared.ends_with("zip")
+                || declared.starts_with("application/vnd.")
+                || declared == "application/java-archive"
+        }
+        "font/woff" | "font/woff2" => {
+            declared == "application/font-woff" || declared == "application/x-font-woff"
+        }
+        _ => false,
+    }
+}
+
+/// A payload whose declared type does not match the type identified from its content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub record_id: String,
+    pub target_uri: Option<String>,
+    /// The type declared by the Content-Type of the HTTP message, or else of the record.
+    pub declared: String,
+    pub identified: &'static str,
+}
+
+/// Compare the type declared for a record's payload with the type identified from its content.
+///
+/// A mismatch is only reported if both types are known. Records without a payload, and payloads
+/// of unrecognized formats, are never reported.
+pub fn check<T: BodyKind + AsRef<[u8]>>(record: &Record<T>) -> Option<Mismatch> {
+    let identified = media_type(record.payload()?)?;
+    let declared = http::payload_media_type(
+        None,
+        record
+            .header(WarcHeader::ContentType)
+            .map(|value| value.into_owned()),
+        record.body(),
+    );
+    if declared.is_empty() || compatible(&declared, identified) {
+        return None;
+    }
+
+    Some(Mismatch {
+        record_id: record.warc_id().to_owned(),
+        target_uri: record
+            .header(WarcHeader::TargetURI)
+            .map(|value| value.into_owned()),
+        declared,
+        identified,
+    })
+}
+
+/// The outcome of checking the payload types of records.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MimeReport {
+    /// The number of records checked.
+    pub records: u64,
+    /// The number of records whose payload type was identified.
+    pub identified: u64,
+    /// The records whose declared type does not match, in order.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl MimeReport {
+    /// Check a record, returning its mismatch if it has one.
+    pub fn add<T: BodyKind + AsRef<[u8]>>(&mut self, record: &Record<T>) -> Option<&Mismatch> {
+        self.records += 1;
+        if record.payload().and_then(media_type).is_some() {
+            self.identified += 1;
+        }
+        self.mismatches.push(check(record)?);
+        self.mismatches.last()
+    }
+}
+
+/// Copy every record from the reader to the writer, checking its payload type.
+///
+/// Records with a payload of a recognized format and no WARC-Identified-Payload-Type header are
+/// given one. Other records are written unchanged.
+pub fn rewrite<R: BufRead, W: Write>(
+    reader: WarcReader<R>,
+    writer: &mut WarcWriter<W>,
+) -> Result<MimeReport, Error> {
+    let mut report = MimeReport::default();
+    for record in reader.iter_records() {
+        let mut record = record?;
+        report.add(&record);
+        if record.identified_payload_type().is_none() {
+            record.identify_payload_type();
+        }
+        writer.write(&record).map_err(|_| Error::WriteData)?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, media_type, rewrite};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn signatures() {
+        assert_eq!(media_type(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(
+            media_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(media_type(b"\r\n  <!DOCTYPE HTML>"), Some("text/html"));
+        assert_eq!(
+            media_type(b"<?xml version=\"1.0\"?>"),
+            Some("application/xml")
+        );
+        assert_eq!(media_type(b"plain text"), None);
+        assert_eq!(media_type(b""), None);
+    }
+
+    fn response(content_type: &str, payload: &[u8]) -> Record<BufferedBody> {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record.set_warc_type(RecordType::Response);
+        record
+            .set_header(
+                WarcHeader::ContentType,
+                "application/http; msgtype=response",
+            )
+            .unwrap();
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\n\r\n", content_type);
+        record.replace_body([head.as_bytes(), payload].concat());
+        record
+    }
+
+    #[test]
+    fn mismatches() {
+        let html = response("image/png", b"<!doctype html><p>");
+        let mismatch = check(&html).unwrap();
+        assert_eq!(
+            (mismatch.declared.as_str(), mismatch.identified),
+            ("image/png", "text/html")
+        );
+        assert!(check(&response("text/html; charset=utf-8", b"<html>")).is_none());
+        assert!(check(&response("image/svg+xml", b"<?xml version=\"1.0\"?>")).is_none());
+        assert!(check(&response("text/plain", b"no signature")).is_none());
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write(&html).unwrap();
+        writer
+            .write(&response("application/pdf", b"%PDF-1.4"))
+            .unwrap();
+        drop(writer);
+
+        let mut output = Vec::new();
+        let report = rewrite(
+            WarcReader::new(&buffer[..]),
+            &mut WarcWriter::new(&mut output),
+        )
+        .unwrap();
+        assert_eq!((report.records, report.identified), (2, 2));
+        assert_eq!(report.mismatches.len(), 1);
+        let types: Vec<_> = WarcReader::new(&output[..])
+            .iter_records()
+            .map(|record| {
+                record
+                    .unwrap()
+                    .identified_payload_type()
+                    .unwrap()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(types, vec!["text/html", "application/pdf"]);
+    }
+}
@@ -0,0 +1,459 @@
+//! Statistics gathered over the records of an archive.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+#[cfg(feature = "gzip")]
+use std::io::{BufRead, BufReader, Read};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{http, BufferedBody, Record, RecordType};
+#[cfg(feature = "gzip")]
+use crate::{Error, WarcReader};
+
+#[cfg(feature = "gzip")]
+use libflate::gzip::Decoder as GzipReader;
+
+/// The stored and decompressed sizes of a single record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordSize {
+    pub record_id: String,
+    pub record_type: RecordType,
+    /// The media type of the record's payload, or `unknown`.
+    pub media_type: String,
+    /// The number of bytes the record occupies in the compressed stream.
+    pub compressed: u64,
+    /// The number of bytes of the record once decompressed.
+    pub uncompressed: u64,
+}
+
+impl RecordSize {
+    /// Describe the sizes of a record, which occupied `compressed` bytes in the compressed stream.
+    ///
+    /// The uncompressed size is the length the record was read with, or else its serialized
+    /// length.
+    pub fn new(record: &Record<BufferedBody>, compressed: u64) -> Self {
+        let media_type = http::payload_media_type(
+            record
+                .header(WarcHeader::IdentifiedPayloadType)
+                .map(|value| value.into_owned()),
+            record
+                .header(WarcHeader::ContentType)
+                .map(|value| value.into_owned()),
+            record.body(),
+        );
+
+        RecordSize {
+            record_id: record.warc_id().to_owned(),
+            record_type: record.warc_type().clone(),
+            media_type: if media_type.is_empty() {
+                "unknown".to_owned()
+            } else {
+                media_type
+            },
+            compressed,
+            uncompressed: record
+                .stored_len()
+                .unwrap_or_else(|| record.serialized_len()),
+        }
+    }
+
+    /// Return the compressed size as a fraction of the uncompressed size.
+    pub fn ratio(&self) -> f64 {
+        ratio(self.compressed, self.uncompressed)
+    }
+}
+
+/// The total sizes of a group of records.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SizeTotals {
+    pub records: u64,
+    pub compressed: u64,
+    pub uncompressed: u64,
+}
+
+impl SizeTotals {
+    fn add(&mut self, size: &RecordSize) {
+        self.records += 1;
+        self.compressed += size.compressed;
+        self.uncompressed += size.uncompressed;
+    }
+
+    /// Return the compressed size as a fraction of the uncompressed size.
+    pub fn ratio(&self) -> f64 {
+        ratio(self.compressed, self.uncompressed)
+    }
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> f64 {
+    if uncompressed == 0 {
+        1.0
+    } else {
+        compressed as f64 / uncompressed as f64
+    }
+}
+
+/// The compression efficiency of each record of a compressed archive, and of each media type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompressionStats {
+    records: Vec<RecordSize>,
+    by_media_type: BTreeMap<String, SizeTotals>,
+    total: SizeTotals,
+}
+
+impl CompressionStats {
+    /// Create empty statistics.
+    pub fn new() -> Self {
+        CompressionStats::default()
+    }
+
+    /// Gather the statistics of a stream of gzip members, each holding one or more records.
+    ///
+    /// When a member holds several records, its compressed size is divided between them in
+    /// proportion to their uncompressed sizes.
+    #[cfg(feature = "gzip")]
+    pub fn from_gzip<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut stats = CompressionStats::new();
+        let mut input = CountingReader(BufReader::new(reader), 0);
+        loop {
+            match input.0.fill_buf() {
+                Err(_) => return Err(Error::ReadData),
+                Ok([]) => break,
+                Ok(_) => {}
+            }
+
+            let start = input.1;
+            let mut data = Vec::new();
+            GzipReader::new(&mut input)
+                .and_then(|mut member| member.read_to_end(&mut data))
+                .map_err(|_| Error::ReadData)?;
+            let member_len = input.1 - start;
+
+            let records = WarcReader::new(&data[..])
+                .iter_records()
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut remaining = member_len;
+            for (i, record) in records.iter().enumerate() {
+                let mut size = RecordSize::new(record, 0);
+                size.compressed = if i + 1 == records.len() {
+                    remaining
+                } else {
+                    member_len * size.uncompressed / (data.len() as u64).max(1)
+                };
+                remaining -= size.compressed.min(remaining);
+                stats.add(size);
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Add the sizes of a record.
+    pub fn add(&mut self, size: RecordSize) {
+        self.total.add(&size);
+        self.by_media_type
+            .entry(size.media_type.clone())
+            .or_default()
+            .add(&size);
+        self.records.push(size);
+    }
+
+    /// Return the sizes of each record, in the order they were added.
+    pub fn records(&self) -> &[RecordSize] {
+        &self.records
+    }
+
+    /// Return the total sizes of the records of each media type.
+    pub fn by_media_type(&self) -> &BTreeMap<String, SizeTotals> {
+        &self.by_media_type
+    }
+
+    /// Return the total sizes of all records.
+    pub fn total(&self) -> SizeTotals {
+        self.total
+    }
+}
+
+/// The totals of the captures of one host or URL prefix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartitionTotals {
+    pub records: u64,
+    /// The number of bytes the records occupy, as read or else as serialized.
+    pub bytes: u64,
+    /// The number of HTTP responses with each status code.
+    pub statuses: BTreeMap<u16, u64>,
+    pub last_capture: Option<DateTime<Utc>>,
+    /// The most by which `records` may overcount, because the partition replaced one evicted to
+    /// bound memory. The other totals only cover records added since then.
+    pub error: u64,
+}
+
+impl PartitionTotals {
+    fn add(&mut self, bytes: u64, status: Option<u16>, date: DateTime<Utc>) {
+        self.records += 1;
+        self.bytes += bytes;
+        if let Some(status) = status {
+            *self.statuses.entry(status).or_default() += 1;
+        }
+        if self.last_capture.is_none_or(|last| date > last) {
+            self.last_capture = Some(date);
+        }
+    }
+}
+
+/// Partitions tracked with the Space-Saving algorithm, which keeps the most frequent keys, with
+/// a bounded overcount, once there are more keys than the capacity.
+#[derive(Clone, Debug, PartialEq)]
+struct Partitions {
+    capacity: usize,
+    totals: HashMap<String, PartitionTotals>,
+    by_records: BTreeSet<(u64, String)>,
+}
+
+impl Partitions {
+    fn new(capacity: usize) -> Self {
+        Partitions {
+            capacity,
+            totals: HashMap::new(),
+            by_records: BTreeSet::new(),
+        }
+    }
+
+    fn add(&mut self, key: String, bytes: u64, status: Option<u16>, date: DateTime<Utc>) {
+        let mut totals = match self.totals.remove(&key) {
+            Some(totals) => {
+                self.by_records.remove(&(totals.records, key.clone()));
+                totals
+            }
+            None if self.totals.len() >= self.capacity.max(1) => {
+                let smallest = self.by_records.pop_first().unwrap();
+                self.totals.remove(&smallest.1);
+                PartitionTotals {
+                    records: smallest.0,
+                    error: smallest.0,
+                    ..PartitionTotals::default()
+                }
+            }
+            None => PartitionTotals::default(),
+        };
+
+        totals.add(bytes, status, date);
+        self.by_records.insert((totals.records, key.clone()));
+        self.totals.insert(key, totals);
+    }
+
+    fn sorted(&self) -> Vec<(&str, &PartitionTotals)> {
+        self.by_records
+            .iter()
+            .rev()
+            .map(|(_, key)| (key.as_str(), &self.totals[key]))
+            .collect()
+    }
+}
+
+/// The captures of an archive broken down by host and by URL prefix.
+///
+/// Only records with a WARC-Target-URI are counted. The number of partitions kept is bounded:
+/// once it is reached, the partition with the fewest records is evicted to make room for a new
+/// one, so that the largest partitions are reported even when there are far too many to keep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionStats {
+    prefix_depth: usize,
+    hosts: Partitions,
+    prefixes: Partitions,
+}
+
+impl Default for PartitionStats {
+    fn default() -> Self {
+        PartitionStats::new()
+    }
+}
+
+impl PartitionStats {
+    /// Create empty statistics, keeping at most 10,000 partitions of each kind and partitioning
+    /// prefixes by the first segment of the path.
+    pub fn new() -> Self {
+        PartitionStats {
+            prefix_depth: 1,
+            hosts: Partitions::new(10_000),
+            prefixes: Partitions::new(10_000),
+        }
+    }
+
+    /// Set the number of partitions of each kind kept.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.hosts.capacity = capacity;
+        self.prefixes.capacity = capacity;
+
+        self
+    }
+
+    /// Set the number of path segments in each URL prefix. A depth of 0 partitions prefixes by
+    /// scheme and host.
+    pub fn prefix_depth(mut self, depth: usize) -> Self {
+        self.prefix_depth = depth;
+
+        self
+    }
+
+    /// Add a record.
+    pub fn add<T: BodyKind + AsRef<[u8]>>(&mut self, record: &Record<T>) {
+        let url = match record
+            .header(WarcHeader::TargetURI)
+            .and_then(|uri| Url::parse(uri.trim_matches(|c| c == '<' || c == '>')).ok())
+        {
+            Some(url) => url,
+            None => return,
+        };
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return,
+        };
+
+        let bytes = record
+            .stored_len()
+            .unwrap_or_else(|| record.serialized_len());
+        let status = match record.warc_type() {
+            RecordType::Response | RecordType::Revisit => http::status(record.body()),
+            _ => None,
+        };
+        let date = *record.date();
+
+        let mut prefix = format!("{}://{}/", url.scheme(), host);
+        if let Some(segments) = url.path_segments() {
+            for segment in segments.take(self.prefix_depth) {
+                if !segment.is_empty() {
+                    prefix.push_str(segment);
+                    prefix.push('/');
+                }
+            }
+        }
+        self.hosts.add(host, bytes, status, date);
+        self.prefixes.add(prefix, bytes, status, date);
+    }
+
+    /// Return the totals of each host, with the most records first.
+    pub fn by_host(&self) -> Vec<(&str, &PartitionTotals)> {
+        self.hosts.sorted()
+    }
+
+    /// Return the totals of each URL prefix, with the most records first.
+    pub fn by_prefix(&self) -> Vec<(&str, &PartitionTotals)> {
+        self.prefixes.sorted()
+    }
+}
+
+/// A reader which counts the bytes read from the inner reader.
+#[cfg(feature = "gzip")]
+pub(crate) struct CountingReader<R>(pub R, pub u64);
+
+#[cfg(feature = "gzip")]
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.0.read(buf)?;
+        self.1 += len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "gzip")]
+    use super::CompressionStats;
+    use super::PartitionStats;
+    use crate::header::WarcHeader;
+    #[cfg(feature = "gzip")]
+    use crate::{store_compressed_media, WarcWriter};
+    use crate::{BufferedBody, Record, RecordType};
+
+    #[test]
+    fn partitions() {
+        let mut stats = PartitionStats::new().capacity(2);
+        for (uri, status) in [
+            ("http://a.example/x/1", "200"),
+            ("http://a.example/x/2", "404"),
+            ("http://a.example/y", "200"),
+            ("http://b.example/", "301"),
+            ("http://c.example/", "200"),
+            ("http://c.example/", "200"),
+            ("http://c.example/", "200"),
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_type(RecordType::Response);
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record.replace_body(format!("HTTP/1.1 {} X\r\n\r\n", status));
+            stats.add(&record);
+        }
+
+        let hosts = stats.by_host();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].0, "c.example");
+        assert_eq!(hosts[0].1.records, 4);
+        assert_eq!(hosts[0].1.error, 1);
+        assert_eq!(hosts[1].0, "a.example");
+        assert_eq!(hosts[1].1.records, 3);
+        assert_eq!(hosts[1].1.error, 0);
+        assert_eq!(hosts[1].1.statuses[&200], 2);
+        assert_eq!(hosts[1].1.statuses[&404], 1);
+        assert!(hosts[1].1.last_capture.is_some());
+
+        let mut stats = PartitionStats::new();
+        for uri in [
+            "http://a.example/x/1",
+            "http://a.example/x/2",
+            "http://a.example/",
+        ] {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            stats.add(&record);
+        }
+        let prefixes: Vec<_> = stats
+            .by_prefix()
+            .into_iter()
+            .map(|(prefix, totals)| (prefix, totals.records))
+            .collect();
+        assert_eq!(
+            prefixes,
+            vec![("http://a.example/x/", 2), ("http://a.example/", 1)]
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_members() {
+        let mut buffer = Vec::new();
+        let mut sizes = Vec::new();
+        {
+            let mut writer = WarcWriter::new(&mut buffer);
+            writer.set_member_compression(store_compressed_media);
+            for (content_type, body) in [
+                ("text/plain", vec![b'a'; 4000]),
+                (
+                    "image/jpeg",
+                    (0..4000).map(|i| (i * 7 % 251) as u8).collect(),
+                ),
+                ("text/plain", vec![b'b'; 2000]),
+            ] {
+                let mut record = Record::<BufferedBody>::default();
+                record.set_warc_version("1.0");
+                record
+                    .set_header(WarcHeader::ContentType, content_type)
+                    .unwrap();
+                record.replace_body(body);
+                sizes.push(writer.write(&record).unwrap() as u64);
+            }
+        }
+
+        let stats = CompressionStats::from_gzip(&buffer[..]).unwrap();
+        let compressed: Vec<_> = stats.records().iter().map(|r| r.compressed).collect();
+        assert_eq!(compressed, sizes);
+        assert_eq!(stats.total().compressed, buffer.len() as u64);
+
+        let text = stats.by_media_type()["text/plain"];
+        assert_eq!(text.records, 2);
+        assert!(text.ratio() < 0.1);
+        assert!(stats.by_media_type()["image/jpeg"].ratio() > 1.0);
+    }
+}
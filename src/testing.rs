@@ -0,0 +1,194 @@
+//! Helpers for constructing records in tests.
+//!
+//! All values generated by this module are deterministic, so records built from the same inputs
+//! are equal and serialize identically.
+
+use chrono::prelude::*;
+
+use crate::{RecordBuilder, RecordType};
+
+/// The WARC-Date given to every record built by this module.
+pub const FIXTURE_DATE: &str = "2020-01-01T00:00:00Z";
+
+/// Return `FIXTURE_DATE` as a date.
+pub fn fixture_date() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// Return a deterministic WARC-Record-ID for the given sequence number.
+pub fn fixture_record_id(n: u64) -> String {
+    format!(
+        "<urn:uuid:00000000-0000-4000-8000-{:012x}>",
+        n & 0xffff_ffff_ffff
+    )
+}
+
+/// A builder of records whose mandatory headers are filled with deterministic values.
+///
+/// Each record started by the same builder receives the next sequential record ID.
+#[derive(Clone, Debug)]
+pub struct FixtureBuilder {
+    next_id: u64,
+    date: DateTime<Utc>,
+}
+
+impl FixtureBuilder {
+    /// Create a new builder starting at record ID 1 and dated `FIXTURE_DATE`.
+    pub fn new() -> Self {
+        FixtureBuilder {
+            next_id: 1,
+            date: fixture_date(),
+        }
+    }
+
+    /// Set the date of the records started by this builder.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = date;
+
+        self
+    }
+
+    /// Start a record of the given type with the next record ID and the builder's date.
+    pub fn record(&mut self, warc_type: RecordType) -> RecordBuilder {
+        let id = fixture_record_id(self.next_id);
+        self.next_id += 1;
+
+        RecordBuilder::default()
+            .warc_id(id)
+            .date(self.date)
+            .warc_type(warc_type)
+    }
+}
+
+impl Default for FixtureBuilder {
+    fn default() -> Self {
+        FixtureBuilder::new()
+    }
+}
+
+/// Ready-made records for common test cases.
+///
+/// The records built here are always accepted by `RecordBuilder::build`, whatever the arguments.
+/// Should one ever be rejected, the function panics, as that is a bug in this module.
+pub mod fixtures {
+    use super::{fixture_date, fixture_record_id};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordBuilder, RecordType};
+
+    fn stable_id(warc_type: &RecordType, uri: &str) -> String {
+        // FNV-1a, which is stable across platforms and releases unlike `DefaultHasher`.
+        let hash = warc_type
+            .to_string()
+            .bytes()
+            .chain(uri.bytes())
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        fixture_record_id(hash)
+    }
+
+    fn build(builder: RecordBuilder) -> Record<BufferedBody> {
+        builder.build().expect("BUG: fixture records are valid")
+    }
+
+    /// Build a `response` record for `uri` containing an HTTP 200 response with an HTML body.
+    pub fn html_response(uri: &str, body: &str) -> Record<BufferedBody> {
+        let http = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        build(
+            RecordBuilder::default()
+                .warc_id(stable_id(&RecordType::Response, uri))
+                .date(fixture_date())
+                .warc_type(RecordType::Response)
+                .header(WarcHeader::TargetURI, uri)
+                .header(
+                    WarcHeader::ContentType,
+                    "application/http; msgtype=response",
+                )
+                .body(http.into_bytes()),
+        )
+    }
+
+    /// Build a `request` record for a HTTP GET of `uri`.
+    pub fn http_request(uri: &str) -> Record<BufferedBody> {
+        let host = url::Url::parse(uri)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        let path = url::Url::parse(uri)
+            .map(|url| url[url::Position::BeforePath..].to_owned())
+            .unwrap_or_else(|_| "/".to_owned());
+        let http = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host);
+        build(
+            RecordBuilder::default()
+                .warc_id(stable_id(&RecordType::Request, uri))
+                .date(fixture_date())
+                .warc_type(RecordType::Request)
+                .header(WarcHeader::TargetURI, uri)
+                .header(WarcHeader::ContentType, "application/http; msgtype=request")
+                .body(http.into_bytes()),
+        )
+    }
+
+    /// Build a `resource` record for `uri` with the given content type and body.
+    pub fn resource(uri: &str, content_type: &str, body: &[u8]) -> Record<BufferedBody> {
+        build(
+            RecordBuilder::default()
+                .warc_id(stable_id(&RecordType::Resource, uri))
+                .date(fixture_date())
+                .warc_type(RecordType::Resource)
+                .header(WarcHeader::TargetURI, uri)
+                .header(WarcHeader::ContentType, content_type)
+                .body(body.to_vec()),
+        )
+    }
+
+    /// Build the smallest valid `warcinfo` record.
+    pub fn minimal_warcinfo() -> Record<BufferedBody> {
+        build(
+            RecordBuilder::default()
+                .warc_id(fixture_record_id(0))
+                .date(fixture_date())
+                .warc_type(RecordType::WarcInfo)
+                .header(WarcHeader::ContentType, "application/warc-fields")
+                .body(b"software: warc-rs\r\nformat: WARC File Format 1.0\r\n".to_vec()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixture_record_id, fixtures, FixtureBuilder, FIXTURE_DATE};
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    #[test]
+    fn deterministic_fixtures() {
+        assert_eq!(
+            fixtures::html_response("http://example.com/", "<html></html>"),
+            fixtures::html_response("http://example.com/", "<html></html>")
+        );
+        assert_ne!(
+            fixtures::html_response("http://example.com/", "").warc_id(),
+            fixtures::http_request("http://example.com/").warc_id()
+        );
+
+        let warcinfo = fixtures::minimal_warcinfo();
+        assert_eq!(warcinfo.warc_type(), &RecordType::WarcInfo);
+        assert_eq!(warcinfo.header(WarcHeader::Date).unwrap(), FIXTURE_DATE);
+    }
+
+    #[test]
+    fn builder_sequence() {
+        let mut builder = FixtureBuilder::new();
+        let first = builder.record(RecordType::Resource).build().unwrap();
+        let second = builder.record(RecordType::Metadata).build().unwrap();
+        assert_eq!(first.warc_id(), fixture_record_id(1));
+        assert_eq!(second.warc_id(), fixture_record_id(2));
+        assert_eq!(second.header(WarcHeader::Date).unwrap(), FIXTURE_DATE);
+    }
+}
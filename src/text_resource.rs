@@ -0,0 +1,216 @@
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Error, Record, RecordType};
+
+/// A builder for resource records holding text, such as text extracted from a capture, encoded
+/// in a declared charset.
+///
+/// The text is encoded in the charset, and the record's Content-Type carries the charset as a
+/// parameter, so that readers can decode it again. UTF-8 and US-ASCII are always supported; other
+/// charsets, named by any of their WHATWG labels, require the `charset` feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextResource {
+    text: String,
+    charset: String,
+    media_type: String,
+    target_uri: Option<String>,
+    refers_to: Option<String>,
+    date: Option<DateTime<Utc>>,
+}
+
+impl TextResource {
+    /// Create a builder for a `text/plain` resource holding the text, encoded in UTF-8.
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        TextResource {
+            text: text.into(),
+            charset: "utf-8".to_owned(),
+            media_type: "text/plain".to_owned(),
+            target_uri: None,
+            refers_to: None,
+            date: None,
+        }
+    }
+
+    /// Set the charset the text is encoded in.
+    pub fn charset<S: Into<String>>(mut self, charset: S) -> Self {
+        self.charset = charset.into();
+
+        self
+    }
+
+    /// Set the media type of the resource, without parameters.
+    pub fn media_type<S: Into<String>>(mut self, media_type: S) -> Self {
+        self.media_type = media_type.into();
+
+        self
+    }
+
+    /// Set the WARC-Target-URI of the record.
+    pub fn target_uri<S: Into<String>>(mut self, target_uri: S) -> Self {
+        self.target_uri = Some(target_uri.into());
+
+        self
+    }
+
+    /// Set the WARC-Refers-To of the record, such as the ID of the capture the text was derived
+    /// from.
+    pub fn refers_to<S: Into<String>>(mut self, record_id: S) -> Self {
+        self.refers_to = Some(record_id.into());
+
+        self
+    }
+
+    /// Set the WARC-Date of the record. By default, the record is dated when it is built.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Encode the text, returning the name of the charset to declare and the encoded bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedBody` if the charset is not supported, or cannot represent every
+    /// character of the text.
+    pub fn encode(&self) -> Result<(String, Vec<u8>), Error> {
+        let label = self.charset.trim().to_ascii_lowercase();
+        let unrepresentable = || {
+            Error::MalformedBody(format!(
+                "text cannot be encoded in charset {:?}",
+                self.charset
+            ))
+        };
+        match label.as_str() {
+            "utf-8" | "utf8" => return Ok(("utf-8".to_owned(), self.text.clone().into_bytes())),
+            "us-ascii" | "ascii" => {
+                if !self.text.is_ascii() {
+                    return Err(unrepresentable());
+                }
+                return Ok(("us-ascii".to_owned(), self.text.clone().into_bytes()));
+            }
+            _ => {}
+        }
+
+        #[cfg(feature = "charset")]
+        {
+            if let Some(encoding) = encoding_rs::Encoding::for_label(label.as_bytes()) {
+                let name = encoding.name().to_ascii_lowercase();
+                // encoders for UTF-16 produce UTF-8, as the WHATWG standard only decodes UTF-16
+                let bytes = if encoding == encoding_rs::UTF_16LE {
+                    self.text
+                        .encode_utf16()
+                        .flat_map(u16::to_le_bytes)
+                        .collect()
+                } else if encoding == encoding_rs::UTF_16BE {
+                    self.text
+                        .encode_utf16()
+                        .flat_map(u16::to_be_bytes)
+                        .collect()
+                } else {
+                    let (bytes, _, unmappable) = encoding.encode(&self.text);
+                    if unmappable {
+                        return Err(unrepresentable());
+                    }
+                    bytes.into_owned()
+                };
+                return Ok((name, bytes));
+            }
+        }
+
+        Err(Error::MalformedBody(format!(
+            "unsupported charset {:?}",
+            self.charset
+        )))
+    }
+
+    /// Build the resource record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text cannot be encoded, as described for `encode`.
+    pub fn build(self) -> Result<Record<BufferedBody>, Error> {
+        let (charset, body) = self.encode()?;
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(RecordType::Resource);
+        if let Some(date) = self.date {
+            record.set_date(date);
+        }
+        if let Some(target_uri) = self.target_uri {
+            record.set_header(WarcHeader::TargetURI, target_uri)?;
+        }
+        if let Some(refers_to) = self.refers_to {
+            record.set_header(WarcHeader::RefersTo, refers_to)?;
+        }
+        record.set_header(
+            WarcHeader::ContentType,
+            format!("{}; charset={}", self.media_type, charset),
+        )?;
+        record.replace_body(body);
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextResource;
+    use crate::header::WarcHeader;
+    use crate::{Error, RecordType};
+
+    #[test]
+    fn text_resource() {
+        let record = TextResource::new("Grüße")
+            .target_uri("http://example.com/")
+            .refers_to("<urn:uuid:00000000-0000-0000-0000-000000000000>")
+            .build()
+            .unwrap();
+        assert_eq!(record.warc_type(), &RecordType::Resource);
+        assert_eq!(
+            record.header(WarcHeader::ContentType).as_deref(),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(record.body(), "Grüße".as_bytes());
+        assert_eq!(record.content_length(), 7);
+
+        let record = TextResource::new("a,b")
+            .charset("US-ASCII")
+            .media_type("text/csv")
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::ContentType).as_deref(),
+            Some("text/csv; charset=us-ascii")
+        );
+        assert!(matches!(
+            TextResource::new("Grüße").charset("us-ascii").build(),
+            Err(Error::MalformedBody(_))
+        ));
+
+        #[cfg(feature = "charset")]
+        {
+            let record = TextResource::new("Grüße")
+                .charset("latin1")
+                .build()
+                .unwrap();
+            assert_eq!(
+                record.header(WarcHeader::ContentType).as_deref(),
+                Some("text/plain; charset=windows-1252")
+            );
+            assert_eq!(record.body(), b"Gr\xfc\xdfe");
+
+            let record = TextResource::new("Язык")
+                .charset("utf-16le")
+                .build()
+                .unwrap();
+            assert_eq!(record.body(), b"\x2f\x04\x37\x04\x4b\x04\x3a\x04");
+            assert!(TextResource::new("Язык").charset("latin1").build().is_err());
+        }
+        #[cfg(not(feature = "charset"))]
+        assert!(TextResource::new("Grüße")
+            .charset("latin1")
+            .build()
+            .is_err());
+    }
+}
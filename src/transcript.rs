@@ -0,0 +1,223 @@
+use std::net::IpAddr;
+
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, CaptureSession, Record};
+
+const HTTP_REQUEST_CONTENT_TYPE: &str = "application/http;msgtype=request";
+const HTTP_RESPONSE_CONTENT_TYPE: &str = "application/http;msgtype=response";
+
+/// A raw exchange captured from a socket or proxy, convertible into request and response records.
+///
+/// The client bytes become the body of the request record and the server bytes become the body of
+/// the response record. Both records are linked as described by `CaptureSession`, and carry the
+/// negotiated protocols in a WARC-Protocol header and the TLS cipher suite, if any, in a
+/// WARC-Cipher-Suite header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcript {
+    target_uri: String,
+    client: Vec<u8>,
+    server: Vec<u8>,
+    date: DateTime<Utc>,
+    peer: Option<IpAddr>,
+    protocols: Vec<String>,
+    cipher_suite: Option<String>,
+    warcinfo_id: Option<String>,
+    content_types: (String, String),
+}
+
+impl Transcript {
+    /// Create a transcript of an exchange with the given URI, dated now.
+    ///
+    /// The exchange is assumed to be HTTP; use `content_types` for other protocols.
+    pub fn new<S, C, V>(target_uri: S, client: C, server: V) -> Self
+    where
+        S: Into<String>,
+        C: Into<Vec<u8>>,
+        V: Into<Vec<u8>>,
+    {
+        Transcript {
+            target_uri: target_uri.into(),
+            client: client.into(),
+            server: server.into(),
+            date: Utc::now(),
+            peer: None,
+            protocols: Vec::new(),
+            cipher_suite: None,
+            warcinfo_id: None,
+            content_types: (
+                HTTP_REQUEST_CONTENT_TYPE.to_owned(),
+                HTTP_RESPONSE_CONTENT_TYPE.to_owned(),
+            ),
+        }
+    }
+
+    /// Set the time at which the exchange started.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = date;
+
+        self
+    }
+
+    /// Set the address of the remote peer.
+    pub fn peer<A: Into<IpAddr>>(mut self, peer: A) -> Self {
+        self.peer = Some(peer.into());
+
+        self
+    }
+
+    /// Add a negotiated protocol, such as `h2` or `tls/1.3`.
+    ///
+    /// Protocols are listed in the WARC-Protocol header in the order they were added, from the
+    /// application layer down.
+    pub fn protocol<S: Into<String>>(mut self, protocol: S) -> Self {
+        self.protocols.push(protocol.into());
+
+        self
+    }
+
+    /// Set the negotiated TLS cipher suite, such as `TLS_AES_128_GCM_SHA256`.
+    pub fn cipher_suite<S: Into<String>>(mut self, cipher_suite: S) -> Self {
+        self.cipher_suite = Some(cipher_suite.into());
+
+        self
+    }
+
+    /// Set the WARC-Warcinfo-ID given to both records.
+    pub fn warcinfo_id<S: Into<String>>(mut self, warcinfo_id: S) -> Self {
+        self.warcinfo_id = Some(warcinfo_id.into());
+
+        self
+    }
+
+    /// Set the Content-Type of the request and response records.
+    pub fn content_types<S: Into<String>, T: Into<String>>(
+        mut self,
+        request: S,
+        response: T,
+    ) -> Self {
+        self.content_types = (request.into(), response.into());
+
+        self
+    }
+
+    /// Convert this transcript into its request and response records, in that order.
+    pub fn into_records(self) -> Vec<Record<BufferedBody>> {
+        let Transcript {
+            target_uri,
+            client,
+            server,
+            date,
+            peer,
+            protocols,
+            cipher_suite,
+            warcinfo_id,
+            content_types: (request_type, response_type),
+        } = self;
+
+        let mut session = CaptureSession::new();
+        if let Some(peer) = peer {
+            session = session.ip_address(peer.to_string());
+        }
+        if let Some(warcinfo_id) = warcinfo_id {
+            session = session.warcinfo_id(warcinfo_id);
+        }
+
+        let protocols = protocols.join(", ");
+        let fill = |record: &mut Record<BufferedBody>, body: Vec<u8>, content_type: String| {
+            record.replace_body(body);
+            record
+                .set_header(WarcHeader::ContentType, content_type)
+                .unwrap();
+            if !protocols.is_empty() {
+                record
+                    .set_header(WarcHeader::Protocol, protocols.as_str())
+                    .unwrap();
+            }
+            if let Some(ref cipher_suite) = cipher_suite {
+                record
+                    .set_header(WarcHeader::CipherSuite, cipher_suite.as_str())
+                    .unwrap();
+            }
+        };
+
+        let capture = session.begin_at(target_uri, date);
+        fill(capture.request(), client, request_type);
+        fill(capture.response(), server, response_type);
+
+        session.into_records()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transcript;
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn https_exchange() {
+        let records = Transcript::new(
+            "https://example.com/",
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..],
+            &b"HTTP/1.1 204 No Content\r\n\r\n"[..],
+        )
+        .peer(Ipv4Addr::new(93, 184, 216, 34))
+        .protocol("http/1.1")
+        .protocol("tls/1.3")
+        .cipher_suite("TLS_AES_128_GCM_SHA256")
+        .into_records();
+
+        assert_eq!(records.len(), 2);
+        let (request, response) = (&records[0], &records[1]);
+        assert_eq!(request.warc_type(), &RecordType::Request);
+        assert_eq!(response.warc_type(), &RecordType::Response);
+        assert_eq!(request.date(), response.date());
+        assert_eq!(
+            request.body(),
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..]
+        );
+        assert_eq!(response.body(), &b"HTTP/1.1 204 No Content\r\n\r\n"[..]);
+        assert_eq!(
+            request.header(WarcHeader::ConcurrentTo).unwrap(),
+            response.warc_id()
+        );
+        assert_eq!(
+            response.header(WarcHeader::ContentType).unwrap(),
+            "application/http;msgtype=response"
+        );
+
+        for record in &records {
+            assert_eq!(
+                record.header(WarcHeader::IPAddress).unwrap(),
+                "93.184.216.34"
+            );
+            assert_eq!(
+                record.header(WarcHeader::Protocol).unwrap(),
+                "http/1.1, tls/1.3"
+            );
+            assert_eq!(
+                record.header(WarcHeader::CipherSuite).unwrap(),
+                "TLS_AES_128_GCM_SHA256"
+            );
+        }
+    }
+
+    #[test]
+    fn plain_exchange() {
+        let records = Transcript::new("dns:example.com", "query", "answer")
+            .content_types("application/octet-stream", "text/dns")
+            .into_records();
+
+        assert_eq!(
+            records[0].header(WarcHeader::ContentType).unwrap(),
+            "application/octet-stream"
+        );
+        assert!(records[0].header(WarcHeader::IPAddress).is_none());
+        assert!(records[0].header(WarcHeader::Protocol).is_none());
+        assert!(records[1].header(WarcHeader::CipherSuite).is_none());
+    }
+}
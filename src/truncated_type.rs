@@ -1,3 +1,6 @@
+/// The reason a record was truncated, given by its WARC-Truncated header.
+///
+/// Unknown reasons are kept as written, so that they are written back unchanged.
 #[derive(Clone, Debug, PartialEq)]
 pub enum TruncatedType {
     Length,
@@ -7,8 +10,8 @@ pub enum TruncatedType {
     Unknown(String),
 }
 
-impl ToString for TruncatedType {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for TruncatedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let stringified = match *self {
             TruncatedType::Length => "length",
             TruncatedType::Time => "time",
@@ -16,19 +19,19 @@ impl ToString for TruncatedType {
             TruncatedType::Unspecified => "unspecified",
             TruncatedType::Unknown(ref val) => val.as_ref(),
         };
-        stringified.to_string()
+        write!(f, "{}", stringified)
     }
 }
 
 impl<S: AsRef<str>> From<S> for TruncatedType {
     fn from(string: S) -> Self {
-        let lower: String = string.as_ref().to_lowercase();
-        match lower.as_str() {
+        let string = string.as_ref();
+        match string.to_lowercase().as_str() {
             "length" => TruncatedType::Length,
             "time" => TruncatedType::Time,
             "disconnect" => TruncatedType::Disconnect,
             "unspecified" => TruncatedType::Unspecified,
-            _ => TruncatedType::Unknown(lower),
+            _ => TruncatedType::Unknown(string.to_owned()),
         }
     }
 }
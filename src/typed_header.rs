@@ -0,0 +1,393 @@
+//! Typed values of well-known headers.
+//!
+//! Each type here is the parsed value of one header, read with `Record::get` and written with
+//! `Record::set`:
+//!
+//! ```
+//! use warc::typed_header::{ConcurrentTo, TargetUri};
+//! use warc::{BufferedBody, Record};
+//!
+//! let mut record = Record::<BufferedBody>::default();
+//! record.set(&TargetUri("http://example.com/".parse().unwrap())).unwrap();
+//! assert_eq!(record.get::<TargetUri>().unwrap().0.host_str(), Some("example.com"));
+//! assert_eq!(record.get::<ConcurrentTo>(), None);
+//! ```
+//!
+//! Headers remain available as strings through `Record::header`, which is also the way to read a
+//! value which does not parse.
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use url::Url;
+
+#[cfg(feature = "digest")]
+use crate::digest::Digest;
+use crate::header::WarcHeader;
+
+/// The value of a header, parsed as a Rust type.
+pub trait TypedHeader: Sized {
+    /// Return the header this is the value of.
+    fn header() -> WarcHeader;
+
+    /// Parse the value of the header, or return `None` if it is not well-formed.
+    fn parse(value: &str) -> Option<Self>;
+
+    /// Format the value as it is written in the header.
+    fn format(&self) -> String;
+}
+
+/// Implement `TypedHeader` for a wrapper around a type which is formatted with `Display`, and
+/// parsed with `FromStr` unless another parser is given.
+macro_rules! typed_header {
+    ($(#[$doc:meta])* $name:ident($inner:ty) = $header:ident) => {
+        typed_header!($(#[$doc])* $name($inner) = $header, |value: &str| value.parse().ok());
+    };
+    ($(#[$doc:meta])* $name:ident($inner:ty) = $header:ident, $parse:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name(pub $inner);
+
+        impl TypedHeader for $name {
+            fn header() -> WarcHeader {
+                WarcHeader::$header
+            }
+
+            fn parse(value: &str) -> Option<Self> {
+                let parse: fn(&str) -> Option<$inner> = $parse;
+                parse(value.trim()).map($name)
+            }
+
+            fn format(&self) -> String {
+                self.0.to_string()
+            }
+        }
+    };
+}
+
+typed_header!(
+    /// The WARC-Target-URI header.
+    TargetUri(Url) = TargetURI
+);
+typed_header!(
+    /// The WARC-Refers-To-Target-URI header.
+    RefersToTargetUri(Url) = RefersToTargetURI
+);
+typed_header!(
+    /// The WARC-IP-Address header.
+    IpAddress(IpAddr) = IPAddress
+);
+typed_header!(
+    /// The WARC-Warcinfo-ID header.
+    WarcinfoId(String) = WarcInfoID
+);
+typed_header!(
+    /// The WARC-Filename header.
+    Filename(String) = Filename
+);
+typed_header!(
+    /// The WARC-Identified-Payload-Type header.
+    IdentifiedPayloadType(String) = IdentifiedPayloadType
+);
+typed_header!(
+    /// The WARC-Segment-Origin-ID header.
+    SegmentOriginId(String) = SegmentOriginID
+);
+typed_header!(
+    /// The WARC-Segment-Number header.
+    SegmentNumber(u64) = SegmentNumber
+);
+typed_header!(
+    /// The WARC-Segment-Total-Length header.
+    SegmentTotalLength(u64) = SegmentTotalLength
+);
+#[cfg(feature = "digest")]
+typed_header!(
+    /// The WARC-Block-Digest header, whose algorithm is supported and whose value is of the
+    /// algorithm's length.
+    BlockDigest(Digest) = BlockDigest,
+    Digest::parse
+);
+#[cfg(feature = "digest")]
+typed_header!(
+    /// The WARC-Payload-Digest header, whose algorithm is supported and whose value is of the
+    /// algorithm's length.
+    PayloadDigest(Digest) = PayloadDigest,
+    Digest::parse
+);
+
+/// The WARC-Concurrent-To header: the IDs of the records captured in the same transaction,
+/// separated by whitespace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConcurrentTo(pub Vec<String>);
+
+impl TypedHeader for ConcurrentTo {
+    fn header() -> WarcHeader {
+        WarcHeader::ConcurrentTo
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let ids: Vec<_> = value.split_whitespace().map(|id| id.to_owned()).collect();
+
+        Some(ConcurrentTo(ids)).filter(|ids| !ids.0.is_empty())
+    }
+
+    fn format(&self) -> String {
+        self.0.join(" ")
+    }
+}
+
+/// The WARC-Refers-To-Date header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefersToDate(pub DateTime<Utc>);
+
+impl TypedHeader for RefersToDate {
+    fn header() -> WarcHeader {
+        WarcHeader::RefersToDate
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        DateTime::parse_from_rfc3339(value.trim())
+            .ok()
+            .map(|date| RefersToDate(date.with_timezone(&Utc)))
+    }
+
+    fn format(&self) -> String {
+        self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+}
+
+/// The Content-Type header: a media type, in lowercase, and its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentType {
+    pub media_type: String,
+    /// The parameters in the order given, with names in lowercase and values unquoted. Values in
+    /// the extended notation of RFC 8187, `name*=utf-8''...`, are decoded.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl ContentType {
+    /// Return the value of a parameter, found without regard to the case of its name.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl TypedHeader for ContentType {
+    fn header() -> WarcHeader {
+        WarcHeader::ContentType
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';');
+        let media_type = parts.next()?.trim().to_ascii_lowercase();
+        match media_type.split_once('/') {
+            Some((type_, subtype)) if !type_.is_empty() && !subtype.is_empty() => {}
+            _ => return None,
+        }
+        let parameters = parts
+            .filter_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                let name = name.trim().to_ascii_lowercase();
+                let value = value.trim();
+                match name.strip_suffix('*') {
+                    Some(name) => Some((name.to_owned(), decode_ext_value(value)?)),
+                    None => Some((name, unquote(value))),
+                }
+            })
+            .collect();
+
+        Some(ContentType {
+            media_type,
+            parameters,
+        })
+    }
+
+    fn format(&self) -> String {
+        let mut value = self.media_type.clone();
+        for (name, parameter) in &self.parameters {
+            value.push(';');
+            value.push_str(name);
+            if !parameter.is_empty() && parameter.bytes().all(is_token_byte) {
+                value.push('=');
+                value.push_str(parameter);
+            } else if parameter.chars().all(|c| c == '\t' || !c.is_control()) {
+                value.push_str("=\"");
+                for c in parameter.chars() {
+                    if c == '"' || c == '\\' {
+                        value.push('\\');
+                    }
+                    value.push(c);
+                }
+                value.push('"');
+            } else {
+                value.push_str("*=utf-8''");
+                for b in parameter.bytes() {
+                    if is_token_byte(b) && !b"%'*".contains(&b) {
+                        value.push(b as char);
+                    } else {
+                        value.push_str(&format!("%{:02X}", b));
+                    }
+                }
+            }
+        }
+
+        value
+    }
+}
+
+/// Return whether a byte may appear in an HTTP token, as defined by RFC 9110.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Remove the quotes around a parameter value and the backslashes of its quoted pairs. Values
+/// which are not quoted are returned as they are.
+fn unquote(value: &str) -> String {
+    let inner = match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(inner) => inner,
+        None => return value.to_owned(),
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
+
+/// Decode a parameter value in the extended notation of RFC 8187, such as `utf-8''%E2%82%AC`.
+/// Only the UTF-8 charset is supported.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+    if !charset.eq_ignore_ascii_case("utf-8") {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{
+        ConcurrentTo, ContentType, IpAddress, RefersToDate, SegmentNumber, TargetUri, TypedHeader,
+    };
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record};
+
+    #[test]
+    fn typed_headers() {
+        let mut record = Record::<BufferedBody>::default();
+        assert_eq!(record.get::<TargetUri>(), None);
+
+        record
+            .set_header(WarcHeader::TargetURI, "http://example.com/a b")
+            .unwrap();
+        assert_eq!(
+            record.get::<TargetUri>().unwrap().0.as_str(),
+            "http://example.com/a%20b"
+        );
+        record
+            .set_header(WarcHeader::TargetURI, "not a uri")
+            .unwrap();
+        assert_eq!(record.get::<TargetUri>(), None);
+
+        record
+            .set(&ConcurrentTo(vec![
+                "<urn:test:a>".to_owned(),
+                "<urn:test:b>".to_owned(),
+            ]))
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::ConcurrentTo).unwrap(),
+            "<urn:test:a> <urn:test:b>"
+        );
+        assert_eq!(record.get::<ConcurrentTo>().unwrap().0.len(), 2);
+
+        record
+            .set_header(WarcHeader::IPAddress, " 2001:db8::1 ")
+            .unwrap();
+        assert!(record.get::<IpAddress>().unwrap().0.is_ipv6());
+        record.set(&SegmentNumber(2)).unwrap();
+        assert_eq!(record.header(WarcHeader::SegmentNumber).unwrap(), "2");
+
+        let date = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            RefersToDate::parse("2020-01-02T03:04:05Z"),
+            Some(RefersToDate(date))
+        );
+
+        let content_type =
+            ContentType::parse("Application/HTTP; msgtype=response; Note=\"a b\"").unwrap();
+        assert_eq!(content_type.media_type, "application/http");
+        assert_eq!(content_type.parameter("MsgType"), Some("response"));
+        assert_eq!(
+            content_type.format(),
+            "application/http;msgtype=response;note=\"a b\""
+        );
+        assert_eq!(ContentType::parse("text"), None);
+
+        #[cfg(feature = "digest")]
+        {
+            use super::PayloadDigest;
+            use crate::digest::Algorithm;
+
+            record
+                .set_header(
+                    WarcHeader::PayloadDigest,
+                    "sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ",
+                )
+                .unwrap();
+            let digest = record.get::<PayloadDigest>().unwrap().0;
+            assert_eq!(digest.algorithm, Algorithm::Sha1);
+        }
+    }
+
+    #[test]
+    fn content_type_parameters() {
+        let content_type = |value: &str| ContentType {
+            media_type: "text/plain".to_owned(),
+            parameters: vec![("note".to_owned(), value.to_owned())],
+        };
+        let cases = [
+            ("", "text/plain;note=\"\""),
+            (r#"say "hi" \o/"#, r#"text/plain;note="say \"hi\" \\o/""#),
+            ("caf\u{e9}\ttab", "text/plain;note=\"caf\u{e9}\ttab\""),
+            ("a\r\nb%", "text/plain;note*=utf-8''a%0D%0Ab%25"),
+        ];
+        for (value, formatted) in cases.iter() {
+            assert_eq!(content_type(value).format(), *formatted);
+            assert_eq!(ContentType::parse(formatted), Some(content_type(value)));
+        }
+
+        let unsupported = ContentType::parse("text/plain;note*=latin-1''a").unwrap();
+        assert_eq!(unsupported.parameter("note"), None);
+    }
+}
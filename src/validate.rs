@@ -0,0 +1,904 @@
+//! Validation of WARC files against rules.
+//!
+//! A `Validator` runs a set of `Rule`s over the records of a file and gathers what they find into
+//! one report. `Validator::standard` holds the rules of the standard (ISO 28500) which go beyond
+//! what a reader already enforces. Further rules, such as naming conventions or metadata which an
+//! organization requires in every file, can be added by implementing `Rule`; a `Schema` is itself
+//! a rule.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+
+use url::Url;
+
+use crate::header::WarcHeader;
+use crate::record_ids::check_record_id;
+use crate::{http, BufferedBody, Record, RecordType, RevisitProfile, Schema, WarcReader};
+
+/// How serious a finding is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// A practice the standard recommends against, or a convention which is not followed.
+    Warning,
+    /// A requirement which is not met.
+    Error,
+}
+
+/// A problem found by a rule, before it is attributed to a record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Issue {
+    /// How serious the issue is.
+    pub severity: Severity,
+    /// A description of the issue, such as the header at fault and what was wrong with it.
+    pub message: String,
+}
+
+impl Issue {
+    /// Create an issue of severity `Error`.
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Issue {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    /// Create an issue of severity `Warning`.
+    pub fn warning<S: Into<String>>(message: S) -> Self {
+        Issue {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+/// A check which a `Validator` runs over the records of a file.
+pub trait Rule {
+    /// Return the name of the rule, which identifies it in reports.
+    fn name(&self) -> &str;
+
+    /// Check a record. Records are given in the order of the file.
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue>;
+
+    /// Check the file once all of its records have been given, and reset any state kept for it.
+    fn finish(&mut self) -> Vec<Issue> {
+        Vec::new()
+    }
+}
+
+/// An issue found by a rule, with the record it was found in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    /// The name of the rule which found the issue, as returned by `Rule::name`.
+    pub rule: String,
+    /// How serious the issue is.
+    pub severity: Severity,
+    /// The index of the record in the file, or `None` for issues with the file as a whole and
+    /// for records checked by `Validator::validate_record`.
+    pub record: Option<usize>,
+    /// The WARC-Record-ID of the record, or `None` for issues with the file as a whole and for
+    /// records which could not be parsed.
+    pub record_id: Option<String>,
+    /// The description of the issue given by the rule.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} [{}]", severity, self.rule)?;
+        if let Some(record) = self.record {
+            write!(f, " record {}", record)?;
+        }
+        if let Some(ref record_id) = self.record_id {
+            write!(f, " {}", record_id)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The findings of a `Validator` for one file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// The number of records read, including those which could not be parsed.
+    pub records: usize,
+    /// Every finding, in the order found.
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Return true if no finding is an error.
+    pub fn is_valid(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|finding| finding.severity < Severity::Error)
+    }
+
+    /// Return the findings of the given rule.
+    pub fn by_rule<'a>(&'a self, rule: &'a str) -> impl Iterator<Item = &'a Finding> + 'a {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.rule == rule)
+    }
+}
+
+/// A set of rules run over the records of files.
+#[derive(Default)]
+pub struct Validator {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Validator {
+    /// Create a validator with no rules.
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    /// Create a validator with the rules of the standard.
+    pub fn standard() -> Self {
+        let validator = Validator::new()
+            .rule(RequiredHeaders)
+            .rule(ProhibitedHeaders)
+            .rule(RecordIds)
+            .rule(TargetUris)
+            .rule(ContentTypes)
+            .rule(DigestLabels)
+            .rule(UniqueRecordIds::default())
+            .rule(WarcinfoFirst::default());
+        #[cfg(feature = "digest")]
+        let validator = validator.rule(TruncatedDigests);
+
+        validator
+    }
+
+    /// Add a rule, which runs after those added before.
+    pub fn rule<R: Rule + 'static>(mut self, rule: R) -> Self {
+        self.rules.push(Box::new(rule));
+
+        self
+    }
+
+    /// Read every record of a file and check it against the rules.
+    ///
+    /// Records which cannot be parsed are reported as errors of the `well-formed` rule.
+    pub fn validate<R: BufRead>(&mut self, reader: WarcReader<R>) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for (index, record) in reader.iter_records().enumerate() {
+            report.records += 1;
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    report.findings.push(Finding {
+                        rule: "well-formed".to_owned(),
+                        severity: Severity::Error,
+                        record: Some(index),
+                        record_id: None,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            report.findings.extend(self.check(Some(index), &record));
+        }
+        report.findings.extend(self.finish());
+
+        report
+    }
+
+    /// Check a single record against the rules, as a writer might before writing it.
+    ///
+    /// Rules which keep state, such as `UniqueRecordIds`, treat the records checked as one file
+    /// until `finish` is called.
+    pub fn validate_record(&mut self, record: &Record<BufferedBody>) -> Vec<Finding> {
+        self.check(None, record)
+    }
+
+    /// End a file of records checked by `validate_record`, returning the findings of the rules
+    /// about the file as a whole.
+    pub fn finish(&mut self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for rule in &mut self.rules {
+            for issue in rule.finish() {
+                findings.push(Finding {
+                    rule: rule.name().to_owned(),
+                    severity: issue.severity,
+                    record: None,
+                    record_id: None,
+                    message: issue.message,
+                });
+            }
+        }
+
+        findings
+    }
+
+    fn check(&mut self, index: Option<usize>, record: &Record<BufferedBody>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for rule in &mut self.rules {
+            for issue in rule.check(record) {
+                findings.push(Finding {
+                    rule: rule.name().to_owned(),
+                    severity: issue.severity,
+                    record: index,
+                    record_id: Some(record.warc_id().to_owned()),
+                    message: issue.message,
+                });
+            }
+        }
+
+        findings
+    }
+}
+
+/// The headers the standard requires or recommends for records of each type, and those the
+/// profile of a revisit record requires.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequiredHeaders;
+
+impl Rule for RequiredHeaders {
+    fn name(&self) -> &str {
+        "required-headers"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        let required: &[WarcHeader] = match record.warc_type() {
+            RecordType::Response
+            | RecordType::Resource
+            | RecordType::Request
+            | RecordType::Conversion => &[WarcHeader::TargetURI],
+            RecordType::Revisit => &[WarcHeader::TargetURI, WarcHeader::Profile],
+            RecordType::Continuation => &[
+                WarcHeader::TargetURI,
+                WarcHeader::SegmentOriginID,
+                WarcHeader::SegmentNumber,
+            ],
+            _ => &[],
+        };
+        let mut issues: Vec<_> = required
+            .iter()
+            .filter(|header| record.header((*header).clone()).is_none())
+            .map(|header| Issue::error(format!("missing {}", header)))
+            .collect();
+        if record.warc_type() == &RecordType::Revisit
+            && record.revisit_profile() == Some(RevisitProfile::IdenticalPayloadDigest)
+            && record.header(WarcHeader::PayloadDigest).is_none()
+        {
+            issues.push(Issue::error(format!(
+                "missing {} required by the identical-payload-digest profile",
+                WarcHeader::PayloadDigest
+            )));
+        }
+        if record.content_length() > 0
+            && record.warc_type() != &RecordType::Continuation
+            && record.header(WarcHeader::ContentType).is_none()
+        {
+            issues.push(Issue::warning("non-empty block without Content-Type"));
+        }
+
+        issues
+    }
+}
+
+/// The headers the standard prohibits in records of each type, or which the version of the
+/// record does not define.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProhibitedHeaders;
+
+impl ProhibitedHeaders {
+    /// Return true if the standard allows the header in records of the type.
+    fn allows(header: &WarcHeader, record_type: &RecordType) -> bool {
+        use RecordType::*;
+
+        match *header {
+            WarcHeader::Filename => *record_type == WarcInfo,
+            WarcHeader::WarcInfoID => *record_type != WarcInfo,
+            WarcHeader::Profile | WarcHeader::RefersToTargetURI | WarcHeader::RefersToDate => {
+                *record_type == Revisit
+            }
+            WarcHeader::RefersTo => !matches!(
+                *record_type,
+                WarcInfo | Response | Resource | Request | Continuation
+            ),
+            WarcHeader::ConcurrentTo => {
+                !matches!(*record_type, WarcInfo | Conversion | Continuation)
+            }
+            WarcHeader::SegmentOriginID | WarcHeader::SegmentTotalLength => {
+                *record_type == Continuation
+            }
+            WarcHeader::IdentifiedPayloadType => record_type.has_payload(),
+            _ => true,
+        }
+    }
+}
+
+impl Rule for ProhibitedHeaders {
+    fn name(&self) -> &str {
+        "prohibited-headers"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        let version = record.version();
+        [
+            WarcHeader::Filename,
+            WarcHeader::WarcInfoID,
+            WarcHeader::Profile,
+            WarcHeader::RefersTo,
+            WarcHeader::RefersToTargetURI,
+            WarcHeader::RefersToDate,
+            WarcHeader::ConcurrentTo,
+            WarcHeader::SegmentOriginID,
+            WarcHeader::SegmentTotalLength,
+            WarcHeader::IdentifiedPayloadType,
+        ]
+        .iter()
+        .filter(|header| record.header((*header).clone()).is_some())
+        .filter_map(|header| {
+            if !version.defines_header(header) {
+                Some(Issue::error(format!(
+                    "{} not defined by {}",
+                    header, version
+                )))
+            } else if !ProhibitedHeaders::allows(header, record.warc_type()) {
+                Some(Issue::error(format!(
+                    "{} not allowed in {} records",
+                    header,
+                    record.warc_type()
+                )))
+            } else {
+                None
+            }
+        })
+        .collect()
+    }
+}
+
+/// WARC-Record-ID values must be URIs enclosed in angle brackets.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecordIds;
+
+impl Rule for RecordIds {
+    fn name(&self) -> &str {
+        "record-id"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        match check_record_id(record.warc_id()) {
+            Ok(()) => Vec::new(),
+            Err(reason) => vec![Issue::error(format!("WARC-Record-ID {}", reason))],
+        }
+    }
+}
+
+/// Content-Type values must be media types, and should be those the standard gives for warcinfo
+/// records and captured HTTP messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContentTypes;
+
+impl Rule for ContentTypes {
+    fn name(&self) -> &str {
+        "content-type"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        let content_type = match record.header(WarcHeader::ContentType) {
+            Some(content_type) => content_type,
+            None => return Vec::new(),
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let is_token = |s: &str| {
+            !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b))
+        };
+        match media_type.split_once('/') {
+            Some((type_, subtype)) if is_token(type_) && is_token(subtype) => {}
+            _ => {
+                return vec![Issue::error(format!(
+                    "not a media type: {:?}",
+                    content_type
+                ))]
+            }
+        }
+
+        let is_http = record
+            .header(WarcHeader::TargetURI)
+            .is_some_and(|uri| uri.starts_with("http:") || uri.starts_with("https:"));
+        match record.warc_type() {
+            RecordType::WarcInfo if media_type != "application/warc-fields" => {
+                vec![Issue::warning(format!(
+                    "warcinfo record of type {:?}, not application/warc-fields",
+                    media_type
+                ))]
+            }
+            RecordType::Request | RecordType::Response
+                if is_http && !http::is_http_message(&content_type) =>
+            {
+                vec![Issue::warning(format!(
+                    "HTTP {} of type {:?}, not application/http",
+                    record.warc_type(),
+                    media_type
+                ))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// WARC-Target-URI values must be URIs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TargetUris;
+
+impl Rule for TargetUris {
+    fn name(&self) -> &str {
+        "target-uri"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        match record.header(WarcHeader::TargetURI) {
+            Some(uri) if Url::parse(&uri).is_err() => {
+                vec![Issue::error(format!("not a URI: {:?}", uri))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// WARC-Target-URI values must use one of a list of schemes.
+///
+/// This rule is not part of `Validator::standard`, since the standard allows any scheme, but
+/// stray schemes such as `javascript:` break many indexing systems. Values which are not URIs
+/// are left to `TargetUris`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UriSchemes {
+    schemes: HashSet<String>,
+}
+
+impl Default for UriSchemes {
+    /// Allow the `http`, `https`, `dns`, `ftp`, `urn` and `mailto` schemes.
+    fn default() -> Self {
+        UriSchemes::new(&["http", "https", "dns", "ftp", "urn", "mailto"])
+    }
+}
+
+impl UriSchemes {
+    /// Allow only the given schemes, compared without regard to case.
+    pub fn new(schemes: &[&str]) -> Self {
+        UriSchemes {
+            schemes: schemes
+                .iter()
+                .map(|scheme| scheme.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Allow another scheme.
+    pub fn allow(mut self, scheme: &str) -> Self {
+        self.schemes.insert(scheme.to_ascii_lowercase());
+
+        self
+    }
+}
+
+impl Rule for UriSchemes {
+    fn name(&self) -> &str {
+        "uri-scheme"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        let uri = match record.header(WarcHeader::TargetURI) {
+            Some(uri) => uri,
+            None => return Vec::new(),
+        };
+        match Url::parse(&uri) {
+            Ok(url) if !self.schemes.contains(url.scheme()) => vec![Issue::error(format!(
+                "scheme not allowed: {:?}",
+                url.scheme()
+            ))],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Digest values should be labeled with their algorithm, as `algorithm:value`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DigestLabels;
+
+impl Rule for DigestLabels {
+    fn name(&self) -> &str {
+        "digest-labels"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        [WarcHeader::BlockDigest, WarcHeader::PayloadDigest]
+            .iter()
+            .filter_map(|header| {
+                let value = record.header(header.clone())?;
+                match value.split_once(':') {
+                    Some((algorithm, digest)) if !algorithm.is_empty() && !digest.is_empty() => {
+                        None
+                    }
+                    _ => Some(Issue::warning(format!(
+                        "{} is not labeled with its algorithm: {:?}",
+                        header, value
+                    ))),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The digests of a truncated record should cover the content as stored, not the original
+/// content.
+#[cfg(feature = "digest")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TruncatedDigests;
+
+#[cfg(feature = "digest")]
+impl Rule for TruncatedDigests {
+    fn name(&self) -> &str {
+        "truncated-digests"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        record
+            .check_truncated_digests()
+            .iter()
+            .map(|warning| Issue::warning(warning.to_string()))
+            .collect()
+    }
+}
+
+/// Record IDs must be unique.
+#[derive(Clone, Debug, Default)]
+pub struct UniqueRecordIds {
+    seen: HashSet<String>,
+}
+
+impl Rule for UniqueRecordIds {
+    fn name(&self) -> &str {
+        "unique-record-ids"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        if self.seen.insert(record.warc_id().to_owned()) {
+            Vec::new()
+        } else {
+            vec![Issue::error("duplicate WARC-Record-ID")]
+        }
+    }
+
+    fn finish(&mut self) -> Vec<Issue> {
+        self.seen.clear();
+        Vec::new()
+    }
+}
+
+/// A file should begin with a warcinfo record describing it.
+#[derive(Clone, Debug, Default)]
+pub struct WarcinfoFirst {
+    started: bool,
+}
+
+impl Rule for WarcinfoFirst {
+    fn name(&self) -> &str {
+        "warcinfo-first"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        if std::mem::replace(&mut self.started, true) || record.warc_type() == &RecordType::WarcInfo
+        {
+            Vec::new()
+        } else {
+            vec![Issue::warning("the first record is not a warcinfo record")]
+        }
+    }
+
+    fn finish(&mut self) -> Vec<Issue> {
+        self.started = false;
+        Vec::new()
+    }
+}
+
+impl Rule for Schema {
+    fn name(&self) -> &str {
+        "schema"
+    }
+
+    fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+        Schema::check(self, record)
+            .into_iter()
+            .map(|violation| Issue::error(violation.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ContentTypes, Issue, ProhibitedHeaders, RecordIds, RequiredHeaders, Rule, Severity,
+        UriSchemes, Validator,
+    };
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, RevisitProfile, Schema, WarcReader, WarcWriter};
+
+    /// Every file must hold a metadata record.
+    #[derive(Default)]
+    struct MetadataPerFile {
+        found: bool,
+    }
+
+    impl Rule for MetadataPerFile {
+        fn name(&self) -> &str {
+            "metadata-per-file"
+        }
+
+        fn check(&mut self, record: &Record<BufferedBody>) -> Vec<Issue> {
+            self.found |= record.warc_type() == &RecordType::Metadata;
+            Vec::new()
+        }
+
+        fn finish(&mut self) -> Vec<Issue> {
+            if std::mem::take(&mut self.found) {
+                Vec::new()
+            } else {
+                vec![Issue::error("no metadata record")]
+            }
+        }
+    }
+
+    #[test]
+    fn uri_schemes() {
+        let mut rule = UriSchemes::default();
+        let mut record = Record::<BufferedBody>::default();
+        assert!(rule.check(&record).is_empty());
+        for &(uri, allowed) in &[
+            ("HTTPS://example.com/", true),
+            ("dns:example.com", true),
+            ("urn:uuid:00000000-0000-0000-0000-000000000000", true),
+            ("javascript:void(0)", false),
+            ("not a uri", true),
+        ] {
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            assert_eq!(rule.check(&record).is_empty(), allowed, "{}", uri);
+        }
+
+        let mut rule = UriSchemes::new(&["http"]).allow("JavaScript");
+        assert!(rule.check(&record).is_empty());
+        record
+            .set_header(WarcHeader::TargetURI, "https://example.com/")
+            .unwrap();
+        assert_eq!(
+            rule.check(&record),
+            vec![Issue::error("scheme not allowed: \"https\"")]
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_version("1.0");
+        response.set_warc_type(RecordType::Response);
+        response.replace_body("HTTP/1.1 200 OK\r\n\r\n");
+        response
+            .set_header(WarcHeader::BlockDigest, "3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ")
+            .unwrap();
+        let mut resource = Record::<BufferedBody>::default();
+        resource.set_warc_version("1.0");
+        resource
+            .set_header(WarcHeader::TargetURI, "not a uri")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write(&response).unwrap();
+        writer.write(&resource).unwrap();
+        writer.write(&resource).unwrap();
+        drop(writer);
+
+        let mut validator = Validator::standard()
+            .rule(MetadataPerFile::default())
+            .rule(Schema::new().require(&[RecordType::Response], "X-Crawl-Job"));
+        let report = validator.validate(WarcReader::new(&buffer[..]));
+        assert_eq!(report.records, 3);
+        assert!(!report.is_valid());
+        let found: Vec<_> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.rule.as_str(), finding.severity, finding.record))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("required-headers", Severity::Error, Some(0)),
+                ("required-headers", Severity::Warning, Some(0)),
+                ("digest-labels", Severity::Warning, Some(0)),
+                ("warcinfo-first", Severity::Warning, Some(0)),
+                ("schema", Severity::Error, Some(0)),
+                ("target-uri", Severity::Error, Some(1)),
+                ("target-uri", Severity::Error, Some(2)),
+                ("unique-record-ids", Severity::Error, Some(2)),
+                ("metadata-per-file", Severity::Error, None),
+            ]
+        );
+        assert_eq!(
+            report.findings[0].to_string(),
+            format!(
+                "error [required-headers] record 0 {}: missing warc-target-uri",
+                response.warc_id()
+            )
+        );
+
+        let again = validator.validate(WarcReader::new(&buffer[..]));
+        assert_eq!(again, report);
+        assert_eq!(report.by_rule("schema").count(), 1);
+    }
+
+    #[test]
+    fn revisit_profiles() {
+        let mut rule = RequiredHeaders;
+        let mut revisit = Record::<BufferedBody>::default();
+        revisit.set_warc_type(RecordType::Revisit);
+        revisit
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+        assert_eq!(
+            rule.check(&revisit),
+            vec![Issue::error("missing warc-profile")]
+        );
+
+        revisit.set_revisit_profile(RevisitProfile::ServerNotModified);
+        assert!(rule.check(&revisit).is_empty());
+
+        revisit.set_revisit_profile(RevisitProfile::IdenticalPayloadDigest);
+        assert_eq!(
+            rule.check(&revisit),
+            vec![Issue::error(
+                "missing warc-payload-digest required by the identical-payload-digest profile"
+            )]
+        );
+        revisit
+            .set_header(
+                WarcHeader::PayloadDigest,
+                "sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ",
+            )
+            .unwrap();
+        assert!(rule.check(&revisit).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "digest")]
+    fn truncated_digests() {
+        use crate::digest::Algorithm;
+        use crate::TruncatedType;
+
+        let mut record = Record::<BufferedBody>::default();
+        record.replace_body(&b"hel"[..]);
+        record
+            .set_header(
+                WarcHeader::BlockDigest,
+                Algorithm::Sha1.compute(b"hello").to_string(),
+            )
+            .unwrap();
+        let mut validator = Validator::standard();
+        assert!(validator
+            .validate_record(&record)
+            .iter()
+            .all(|finding| finding.rule != "truncated-digests"));
+
+        record.set_truncated_type(TruncatedType::Length);
+        let found: Vec<_> = validator
+            .validate_record(&record)
+            .into_iter()
+            .filter(|finding| finding.rule == "truncated-digests")
+            .map(|finding| (finding.severity, finding.message))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(
+                Severity::Warning,
+                "Digest of truncated record covers the original content: warc-block-digest"
+                    .to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn standard_rules() {
+        let mut validator = Validator::new()
+            .rule(ProhibitedHeaders)
+            .rule(RecordIds)
+            .rule(ContentTypes);
+        let rules = |findings: Vec<super::Finding>| -> Vec<(String, String)> {
+            findings
+                .into_iter()
+                .map(|finding| (finding.rule, finding.message))
+                .collect()
+        };
+
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        warcinfo
+            .set_header(WarcHeader::Filename, "a.warc.gz")
+            .unwrap();
+        warcinfo
+            .set_header(WarcHeader::ContentType, "application/warc-fields")
+            .unwrap();
+        assert!(validator.validate_record(&warcinfo).is_empty());
+        let mut identified = warcinfo.clone();
+        identified
+            .set_header(WarcHeader::IdentifiedPayloadType, "text/plain")
+            .unwrap();
+        assert_eq!(
+            rules(validator.validate_record(&identified)),
+            vec![(
+                "prohibited-headers".to_owned(),
+                "warc-identified-payload-type not allowed in warcinfo records".to_owned()
+            )]
+        );
+        warcinfo
+            .set_header(WarcHeader::ContentType, "text/plain")
+            .unwrap();
+        assert_eq!(
+            validator.validate_record(&warcinfo)[0].severity,
+            Severity::Warning
+        );
+
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_type(RecordType::Response);
+        response
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+        response
+            .set_header(WarcHeader::Filename, "a.warc.gz")
+            .unwrap();
+        response
+            .set_header(WarcHeader::RefersToDate, "2020-01-01T00:00:00Z")
+            .unwrap();
+        response
+            .set_header(WarcHeader::ContentType, "text/html")
+            .unwrap();
+        response.set_warc_id("urn:uuid:no-brackets");
+        assert_eq!(
+            rules(validator.validate_record(&response)),
+            vec![
+                (
+                    "prohibited-headers".to_owned(),
+                    "warc-filename not allowed in response records".to_owned()
+                ),
+                (
+                    "prohibited-headers".to_owned(),
+                    "warc-refers-to-date not defined by WARC/1.0".to_owned()
+                ),
+                (
+                    "record-id".to_owned(),
+                    "WARC-Record-ID not enclosed in angle brackets".to_owned()
+                ),
+                (
+                    "content-type".to_owned(),
+                    "HTTP response of type \"text/html\", not application/http".to_owned()
+                ),
+            ]
+        );
+
+        let mut resource = Record::<BufferedBody>::default();
+        resource
+            .set_header(WarcHeader::ContentType, "text")
+            .unwrap();
+        assert_eq!(
+            rules(validator.validate_record(&resource)),
+            vec![(
+                "content-type".to_owned(),
+                "not a media type: \"text\"".to_owned()
+            )]
+        );
+        assert!(validator.finish().is_empty());
+    }
+}
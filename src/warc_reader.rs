@@ -1,14 +1,26 @@
-use crate::parser;
-use crate::{BufferedBody, Error, RawRecordHeader, Record, StreamingBody};
-
-use std::convert::TryInto;
+use crate::header::WarcHeader;
+use crate::parser::HeaderLine;
+use crate::recompress::Codec;
+use crate::{legacy, parser};
+use crate::{
+    BufferedBody, Error, HeaderPolicy, ParserConfig, RawRecordHeader, ReadPolicy, Reassemble,
+    Record, SeparatorPolicy, StreamingBody, Warning,
+};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fs;
 use std::io;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
 #[cfg(feature = "gzip")]
-use libflate::gzip::Decoder as GzipReader;
+use libflate::gzip::MultiDecoder as GzipReader;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+#[cfg(feature = "mmap")]
+use crate::{RecordRef, RecordRefIter};
 
 const KB: usize = 1_024;
 const MB: usize = 1_048_576;
@@ -16,12 +28,23 @@ const MB: usize = 1_048_576;
 /// A reader which iteratively parses WARC records from a stream.
 pub struct WarcReader<R> {
     reader: R,
+    config: ParserConfig,
 }
 
 impl<R: BufRead> WarcReader<R> {
     /// Create a new reader.
     pub fn new(r: R) -> Self {
-        WarcReader { reader: r }
+        WarcReader {
+            reader: r,
+            config: ParserConfig::default(),
+        }
+    }
+
+    /// Set the configuration used to interpret the records read.
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+
+        self
     }
 
     /// Create an iterator over all of the raw records read.
@@ -29,7 +52,7 @@ impl<R: BufRead> WarcReader<R> {
     /// This only does well-formedness checks on the headers. See `RawRecordHeader` for more
     /// information.
     pub fn iter_raw_records(self) -> RawRecordIter<R> {
-        RawRecordIter::new(self.reader)
+        RawRecordIter::new(self.reader, self.config)
     }
 
     /// Create an iterator over all of the records read.
@@ -37,7 +60,36 @@ impl<R: BufRead> WarcReader<R> {
     /// This will fully build each record and check it for semantic correctness. See the `Record`
     /// type for more information.
     pub fn iter_records(self) -> RecordIter<R> {
-        RecordIter::new(self.reader)
+        RecordIter::new(self.reader, self.config)
+    }
+
+    /// Create an iterator over all of the records read, joining segmented records into the
+    /// records they were split from. See `Reassemble` for how segments are joined.
+    pub fn iter_reassembled_records(self) -> Reassemble<RecordIter<R>> {
+        Reassemble::new(self.iter_records())
+    }
+
+    /// Create an iterator over all of the records read, each with its position in the input.
+    ///
+    /// The iterator also gives the position of the record an error was returned for, so that
+    /// failures can be reported precisely and reading resumed from the next record.
+    pub fn iter_positioned_records(self) -> PositionedRecordIter<R> {
+        PositionedRecordIter {
+            records: self.iter_records(),
+            position: None,
+            index: 0,
+        }
+    }
+
+    /// Create an iterator over all of the records read, whose bodies are `Bytes` buffers.
+    ///
+    /// Each body takes ownership of the buffer it was read into, so it can be passed on to
+    /// libraries which use `Bytes` without being copied.
+    #[cfg(feature = "bytes")]
+    pub fn iter_bytes_records(self) -> BytesRecordIter<R> {
+        BytesRecordIter {
+            records: self.iter_records(),
+        }
     }
 
     /// Create a streaming iterator over all of the records read.
@@ -45,7 +97,195 @@ impl<R: BufRead> WarcReader<R> {
     /// This will build each record header, and allow the caller to decide whether to read
     /// the body or not.
     pub fn stream_records(&mut self) -> StreamingIter<'_, R> {
-        StreamingIter::new(&mut self.reader)
+        StreamingIter::new(&mut self.reader, &self.config)
+    }
+
+    /// Count the remaining records and the bytes of their blocks, without building them.
+    ///
+    /// Only version lines and Content-Length headers are interpreted: other header lines are
+    /// skipped as they are read, and blocks are skipped without being copied, so nothing is
+    /// allocated. The reader's configuration is not used, and the first record which is not
+    /// well-formed ends the scan with an error.
+    pub fn scan(&mut self) -> Result<ScanSummary, Error> {
+        let mut summary = ScanSummary::default();
+        let mut line = [0; 64];
+        loop {
+            let (len, stored) = match read_line_prefix(&mut self.reader, &mut line)? {
+                Some(read) => read,
+                None => return Ok(summary),
+            };
+            if !line[..stored].starts_with(b"WARC/") {
+                return Err(Error::ParseHeaders);
+            }
+            summary.bytes += len as u64;
+
+            let mut content_length = None;
+            loop {
+                let (len, stored) =
+                    read_line_prefix(&mut self.reader, &mut line)?.ok_or(Error::UnexpectedEOB)?;
+                summary.bytes += len as u64;
+                let line = &line[..stored];
+                if line == b"\r\n" {
+                    break;
+                }
+                if line.len() > 15 && line[..15].eq_ignore_ascii_case(b"content-length:") {
+                    let value = std::str::from_utf8(&line[15..])
+                        .ok()
+                        .filter(|_| stored == len)
+                        .and_then(|value| value.trim().parse::<u64>().ok());
+                    content_length = Some(value.ok_or(Error::ParseHeaders)?);
+                }
+            }
+            let content_length =
+                content_length.ok_or(Error::MissingHeader(WarcHeader::ContentLength))?;
+
+            skip_bytes(&mut self.reader, content_length)?;
+            let mut separator = [0; 4];
+            self.reader
+                .read_exact(&mut separator)
+                .map_err(|_| Error::UnexpectedEOB)?;
+            if &separator != b"\r\n\r\n" {
+                return Err(Error::ReadOverflow);
+            }
+            summary.records += 1;
+            summary.content_bytes += content_length;
+            summary.bytes += content_length + 4;
+        }
+    }
+
+    /// Count the remaining records, as `scan` does.
+    pub fn count_records(&mut self) -> Result<u64, Error> {
+        self.scan().map(|summary| summary.records)
+    }
+}
+
+/// The totals found by `WarcReader::scan`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ScanSummary {
+    /// The number of records.
+    pub records: u64,
+    /// The total of the Content-Length of the records.
+    pub content_bytes: u64,
+    /// The total length of the records, including their headers and separators.
+    pub bytes: u64,
+}
+
+/// Where a record was read from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RecordPosition {
+    /// The offset of the start of the record within the input.
+    pub offset: u64,
+    /// The number of records read before this one, counting those an error was returned for.
+    pub index: u64,
+}
+
+/// Read a line, storing as much of its start as fits in the buffer.
+///
+/// The length of the line and the number of bytes stored are returned, or `None` at the end of
+/// the input.
+fn read_line_prefix<R: BufRead>(
+    reader: &mut R,
+    buffer: &mut [u8],
+) -> Result<Option<(usize, usize)>, Error> {
+    let mut len = 0;
+    let mut stored = 0;
+    loop {
+        let available = reader.fill_buf().map_err(|_| Error::ReadData)?;
+        if available.is_empty() {
+            return match len {
+                0 => Ok(None),
+                _ => Err(Error::UnexpectedEOB),
+            };
+        }
+        let (used, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(pos) => (pos + 1, true),
+            None => (available.len(), false),
+        };
+        let copied = used.min(buffer.len() - stored);
+        buffer[stored..stored + copied].copy_from_slice(&available[..copied]);
+        stored += copied;
+        len += used;
+        reader.consume(used);
+        if done {
+            return Ok(Some((len, stored)));
+        }
+    }
+}
+
+/// Skip the given number of bytes.
+fn skip_bytes<R: BufRead>(reader: &mut R, mut len: u64) -> Result<(), Error> {
+    while len > 0 {
+        let available = reader.fill_buf().map_err(|_| Error::ReadData)?.len();
+        if available == 0 {
+            return Err(Error::UnexpectedEOB);
+        }
+        let used = available.min(usize::try_from(len).unwrap_or(usize::MAX));
+        reader.consume(used);
+        len -= used as u64;
+    }
+
+    Ok(())
+}
+
+impl<R: BufRead + Seek> WarcReader<R> {
+    /// Read the record starting at the given offset, such as one given by an index, without
+    /// reading the records before it.
+    ///
+    /// The reader is left after the record, so that iteration continues from the next record.
+    pub fn read_record_at(&mut self, offset: u64) -> Result<Record<BufferedBody>, Error> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|_| Error::ReadData)?;
+        let config = self.config.clone().read_policy(ReadPolicy::FailFast);
+        RecordIter::new(&mut self.reader, config)
+            .next()
+            .unwrap_or(Err(Error::UnexpectedEOB))
+    }
+
+    /// Read the record held by the gzip member starting at the given offset of a compressed
+    /// file, such as one given by an index of a `.warc.gz` file.
+    ///
+    /// Only the first record of the member is read. The position of the reader after the member
+    /// is unspecified.
+    #[cfg(feature = "gzip")]
+    pub fn read_gzip_record_at(&mut self, offset: u64) -> Result<Record<BufferedBody>, Error> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|_| Error::ReadData)?;
+        let member = libflate::gzip::Decoder::new(&mut self.reader).map_err(|_| Error::ReadData)?;
+        let config = self.config.clone().read_policy(ReadPolicy::FailFast);
+        RecordIter::new(BufReader::new(member), config)
+            .next()
+            .unwrap_or(Err(Error::UnexpectedEOB))
+    }
+}
+
+impl<R: Read> WarcReader<BufReader<R>> {
+    /// Create a new reader over any source of bytes, such as a socket or standard input, which is
+    /// buffered by the reader.
+    ///
+    /// Records are parsed incrementally as they are read, so the input need never be held in
+    /// memory as a whole.
+    pub fn from_reader(reader: R) -> Self {
+        WarcReader::new(BufReader::with_capacity(MB, reader))
+    }
+}
+
+impl<'r> WarcReader<Box<dyn BufRead + 'r>> {
+    /// Create a new reader which decompresses its input if it starts with the magic number of a
+    /// supported compression format.
+    ///
+    /// Gzip input is read member by member, so files with one member per record, as written by
+    /// crawlers, are read as a whole. Input which is not compressed is read as it is.
+    pub fn detect<R: BufRead + 'r>(mut reader: R) -> io::Result<Self> {
+        let codec = Codec::detect(reader.fill_buf()?);
+        Ok(WarcReader::new(codec.decoder(reader)?))
+    }
+
+    /// Create a new reader which reads from a file, compressed or not, as for `detect`.
+    pub fn from_path_detect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        WarcReader::detect(BufReader::with_capacity(MB, file))
     }
 }
 
@@ -56,8 +296,9 @@ impl WarcReader<BufReader<fs::File>> {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&path)?;
-        let reader = BufReader::with_capacity(1 * MB, file);
+        let reader = BufReader::with_capacity(MB, file);
 
         Ok(WarcReader::new(reader))
     }
@@ -67,109 +308,814 @@ impl WarcReader<BufReader<fs::File>> {
 impl WarcReader<BufReader<GzipReader<std::fs::File>>> {
     /// Create a new reader which reads from a compressed file.
     ///
-    /// Only GZIP compression is currently supported.
+    /// Only GZIP compression is currently supported. Every member of the file is read, however
+    /// the records fall across members; see `gzip_members::survey` for whether the file can be
+    /// read from the offset of a record.
     pub fn from_path_gzip<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&path)?;
         let gzip_stream = GzipReader::new(file)?;
-        let reader = BufReader::with_capacity(1 * MB, gzip_stream);
+        let reader = BufReader::with_capacity(MB, gzip_stream);
 
         Ok(WarcReader::new(reader))
     }
 }
 
-pub struct RawRecordIter<R> {
-    reader: R,
-}
+#[cfg(feature = "mmap")]
+impl WarcReader<io::Cursor<Mmap>> {
+    /// Create a new reader which maps an uncompressed file into memory.
+    ///
+    /// Records can be read as from any other reader, or borrowed from the map without copying
+    /// by `iter_record_refs`, which makes repeated scans of the same file much faster.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, for as long as
+    /// the reader or any record borrowed from it is alive. A change to a mapped file is undefined
+    /// behavior, and truncating it may kill the process with `SIGBUS`.
+    pub unsafe fn from_mmap<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the map is read-only, and the caller upholds that the file is not modified while
+        // it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(WarcReader::new(io::Cursor::new(map)))
+    }
 
-impl<R: BufRead> RawRecordIter<R> {
-    pub(crate) fn new(reader: R) -> RawRecordIter<R> {
-        RawRecordIter { reader }
+    /// Return an iterator over the records from the reader's position onwards, borrowed from the
+    /// map.
+    ///
+    /// Records are parsed strictly, whatever the reader's configuration, and the position of
+    /// the reader is left where it is.
+    pub fn iter_record_refs(&self) -> RecordRefIter<'_> {
+        let map = self.reader.get_ref();
+        let position = usize::try_from(self.reader.position())
+            .unwrap_or(map.len())
+            .min(map.len());
+
+        RecordRef::iter(&map[position..])
     }
 }
 
-impl<R: BufRead> Iterator for RawRecordIter<R> {
-    type Item = Result<(RawRecordHeader, Vec<u8>), Error>;
+/// Read a header block up to and including the blank line ending it.
+///
+/// Any line already read by a resynchronization is used as the start of the block. `None` is
+/// returned if the input ends before a complete header block.
+fn read_header_block<R: BufRead>(
+    reader: &mut R,
+    pending: &mut Vec<u8>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut header_buffer: Vec<u8> = Vec::with_capacity(64 * KB);
+    header_buffer.append(pending);
+    let mut found_headers = false;
+    while !found_headers {
+        let bytes_read = match reader.read_until(b'\n', &mut header_buffer) {
+            Err(_) => return Err(Error::ReadData),
+            Ok(len) => len,
+        };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut header_buffer: Vec<u8> = Vec::with_capacity(64 * KB);
-        let mut found_headers = false;
-        while !found_headers {
-            let bytes_read = match self.reader.read_until(b'\n', &mut header_buffer) {
-                Err(_) => return Some(Err(Error::ReadData)),
-                Ok(len) => len,
-            };
+        if bytes_read == 0 {
+            return Ok(None);
+        }
 
-            if bytes_read == 0 {
-                return None;
+        if bytes_read == 2 {
+            let last_two_chars = header_buffer.len() - 2;
+            if &header_buffer[last_two_chars..] == b"\r\n" {
+                found_headers = true;
             }
+        }
+    }
+
+    Ok(Some(header_buffer))
+}
+
+/// A parsed header block.
+pub(crate) enum HeaderBlock {
+    /// The headers, the expected length of the body, any repairs made to the headers, and the
+    /// spellings of the header names which differ from the lowercase names.
+    Record(
+        RawRecordHeader,
+        usize,
+        Vec<Warning>,
+        HashMap<WarcHeader, String>,
+    ),
+    /// The record is to be skipped, and its body has the given length.
+    Skip(usize),
+    /// The Content-Length header could not be interpreted, so the body is to be framed by the
+    /// start of the next record. Only returned under `ReadPolicy::BestEffort`.
+    Unframed(RawRecordHeader, Vec<Warning>, HashMap<WarcHeader, String>),
+}
 
-            if bytes_read == 2 {
-                let last_two_chars = header_buffer.len() - 2;
-                if &header_buffer[last_two_chars..] == b"\r\n" {
-                    found_headers = true;
+/// Parse a header block, handling malformed and duplicate header lines as the configuration
+/// requires.
+pub(crate) fn parse_header_block(
+    header_buffer: &[u8],
+    config: &ParserConfig,
+) -> Result<HeaderBlock, Error> {
+    let (version, lines) = parser::header_lines(header_buffer).ok_or(Error::ParseHeaders)?;
+
+    let mut skip = false;
+    let mut warnings = Vec::new();
+    let mut headers: Vec<(WarcHeader, &str, &[u8])> = Vec::with_capacity(lines.len());
+    for line in lines {
+        let (policy, line) = match line {
+            HeaderLine::Field(name, value) => {
+                let header = WarcHeader::from(name);
+                match headers.iter().position(|(h, _, _)| *h == header) {
+                    Some(i) if !header.is_repeatable() => match config.duplicate_headers {
+                        HeaderPolicy::Reject => {
+                            return Err(Error::MalformedHeader(
+                                header,
+                                "duplicate header".to_string(),
+                            ))
+                        }
+                        HeaderPolicy::KeepFirst => {
+                            warnings.push(Warning::DuplicateHeader(header));
+                        }
+                        HeaderPolicy::KeepLast => {
+                            warnings.push(Warning::DuplicateHeader(header.clone()));
+                            headers[i] = (header, name, value);
+                        }
+                        HeaderPolicy::SkipRecord => skip = true,
+                    },
+                    _ => headers.push((header, name, value)),
                 }
+                continue;
             }
+            HeaderLine::EmptyName(line) => (config.empty_header_names, line),
+            HeaderLine::MissingColon(line) => (config.missing_colons, line),
+        };
+        match policy {
+            HeaderPolicy::Reject => return Err(Error::ParseHeaders),
+            HeaderPolicy::KeepFirst | HeaderPolicy::KeepLast => warnings.push(
+                Warning::MalformedHeaderLine(String::from_utf8_lossy(line).into_owned()),
+            ),
+            HeaderPolicy::SkipRecord => skip = true,
         }
+    }
+
+    let declared_body_len: Option<u64> = match headers
+        .iter()
+        .find(|(header, _, _)| *header == WarcHeader::ContentLength)
+    {
+        Some((_, _, value)) => std::str::from_utf8(value)
+            .ok()
+            .and_then(|value| value.parse().ok()),
+        None => Some(0),
+    };
+    let unframed = declared_body_len.is_none();
+    if unframed && (skip || config.read_policy != ReadPolicy::BestEffort) {
+        return Err(Error::ParseHeaders);
+    }
+    let declared_body_len = declared_body_len.unwrap_or_default();
+    if config
+        .max_body_len
+        .is_some_and(|max| declared_body_len > max)
+    {
+        return Err(Error::BodyTooLarge(declared_body_len));
+    }
+    let expected_body_len =
+        usize::try_from(declared_body_len).map_err(|_| Error::BodyTooLarge(declared_body_len))?;
+    if skip {
+        return Ok(HeaderBlock::Skip(expected_body_len));
+    }
+    let names = headers
+        .iter()
+        .filter(|(header, name, _)| header.to_string() != *name)
+        .map(|(header, name, _)| (header.clone(), (*name).to_owned()))
+        .collect();
+    let headers = headers
+        .into_iter()
+        .map(|(_, name, value)| (name, value))
+        .collect();
+    if unframed {
+        return Ok(HeaderBlock::Unframed(
+            raw_header(version, headers),
+            warnings,
+            names,
+        ));
+    }
+
+    Ok(HeaderBlock::Record(
+        raw_header(version, headers),
+        expected_body_len,
+        warnings,
+        names,
+    ))
+}
 
-        let headers_parsed = match parser::headers(&header_buffer) {
-            Err(_) => return Some(Err(Error::ParseHeaders)),
-            Ok(parsed) => parsed.1,
+/// Build a raw header from the output of the header parser.
+pub(crate) fn raw_header(version: &str, headers: Vec<(&str, &[u8])>) -> RawRecordHeader {
+    let mut headers = RawRecordHeader {
+        version: version.to_owned(),
+        headers: headers
+            .into_iter()
+            .map(|(token, value)| (token.into(), value.to_owned()))
+            .collect(),
+    };
+    legacy::rename_headers(&mut headers);
+
+    headers
+}
+
+/// A failure to read a record body, along with whatever was read before the failure.
+struct BodyError {
+    error: Error,
+    partial: Vec<u8>,
+}
+
+/// The separator following a record's body.
+struct Separator {
+    len: usize,
+    /// A warning if the separator is not the standard one.
+    warning: Option<Warning>,
+    /// The line read after an incomplete separator, which starts the next record.
+    next: Vec<u8>,
+}
+
+/// Read the separator following a record's body, as the policy allows.
+///
+/// On failure, the error is returned with the bytes read.
+fn read_separator<R: BufRead>(
+    reader: &mut R,
+    policy: SeparatorPolicy,
+) -> Result<Separator, (Error, Vec<u8>)> {
+    let mut separator = Vec::with_capacity(4);
+    let mut next = Vec::new();
+    let mut line_endings = 0;
+    let mut eof = false;
+    while line_endings < 2 {
+        match reader.read_until(b'\n', &mut next) {
+            Err(_) => return Err((Error::ReadData, separator)),
+            Ok(0) => {
+                eof = true;
+                break;
+            }
+            Ok(_) if next == b"\r\n" || next == b"\n" => {
+                separator.append(&mut next);
+                line_endings += 1;
+            }
+            Ok(_) => break,
+        }
+    }
+
+    let standard = separator == b"\r\n\r\n";
+    let accepted = standard
+        || match policy {
+            SeparatorPolicy::Strict => false,
+            SeparatorPolicy::LineEndings => line_endings == 2,
+            SeparatorPolicy::Lenient => eof || line_endings == 2 || next.starts_with(b"WARC/"),
         };
-        let version_ref = headers_parsed.0;
-        let headers_ref = headers_parsed.1;
-        let expected_body_len = headers_parsed.2;
-
-        let mut body_buffer: Vec<u8> = Vec::with_capacity(1 * MB);
-        let mut found_body = expected_body_len == 0;
-        let mut body_bytes_read = 0;
-        let maximum_read_range = expected_body_len + 4;
-        while !found_body {
-            let bytes_read = match self.reader.read_until(b'\n', &mut body_buffer) {
-                Err(_) => return Some(Err(Error::ReadData)),
-                Ok(len) => len,
-            };
+    if !accepted {
+        let error = if eof {
+            Error::UnexpectedEOB
+        } else {
+            Error::ReadOverflow
+        };
+        separator.append(&mut next);
+        return Err((error, separator));
+    }
 
-            body_bytes_read += bytes_read;
+    Ok(Separator {
+        len: separator.len(),
+        warning: if standard {
+            None
+        } else {
+            Some(Warning::NonstandardSeparator(
+                String::from_utf8_lossy(&separator).into_owned(),
+            ))
+        },
+        next,
+    })
+}
 
-            // we expect 4 characters (\r\n\r\n) after the body
-            if bytes_read == 2 && body_bytes_read == maximum_read_range {
-                found_body = true;
+/// Read a body of the expected length, and the separator following it.
+fn read_body<R: BufRead>(
+    reader: &mut R,
+    expected_body_len: usize,
+    policy: SeparatorPolicy,
+) -> Result<(Vec<u8>, Separator), BodyError> {
+    let mut body_buffer: Vec<u8> = Vec::with_capacity(expected_body_len.min(MB));
+    if reader
+        .take(expected_body_len as u64)
+        .read_to_end(&mut body_buffer)
+        .is_err()
+    {
+        return Err(BodyError {
+            error: Error::ReadData,
+            partial: body_buffer,
+        });
+    }
+    if body_buffer.len() < expected_body_len {
+        return Err(BodyError {
+            error: Error::UnexpectedEOB,
+            partial: body_buffer,
+        });
+    }
+
+    match read_separator(reader, policy) {
+        Ok(separator) => Ok((body_buffer, separator)),
+        Err((error, mut overflow)) => {
+            if !matches!(error, Error::UnexpectedEOB) {
+                body_buffer.append(&mut overflow);
             }
+            Err(BodyError {
+                error,
+                partial: body_buffer,
+            })
+        }
+    }
+}
 
-            if bytes_read == 0 {
-                return Some(Err(Error::UnexpectedEOB));
+/// Read a body whose length is not known, up to the next line which starts a record, and store
+/// that line in `pending`.
+///
+/// The body and the length of the separator stripped from its end are returned, along with a
+/// warning if the separator is not the standard one.
+fn read_unframed<R: BufRead>(
+    reader: &mut R,
+    pending: &mut Vec<u8>,
+) -> Result<(Vec<u8>, usize, Option<Warning>), Error> {
+    let mut body = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Err(_) => return Err(Error::ReadData),
+            Ok(0) => break,
+            Ok(_) if line.starts_with(b"WARC/") => {
+                pending.append(&mut line);
+                break;
             }
+            Ok(_) => body.extend_from_slice(&line),
+        }
+    }
+
+    if body.ends_with(b"\r\n\r\n") {
+        body.truncate(body.len() - 4);
+        return Ok((body, 4, None));
+    }
+    let len = body
+        .iter()
+        .rev()
+        .take_while(|&&b| b == b'\r' || b == b'\n')
+        .count();
+    let separator = body.split_off(body.len() - len);
+    let warning = Warning::NonstandardSeparator(String::from_utf8_lossy(&separator).into_owned());
+
+    Ok((body, len, Some(warning)))
+}
+
+/// Skip forward to the next line which starts a record, and store it in `pending`.
+///
+/// The unread part of an overflowing body is checked first, since the start of the next record
+/// may have been consumed along with it.
+fn resync<R: BufRead>(reader: &mut R, pending: &mut Vec<u8>, overflow: &[u8]) -> Result<(), Error> {
+    let last_line = overflow[..overflow.len().saturating_sub(1)]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map(|pos| &overflow[pos + 1..])
+        .unwrap_or(overflow);
+    if last_line.starts_with(b"WARC/") {
+        pending.extend_from_slice(last_line);
+        return Ok(());
+    }
 
-            if body_bytes_read > maximum_read_range {
-                return Some(Err(Error::ReadOverflow));
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Err(_) => return Err(Error::ReadData),
+            Ok(0) => return Ok(()),
+            Ok(_) if line.starts_with(b"WARC/") => {
+                pending.append(&mut line);
+                return Ok(());
             }
+            Ok(_) => {}
         }
+    }
+}
 
-        let body_ref = &body_buffer[..expected_body_len];
+/// Skip forward to the first `WARC/` version line, and store it in `pending`.
+///
+/// The version line may be preceded by a byte order mark, blank lines, or other data on the same
+/// line. The number of bytes skipped is returned.
+fn skip_leading<R: BufRead>(reader: &mut R, pending: &mut Vec<u8>) -> Result<u64, Error> {
+    let mut skipped = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Err(_) => return Err(Error::ReadData),
+            Ok(0) => return Ok(skipped),
+            Ok(len) => match line.windows(5).position(|w| w == b"WARC/") {
+                Some(start) => {
+                    pending.extend_from_slice(&line[start..]);
+                    return Ok(skipped + start as u64);
+                }
+                None => skipped += len as u64,
+            },
+        }
+    }
+}
 
-        let headers = RawRecordHeader {
-            version: version_ref.to_owned(),
-            headers: headers_ref
-                .into_iter()
-                .map(|(token, value)| (token.into(), value.to_owned()))
-                .collect(),
+/// Why a range of bytes read was not interpreted as part of a record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SkipReason {
+    /// Data before the first record, skipped by `ParserConfig::skip_leading_garbage`.
+    LeadingGarbage,
+    /// A malformed record, and any data skipped after it to find the start of the next record.
+    Resync,
+    /// A record skipped by a `HeaderPolicy::SkipRecord` policy.
+    SkippedRecord,
+    /// A separator other than two CRLF sequences, accepted by a `SeparatorPolicy`.
+    Separator,
+    /// Data after the last record which does not form a complete record.
+    TrailingData,
+}
+
+/// A range of bytes which a reader read but did not interpret as part of a record.
+///
+/// Offsets count the bytes read from the reader's input, which for compressed files is the
+/// decompressed data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedRange {
+    pub offset: u64,
+    pub len: u64,
+    pub reason: SkipReason,
+}
+
+/// A reader which counts the bytes consumed from it.
+struct Counting<R> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Counting<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.pos += amt as u64;
+    }
+}
+
+/// A whole raw record, with any repairs made under `ReadPolicy::BestEffort`.
+struct RawRead {
+    headers: RawRecordHeader,
+    body: Vec<u8>,
+    warnings: Vec<Warning>,
+    stored_len: u64,
+    names: HashMap<WarcHeader, String>,
+}
+
+/// The state shared by the iterators which read whole records.
+struct RecordSource<R> {
+    reader: Counting<R>,
+    config: ParserConfig,
+    pending: Vec<u8>,
+    started: bool,
+    done: bool,
+    /// The offset of the start of the record most recently read.
+    record_start: u64,
+    /// The offset up to which every byte read has been interpreted or reported as skipped.
+    accounted: u64,
+    /// The reason for skipping the bytes read since `accounted`.
+    skip_reason: Option<SkipReason>,
+    skipped: Vec<SkippedRange>,
+}
+
+impl<R: BufRead> RecordSource<R> {
+    fn new(reader: R, config: ParserConfig) -> Self {
+        RecordSource {
+            reader: Counting {
+                inner: reader,
+                pos: 0,
+            },
+            config,
+            pending: Vec::new(),
+            started: false,
+            done: false,
+            record_start: 0,
+            accounted: 0,
+            skip_reason: None,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Return the offset of the next unread byte, counting bytes read ahead into `pending` as
+    /// unread.
+    fn offset(&self) -> u64 {
+        self.reader.pos - self.pending.len() as u64
+    }
+
+    /// Report the bytes read since `accounted` up to the offset as skipped.
+    fn account(&mut self, offset: u64) {
+        if offset > self.accounted {
+            self.skipped.push(SkippedRange {
+                offset: self.accounted,
+                len: offset - self.accounted,
+                reason: self.skip_reason.unwrap_or(SkipReason::TrailingData),
+            });
+            self.accounted = offset;
+        }
+        self.skip_reason = None;
+    }
+
+    /// Record that an error is being returned, and prepare to continue according to the policy.
+    fn fail(&mut self, error: Error, overflow: &[u8], needs_resync: bool) -> Error {
+        if needs_resync {
+            self.skip_reason.get_or_insert(SkipReason::Resync);
+        }
+        if self.config.read_policy == ReadPolicy::FailFast
+            || (needs_resync && resync(&mut self.reader, &mut self.pending, overflow).is_err())
+        {
+            self.done = true;
+        }
+        error
+    }
+
+    fn next_raw(&mut self) -> Option<Result<RawRead, Error>> {
+        if self.done {
+            return None;
+        }
+
+        let mut skipped = 0;
+        if !self.started {
+            self.started = true;
+            if self.config.skip_leading_garbage {
+                match skip_leading(&mut self.reader, &mut self.pending) {
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    Ok(len) => skipped = len,
+                }
+                self.skip_reason = Some(SkipReason::LeadingGarbage);
+                self.account(skipped);
+            }
+        }
+
+        let mut read = self.read_raw();
+        if read.is_none() || self.done {
+            let offset = self.offset();
+            self.account(offset);
+        }
+        if let Some(Ok(ref mut read)) = read {
+            if skipped > 0 {
+                read.warnings
+                    .insert(0, Warning::SkippedLeadingBytes(skipped));
+            }
+        }
+        read
+    }
+
+    /// Read the body of a record whose Content-Length could not be interpreted, and replace the
+    /// header with the length of the body read.
+    fn read_unframed(
+        &mut self,
+        start: u64,
+        header_len: usize,
+        mut headers: RawRecordHeader,
+        mut warnings: Vec<Warning>,
+        names: HashMap<WarcHeader, String>,
+    ) -> Result<RawRead, Error> {
+        let (body, separator_len, separator_warning) =
+            match read_unframed(&mut self.reader, &mut self.pending) {
+                Ok(read) => read,
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+        let normalized = body.len().to_string();
+        if let Some(original) = headers
+            .as_mut()
+            .insert(WarcHeader::ContentLength, normalized.clone().into())
+        {
+            warnings.push(Warning::NormalizedHeader {
+                header: WarcHeader::ContentLength,
+                original: String::from_utf8_lossy(&original).into_owned(),
+                normalized,
+            });
+        }
+        self.account(start);
+        let separator_start = start + (header_len + body.len()) as u64;
+        if let Some(ref warning) = separator_warning {
+            if separator_len > 0 {
+                self.skipped.push(SkippedRange {
+                    offset: separator_start,
+                    len: separator_len as u64,
+                    reason: SkipReason::Separator,
+                });
+            }
+            warnings.push(warning.clone());
+        }
+        self.accounted = separator_start + separator_len as u64;
+
+        Ok(RawRead {
+            headers,
+            stored_len: (header_len + body.len() + separator_len) as u64,
+            body,
+            warnings,
+            names,
+        })
+    }
+
+    fn read_raw(&mut self) -> Option<Result<RawRead, Error>> {
+        let (start, header_buffer, headers, expected_body_len, header_warnings, names) = loop {
+            let start = self.offset();
+            self.record_start = start;
+            let header_buffer = match read_header_block(&mut self.reader, &mut self.pending) {
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                Ok(None) => return None,
+                Ok(Some(buffer)) => buffer,
+            };
+
+            match parse_header_block(&header_buffer, &self.config) {
+                Err(e) => return Some(Err(self.fail(e, &[], true))),
+                Ok(HeaderBlock::Record(headers, len, warnings, names)) => {
+                    break (start, header_buffer, headers, len, warnings, names)
+                }
+                Ok(HeaderBlock::Unframed(headers, warnings, names)) => {
+                    return Some(self.read_unframed(
+                        start,
+                        header_buffer.len(),
+                        headers,
+                        warnings,
+                        names,
+                    ));
+                }
+                Ok(HeaderBlock::Skip(len)) => {
+                    self.skip_reason.get_or_insert(SkipReason::SkippedRecord);
+                    match read_body(&mut self.reader, len, self.config.separators) {
+                        Ok((_, mut separator)) => self.pending.append(&mut separator.next),
+                        Err(BodyError {
+                            error: Error::ReadOverflow,
+                            partial,
+                        }) => {
+                            let split = len.min(partial.len());
+                            if resync(&mut self.reader, &mut self.pending, &partial[split..])
+                                .is_err()
+                            {
+                                self.done = true;
+                                return None;
+                            }
+                        }
+                        Err(BodyError {
+                            error: Error::UnexpectedEOB,
+                            ..
+                        }) => {
+                            self.done = true;
+                            return None;
+                        }
+                        Err(BodyError { error, .. }) => {
+                            self.done = true;
+                            return Some(Err(error));
+                        }
+                    }
+                }
+            }
         };
-        let body = body_ref.to_owned();
-        Some(Ok((headers, body)))
+
+        let header_len = header_buffer.len() as u64;
+        let raw_read =
+            |headers, body: Vec<u8>, separator_len: usize, mut warnings: Vec<Warning>| {
+                let mut all_warnings = header_warnings.clone();
+                all_warnings.append(&mut warnings);
+                RawRead {
+                    headers,
+                    stored_len: header_len + (body.len() + separator_len) as u64,
+                    body,
+                    warnings: all_warnings,
+                    names: names.clone(),
+                }
+            };
+
+        match read_body(&mut self.reader, expected_body_len, self.config.separators) {
+            Ok((body, mut separator)) => {
+                self.pending.append(&mut separator.next);
+                self.account(start);
+                let separator_start = start + header_len + body.len() as u64;
+                if separator.warning.is_some() {
+                    self.skipped.push(SkippedRange {
+                        offset: separator_start,
+                        len: separator.len as u64,
+                        reason: SkipReason::Separator,
+                    });
+                }
+                self.accounted = separator_start + separator.len as u64;
+                let warnings = separator.warning.into_iter().collect();
+                Some(Ok(raw_read(headers, body, separator.len, warnings)))
+            }
+            Err(BodyError { error, partial }) => {
+                let best_effort = self.config.read_policy == ReadPolicy::BestEffort;
+                match error {
+                    Error::UnexpectedEOB => {
+                        self.done = true;
+                        if best_effort {
+                            self.account(start);
+                            self.accounted = self.offset();
+                            let warning = Warning::TruncatedBody {
+                                expected: expected_body_len as u64,
+                                actual: partial.len() as u64,
+                            };
+                            Some(Ok(raw_read(headers, partial, 0, vec![warning])))
+                        } else {
+                            Some(Err(error))
+                        }
+                    }
+                    Error::ReadOverflow => {
+                        let split = expected_body_len.min(partial.len());
+                        let reason = self.skip_reason;
+                        let error = self.fail(error, &partial[split..], true);
+                        if best_effort && !self.done {
+                            self.skip_reason = reason;
+                            self.account(start);
+                            self.accounted = start + header_len + split as u64;
+                            self.skip_reason = Some(SkipReason::Resync);
+                            let mut body = partial;
+                            body.truncate(split);
+                            let warnings = vec![Warning::UnterminatedBody];
+                            Some(Ok(raw_read(headers, body, 4, warnings)))
+                        } else {
+                            Some(Err(error))
+                        }
+                    }
+                    _ => {
+                        self.done = true;
+                        Some(Err(error))
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct RawRecordIter<R> {
+    source: RecordSource<R>,
+}
+
+impl<R: BufRead> RawRecordIter<R> {
+    pub(crate) fn new(reader: R, config: ParserConfig) -> RawRecordIter<R> {
+        RawRecordIter {
+            source: RecordSource::new(reader, config),
+        }
+    }
+
+    /// Return the ranges of bytes read so far which were not interpreted as part of a record, in
+    /// order.
+    ///
+    /// Once iteration has ended, this accounts for every byte read.
+    pub fn skipped(&self) -> &[SkippedRange] {
+        &self.source.skipped
+    }
+}
+
+impl<R: BufRead> Iterator for RawRecordIter<R> {
+    type Item = Result<(RawRecordHeader, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.source
+            .next_raw()
+            .map(|read| read.map(|read| (read.headers, read.body)))
     }
 }
 
 pub struct RecordIter<R> {
-    reader: R,
+    source: RecordSource<R>,
 }
 
 impl<R: BufRead> RecordIter<R> {
-    pub(crate) fn new(reader: R) -> RecordIter<R> {
-        RecordIter { reader }
+    pub(crate) fn new(reader: R, config: ParserConfig) -> RecordIter<R> {
+        RecordIter {
+            source: RecordSource::new(reader, config),
+        }
+    }
+
+    /// Return the ranges of bytes read so far which were not interpreted as part of a record, in
+    /// order.
+    ///
+    /// Once iteration has ended, this accounts for every byte read.
+    pub fn skipped(&self) -> &[SkippedRange] {
+        &self.source.skipped
     }
 }
 
@@ -177,188 +1123,272 @@ impl<R: BufRead> Iterator for RecordIter<R> {
     type Item = Result<Record<BufferedBody>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut header_buffer: Vec<u8> = Vec::with_capacity(64 * KB);
-        let mut found_headers = false;
-        while !found_headers {
-            let bytes_read = match self.reader.read_until(b'\n', &mut header_buffer) {
-                Err(_) => return Some(Err(Error::ReadData)),
-                Ok(len) => len,
-            };
+        let RawRead {
+            headers,
+            body,
+            mut warnings,
+            stored_len,
+            names,
+        } = match self.source.next_raw()? {
+            Err(e) => return Some(Err(e)),
+            Ok(read) => read,
+        };
 
-            if bytes_read == 0 {
-                return None;
+        let config = &self.source.config;
+        let record = if config.read_policy == ReadPolicy::BestEffort {
+            Record::from_raw_best_effort(headers, config)
+        } else {
+            Record::from_raw_with_config(headers, config)
+        };
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                return Some(Err(self.source.fail(e, &[], false)));
             }
+        };
 
-            if bytes_read == 2 {
-                let last_two_chars = header_buffer.len() - 2;
-                if &header_buffer[last_two_chars..] == b"\r\n" {
-                    found_headers = true;
+        let mut record = record.add_body(body);
+        #[cfg(feature = "gzip")]
+        {
+            if self.source.config.decode_blocks {
+                if let Err(e) = record.decode_block() {
+                    return Some(Err(self.source.fail(e, &[], false)));
                 }
             }
         }
+        record.append_warnings(&mut warnings);
+        record.set_stored_len(stored_len);
+        record.set_header_names(names);
+        Some(Ok(record))
+    }
+}
 
-        let headers_parsed = match parser::headers(&header_buffer) {
-            Err(_) => return Some(Err(Error::ParseHeaders)),
-            Ok(parsed) => parsed.1,
-        };
-        let version_ref = headers_parsed.0;
-        let headers_ref = headers_parsed.1;
-        let expected_body_len = headers_parsed.2;
-
-        let mut body_buffer: Vec<u8> = Vec::with_capacity(1 * MB);
-        let mut found_body = expected_body_len == 0;
-        let mut body_bytes_read = 0;
-        let maximum_read_range = expected_body_len + 4;
-        while !found_body {
-            let bytes_read = match self.reader.read_until(b'\n', &mut body_buffer) {
-                Err(_) => return Some(Err(Error::ReadData)),
-                Ok(len) => len,
-            };
+pub struct PositionedRecordIter<R> {
+    records: RecordIter<R>,
+    position: Option<RecordPosition>,
+    index: u64,
+}
 
-            body_bytes_read += bytes_read;
+impl<R: BufRead> PositionedRecordIter<R> {
+    /// Return the position of the record most recently returned, or which the most recent error
+    /// was returned for.
+    pub fn position(&self) -> Option<RecordPosition> {
+        self.position
+    }
 
-            // we expect 4 characters (\r\n\r\n) after the body
-            if bytes_read == 2 && body_bytes_read == maximum_read_range {
-                found_body = true;
-            }
+    /// Return the ranges of bytes read so far which were not interpreted as part of a record, in
+    /// order.
+    pub fn skipped(&self) -> &[SkippedRange] {
+        self.records.skipped()
+    }
+}
 
-            if bytes_read == 0 {
-                return Some(Err(Error::UnexpectedEOB));
-            }
+impl<R: BufRead> Iterator for PositionedRecordIter<R> {
+    type Item = Result<(RecordPosition, Record<BufferedBody>), Error>;
 
-            if body_bytes_read > maximum_read_range {
-                return Some(Err(Error::ReadOverflow));
-            }
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        let position = RecordPosition {
+            offset: self.records.source.record_start,
+            index: self.index,
+        };
+        self.position = Some(position);
+        self.index += 1;
+        Some(record.map(|record| (position, record)))
+    }
+}
 
-        let body_ref = &body_buffer[..expected_body_len];
+#[cfg(feature = "bytes")]
+pub struct BytesRecordIter<R> {
+    records: RecordIter<R>,
+}
 
-        let headers = RawRecordHeader {
-            version: version_ref.to_owned(),
-            headers: headers_ref
-                .into_iter()
-                .map(|(token, value)| (token.into(), value.to_owned()))
-                .collect(),
-        };
-        let body = body_ref.to_owned();
-        match headers.try_into() {
-            Ok(b) => {
-                let buffered: Record<_> = b;
-                Some(Ok(buffered.add_body(body)))
-            }
-            Err(e) => Some(Err(e)),
-        }
+#[cfg(feature = "bytes")]
+impl<R: BufRead> BytesRecordIter<R> {
+    /// Return the ranges of bytes read so far which were not interpreted as part of a record, in
+    /// order.
+    pub fn skipped(&self) -> &[SkippedRange] {
+        self.records.skipped()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<R: BufRead> Iterator for BytesRecordIter<R> {
+    type Item = Result<Record<crate::BytesBody>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records
+            .next()
+            .map(|record| record.map(Record::into_bytes))
     }
 }
 
 pub struct StreamingIter<'r, R> {
     reader: &'r mut R,
+    config: &'r ParserConfig,
+    pending: Vec<u8>,
     current_item_size: u64,
-    first_record: bool,
+    body_pending: bool,
+    started: bool,
+    done: bool,
 }
 
-impl<R: BufRead> StreamingIter<'_, R> {
-    pub(crate) fn new(reader: &mut R) -> StreamingIter<'_, R> {
+impl<'r, R: BufRead> StreamingIter<'r, R> {
+    pub(crate) fn new(reader: &'r mut R, config: &'r ParserConfig) -> StreamingIter<'r, R> {
         StreamingIter {
             reader,
+            config,
+            pending: Vec::new(),
             current_item_size: 0,
-            first_record: true,
+            body_pending: false,
+            started: false,
+            done: false,
         }
     }
 
-    fn skip_body(&mut self) -> Result<(), Error> {
-        let mut read_buffer = [0u8; 1 * MB];
+    /// Skip the rest of the current body and its separator, returning any error with the bytes
+    /// read past the body.
+    fn skip_body(&mut self) -> Result<(), (Error, Vec<u8>)> {
+        let mut read_buffer = [0u8; MB];
         let maximum_read_range = self.current_item_size;
         let mut body_bytes_left = maximum_read_range;
         while body_bytes_left > 0 {
             let read_size = std::cmp::min(body_bytes_left, read_buffer.len() as u64) as usize;
             let bytes_read = match self.reader.read(&mut read_buffer[..read_size]) {
-                Err(_) => return Err(Error::ReadData),
+                Err(_) => return Err((Error::ReadData, Vec::new())),
                 Ok(len) => len as u64,
             };
             if bytes_read == 0 {
-                return Err(Error::UnexpectedEOB);
+                return Err((Error::UnexpectedEOB, Vec::new()));
             }
             body_bytes_left -= bytes_read;
         }
 
-        let mut crlfs = [0; 4];
-
-        match self.reader.read(&mut crlfs) {
-            Ok(4) => {}
-            Ok(_) => return Err(Error::UnexpectedEOB),
-            Err(_) => return Err(Error::ReadData),
+        match read_separator(self.reader, self.config.separators) {
+            Ok(mut separator) => {
+                self.pending.append(&mut separator.next);
+                Ok(())
+            }
+            Err((Error::ReadOverflow, overflow)) => Err((Error::ParseHeaders, overflow)),
+            Err(error) => Err(error),
         }
+    }
 
-        if &crlfs == b"\x0d\x0a\x0d\x0a" {
-            Ok(())
-        } else {
-            Err(Error::ParseHeaders)
+    /// Record that an error is being returned, and prepare to continue according to the policy.
+    fn fail(&mut self, error: Error, overflow: &[u8]) -> Error {
+        let recoverable = match error {
+            Error::ParseHeaders | Error::MalformedHeader(..) | Error::ReadOverflow => true,
+            Error::ReadData | Error::UnexpectedEOB => false,
+            _ => return error,
+        };
+        if self.config.read_policy == ReadPolicy::FailFast
+            || !recoverable
+            || resync(self.reader, &mut self.pending, overflow).is_err()
+        {
+            self.done = true;
         }
+        error
     }
 
     pub fn next_item(&mut self) -> Option<Result<Record<StreamingBody<'_, R>>, Error>> {
-        if self.first_record {
-            self.first_record = false;
-        } else if let Err(e) = self.skip_body() {
-            return Some(Err(e));
-        }
-
-        let mut header_buffer: Vec<u8> = Vec::with_capacity(64 * KB);
-        let mut found_headers = false;
-        while !found_headers {
-            let bytes_read = match self.reader.read_until(b'\n', &mut header_buffer) {
-                Err(_) => return Some(Err(Error::ReadData)),
-                Ok(len) => len,
-            };
+        if self.done {
+            return None;
+        }
 
-            if bytes_read == 0 {
-                return None;
+        if self.body_pending {
+            self.body_pending = false;
+            if let Err((e, overflow)) = self.skip_body() {
+                self.current_item_size = 0;
+                return Some(Err(self.fail(e, &overflow)));
             }
-
-            if bytes_read == 2 {
-                let last_two_chars = header_buffer.len() - 2;
-                if &header_buffer[last_two_chars..] == b"\r\n" {
-                    found_headers = true;
+        }
+        self.current_item_size = 0;
+
+        let mut skipped = 0;
+        if !self.started {
+            self.started = true;
+            if self.config.skip_leading_garbage {
+                match skip_leading(self.reader, &mut self.pending) {
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    Ok(len) => skipped = len,
                 }
             }
         }
 
-        let headers_parsed = match parser::headers(&header_buffer) {
-            Err(_) => return Some(Err(Error::ParseHeaders)),
-            Ok(parsed) => parsed.1,
+        let (header_buffer, headers, expected_body_len, mut header_warnings, names) = loop {
+            let header_buffer = match read_header_block(self.reader, &mut self.pending) {
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                Ok(None) => return None,
+                Ok(Some(buffer)) => buffer,
+            };
+
+            match parse_header_block(&header_buffer, self.config) {
+                Err(e) => return Some(Err(self.fail(e, &[]))),
+                // a streamed body cannot be framed by reading ahead to the next record
+                Ok(HeaderBlock::Unframed(..)) => {
+                    return Some(Err(self.fail(Error::ParseHeaders, &[])))
+                }
+                Ok(HeaderBlock::Record(headers, len, warnings, names)) => {
+                    break (header_buffer, headers, len, warnings, names)
+                }
+                Ok(HeaderBlock::Skip(len)) => {
+                    self.current_item_size = len as u64;
+                    let skipped = self.skip_body();
+                    self.current_item_size = 0;
+                    if let Err((e, overflow)) = skipped {
+                        return Some(Err(self.fail(e, &overflow)));
+                    }
+                }
+            }
         };
-        let version_ref = headers_parsed.0;
-        let headers_ref = headers_parsed.1;
-        self.current_item_size = headers_parsed.2 as u64;
-
-        let headers = RawRecordHeader {
-            version: version_ref.to_owned(),
-            headers: headers_ref
-                .into_iter()
-                .map(|(token, value)| (token.into(), value.to_owned()))
-                .collect(),
+
+        let record = if self.config.read_policy == ReadPolicy::BestEffort {
+            Record::from_raw_best_effort(headers, self.config)
+        } else {
+            Record::from_raw_with_config(headers, self.config)
         };
-        match headers.try_into() {
-            Ok(b) => {
-                let record: Record<_> = b;
-                let fixed_stream_result = record
-                    .add_fixed_stream(self.reader, &mut self.current_item_size)
-                    .map_err(|_| Error::ReadData);
-                Some(fixed_stream_result)
+        let mut record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                if self.config.read_policy == ReadPolicy::FailFast {
+                    self.done = true;
+                }
+                // the body of this record is skipped by the next call
+                self.current_item_size = expected_body_len as u64;
+                self.body_pending = true;
+                return Some(Err(e));
             }
-            Err(e) => Some(Err(e)),
+        };
+
+        if skipped > 0 {
+            header_warnings.insert(0, Warning::SkippedLeadingBytes(skipped));
         }
+        record.append_warnings(&mut header_warnings);
+        record.set_header_names(names);
+        record.set_stored_len(header_buffer.len() as u64 + expected_body_len as u64 + 4);
+        self.current_item_size = expected_body_len as u64;
+        self.body_pending = true;
+        let fixed_stream_result = record
+            .add_fixed_stream(self.reader, &mut self.current_item_size)
+            .map_err(|_| Error::ReadData);
+        Some(fixed_stream_result)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_conversion)]
 mod iter_raw_tests {
-    use std::collections::HashMap;
-    use std::io::{BufReader, Cursor};
+    use std::io::{BufReader, Cursor, Read};
     use std::iter::FromIterator;
 
-    use crate::{header::WarcHeader, WarcReader};
+    use crate::header::{HeaderMap, WarcHeader};
+    use crate::WarcReader;
     macro_rules! create_reader {
         ($raw:expr) => {{
             BufReader::new(Cursor::new($raw.get(..).unwrap()))
@@ -379,7 +1409,7 @@ mod iter_raw_tests {
         ";
 
         let expected_version = "1.0";
-        let expected_headers: HashMap<WarcHeader, Vec<u8>> = HashMap::from_iter(
+        let expected_headers = HeaderMap::from_iter(
             vec![
                 (WarcHeader::WarcType, b"dunno".to_vec()),
                 (WarcHeader::ContentLength, b"5".to_vec()),
@@ -424,7 +1454,7 @@ mod iter_raw_tests {
         let mut reader = WarcReader::new(create_reader!(raw)).iter_raw_records();
         {
             let expected_version = "1.0";
-            let expected_headers: HashMap<WarcHeader, Vec<u8>> = HashMap::from_iter(
+            let expected_headers = HeaderMap::from_iter(
                 vec![
                     (WarcHeader::WarcType, b"dunno".to_vec()),
                     (WarcHeader::ContentLength, b"5".to_vec()),
@@ -446,7 +1476,7 @@ mod iter_raw_tests {
 
         {
             let expected_version = "1.0";
-            let expected_headers: HashMap<WarcHeader, Vec<u8>> = HashMap::from_iter(
+            let expected_headers = HeaderMap::from_iter(
                 vec![
                     (WarcHeader::WarcType, b"another".to_vec()),
                     (WarcHeader::ContentLength, b"6".to_vec()),
@@ -466,9 +1496,176 @@ mod iter_raw_tests {
             assert_eq!(body, expected_body);
         }
     }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes_records() {
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 5\r\n\
+            WARC-Record-Id: <urn:test:bytes-records:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+        ";
+
+        let mut reader = WarcReader::new(create_reader!(raw)).iter_bytes_records();
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.bytes(), bytes::Bytes::from_static(b"12345"));
+        assert_eq!(record.clone().into_buffered().body(), b"12345");
+        let (headers, body) = record.into_raw_parts();
+        assert_eq!(headers.as_ref()[&WarcHeader::ContentLength], b"5");
+        assert_eq!(&body[..], b"12345");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn scan() {
+        let record = |len: usize| {
+            format!(
+                "WARC/1.0\r\n\
+                 Warc-Type: dunno\r\n\
+                 content-length:{}\r\n\
+                 WARC-Record-Id: <urn:test:scan:record-{}>\r\n\
+                 WARC-Date: 2020-07-08T02:52:55Z\r\n\
+                 WARC-Target-URI: http://example.com/{}\r\n\
+                 \r\n\
+                 {}\r\n\
+                 \r\n",
+                len,
+                len,
+                "long/".repeat(20),
+                "x".repeat(len)
+            )
+        };
+        let raw = [record(5), record(0), record(100_000)].concat();
+
+        let mut reader = WarcReader::new(BufReader::with_capacity(16, Cursor::new(&raw)));
+        let summary = reader.scan().unwrap();
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.content_bytes, 100_005);
+        assert_eq!(summary.bytes, raw.len() as u64);
+        assert_eq!(reader.count_records(), Ok(0));
+
+        let raw = &raw.as_bytes()[..raw.len() - 1];
+        let mut reader = WarcReader::new(create_reader!(raw));
+        assert_eq!(reader.count_records(), Err(crate::Error::UnexpectedEOB));
+    }
+
+    #[test]
+    fn from_reader() {
+        // a source returning a few bytes at a time, as a socket may
+        struct Trickle<'a>(&'a [u8]);
+
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(7);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 5\r\n\
+            WARC-Record-Id: <urn:test:from-reader:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+        ";
+        let input = [&raw[..], &raw[..]].concat();
+        let records = WarcReader::from_reader(Trickle(&input))
+            .iter_records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].body(), b"12345");
+    }
+
+    #[test]
+    fn detect() {
+        use crate::{BufferedBody, Record, WarcWriter};
+
+        let mut plain = Vec::new();
+        #[cfg(feature = "gzip")]
+        let mut gzip = Vec::new();
+        for body in &[&b"first"[..], b"second"] {
+            let mut record = Record::<BufferedBody>::with_body(*body);
+            record.set_warc_version("1.0");
+            WarcWriter::new(&mut plain).write(&record).unwrap();
+            #[cfg(feature = "gzip")]
+            {
+                let mut writer = WarcWriter::new(&mut gzip);
+                writer.set_member_compression(|_, _| crate::MemberCompression::Compressed);
+                writer.write(&record).unwrap();
+            }
+        }
+
+        let inputs = [
+            plain,
+            #[cfg(feature = "gzip")]
+            gzip,
+        ];
+        for input in &inputs {
+            let records = WarcReader::detect(&input[..])
+                .unwrap()
+                .iter_records()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[1].body(), b"second");
+        }
+    }
+
+    #[test]
+    fn read_record_at() {
+        use crate::{BufferedBody, Record, WarcWriter};
+        use std::io::Cursor;
+
+        let mut plain = Vec::new();
+        let mut offsets = Vec::new();
+        #[cfg(feature = "gzip")]
+        let mut gzip = Vec::new();
+        #[cfg(feature = "gzip")]
+        let mut gzip_offsets = Vec::new();
+        for body in &[&b"first"[..], b"second", b"third"] {
+            let mut record = Record::<BufferedBody>::with_body(*body);
+            record.set_warc_version("1.0");
+            offsets.push(plain.len() as u64);
+            WarcWriter::new(&mut plain).write(&record).unwrap();
+            #[cfg(feature = "gzip")]
+            {
+                gzip_offsets.push(gzip.len() as u64);
+                let mut writer = WarcWriter::new(&mut gzip);
+                writer.set_member_compression(|_, _| crate::MemberCompression::Compressed);
+                writer.write(&record).unwrap();
+            }
+        }
+
+        let mut reader = WarcReader::new(Cursor::new(plain));
+        assert_eq!(reader.read_record_at(offsets[1]).unwrap().body(), b"second");
+        assert_eq!(reader.read_record_at(offsets[0]).unwrap().body(), b"first");
+        let rest: Vec<_> = reader.iter_records().map(|r| r.unwrap()).collect();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].body(), b"second");
+
+        #[cfg(feature = "gzip")]
+        {
+            let mut reader = WarcReader::new(Cursor::new(gzip));
+            let record = reader.read_gzip_record_at(gzip_offsets[2]).unwrap();
+            assert_eq!(record.body(), b"third");
+            assert!(reader.read_gzip_record_at(gzip_offsets[1] + 1).is_err());
+        }
+    }
 }
 
 #[cfg(test)]
+#[allow(unused_imports)]
 mod next_item_tests {
     use std::collections::HashMap;
     use std::io::{BufReader, Cursor};
@@ -507,6 +1704,7 @@ mod next_item_tests {
         assert_eq!(record.content_length(), 5);
         assert_eq!(record.warc_id(), "<urn:test:basic-record:record-0>");
         assert_eq!(record.body(), b"12345");
+        assert_eq!(record.stored_len(), Some(raw.len() as u64));
     }
 
     #[test]
@@ -671,4 +1869,608 @@ mod next_item_tests {
             assert_eq!(record.body(), b"12345678");
         }
     }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_items() {
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 5\r\n\
+            WARC-Record-Id: <urn:test:mapped:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+            WARC/1.0\r\n\
+            Warc-Type: another\r\n\
+            WARC-Record-Id: <urn:test:mapped:record-1>\r\n\
+            WARC-Date: 2020-07-08T02:52:56Z\r\n\
+            Content-Length: 6\r\n\
+            \r\n\
+            123456\r\n\
+            \r\n\
+        ";
+
+        let path = std::env::temp_dir().join(format!("warc-mapped-{}.warc", std::process::id()));
+        std::fs::write(&path, &raw[..]).unwrap();
+        // SAFETY: the file is private to this test, and is not modified while it is mapped.
+        let reader = unsafe { WarcReader::from_mmap(&path) }.unwrap();
+
+        for _ in 0..2 {
+            let ids: Vec<_> = reader
+                .iter_record_refs()
+                .map(|record| record.unwrap().warc_id())
+                .collect();
+            assert_eq!(
+                ids,
+                vec!["<urn:test:mapped:record-0>", "<urn:test:mapped:record-1>"]
+            );
+        }
+        let bodies: Vec<_> = reader
+            .iter_records()
+            .map(|record| record.unwrap().body().to_vec())
+            .collect();
+        assert_eq!(bodies, vec![b"12345".to_vec(), b"123456".to_vec()]);
+
+        std::fs::write(&path, b"").unwrap();
+        // SAFETY: as above; the previous map was consumed and dropped by `iter_records`.
+        let reader = unsafe { WarcReader::from_mmap(&path) }.unwrap();
+        assert!(reader.iter_record_refs().next().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod policy_tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::header::WarcHeader;
+    use crate::{
+        Error, HeaderNormalizer, HeaderPolicy, ParseMode, ParserConfig, ReadPolicy, RecordPosition,
+        SeparatorPolicy, SkipReason, SkippedRange, WarcReader, Warning,
+    };
+
+    macro_rules! create_reader {
+        ($raw:expr) => {{
+            BufReader::new(Cursor::new($raw.get(..).unwrap()))
+        }};
+    }
+
+    const RAW: &[u8] = b"\
+        WARC/1.0\r\n\
+        Warc-Type: dunno\r\n\
+        Content-Length: 5\r\n\
+        WARC-Record-Id: <urn:test:policy:record-0>\r\n\
+        WARC-Date: 2020-07-08T02:52:55Z\r\n\
+        \r\n\
+        12345\r\n\
+        \r\n\
+        WARC/1.0\r\n\
+        this line is not a header\r\n\
+        \r\n\
+        garbage\r\n\
+        \r\n\
+        WARC/1.0\r\n\
+        Warc-Type: dunno\r\n\
+        Content-Length: 6\r\n\
+        WARC-Record-Id: <urn:test:policy:record-2>\r\n\
+        \r\n\
+        123456\r\n\
+        \r\n\
+        WARC/1.0\r\n\
+        Warc-Type: dunno\r\n\
+        Content-Length: 8\r\n\
+        WARC-Record-Id: <urn:test:policy:record-3>\r\n\
+        WARC-Date: 2020-07-08T02:52:55Z\r\n\
+        \r\n\
+        1234\
+    ";
+
+    fn read_with(policy: ReadPolicy) -> Vec<Result<String, ()>> {
+        let config = ParserConfig::new().read_policy(policy);
+        WarcReader::new(create_reader!(RAW))
+            .with_config(config)
+            .iter_records()
+            .map(|r| r.map(|r| r.warc_id().to_owned()).map_err(|_| ()))
+            .collect()
+    }
+
+    #[test]
+    fn fail_fast() {
+        assert_eq!(
+            read_with(ReadPolicy::FailFast),
+            vec![Ok("<urn:test:policy:record-0>".to_owned()), Err(())]
+        );
+    }
+
+    #[test]
+    fn skip() {
+        assert_eq!(
+            read_with(ReadPolicy::Skip),
+            vec![
+                Ok("<urn:test:policy:record-0>".to_owned()),
+                Err(()),
+                Err(()),
+                Err(())
+            ]
+        );
+    }
+
+    #[test]
+    fn positions() {
+        let starts: Vec<u64> = (0..RAW.len())
+            .filter(|&i| RAW[i..].starts_with(b"WARC/1.0"))
+            .map(|i| i as u64)
+            .collect();
+        let config = ParserConfig::new().read_policy(ReadPolicy::Skip);
+        let mut records = WarcReader::new(create_reader!(RAW))
+            .with_config(config)
+            .iter_positioned_records();
+        assert_eq!(records.position(), None);
+
+        let (position, record) = records.next().unwrap().unwrap();
+        assert_eq!(
+            position,
+            RecordPosition {
+                offset: 0,
+                index: 0
+            }
+        );
+        assert_eq!(record.warc_id(), "<urn:test:policy:record-0>");
+        let mut failed = Vec::new();
+        while let Some(record) = records.next() {
+            assert!(record.is_err());
+            failed.push(records.position().unwrap());
+        }
+        let offsets: Vec<_> = failed.iter().map(|position| position.offset).collect();
+        assert_eq!(offsets, starts[1..]);
+        assert_eq!(failed[2].index, 3);
+    }
+
+    #[test]
+    fn best_effort() {
+        let config = ParserConfig::new().read_policy(ReadPolicy::BestEffort);
+        let records: Vec<_> = WarcReader::new(create_reader!(RAW))
+            .with_config(config)
+            .iter_records()
+            .collect();
+        assert_eq!(records.len(), 4);
+        assert!(records[1].is_err());
+
+        assert_eq!(records[0].as_ref().unwrap().stored_len(), Some(135));
+
+        let missing_date = records[2].as_ref().unwrap();
+        assert_eq!(missing_date.warc_id(), "<urn:test:policy:record-2>");
+        assert_eq!(missing_date.body(), b"123456");
+        assert_eq!(
+            missing_date.warnings(),
+            &[Warning::MissingHeader(crate::header::WarcHeader::Date)]
+        );
+
+        let truncated = records[3].as_ref().unwrap();
+        assert_eq!(truncated.body(), b"1234");
+        assert_eq!(
+            truncated.warnings(),
+            &[Warning::TruncatedBody {
+                expected: 8,
+                actual: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_modes() {
+        let raw: &[u8] = b"\
+            WARC/1.0\r\n\
+            WARC-Type: resource\r\n\
+            WARC-Record-ID: <urn:test:mode:0>\r\n\
+            WARC-Date: 2020-07-08 02:52:55\r\n\
+            Content-Length: 5\r\n\
+            Content-Length: 6\r\n\
+            \r\n\
+            12345\n\n\
+            WARC/1.0\r\n\
+            WARC-Type: resource\r\n\
+            WARC-Record-ID: <urn:test:mode:1>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            Content-Length: five\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+        ";
+        let read = |config: ParserConfig| {
+            WarcReader::new(create_reader!(raw))
+                .with_config(config)
+                .iter_records()
+                .collect::<Vec<_>>()
+        };
+
+        let strict = read(ParserConfig::new().parse_mode(ParseMode::Strict));
+        assert!(strict.iter().all(Result::is_err));
+        assert_eq!(read(ParserConfig::new()).len(), strict.len());
+
+        let records: Vec<_> = read(ParserConfig::new().parse_mode(ParseMode::Lenient))
+            .into_iter()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].body(), b"12345");
+        assert_eq!(records[0].warnings().len(), 3);
+        assert!(records[0]
+            .warnings()
+            .contains(&Warning::DuplicateHeader(WarcHeader::ContentLength)));
+        assert!(records[0]
+            .warnings()
+            .contains(&Warning::NonstandardSeparator("\n\n".to_owned())));
+        assert_eq!(records[1].warc_id(), "<urn:test:mode:1>");
+        assert_eq!(records[1].body(), b"12345");
+
+        let config = ParserConfig::new()
+            .parse_mode(ParseMode::Lenient)
+            .parse_mode(ParseMode::Strict);
+        assert_eq!(config, ParserConfig::new());
+        let config = ParserConfig::new()
+            .parse_mode(ParseMode::Lenient)
+            .read_policy(ReadPolicy::FailFast);
+        let records = read(config);
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_ok() && records[1].is_err());
+    }
+
+    #[test]
+    fn unframed() {
+        let raw: &[u8] = b"\
+            WARC/1.0\r\n\
+            WARC-Type: resource\r\n\
+            WARC-Record-ID: <urn:test:unframed:0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            Content-Length: five\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+            WARC/1.0\r\n\
+            WARC-Type: resource\r\n\
+            WARC-Record-ID: <urn:test:unframed:1>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            WARC-Target-URI: http://example.com/\xff\r\n\
+            Content-Length: 12\r\n\
+            \r\n\
+            line\r\nline\r\n\n\
+            WARC/1.0\r\n\
+            WARC-Type: resource\r\n\
+            WARC-Record-ID: <urn:test:unframed:2>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            Content-Length: \xff\r\n\
+            \r\n\
+            abc\n\
+        ";
+        let read = |policy| {
+            let config = ParserConfig::new().read_policy(policy);
+            WarcReader::new(create_reader!(raw))
+                .with_config(config)
+                .iter_records()
+                .collect::<Vec<_>>()
+        };
+
+        let records = read(ReadPolicy::Skip);
+        assert_eq!(records.len(), 3);
+        assert!(records[0].is_err() && records[2].is_err());
+
+        let records: Vec<_> = read(ReadPolicy::BestEffort)
+            .into_iter()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].body(), b"12345");
+        assert_eq!(records[0].content_length(), 5);
+        assert_eq!(
+            records[0].warnings(),
+            &[Warning::NormalizedHeader {
+                header: WarcHeader::ContentLength,
+                original: "five".to_string(),
+                normalized: "5".to_string(),
+            }]
+        );
+        assert_eq!(records[1].body(), b"line\r\nline\r\n");
+        assert_eq!(records[2].warc_id(), "<urn:test:unframed:2>");
+        assert_eq!(records[2].body(), b"abc");
+        assert_eq!(
+            records[2].warnings()[1],
+            Warning::NonstandardSeparator("\n".to_string())
+        );
+    }
+
+    #[test]
+    fn separators() {
+        let record = |id: u8, separator: &str| {
+            format!(
+                "WARC/1.0\r\nWARC-Type: dunno\r\nContent-Length: 2\r\n\
+                 WARC-Record-ID: <urn:test:{}>\r\nWARC-Date: 2020-07-08T02:52:55Z\r\n\r\nab{}",
+                id, separator
+            )
+        };
+        let raw = [
+            record(0, "\n\n"),
+            record(1, "\r\n"),
+            record(2, "\r\n\r\n"),
+            record(3, ""),
+        ]
+        .concat();
+        let read_with = |policy: SeparatorPolicy| -> Vec<Result<Vec<Warning>, ()>> {
+            let config = ParserConfig::new().separators(policy);
+            WarcReader::new(create_reader!(raw))
+                .with_config(config)
+                .iter_records()
+                .map(|r| r.map(|r| r.warnings().to_vec()).map_err(|_| ()))
+                .collect()
+        };
+        let separator = |s: &str| Ok(vec![Warning::NonstandardSeparator(s.to_owned())]);
+
+        assert_eq!(
+            read_with(SeparatorPolicy::Strict),
+            vec![Err(()), Err(()), Ok(vec![]), Err(())]
+        );
+        assert_eq!(
+            read_with(SeparatorPolicy::LineEndings),
+            vec![separator("\n\n"), Err(()), Ok(vec![]), Err(())]
+        );
+        assert_eq!(
+            read_with(SeparatorPolicy::Lenient),
+            vec![
+                separator("\n\n"),
+                separator("\r\n"),
+                Ok(vec![]),
+                separator("")
+            ]
+        );
+
+        let config = ParserConfig::new().separators(SeparatorPolicy::Lenient);
+        let mut reader = WarcReader::new(create_reader!(raw)).with_config(config);
+        let mut stream_iter = reader.stream_records();
+        let mut count = 0;
+        while let Some(record) = stream_iter.next_item() {
+            assert!(record.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn leading_garbage() {
+        let raw = [&b"\xef\xbb\xbf\r\n\r\njunk"[..], &RAW[..135]].concat();
+
+        let strict: Vec<_> = WarcReader::new(create_reader!(raw))
+            .iter_records()
+            .collect();
+        assert!(strict[0].is_err());
+
+        let config = ParserConfig::new().skip_leading_garbage(true);
+        let record = WarcReader::new(create_reader!(raw))
+            .with_config(config.clone())
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.warc_id(), "<urn:test:policy:record-0>");
+        assert_eq!(record.warnings(), &[Warning::SkippedLeadingBytes(11)]);
+
+        let mut reader = WarcReader::new(create_reader!(raw)).with_config(config);
+        let mut stream_iter = reader.stream_records();
+        let record = stream_iter.next_item().unwrap().unwrap();
+        assert_eq!(record.warnings(), &[Warning::SkippedLeadingBytes(11)]);
+        drop(record);
+        assert!(stream_iter.next_item().is_none());
+    }
+
+    #[test]
+    fn skipped_ranges() {
+        let raw = [&b"junk\r\n"[..], RAW].concat();
+        let starts: Vec<u64> = raw
+            .windows(8)
+            .enumerate()
+            .filter(|(_, window)| window == b"WARC/1.0")
+            .map(|(i, _)| i as u64)
+            .collect();
+        let range = |offset: u64, end: u64, reason| SkippedRange {
+            offset,
+            len: end - offset,
+            reason,
+        };
+
+        let config = ParserConfig::new().skip_leading_garbage(true);
+        let mut records = WarcReader::new(create_reader!(raw))
+            .with_config(config)
+            .iter_raw_records();
+        assert_eq!(records.by_ref().filter(Result::is_ok).count(), 2);
+        assert_eq!(
+            records.skipped(),
+            &[
+                range(0, 6, SkipReason::LeadingGarbage),
+                range(starts[1], starts[2], SkipReason::Resync),
+                range(starts[3], raw.len() as u64, SkipReason::TrailingData),
+            ]
+        );
+
+        let raw = &RAW[..starts[1] as usize - 6];
+        let raw = [&raw[..raw.len() - 4], b"\n\n"].concat();
+        let config = ParserConfig::new().separators(SeparatorPolicy::Lenient);
+        let mut records = WarcReader::new(create_reader!(raw))
+            .with_config(config)
+            .iter_records();
+        assert!(records.next().unwrap().is_ok());
+        assert!(records.next().is_none());
+        let len = raw.len() as u64;
+        assert_eq!(
+            records.skipped(),
+            &[range(len - 2, len, SkipReason::Separator)]
+        );
+    }
+
+    #[test]
+    fn body_too_large() {
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 1000000000000\r\n\
+            WARC-Record-Id: <urn:test:policy:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n";
+
+        let mut records = WarcReader::new(create_reader!(raw)).iter_records();
+        assert_eq!(records.next(), Some(Err(Error::UnexpectedEOB)));
+
+        let config = ParserConfig::new().max_body_len(1_000_000);
+        let mut records = WarcReader::new(create_reader!(raw))
+            .with_config(config)
+            .iter_raw_records();
+        assert_eq!(
+            records.next().map(|record| record.err()),
+            Some(Some(Error::BodyTooLarge(1_000_000_000_000)))
+        );
+    }
+
+    #[test]
+    fn normalize_headers() {
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 0\r\n\
+            WARC-Record-Id: <urn:test:policy:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            WARC-Target-URI: http://example.com/a\tb c\r\n\
+            WARC-Payload-Digest: sha1:ABCD\r\n\
+            \r\n\
+            \r\n\
+            \r\n";
+
+        let config = ParserConfig::new()
+            .normalize_header(WarcHeader::TargetURI, HeaderNormalizer::RemoveWhitespace)
+            .normalize_header(
+                WarcHeader::PayloadDigest,
+                HeaderNormalizer::UppercaseDigestLabel,
+            );
+        let record = WarcReader::new(create_reader!(raw))
+            .with_config(config.clone())
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::TargetURI).unwrap(),
+            "http://example.com/abc"
+        );
+        assert_eq!(
+            record.header(WarcHeader::PayloadDigest).unwrap(),
+            "SHA1:ABCD"
+        );
+        assert_eq!(
+            record.warnings()[0],
+            Warning::NormalizedHeader {
+                header: WarcHeader::TargetURI,
+                original: "http://example.com/a\tb c".to_owned(),
+                normalized: "http://example.com/abc".to_owned(),
+            }
+        );
+        assert_eq!(record.warnings().len(), 2);
+
+        let (headers, _) = WarcReader::new(create_reader!(raw))
+            .with_config(config)
+            .iter_raw_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            headers.as_ref().get(&WarcHeader::TargetURI).unwrap(),
+            b"http://example.com/a\tb c"
+        );
+    }
+
+    #[test]
+    fn header_policies() {
+        let raw = b"\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 5\r\n\
+            Content-Length: 6\r\n\
+            : no name\r\n\
+            WARC-Record-Id: <urn:test:policy:record-0>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            12345\r\n\
+            \r\n\
+            WARC/1.0\r\n\
+            Warc-Type: dunno\r\n\
+            Content-Length: 0\r\n\
+            WARC-Record-Id: <urn:test:policy:record-1>\r\n\
+            WARC-Date: 2020-07-08T02:52:55Z\r\n\
+            \r\n\
+            \r\n\
+            \r\n\
+        ";
+        let read = |config: ParserConfig| {
+            WarcReader::new(create_reader!(raw))
+                .with_config(config)
+                .iter_records()
+                .collect::<Vec<_>>()
+        };
+
+        let strict = read(ParserConfig::new());
+        assert_eq!(strict.len(), 2);
+        assert!(strict[0].is_err());
+
+        let keep_first = read(
+            ParserConfig::new()
+                .duplicate_headers(HeaderPolicy::KeepFirst)
+                .empty_header_names(HeaderPolicy::KeepFirst),
+        );
+        let record = keep_first[0].as_ref().unwrap();
+        assert_eq!(record.body(), b"12345");
+        assert_eq!(
+            record.warnings(),
+            &[
+                Warning::DuplicateHeader(WarcHeader::ContentLength),
+                Warning::MalformedHeaderLine(": no name".to_owned())
+            ]
+        );
+        assert!(keep_first[1].is_ok());
+
+        let keep_last = read(
+            ParserConfig::new()
+                .duplicate_headers(HeaderPolicy::KeepLast)
+                .empty_header_names(HeaderPolicy::KeepLast),
+        );
+        assert!(keep_last[0].is_err());
+
+        let config = ParserConfig::new()
+            .duplicate_headers(HeaderPolicy::KeepFirst)
+            .empty_header_names(HeaderPolicy::SkipRecord);
+        let skipped = read(config.clone());
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            skipped[0].as_ref().unwrap().warc_id(),
+            "<urn:test:policy:record-1>"
+        );
+
+        let mut reader = WarcReader::new(create_reader!(raw)).with_config(config);
+        let mut stream_iter = reader.stream_records();
+        let record = stream_iter.next_item().unwrap().unwrap();
+        assert_eq!(record.warc_id(), "<urn:test:policy:record-1>");
+        drop(record);
+        assert!(stream_iter.next_item().is_none());
+    }
+
+    #[test]
+    fn streaming_skip() {
+        let mut reader = WarcReader::new(create_reader!(RAW));
+        let mut stream_iter = reader.stream_records();
+        assert!(stream_iter.next_item().unwrap().is_ok());
+        assert!(stream_iter.next_item().unwrap().is_err());
+        assert!(stream_iter.next_item().unwrap().is_err());
+        assert!(stream_iter.next_item().unwrap().is_ok());
+        assert!(stream_iter.next_item().unwrap().is_err());
+        assert!(stream_iter.next_item().is_none());
+    }
 }
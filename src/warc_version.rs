@@ -0,0 +1,155 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::header::WarcHeader;
+use crate::DatePrecision;
+
+/// The version of the standard a record is written under, given by its version line.
+///
+/// Versions differ in what they allow: WARC/1.1 permits sub-second precision in WARC-Date and
+/// defines the WARC-Refers-To-Target-URI and WARC-Refers-To-Date headers, which WARC/1.0 does
+/// not. Versions later than 1.1 are assumed to keep what 1.1 allows.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub enum WarcVersion {
+    /// A pre-1.0 draft of the standard, such as WARC/0.17 or WARC/0.18.
+    Draft(String),
+    #[default]
+    V1_0,
+    V1_1,
+    /// A version this crate does not know, such as a later revision of the standard.
+    Unknown(String),
+}
+
+impl WarcVersion {
+    /// Return the version as it appears after `WARC/` in the version line.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            WarcVersion::V1_0 => "1.0",
+            WarcVersion::V1_1 => "1.1",
+            WarcVersion::Draft(ref version) | WarcVersion::Unknown(ref version) => version,
+        }
+    }
+
+    /// Return true if this is a pre-1.0 draft of the standard.
+    pub fn is_draft(&self) -> bool {
+        matches!(*self, WarcVersion::Draft(_))
+    }
+
+    /// Return true if this version allows sub-second precision in WARC-Date.
+    pub fn allows_subsecond_dates(&self) -> bool {
+        self.number() >= (1, 1)
+    }
+
+    /// Return true if this version defines the given header.
+    ///
+    /// Extension headers, which no version defines, are allowed by every version.
+    pub fn defines_header(&self, header: &WarcHeader) -> bool {
+        match *header {
+            WarcHeader::RefersToTargetURI | WarcHeader::RefersToDate => self.number() >= (1, 1),
+            _ => true,
+        }
+    }
+
+    /// Return the finest precision no finer than the given one which this version allows.
+    pub fn date_precision(&self, precision: DatePrecision) -> DatePrecision {
+        if self.allows_subsecond_dates() {
+            precision
+        } else {
+            DatePrecision::Seconds
+        }
+    }
+
+    /// Format a WARC-Date value with the given precision, reduced to whole seconds if this
+    /// version does not allow finer.
+    pub fn format_date(&self, date: &DateTime<Utc>, precision: DatePrecision) -> String {
+        date.to_rfc3339_opts(self.date_precision(precision).into(), true)
+    }
+
+    /// Return the major and minor numbers of this version, or zero for those which cannot be
+    /// read.
+    fn number(&self) -> (u32, u32) {
+        let mut parts = self.as_str().split('.').map(|part| {
+            part.chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+                .parse::<u32>()
+                .unwrap_or(0)
+        });
+        let major = parts.next().unwrap_or(0);
+        let minor = parts.next().unwrap_or(0);
+
+        (major, minor)
+    }
+}
+
+impl fmt::Display for WarcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WARC/{}", self.as_str())
+    }
+}
+
+impl<S: AsRef<str>> From<S> for WarcVersion {
+    fn from(string: S) -> Self {
+        let version = string.as_ref().trim();
+        let version = version.strip_prefix("WARC/").unwrap_or(version);
+        match version {
+            "1.0" => WarcVersion::V1_0,
+            "1.1" => WarcVersion::V1_1,
+            _ if crate::legacy::is_legacy_version(version) => {
+                WarcVersion::Draft(version.to_owned())
+            }
+            _ => WarcVersion::Unknown(version.to_owned()),
+        }
+    }
+}
+
+impl From<WarcVersion> for String {
+    fn from(version: WarcVersion) -> Self {
+        version.as_str().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Timelike, Utc};
+
+    use super::WarcVersion;
+    use crate::header::WarcHeader;
+    use crate::DatePrecision;
+
+    #[test]
+    fn versions() {
+        assert_eq!(WarcVersion::from("1.0"), WarcVersion::V1_0);
+        assert_eq!(WarcVersion::from("WARC/1.1"), WarcVersion::V1_1);
+        assert_eq!(
+            WarcVersion::from("0.17"),
+            WarcVersion::Draft("0.17".to_owned())
+        );
+        let later = WarcVersion::from("1.2");
+        assert_eq!(later, WarcVersion::Unknown("1.2".to_owned()));
+        assert_eq!(later.to_string(), "WARC/1.2");
+        assert_eq!(String::from(WarcVersion::V1_1), "1.1");
+
+        assert!(!WarcVersion::V1_0.allows_subsecond_dates());
+        assert!(WarcVersion::V1_1.allows_subsecond_dates());
+        assert!(later.allows_subsecond_dates());
+        assert!(!WarcVersion::V1_0.defines_header(&WarcHeader::RefersToDate));
+        assert!(WarcVersion::V1_0.defines_header(&WarcHeader::RefersTo));
+        assert!(WarcVersion::V1_1.defines_header(&WarcHeader::RefersToTargetURI));
+
+        let date = Utc
+            .with_ymd_and_hms(2020, 1, 2, 3, 4, 5)
+            .unwrap()
+            .with_nanosecond(123_000_000)
+            .unwrap();
+        assert_eq!(
+            WarcVersion::V1_0.format_date(&date, DatePrecision::Millis),
+            "2020-01-02T03:04:05Z"
+        );
+        assert_eq!(
+            WarcVersion::V1_1.format_date(&date, DatePrecision::Millis),
+            "2020-01-02T03:04:05.123Z"
+        );
+    }
+}
@@ -1,60 +1,909 @@
-use crate::{BufferedBody, RawRecordHeader, Record};
+#[cfg(feature = "digest")]
+use crate::digest::Algorithm;
+use crate::header::WarcHeader;
+use crate::record::BodyKind;
+use crate::{legacy, parser, segment, wget};
+use crate::{
+    DatePrecision, RawRecordHeader, Record, RecordType, Schema, StreamingBody, WarcinfoBuilder,
+    WarcinfoPreset, WgetMetadata,
+};
 
+use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 
 #[cfg(feature = "gzip")]
-use libflate::gzip::Encoder as GzipWriter;
+use crate::http;
+#[cfg(feature = "gzip")]
+use libflate::gzip::{EncodeOptions, Encoder as GzipWriter};
 
 const MB: usize = 1_048_576;
 
+/// Whether the gzip member of a record compresses its data or stores it in uncompressed blocks.
+#[cfg(feature = "gzip")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemberCompression {
+    Compressed,
+    Stored,
+}
+
+#[cfg(feature = "gzip")]
+type CompressionPredicate = Box<dyn FnMut(&RawRecordHeader, &[u8]) -> MemberCompression>;
+
+type WriteHook = Box<dyn FnMut(&WriteEvent)>;
+
+/// A record written by a `WarcWriter`, as delivered to its write hook.
+///
+/// Events carry what preservation systems record as fixity events, such as PREMIS message digest
+/// calculations, so that they can be logged as records are created rather than derived by
+/// reading the archive again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriteEvent {
+    /// The WARC-Record-ID of the record.
+    pub record_id: String,
+    /// The WARC-Type of the record.
+    pub record_type: String,
+    /// The name of the file written to, if the writer has one.
+    pub file: Option<String>,
+    /// The offset of the record in the output, counting the bytes written by this writer from the
+    /// position it started at.
+    pub offset: u64,
+    /// The number of bytes written for the record. When records are written as gzip members, this
+    /// is the compressed size of the member.
+    pub length: u64,
+    /// The WARC-Block-Digest of the record, or a digest computed by the writer if the record has
+    /// none and the writer is set to compute one.
+    pub block_digest: Option<String>,
+    /// The WARC-Payload-Digest of the record.
+    pub payload_digest: Option<String>,
+}
+
+/// The conventions a writer follows when formatting records.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Compatibility {
+    /// Headers are written with lowercase names, in the order they were read or set.
+    #[default]
+    Standard,
+    /// Headers are written with the capitalization used by the standard, in the order written by
+    /// wget and wpull. Unlisted headers follow the digests, ordered by name.
+    Wget,
+}
+
+/// The capitalization of the header names a writer emits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeaderCase {
+    /// Names are written in lowercase, such as `warc-record-id`.
+    Lowercase,
+    /// Names are written with the capitalization used by the standard, such as `WARC-Record-ID`.
+    /// Extension headers are capitalized word by word.
+    Canonical,
+    /// Names are written as they were spelled in the stream a record was read from. Names which
+    /// were not read, such as those of headers set on a new record, are written in lowercase.
+    Preserve,
+}
+
 /// A writer which writes records to an output stream.
 pub struct WarcWriter<W> {
     writer: W,
+    date_precision: Option<DatePrecision>,
+    stamp_warcinfo_id: bool,
+    warcinfo_id: Option<Vec<u8>>,
+    upgrade_legacy: Option<String>,
+    warcinfo_preset: WarcinfoPreset,
+    compatibility: Compatibility,
+    header_case: Option<HeaderCase>,
+    schema: Option<Schema>,
+    dry_run: bool,
+    segment_size: Option<u64>,
+    position: u64,
+    file_name: Option<String>,
+    write_hook: Option<WriteHook>,
+    #[cfg(feature = "digest")]
+    event_digest: Option<Algorithm>,
+    #[cfg(feature = "gzip")]
+    member_compression: Option<CompressionPredicate>,
+    #[cfg(feature = "gzip")]
+    block_compression: Option<CompressionPredicate>,
 }
 
 impl<W: Write> WarcWriter<W> {
     /// Create a new writer.
     pub fn new(w: W) -> Self {
-        WarcWriter { writer: w }
+        WarcWriter {
+            writer: w,
+            date_precision: None,
+            stamp_warcinfo_id: false,
+            warcinfo_id: None,
+            upgrade_legacy: None,
+            warcinfo_preset: WarcinfoPreset::default(),
+            compatibility: Compatibility::default(),
+            header_case: None,
+            schema: None,
+            dry_run: false,
+            segment_size: None,
+            position: 0,
+            file_name: None,
+            write_hook: None,
+            #[cfg(feature = "digest")]
+            event_digest: None,
+            #[cfg(feature = "gzip")]
+            member_compression: None,
+            #[cfg(feature = "gzip")]
+            block_compression: None,
+        }
     }
 
-    /// Write a single record.
+    /// Write each record as its own gzip member, choosing per record how the member is encoded.
+    ///
+    /// The predicate is given the headers and body of each record. Stored members keep the gzip
+    /// framing of every other record but skip the cost of compressing data which is unlikely to
+    /// shrink; `store_compressed_media` is a predicate suited to most crawls.
+    ///
+    /// The output stream should not itself be gzip-compressed.
+    #[cfg(feature = "gzip")]
+    pub fn set_member_compression<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&RawRecordHeader, &[u8]) -> MemberCompression + 'static,
+    {
+        self.member_compression = Some(Box::new(predicate));
+    }
+
+    /// Stop writing records as individual gzip members.
+    #[cfg(feature = "gzip")]
+    pub fn clear_member_compression(&mut self) {
+        self.member_compression = None;
+    }
+
+    /// Compress the block of each record the predicate chooses, storing it gzip-compressed with a
+    /// Content-Encoding header of `gzip`.
+    ///
+    /// This compresses bodies inside an otherwise uncompressed file, for storage which handles
+    /// gzip members poorly. Records returning `MemberCompression::Compressed` are compressed, so
+    /// `store_compressed_media` is again a suitable predicate. Records which already have a
+    /// Content-Encoding header, and empty blocks, are written unchanged. Digest headers are left
+    /// as they are, and describe the original block, which a reader restores with
+    /// `ParserConfig::decode_blocks`.
+    #[cfg(feature = "gzip")]
+    pub fn set_block_compression<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&RawRecordHeader, &[u8]) -> MemberCompression + 'static,
+    {
+        self.block_compression = Some(Box::new(predicate));
+    }
+
+    /// Stop compressing the blocks of records.
+    #[cfg(feature = "gzip")]
+    pub fn clear_block_compression(&mut self) {
+        self.block_compression = None;
+    }
+
+    /// Set whether records are serialized without being written to the output stream.
+    ///
+    /// In a dry run, every record goes through the same changes and checks as it otherwise would,
+    /// and the number of bytes it would occupy is still returned, but nothing reaches the output.
+    /// Passes which write through a `WarcWriter`, such as `retention::enforce`, `sniff::rewrite`,
+    /// `digest::Redigest` and `transplant`, then report exactly the changes they would make.
+    pub fn set_dry_run(&mut self, enabled: bool) {
+        self.dry_run = enabled;
+    }
+
+    /// Return true if this writer is in a dry run.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Return the stream records are written to, which discards them in a dry run.
+    fn output<'w>(&'w mut self, sink: &'w mut io::Sink) -> &'w mut dyn Write {
+        if self.dry_run {
+            sink
+        } else {
+            &mut self.writer
+        }
+    }
+
+    /// Call the hook with a `WriteEvent` for every record written, once it has been written.
+    ///
+    /// The hook is called for records copied verbatim as well, and in a dry run, where the event
+    /// describes where the record would have been written.
+    pub fn set_write_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&WriteEvent) + 'static,
+    {
+        self.write_hook = Some(Box::new(hook));
+    }
+
+    /// Stop calling the write hook.
+    pub fn clear_write_hook(&mut self) {
+        self.write_hook = None;
+    }
+
+    /// Set the file name given in write events. Writers created by `from_path` use the path.
+    pub fn set_file_name<S: Into<String>>(&mut self, file_name: Option<S>) {
+        self.file_name = file_name.map(Into::into);
+    }
+
+    /// Compute a block digest with the given algorithm for write events of records which have no
+    /// WARC-Block-Digest header, or `None` to leave their digest empty.
+    #[cfg(feature = "digest")]
+    pub fn set_event_digest(&mut self, algorithm: Option<Algorithm>) {
+        self.event_digest = algorithm;
+    }
+
+    /// Return the offset the next record will be written at.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Set the offset the next record will be written at, such as the length of a file being
+    /// appended to.
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Set whether records are automatically linked to the most recently written warcinfo record.
+    ///
+    /// When enabled, every record other than a warcinfo record which is written after a warcinfo
+    /// record is given a WARC-Warcinfo-ID header with that record's ID, unless it already has one.
+    pub fn set_stamp_warcinfo_id(&mut self, enabled: bool) {
+        self.stamp_warcinfo_id = enabled;
+    }
+
+    /// Return the ID of the most recently written warcinfo record, if any.
+    pub fn warcinfo_id(&self) -> Option<&[u8]> {
+        self.warcinfo_id.as_deref()
+    }
+
+    /// Set the conventions followed when formatting records.
+    ///
+    /// The default is `Compatibility::Standard`.
+    pub fn set_compatibility(&mut self, compatibility: Compatibility) {
+        self.compatibility = compatibility;
+    }
+
+    /// Set the capitalization of the header names written.
+    ///
+    /// The policy applies to standard and extension headers alike. By default, names are written
+    /// in lowercase, or with canonical capitalization under `Compatibility::Wget`.
+    pub fn set_header_case(&mut self, header_case: HeaderCase) {
+        self.header_case = Some(header_case);
+    }
+
+    /// Set a schema every record written must conform to, or `None` to accept any record.
+    ///
+    /// Records which break the schema are refused with an error of kind `InvalidInput` describing
+    /// each violation, and nothing is written.
+    pub fn set_schema(&mut self, schema: Option<Schema>) {
+        self.schema = schema;
+    }
+
+    fn header_case(&self) -> HeaderCase {
+        self.header_case.unwrap_or(match self.compatibility {
+            Compatibility::Standard => HeaderCase::Lowercase,
+            Compatibility::Wget => HeaderCase::Canonical,
+        })
+    }
+
+    /// Write the manifest, arguments, and log records wget writes at the end of a crawl.
+    ///
+    /// The records are linked to the most recently written warcinfo record, if any. The number
+    /// of bytes written is returned upon success.
+    pub fn write_wget_metadata(&mut self, metadata: &WgetMetadata) -> io::Result<usize> {
+        let warcinfo_id = self
+            .warcinfo_id
+            .as_ref()
+            .map(|id| String::from_utf8_lossy(id).into_owned());
+        let mut bytes_written = 0;
+        for record in metadata.clone().into_records(warcinfo_id.as_deref()) {
+            bytes_written += self.write(&record)?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Set the layout of the warcinfo records written by `write_warcinfo`.
+    ///
+    /// The default preset is `WarcinfoPreset::Heritrix`.
+    pub fn set_warcinfo_preset(&mut self, preset: WarcinfoPreset) {
+        self.warcinfo_preset = preset;
+    }
+
+    /// Write a warcinfo record holding the given fields, laid out by this writer's preset.
+    ///
+    /// The number of bytes written is returned upon success. If the fields cannot be built into a
+    /// record, an error of kind `InvalidInput` is returned and nothing is written.
+    pub fn write_warcinfo(&mut self, warcinfo: &WarcinfoBuilder) -> io::Result<usize> {
+        let record = warcinfo
+            .clone()
+            .into_record(self.warcinfo_preset)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.write(&record)
+    }
+
+    /// Set the version that records written under a pre-1.0 draft of the standard are upgraded to.
+    ///
+    /// Records of other versions are written unchanged. Each upgraded warcinfo record has an
+    /// `upgraded-from` field appended to its body, noting the version it was written under.
+    pub fn set_upgrade_legacy<S: Into<String>>(&mut self, version: Option<S>) {
+        self.upgrade_legacy = version.map(Into::into);
+    }
+
+    /// Set the largest block a record is written with. Records with a larger block are split
+    /// into a first segment and continuation records, as `split_segments` does, and the total
+    /// number of bytes written is returned.
+    ///
+    /// Streamed records are not segmented. By default, no record is segmented.
+    pub fn set_segment_size(&mut self, segment_size: Option<u64>) {
+        self.segment_size = segment_size;
+    }
+
+    /// Set the precision used for the WARC-Date header of every record written.
+    ///
+    /// By default, each record is written with its own date precision.
+    pub fn set_date_precision(&mut self, precision: Option<DatePrecision>) {
+        self.date_precision = precision;
+    }
+
+    /// Write a single record, whose body may be buffered or shared.
     ///
     /// The number of bytes written is returned upon success.
-    pub fn write(&mut self, record: &Record<BufferedBody>) -> io::Result<usize> {
-        let (headers, body) = record.clone().into_raw_parts();
-        self.write_raw(headers, &body)
+    ///
+    /// # Errors
+    ///
+    /// If a date precision is set on this writer which the record's WARC version does not allow,
+    /// an error of kind `InvalidInput` is returned and nothing is written.
+    pub fn write<T>(&mut self, record: &Record<T>) -> io::Result<usize>
+    where
+        T: BodyKind + AsRef<[u8]>,
+    {
+        let headers = record
+            .header_block(self.date_precision)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.write_named(headers, record.body(), record.header_names())
     }
 
     /// Write a single raw record.
     ///
-    /// The number of bytes written is returned upon success.
-    pub fn write_raw<B>(&mut self, headers: RawRecordHeader, body: &B) -> io::Result<usize>
+    /// The Content-Length header is set to the length of the body, replacing any value given, so
+    /// that the record is always framed correctly. The number of bytes written is returned upon
+    /// success. When records are written as gzip members, this is the compressed size of the
+    /// member.
+    pub fn write_raw<B>(&mut self, mut headers: RawRecordHeader, body: &B) -> io::Result<usize>
     where
         B: AsRef<[u8]>,
     {
-        let mut bytes_written = 0;
+        let body = body.as_ref();
+        headers.as_mut().insert(
+            WarcHeader::ContentLength,
+            body.len().to_string().into_bytes(),
+        );
+        self.write_named(headers, body, &HashMap::new())
+    }
 
-        bytes_written += self.writer.write(&[87, 65, 82, 67, 47])?;
-        bytes_written += self.writer.write(headers.version.as_bytes())?;
-        bytes_written += self.writer.write(&[13, 10])?;
+    /// Write a single raw record, with the original spellings of its header names, segmenting it
+    /// if its block is larger than the segment size.
+    fn write_named(
+        &mut self,
+        headers: RawRecordHeader,
+        body: &[u8],
+        names: &HashMap<WarcHeader, String>,
+    ) -> io::Result<usize> {
+        match self.segment_size {
+            Some(segment_size) if body.len() as u64 > segment_size => {
+                let mut bytes_written = 0;
+                for (segment, chunk) in segment::split_segments(headers, body, segment_size) {
+                    bytes_written += self.write_one(segment, chunk, names)?;
+                }
 
-        for (token, value) in headers.as_ref().iter() {
-            bytes_written += self.writer.write(token.to_string().as_bytes())?;
-            bytes_written += self.writer.write(&[58, 32])?;
-            bytes_written += self.writer.write(&value)?;
-            bytes_written += self.writer.write(&[13, 10])?;
+                Ok(bytes_written)
+            }
+            _ => self.write_one(headers, body, names),
         }
-        bytes_written += self.writer.write(&[13, 10])?;
+    }
 
-        bytes_written += self.writer.write(body.as_ref())?;
-        bytes_written += self.writer.write(&[13, 10])?;
-        bytes_written += self.writer.write(&[13, 10])?;
+    fn write_one(
+        &mut self,
+        mut headers: RawRecordHeader,
+        body: &[u8],
+        names: &HashMap<WarcHeader, String>,
+    ) -> io::Result<usize> {
+        self.link_warcinfo(&mut headers);
+        let upgraded_body = self.upgrade_legacy(&mut headers, body);
+        let body = upgraded_body.as_deref().unwrap_or(body);
+        self.check_schema(&headers)?;
+        let block = body;
+        #[cfg(feature = "gzip")]
+        let encoded_body = self.encode_block(&mut headers, body)?;
+        #[cfg(feature = "gzip")]
+        let body = encoded_body.as_deref().unwrap_or(body);
+        let name = HeaderName {
+            case: self.header_case(),
+            names,
+        };
+        let compatibility = self.compatibility;
 
-        Ok(bytes_written)
+        #[cfg(feature = "gzip")]
+        {
+            if let Some(ref mut predicate) = self.member_compression {
+                let mut options = EncodeOptions::new();
+                if predicate(&headers, body) == MemberCompression::Stored {
+                    options = options.no_compression();
+                }
+                let mut sink = io::sink();
+                let mut counter = CountingWriter(self.output(&mut sink), 0);
+                let mut member = GzipWriter::with_options(&mut counter, options)?;
+                write_record(&mut member, &headers, body, compatibility, name)?;
+                member.finish().into_result()?;
+                let length = counter.1;
+                self.record_written(
+                    |h| headers.as_ref().get(h).map(|v| &v[..]),
+                    Some(block),
+                    length,
+                );
+
+                return Ok(length);
+            }
+        }
+
+        let mut sink = io::sink();
+        let length = write_record(self.output(&mut sink), &headers, body, compatibility, name)?;
+        self.record_written(
+            |h| headers.as_ref().get(h).map(|v| &v[..]),
+            Some(block),
+            length,
+        );
+
+        Ok(length)
+    }
+
+    /// Write a single record whose body is streamed from its reader, without holding the body in
+    /// memory.
+    ///
+    /// The body is copied to the output as it is read. Records whose body this writer changes,
+    /// namely warcinfo records upgraded from a legacy version and records whose block is
+    /// compressed, are buffered instead. The member compression predicate is given an empty body,
+    /// and the write hook receives no computed block digest for streamed records.
+    ///
+    /// The number of bytes written is returned upon success.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `UnexpectedEof` is returned if the body ends before its Content-Length,
+    /// in which case a partial record has been written.
+    pub fn write_streaming<R: Read>(
+        &mut self,
+        record: Record<StreamingBody<'_, R>>,
+    ) -> io::Result<usize> {
+        let headers = record
+            .header_block(self.date_precision)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if self.buffers_body(&headers) {
+            return self.write(&record.into_buffered()?);
+        }
+
+        let len = record.content_length();
+        let names = record.header_names().clone();
+        let (_, mut body) = record.into_parts();
+        self.stream_named(headers, &mut body, len, &names)
+    }
+
+    /// Write a single raw record whose body of `len` bytes is streamed from a reader.
+    ///
+    /// The Content-Length header is set to `len`, replacing any value given. The body is only
+    /// buffered when this writer changes it, as with `write_streaming`.
+    pub(crate) fn write_raw_streaming(
+        &mut self,
+        mut headers: RawRecordHeader,
+        body: &mut dyn Read,
+        len: u64,
+    ) -> io::Result<usize> {
+        headers
+            .as_mut()
+            .insert(WarcHeader::ContentLength, len.to_string().into_bytes());
+        if self.buffers_body(&headers) {
+            let mut buf = Vec::new();
+            body.take(len).read_to_end(&mut buf)?;
+            if (buf.len() as u64) < len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            return self.write_raw(headers, &buf);
+        }
+
+        self.stream_named(headers, body, len, &HashMap::new())
+    }
+
+    /// Return true if this writer changes the body of a record with the given headers, and so
+    /// must hold the body in memory to write it.
+    fn buffers_body(&self, headers: &RawRecordHeader) -> bool {
+        #[allow(unused_mut)]
+        let mut buffer = self.upgrade_legacy.is_some()
+            && legacy::is_legacy_version(&headers.version)
+            && is_warcinfo(headers);
+        #[cfg(feature = "gzip")]
+        {
+            buffer |= self.block_compression.is_some();
+        }
+
+        buffer
+    }
+
+    fn stream_named<R: Read + ?Sized>(
+        &mut self,
+        mut headers: RawRecordHeader,
+        mut body: &mut R,
+        len: u64,
+        names: &HashMap<WarcHeader, String>,
+    ) -> io::Result<usize> {
+        self.link_warcinfo(&mut headers);
+        self.upgrade_legacy(&mut headers, &[]);
+        self.check_schema(&headers)?;
+        let name = HeaderName {
+            case: self.header_case(),
+            names,
+        };
+        let compatibility = self.compatibility;
+
+        #[cfg(feature = "gzip")]
+        {
+            if let Some(ref mut predicate) = self.member_compression {
+                let mut options = EncodeOptions::new();
+                if predicate(&headers, &[]) == MemberCompression::Stored {
+                    options = options.no_compression();
+                }
+                let mut sink = io::sink();
+                let mut counter = CountingWriter(self.output(&mut sink), 0);
+                let mut member = GzipWriter::with_options(&mut counter, options)?;
+                stream_record(&mut member, &headers, &mut body, len, compatibility, name)?;
+                member.finish().into_result()?;
+                let length = counter.1;
+                self.record_written(|h| headers.as_ref().get(h).map(|v| &v[..]), None, length);
+
+                return Ok(length);
+            }
+        }
+
+        let mut sink = io::sink();
+        let out = self.output(&mut sink);
+        let length = stream_record(out, &headers, &mut body, len, compatibility, name)?;
+        self.record_written(|h| headers.as_ref().get(h).map(|v| &v[..]), None, length);
+
+        Ok(length)
+    }
+
+    /// Refuse a record which breaks the schema of this writer, if it has one.
+    fn check_schema(&self, headers: &RawRecordHeader) -> io::Result<()> {
+        if let Some(ref schema) = self.schema {
+            let violations = schema.check_raw(headers);
+            if !violations.is_empty() {
+                let message: Vec<_> = violations.iter().map(|v| v.to_string()).collect();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    message.join("; "),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Advance the position past a record just written, and deliver its event to the write hook.
+    fn record_written<'h, H>(&mut self, header: H, block: Option<&[u8]>, length: usize)
+    where
+        H: Fn(&WarcHeader) -> Option<&'h [u8]>,
+    {
+        let offset = self.position;
+        self.position += length as u64;
+        let hook = match self.write_hook {
+            Some(ref mut hook) => hook,
+            None => return,
+        };
+        let value = |h: WarcHeader| header(&h).map(|v| String::from_utf8_lossy(v).into_owned());
+        #[allow(unused_mut)]
+        let mut block_digest = value(WarcHeader::BlockDigest);
+        #[cfg(feature = "digest")]
+        {
+            if let (None, Some(algorithm), Some(block)) = (&block_digest, self.event_digest, block)
+            {
+                block_digest = Some(algorithm.compute(block).to_string());
+            }
+        }
+        #[cfg(not(feature = "digest"))]
+        let _ = block;
+
+        hook(&WriteEvent {
+            record_id: value(WarcHeader::RecordID).unwrap_or_default(),
+            record_type: value(WarcHeader::WarcType).unwrap_or_default(),
+            file: self.file_name.clone(),
+            offset,
+            length: length as u64,
+            block_digest,
+            payload_digest: value(WarcHeader::PayloadDigest),
+        });
+    }
+
+    /// Flush the underlying output stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying output stream.
+    #[cfg(feature = "codec")]
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Return true if this writer makes no changes to the records passed to it beyond linking
+    /// them to a warcinfo record, and has no schema to check, so that a serialized record can be
+    /// copied as-is.
+    pub(crate) fn writes_verbatim(&self) -> bool {
+        #[cfg(feature = "gzip")]
+        {
+            if self.member_compression.is_some() || self.block_compression.is_some() {
+                return false;
+            }
+        }
+
+        self.date_precision.is_none()
+            && self.upgrade_legacy.is_none()
+            && self.compatibility == Compatibility::Standard
+            && matches!(self.header_case, None | Some(HeaderCase::Preserve))
+            && self.schema.is_none()
+    }
+
+    /// Write an already serialized record, including its separator, without changing it.
+    pub(crate) fn write_verbatim(&mut self, record: &[u8]) -> io::Result<usize> {
+        let mut sink = io::sink();
+        self.output(&mut sink).write_all(record)?;
+        match parser::parse_header_block(record) {
+            Ok((rest, (_, headers))) if self.write_hook.is_some() => {
+                let block = &rest[..rest.len().saturating_sub(4)];
+                let header = |h: &WarcHeader| {
+                    headers
+                        .iter()
+                        .find(|(name, _)| WarcHeader::from(*name) == *h)
+                        .map(|(_, value)| *value)
+                };
+                self.record_written(header, Some(block), record.len());
+            }
+            _ => self.position += record.len() as u64,
+        }
+
+        Ok(record.len())
+    }
+
+    /// Compress the block of a record if the block compression predicate chooses it, returning
+    /// the compressed block.
+    #[cfg(feature = "gzip")]
+    fn encode_block(
+        &mut self,
+        headers: &mut RawRecordHeader,
+        body: &[u8],
+    ) -> io::Result<Option<Vec<u8>>> {
+        let predicate = match self.block_compression {
+            Some(ref mut predicate) => predicate,
+            None => return Ok(None),
+        };
+        let content_encoding = WarcHeader::from("Content-Encoding");
+        if body.is_empty()
+            || headers.as_ref().contains_key(&content_encoding)
+            || predicate(headers, body) == MemberCompression::Stored
+        {
+            return Ok(None);
+        }
+
+        let mut encoder = GzipWriter::new(Vec::new())?;
+        encoder.write_all(body)?;
+        let block = encoder.finish().into_result()?;
+        let map = headers.as_mut();
+        map.insert(content_encoding, b"gzip".to_vec());
+        map.insert(
+            WarcHeader::ContentLength,
+            block.len().to_string().into_bytes(),
+        );
+
+        Ok(Some(block))
+    }
+
+    /// Upgrade the version of a legacy record, returning the new body of an upgraded warcinfo
+    /// record.
+    fn upgrade_legacy(&self, headers: &mut RawRecordHeader, body: &[u8]) -> Option<Vec<u8>> {
+        let version = self.upgrade_legacy.as_ref()?;
+        if !legacy::is_legacy_version(&headers.version) {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut headers.version, version.clone());
+        if !is_warcinfo(headers) {
+            return None;
+        }
+        let mut body = body.to_vec();
+        if !body.is_empty() && !body.ends_with(b"\n") {
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(legacy::upgrade_note(&from).as_bytes());
+        headers.as_mut().insert(
+            WarcHeader::ContentLength,
+            body.len().to_string().into_bytes(),
+        );
+
+        Some(body)
+    }
+
+    fn link_warcinfo(&mut self, headers: &mut RawRecordHeader) {
+        if is_warcinfo(headers) {
+            self.warcinfo_id = headers.as_ref().get(&WarcHeader::RecordID).cloned();
+        } else if self.stamp_warcinfo_id {
+            if let Some(ref id) = self.warcinfo_id {
+                if !headers.as_ref().contains_key(&WarcHeader::WarcInfoID) {
+                    headers.as_mut().insert(WarcHeader::WarcInfoID, id.clone());
+                }
+            }
+        }
+    }
+}
+
+fn is_warcinfo(headers: &RawRecordHeader) -> bool {
+    headers
+        .as_ref()
+        .get(&WarcHeader::WarcType)
+        .map(|value| RecordType::from(String::from_utf8_lossy(value)) == RecordType::WarcInfo)
+        .unwrap_or(false)
+}
+
+/// The spelling of the header names written for a record.
+#[derive(Clone, Copy)]
+pub(crate) struct HeaderName<'a> {
+    pub case: HeaderCase,
+    /// The names as they were read, where they differ from the lowercase names.
+    pub names: &'a HashMap<WarcHeader, String>,
+}
+
+impl HeaderName<'_> {
+    fn of(&self, token: &WarcHeader) -> String {
+        match self.case {
+            HeaderCase::Lowercase => token.to_string(),
+            HeaderCase::Canonical => token.canonical_name(),
+            HeaderCase::Preserve => match self.names.get(token) {
+                Some(name) => name.clone(),
+                None => token.to_string(),
+            },
+        }
+    }
+}
+
+pub(crate) fn write_record<W: Write + ?Sized>(
+    out: &mut W,
+    headers: &RawRecordHeader,
+    body: &[u8],
+    compatibility: Compatibility,
+    name: HeaderName,
+) -> io::Result<usize> {
+    let head_len = write_head(out, headers, compatibility, name)?;
+    out.write_all(body)?;
+    out.write_all(&[13, 10, 13, 10])?;
+
+    Ok(head_len + body.len() + 4)
+}
+
+/// Write a record whose body is copied from a reader, which must yield at least `len` bytes.
+fn stream_record<W: Write + ?Sized, R: Read>(
+    out: &mut W,
+    headers: &RawRecordHeader,
+    body: &mut R,
+    len: u64,
+    compatibility: Compatibility,
+    name: HeaderName,
+) -> io::Result<usize> {
+    let head_len = write_head(out, headers, compatibility, name)?;
+    let copied = io::copy(&mut body.take(len), out)?;
+    if copied < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    out.write_all(&[13, 10, 13, 10])?;
+
+    Ok(head_len + len as usize + 4)
+}
+
+/// Write the version line and headers of a record, and the blank line ending them.
+fn write_head<W: Write + ?Sized>(
+    out: &mut W,
+    headers: &RawRecordHeader,
+    compatibility: Compatibility,
+    name: HeaderName,
+) -> io::Result<usize> {
+    let mut bytes_written = 0;
+    let mut put = |bytes: &[u8]| -> io::Result<()> {
+        out.write_all(bytes)?;
+        bytes_written += bytes.len();
+        Ok(())
+    };
+
+    put(&[87, 65, 82, 67, 47])?;
+    put(headers.version.as_bytes())?;
+    put(&[13, 10])?;
+
+    let mut fields: Vec<_> = headers.as_ref().iter().collect();
+    if compatibility == Compatibility::Wget {
+        fields.sort_by_key(|(token, _)| {
+            let position = wget::HEADER_ORDER.iter().position(|h| h == *token);
+            let last = **token == WarcHeader::ContentLength;
+            (
+                last,
+                position.unwrap_or(wget::HEADER_ORDER.len()),
+                token.to_string(),
+            )
+        });
+    }
+    for (token, value) in fields {
+        put(name.of(token).as_bytes())?;
+        put(&[58, 32])?;
+        put(value)?;
+        put(&[13, 10])?;
+    }
+    put(&[13, 10])?;
+
+    Ok(bytes_written)
+}
+
+/// A writer which counts the bytes passed through to the inner writer.
+#[cfg(feature = "gzip")]
+pub(crate) struct CountingWriter<'w, W: ?Sized>(pub &'w mut W, pub usize);
+
+#[cfg(feature = "gzip")]
+impl<W: Write + ?Sized> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.0.write(buf)?;
+        self.1 += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// A member compression predicate which stores media types that are already compressed.
+///
+/// The media type is taken from the WARC-Identified-Payload-Type header, or else the Content-Type
+/// header. For HTTP messages, the Content-Type of the HTTP message is used instead.
+#[cfg(feature = "gzip")]
+pub fn store_compressed_media(headers: &RawRecordHeader, body: &[u8]) -> MemberCompression {
+    let header = |name: &WarcHeader| {
+        headers
+            .as_ref()
+            .get(name)
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    };
+    let media_type = http::payload_media_type(
+        header(&WarcHeader::IdentifiedPayloadType),
+        header(&WarcHeader::ContentType),
+        body,
+    );
+    let media_type = media_type.as_str();
+
+    let compressed = match media_type.split('/').next().unwrap_or("") {
+        "image" => media_type != "image/svg+xml" && media_type != "image/bmp",
+        "audio" | "video" => true,
+        "font" => media_type == "font/woff" || media_type == "font/woff2",
+        _ => matches!(
+            media_type,
+            "application/zip"
+                | "application/gzip"
+                | "application/x-gzip"
+                | "application/x-bzip2"
+                | "application/x-xz"
+                | "application/zstd"
+                | "application/x-7z-compressed"
+                | "application/vnd.rar"
+                | "application/pdf"
+        ),
+    };
+
+    if compressed {
+        MemberCompression::Stored
+    } else {
+        MemberCompression::Compressed
     }
 }
 
@@ -83,25 +932,592 @@ impl WarcWriter<BufWriter<fs::File>> {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let writer = BufWriter::with_capacity(MB, file);
+        let mut writer = WarcWriter::new(writer);
+        writer.set_file_name(Some(path.as_ref().display().to_string()));
+
+        Ok(writer)
+    }
+
+    /// Create a new writer which writes to a file with each record in a gzip member of its own,
+    /// as the standard recommends for `.warc.gz` files.
+    ///
+    /// Unlike a file written by `from_path_gzip`, each record of the file can be read on its own
+    /// from its offset, as indexers and replay tools such as pywb and warcio expect.
+    ///
+    /// An existing file is truncated, so that no bytes of it are left after the members written.
+    #[cfg(feature = "gzip")]
+    pub fn from_path_gzip_members<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
             .open(&path)?;
-        let writer = BufWriter::with_capacity(1 * MB, file);
+        let mut writer = WarcWriter::new(BufWriter::with_capacity(MB, file));
+        writer.set_file_name(Some(path.as_ref().display().to_string()));
+        writer.set_member_compression(|_, _| MemberCompression::Compressed);
 
-        Ok(WarcWriter::new(writer))
+        Ok(writer)
     }
 }
 
 #[cfg(feature = "gzip")]
 impl WarcWriter<BufWriter<GzipWriter<std::fs::File>>> {
     /// Create a new writer which writes to a GZIP-compressed file.
+    ///
+    /// The whole file is written as a single gzip member; see `from_path_gzip_members` for a file
+    /// whose records can be read individually.
     pub fn from_path_gzip<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&path)?;
         let gzip_stream = GzipWriter::new(file)?;
-        let writer = BufWriter::with_capacity(1 * MB, gzip_stream);
+        let writer = BufWriter::with_capacity(MB, gzip_stream);
+        let mut writer = WarcWriter::new(writer);
+        writer.set_file_name(Some(path.as_ref().display().to_string()));
+
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::header::WarcHeader;
+    use crate::{
+        BufferedBody, Compatibility, HeaderCase, Record, RecordType, Schema, WarcReader,
+        WarcWriter, WarcinfoBuilder, WarcinfoPreset, WgetMetadata,
+    };
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn member_compression() {
+        use super::{store_compressed_media, MemberCompression};
+        use std::io::Read;
+
+        let text = vec![b'a'; 4096];
+        let image = vec![b'b'; 4096];
+        let mut html = Record::<BufferedBody>::with_body(text.clone());
+        html.set_header(WarcHeader::ContentType, "text/html")
+            .unwrap();
+        let mut png = Record::<BufferedBody>::with_body(image.clone());
+        png.set_header(WarcHeader::ContentType, "image/png")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_member_compression(store_compressed_media);
+        let html_len = writer.write(&html).unwrap();
+        let png_len = writer.write(&png).unwrap();
+        assert!(html_len < text.len());
+        assert!(png_len > image.len());
+        assert_eq!(html_len + png_len, buffer.len());
+        assert!(buffer[html_len..]
+            .windows(image.len())
+            .any(|window| window == &image[..]));
+
+        let mut decoded = Vec::new();
+        libflate::gzip::MultiDecoder::new(Cursor::new(&buffer))
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        let bodies: Vec<_> = WarcReader::new(BufReader::new(Cursor::new(decoded)))
+            .iter_records()
+            .map(|record| record.unwrap().body().to_vec())
+            .collect();
+        assert_eq!(bodies, vec![text, image]);
+
+        let mut response = Record::<BufferedBody>::with_body(
+            &b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\n\r\n...."[..],
+        );
+        response
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        let (headers, body) = response.into_raw_parts();
+        assert_eq!(
+            store_compressed_media(&headers, &body),
+            MemberCompression::Stored
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn block_compression() {
+        use super::store_compressed_media;
+        use crate::ParserConfig;
+
+        let text = vec![b'a'; 4096];
+        let image = vec![b'b'; 4096];
+        let mut html = Record::<BufferedBody>::with_body(text.clone());
+        html.set_header(WarcHeader::ContentType, "text/html")
+            .unwrap();
+        let mut png = Record::<BufferedBody>::with_body(image.clone());
+        png.set_header(WarcHeader::ContentType, "image/png")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_block_compression(store_compressed_media);
+        writer.write(&html).unwrap();
+        writer.write(&png).unwrap();
+        drop(writer);
+        assert!(buffer.len() < text.len() + image.len());
+
+        let encodings: Vec<_> = WarcReader::new(&buffer[..])
+            .iter_records()
+            .map(|record| {
+                let record = record.unwrap();
+                let encoding = record.header(WarcHeader::from("Content-Encoding"));
+                encoding.map(|encoding| encoding.into_owned())
+            })
+            .collect();
+        assert_eq!(encodings, vec![Some("gzip".to_owned()), None]);
+
+        let records: Vec<_> = WarcReader::new(&buffer[..])
+            .with_config(ParserConfig::new().decode_blocks(true))
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records[0].warc_id(), html.warc_id());
+        assert_eq!(records[0].body(), &text[..]);
+        assert_eq!(
+            records[0].header(WarcHeader::from("Content-Encoding")),
+            None
+        );
+        assert_eq!(records[1].body(), &image[..]);
+    }
+
+    #[test]
+    fn stamp_warcinfo_id() {
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        let before = Record::<BufferedBody>::default();
+        let mut explicit = Record::<BufferedBody>::default();
+        explicit
+            .set_header(WarcHeader::WarcInfoID, "<urn:test:other-warcinfo>")
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_stamp_warcinfo_id(true);
+        writer.write(&before).unwrap();
+        writer.write(&warcinfo).unwrap();
+        writer.write(&Record::<BufferedBody>::default()).unwrap();
+        writer.write(&explicit).unwrap();
+        assert_eq!(writer.warcinfo_id(), Some(warcinfo.warc_id().as_bytes()));
+
+        let ids: Vec<_> = WarcReader::new(BufReader::new(Cursor::new(buffer)))
+            .iter_records()
+            .map(|record| {
+                record
+                    .unwrap()
+                    .header(WarcHeader::WarcInfoID)
+                    .map(|id| id.into_owned())
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                None,
+                None,
+                Some(warcinfo.warc_id().to_owned()),
+                Some("<urn:test:other-warcinfo>".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn write_warcinfo() {
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_warcinfo_preset(WarcinfoPreset::Wget);
+        writer
+            .write_warcinfo(&WarcinfoBuilder::new().software("Wget/1.21"))
+            .unwrap();
+        assert!(writer.warcinfo_id().is_some());
+
+        let record = WarcReader::new(BufReader::new(Cursor::new(buffer)))
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.warc_type(), &RecordType::WarcInfo);
+        assert!(record
+            .body()
+            .starts_with(b"software: Wget/1.21\r\nformat: "));
+    }
+
+    #[test]
+    fn wget_compatibility() {
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+        record
+            .set_header(WarcHeader::from("X-Custom"), "value")
+            .unwrap();
+        record.replace_body(&b"body"[..]);
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_compatibility(Compatibility::Wget);
+        writer.write(&record).unwrap();
+        writer
+            .write_wget_metadata(&WgetMetadata::new().argument("wget"))
+            .unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let names: Vec<_> = output
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .map(|line| line.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "WARC-Type",
+                "WARC-Target-URI",
+                "WARC-Date",
+                "WARC-Record-ID",
+                "X-Custom",
+                "Content-Length"
+            ]
+        );
+        assert_eq!(output.matches("WARC/1.0\r\n").count(), 4);
+    }
 
-        Ok(WarcWriter::new(writer))
+    #[test]
+    fn schema() {
+        let schema = Schema::new()
+            .require(&[RecordType::Response], "X-Collection-Id")
+            .require_one_of(&[], "X-Access", &["open", "dark"]);
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version("1.0");
+        record.set_warc_type(RecordType::Response);
+        record
+            .set_header(WarcHeader::from("X-Access"), "restricted")
+            .unwrap();
+        let violations = schema.check(&record);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0].to_string(),
+            "response record is missing required header: x-collection-id"
+        );
+        assert_eq!(violations[1].value.as_deref(), Some("restricted"));
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_schema(Some(schema));
+        let error = writer.write(&record).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        record
+            .set_header(WarcHeader::from("X-Collection-Id"), "c1")
+            .unwrap();
+        record
+            .set_header(WarcHeader::from("X-Access"), "open")
+            .unwrap();
+        writer.write(&record).unwrap();
+        let mut info = Record::<BufferedBody>::default();
+        info.set_warc_version("1.0");
+        info.set_warc_type(RecordType::WarcInfo);
+        assert!(writer.write(&info).is_err());
+        drop(writer);
+        assert_eq!(
+            String::from_utf8(buffer)
+                .unwrap()
+                .matches("WARC/1.0")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn header_case() {
+        let raw = b"WARC/1.0\r\n\
+            Warc-Type: resource\r\n\
+            WARC-Record-ID: <urn:uuid:1>\r\n\
+            warc-date: 2020-01-01T00:00:00Z\r\n\
+            X-Crawler-ID: a\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            \r\n\
+            \r\n";
+        let record = WarcReader::new(&raw[..])
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let names = |header_case: Option<HeaderCase>| {
+            let mut buffer = Vec::new();
+            let mut writer = WarcWriter::new(&mut buffer);
+            if let Some(header_case) = header_case {
+                writer.set_header_case(header_case);
+            }
+            writer.write(&record).unwrap();
+            let output = String::from_utf8(buffer).unwrap();
+            let mut names: Vec<_> = output
+                .lines()
+                .skip(1)
+                .take_while(|line| !line.is_empty())
+                .map(|line| line.split(':').next().unwrap().to_owned())
+                .collect();
+            names.sort_by_key(|name| name.to_lowercase());
+            names
+        };
+        assert_eq!(
+            names(None),
+            vec![
+                "content-length",
+                "warc-date",
+                "warc-record-id",
+                "warc-type",
+                "x-crawler-id"
+            ]
+        );
+        assert_eq!(
+            names(Some(HeaderCase::Canonical)),
+            vec![
+                "Content-Length",
+                "WARC-Date",
+                "WARC-Record-ID",
+                "WARC-Type",
+                "X-Crawler-Id"
+            ]
+        );
+        assert_eq!(
+            names(Some(HeaderCase::Preserve)),
+            vec![
+                "Content-Length",
+                "warc-date",
+                "WARC-Record-ID",
+                "Warc-Type",
+                "X-Crawler-ID"
+            ]
+        );
+    }
+
+    #[test]
+    fn header_order() {
+        let raw = b"WARC/1.0\r\n\
+            WARC-Type: metadata\r\n\
+            WARC-Concurrent-To: <urn:uuid:2>\r\n\
+            WARC-Record-ID: <urn:uuid:1>\r\n\
+            WARC-Concurrent-To: <urn:uuid:3>\r\n\
+            WARC-Date: 2020-01-01T00:00:00Z\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            \r\n\
+            \r\n";
+        let record = WarcReader::new(&raw[..])
+            .iter_records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            record.header(WarcHeader::ConcurrentTo).unwrap(),
+            "<urn:uuid:2>"
+        );
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_header_case(HeaderCase::Preserve);
+        writer.write(&record).unwrap();
+        assert_eq!(buffer, &raw[..]);
+    }
+
+    #[test]
+    fn upgrade_legacy() {
+        let mut warcinfo = Record::<BufferedBody>::default();
+        warcinfo.set_warc_version("0.17");
+        warcinfo.set_warc_type(RecordType::WarcInfo);
+        warcinfo.replace_body(&b"software: heritrix"[..]);
+        let mut response = Record::<BufferedBody>::default();
+        response.set_warc_version("0.18");
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_upgrade_legacy(Some("1.0"));
+        writer.write(&warcinfo).unwrap();
+        writer.write(&response).unwrap();
+        writer.write(&Record::<BufferedBody>::default()).unwrap();
+
+        let records: Vec<_> = WarcReader::new(BufReader::new(Cursor::new(buffer)))
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records[0].warc_version(), "1.0");
+        assert_eq!(
+            records[0].body(),
+            b"software: heritrix\r\nupgraded-from: WARC/0.17\r\n"
+        );
+        assert_eq!(records[1].warc_version(), "1.0");
+        assert!(!records[1].is_legacy_version());
+        assert_eq!(records[2].body(), b"");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_members_file() {
+        use crate::gzip_members::survey;
+
+        let path =
+            std::env::temp_dir().join(format!("warc-members-{}.warc.gz", std::process::id()));
+        std::fs::write(&path, vec![b'x'; 10_000]).unwrap();
+        let mut writer = WarcWriter::from_path_gzip_members(&path).unwrap();
+        for _ in 0..3 {
+            writer
+                .write(&Record::<BufferedBody>::with_body(&b"body"[..]))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let layout = survey(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(layout.members, 3);
+        assert!(layout.is_random_access());
+    }
+
+    #[test]
+    fn write_raw_content_length() {
+        let mut headers = Record::<BufferedBody>::default()
+            .header_block(None)
+            .unwrap();
+        headers
+            .as_mut()
+            .insert(WarcHeader::ContentLength, b"99".to_vec());
+
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.write_raw(headers.clone(), b"12345").unwrap();
+        headers.as_mut().remove(&WarcHeader::ContentLength);
+        writer.write_raw(headers, b"").unwrap();
+        drop(writer);
+
+        let records: Vec<_> = WarcReader::new(&buffer[..])
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].body(), b"12345");
+        assert_eq!(records[0].content_length(), 5);
+        assert_eq!(records[1].content_length(), 0);
+    }
+
+    #[test]
+    fn write_streaming() {
+        use std::io::Read;
+
+        let mut header = Record::<BufferedBody>::default().strip_body();
+        header.set_warc_version("1.0");
+        let mut source = &b"streamed body"[..];
+        let mut len = 13;
+        let mut record = header
+            .clone()
+            .add_fixed_stream(&mut source, &mut len)
+            .unwrap();
+        let mut prefix = [0; 8];
+        record.body_mut().read_exact(&mut prefix).unwrap();
+        assert_eq!(record.body_mut().remaining(), 5);
+        assert_eq!(record.content_length(), 13);
+
+        let mut rest = &b"streamed body"[..];
+        let mut len = 13;
+        let mut short = &b"short"[..];
+        let mut short_len = 13;
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        let written = writer
+            .write_streaming(
+                header
+                    .clone()
+                    .add_fixed_stream(&mut rest, &mut len)
+                    .unwrap(),
+            )
+            .unwrap();
+        let error = writer
+            .write_streaming(
+                header
+                    .clone()
+                    .add_fixed_stream(&mut short, &mut short_len)
+                    .unwrap(),
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+        drop(writer);
+
+        let records: Vec<_> = WarcReader::new(&buffer[..written])
+            .iter_records()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].body(), b"streamed body");
+    }
+
+    #[test]
+    fn write_hook() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut first = Record::<BufferedBody>::with_body(&b"first"[..]);
+        first.set_warc_version("1.0");
+        first
+            .set_header(WarcHeader::BlockDigest, "sha1:AAAA")
+            .unwrap();
+        let mut second = Record::<BufferedBody>::with_body(&b"second"[..]);
+        second.set_warc_version("1.0");
+        let mut serialized = Vec::new();
+        WarcWriter::new(&mut serialized).write(&second).unwrap();
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut buffer = Vec::new();
+        let mut writer = WarcWriter::new(&mut buffer);
+        writer.set_file_name(Some("a.warc"));
+        writer.set_position(100);
+        let sink = Rc::clone(&events);
+        writer.set_write_hook(move |event| sink.borrow_mut().push(event.clone()));
+        let first_len = writer.write(&first).unwrap() as u64;
+        let second_len = writer.write_verbatim(&serialized).unwrap() as u64;
+        assert_eq!(writer.position(), 100 + first_len + second_len);
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].record_id, first.warc_id());
+        assert_eq!(events[0].record_type, "resource");
+        assert_eq!(events[0].file.as_deref(), Some("a.warc"));
+        assert_eq!((events[0].offset, events[0].length), (100, first_len));
+        assert_eq!(events[0].block_digest.as_deref(), Some("sha1:AAAA"));
+        assert_eq!(events[1].record_id, second.warc_id());
+        assert_eq!(events[1].offset, 100 + first_len);
+        assert_eq!(events[1].length, serialized.len() as u64);
+        assert_eq!(events[1].block_digest, None);
+
+        #[cfg(feature = "digest")]
+        {
+            use crate::digest::{Algorithm, Digest};
+
+            let digests = Rc::new(RefCell::new(Vec::new()));
+            let sink = Rc::clone(&digests);
+            let mut writer = WarcWriter::new(Vec::new());
+            writer.set_event_digest(Some(Algorithm::Sha1));
+            writer.set_write_hook(move |event| sink.borrow_mut().push(event.block_digest.clone()));
+            writer.write(&second).unwrap();
+            writer.write_verbatim(&serialized).unwrap();
+            for digest in digests.borrow().iter() {
+                assert!(Digest::parse(digest.as_ref().unwrap())
+                    .unwrap()
+                    .matches(b"second"));
+            }
+        }
     }
 }
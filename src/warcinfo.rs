@@ -0,0 +1,278 @@
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Error, Record, RecordType};
+
+const CONFORMS_TO_1_0: &str = "http://bibnum.bnf.fr/WARC/WARC_ISO_28500_version1_latestdraft.pdf";
+const CONFORMS_TO_1_1: &str =
+    "https://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/";
+
+/// The layout of the warcinfo records written by a well-known crawler.
+///
+/// A preset decides the WARC version of the record, the `format` and `conformsTo` fields, and the
+/// order in which fields appear, so that tools which pattern-match on the output of that crawler
+/// accept the records.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum WarcinfoPreset {
+    /// The layout written by Heritrix 3.
+    #[default]
+    Heritrix,
+    /// The layout written by GNU Wget.
+    Wget,
+    /// The layout written by Browsertrix Crawler.
+    Browsertrix,
+}
+
+impl WarcinfoPreset {
+    /// Return the WARC version of the records written with this preset.
+    pub fn warc_version(self) -> &'static str {
+        match self {
+            WarcinfoPreset::Heritrix | WarcinfoPreset::Wget => "1.0",
+            WarcinfoPreset::Browsertrix => "1.1",
+        }
+    }
+
+    fn field_order(self) -> &'static [&'static str] {
+        match self {
+            WarcinfoPreset::Heritrix => &[
+                "software",
+                "hostname",
+                "ip",
+                "operator",
+                "description",
+                "robots",
+                "http-header-user-agent",
+                "http-header-from",
+                "format",
+                "conformsTo",
+                "isPartOf",
+            ],
+            WarcinfoPreset::Wget => &[
+                "software",
+                "format",
+                "conformsTo",
+                "robots",
+                "wget-arguments",
+            ],
+            WarcinfoPreset::Browsertrix => &["software", "format", "isPartOf", "creator"],
+        }
+    }
+
+    fn has_conforms_to(self) -> bool {
+        self != WarcinfoPreset::Browsertrix
+    }
+}
+
+/// The fields of a warcinfo record describing the software and crawl which produced a file.
+///
+/// Fields are rendered as `application/warc-fields` according to a `WarcinfoPreset`, either with
+/// `into_record` or by a `WarcWriter` configured with a preset. Fields the preset does not know
+/// about are written after the known fields, in the order they were set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WarcinfoBuilder {
+    pub(crate) filename: Option<String>,
+    date: Option<DateTime<Utc>>,
+    fields: Vec<(String, String)>,
+}
+
+impl WarcinfoBuilder {
+    /// Create an empty set of fields.
+    pub fn new() -> Self {
+        WarcinfoBuilder::default()
+    }
+
+    /// Set the WARC-Filename of the record.
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filename = Some(filename.into());
+
+        self
+    }
+
+    /// Set the WARC-Date of the record. By default, the record is dated when it is built.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Set a field, replacing any earlier value.
+    pub fn field<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.fields.iter_mut().find(|(key, _)| *key == name) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((name, value)),
+        }
+
+        self
+    }
+
+    /// Set the `software` field.
+    pub fn software<S: Into<String>>(self, software: S) -> Self {
+        self.field("software", software)
+    }
+
+    /// Set the `hostname` field.
+    pub fn hostname<S: Into<String>>(self, hostname: S) -> Self {
+        self.field("hostname", hostname)
+    }
+
+    /// Set the `ip` field.
+    pub fn ip<S: Into<String>>(self, ip: S) -> Self {
+        self.field("ip", ip)
+    }
+
+    /// Set the `operator` field.
+    pub fn operator<S: Into<String>>(self, operator: S) -> Self {
+        self.field("operator", operator)
+    }
+
+    /// Set the `description` field.
+    pub fn description<S: Into<String>>(self, description: S) -> Self {
+        self.field("description", description)
+    }
+
+    /// Set the `robots` field, such as `obey` or `ignore`.
+    pub fn robots<S: Into<String>>(self, robots: S) -> Self {
+        self.field("robots", robots)
+    }
+
+    /// Set the `http-header-user-agent` field.
+    pub fn user_agent<S: Into<String>>(self, user_agent: S) -> Self {
+        self.field("http-header-user-agent", user_agent)
+    }
+
+    /// Set the `isPartOf` field, naming the crawl or collection.
+    pub fn is_part_of<S: Into<String>>(self, is_part_of: S) -> Self {
+        self.field("isPartOf", is_part_of)
+    }
+
+    /// Render the fields as an `application/warc-fields` body laid out by the preset.
+    pub fn to_body(&self, preset: WarcinfoPreset) -> Vec<u8> {
+        let version = preset.warc_version();
+        let mut fields = self.fields.clone();
+        let mut defaults = vec![("format", format!("WARC File Format {}", version))];
+        if preset.has_conforms_to() {
+            let conforms_to = match version {
+                "1.0" => CONFORMS_TO_1_0,
+                _ => CONFORMS_TO_1_1,
+            };
+            defaults.push(("conformsTo", conforms_to.to_owned()));
+        }
+        for (name, value) in defaults {
+            if !fields.iter().any(|(key, _)| key == name) {
+                fields.push((name.to_owned(), value));
+            }
+        }
+
+        let order = preset.field_order();
+        fields.sort_by_key(|(key, _)| {
+            order
+                .iter()
+                .position(|name| name == key)
+                .unwrap_or(order.len())
+        });
+
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+
+        body
+    }
+
+    /// Build the warcinfo record holding these fields, laid out by the preset.
+    ///
+    /// An error is returned if the WARC-Filename holds a control character, such as a line break.
+    pub fn into_record(self, preset: WarcinfoPreset) -> Result<Record<BufferedBody>, Error> {
+        let body = self.to_body(preset);
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_version(preset.warc_version());
+        record.set_warc_type(RecordType::WarcInfo);
+        if let Some(date) = self.date {
+            record.set_date(date);
+        }
+        if let Some(filename) = self.filename {
+            if filename.chars().any(char::is_control) {
+                return Err(Error::MalformedHeader(
+                    WarcHeader::Filename,
+                    "control character in filename".to_string(),
+                ));
+            }
+            record.set_header(WarcHeader::Filename, filename)?;
+        }
+        record.set_header(WarcHeader::ContentType, "application/warc-fields")?;
+        record.replace_body(body);
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WarcinfoBuilder, WarcinfoPreset};
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    fn fields() -> WarcinfoBuilder {
+        WarcinfoBuilder::new()
+            .field("extra", "1")
+            .is_part_of("weekly")
+            .robots("obey")
+            .software("crawler/1.0")
+            .hostname("crawl01")
+    }
+
+    #[test]
+    fn heritrix() {
+        let record = fields()
+            .filename("weekly-00000.warc.gz")
+            .into_record(WarcinfoPreset::Heritrix)
+            .unwrap();
+
+        assert_eq!(record.warc_type(), &RecordType::WarcInfo);
+        assert_eq!(record.warc_version(), "1.0");
+        assert_eq!(
+            record.header(WarcHeader::Filename).unwrap(),
+            "weekly-00000.warc.gz"
+        );
+        assert_eq!(
+            String::from_utf8(record.body().to_vec()).unwrap(),
+            "software: crawler/1.0\r\n\
+             hostname: crawl01\r\n\
+             robots: obey\r\n\
+             format: WARC File Format 1.0\r\n\
+             conformsTo: http://bibnum.bnf.fr/WARC/WARC_ISO_28500_version1_latestdraft.pdf\r\n\
+             isPartOf: weekly\r\n\
+             extra: 1\r\n"
+        );
+    }
+
+    #[test]
+    fn browsertrix() {
+        let record = fields()
+            .field("format", "custom")
+            .into_record(WarcinfoPreset::Browsertrix)
+            .unwrap();
+
+        assert_eq!(record.warc_version(), "1.1");
+        assert_eq!(
+            String::from_utf8(record.body().to_vec()).unwrap(),
+            "software: crawler/1.0\r\n\
+             format: custom\r\n\
+             isPartOf: weekly\r\n\
+             extra: 1\r\n\
+             robots: obey\r\n\
+             hostname: crawl01\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_filename() {
+        let result = fields()
+            .filename("a.warc\r\nWARC-Type: resource")
+            .into_record(WarcinfoPreset::Heritrix);
+
+        assert!(result.is_err());
+    }
+}
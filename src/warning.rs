@@ -0,0 +1,85 @@
+use std::fmt;
+
+use crate::header::WarcHeader;
+
+/// A non-fatal problem found while reading a record.
+///
+/// Warnings are only produced when a `ParserConfig` allows the reader to accept and repair data
+/// which does not conform to the standard.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// A header value was not well-formed, and was replaced by a normalized value.
+    NormalizedHeader {
+        header: WarcHeader,
+        original: String,
+        normalized: String,
+    },
+    /// A header required by the standard was missing, and a default value was used.
+    MissingHeader(WarcHeader),
+    /// The input ended before the end of the record's body.
+    TruncatedBody { expected: u64, actual: u64 },
+    /// The record's body was not followed by the record separator. Any data between the declared
+    /// end of the body and the next record was discarded.
+    UnterminatedBody,
+    /// The record's body was followed by the given separator instead of two CRLF sequences.
+    NonstandardSeparator(String),
+    /// Data which was not part of any record was found before the first record, and skipped.
+    SkippedLeadingBytes(u64),
+    /// A header which may only appear once was repeated, and only one of its values was kept.
+    DuplicateHeader(WarcHeader),
+    /// A header line with an empty name or without a colon was dropped.
+    MalformedHeaderLine(String),
+    /// The record is truncated, but the given digest header does not match the content stored,
+    /// and so presumably covers the content before truncation.
+    TruncatedDigest(WarcHeader),
+    /// The record was a revisit, and was reconstructed as a response using the payload of the
+    /// record with the given ID.
+    Reconstructed(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::NormalizedHeader {
+                ref header,
+                ref original,
+                ref normalized,
+            } => write!(
+                f,
+                "Normalized header: {}: {:?} -> {:?}",
+                header, original, normalized
+            ),
+            Warning::MissingHeader(ref h) => write!(f, "Missing required header: {}", h),
+            Warning::TruncatedBody { expected, actual } => write!(
+                f,
+                "Truncated body: expected {} bytes, found {}",
+                expected, actual
+            ),
+            Warning::UnterminatedBody => write!(f, "Body not followed by record separator."),
+            Warning::NonstandardSeparator(ref separator) => {
+                write!(f, "Nonstandard record separator: {:?}", separator)
+            }
+            Warning::SkippedLeadingBytes(len) => {
+                write!(f, "Skipped {} bytes before the first record.", len)
+            }
+            Warning::DuplicateHeader(ref h) => write!(f, "Duplicate header: {}", h),
+            Warning::MalformedHeaderLine(ref line) => {
+                write!(f, "Dropped malformed header line: {:?}", line)
+            }
+            Warning::TruncatedDigest(ref h) => {
+                write!(
+                    f,
+                    "Digest of truncated record covers the original content: {}",
+                    h
+                )
+            }
+            Warning::Reconstructed(ref id) => {
+                write!(
+                    f,
+                    "Reconstructed from a revisit using the payload of {}",
+                    id
+                )
+            }
+        }
+    }
+}
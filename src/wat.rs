@@ -0,0 +1,441 @@
+//! Generation of WAT files, which hold metadata of captured pages as JSON, in the form published
+//! by Common Crawl.
+//!
+//! Each response record is converted to a `metadata` record holding a JSON envelope describing
+//! it: where it was read from, its WARC headers, the status line and headers of its HTTP response
+//! and, for HTML pages, the title, meta tags and links of the page. The metadata record keeps the
+//! WARC-Target-URI and WARC-Date of the response, and refers to it by WARC-Refers-To. As in Common
+//! Crawl WAT files, numbers are given as strings.
+//!
+//! ```
+//! use warc::header::WarcHeader;
+//! use warc::wat::Wat;
+//! use warc::{BufferedBody, Record, RecordType};
+//!
+//! let mut response = Record::<BufferedBody>::with_body(
+//!     &b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<title>Hi</title>"[..],
+//! );
+//! response.set_warc_type(RecordType::Response);
+//! response
+//!     .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+//!     .unwrap();
+//!
+//! let envelope = Wat::new().envelope(&response, None);
+//! let http = &envelope["Envelope"]["Payload-Metadata"]["HTTP-Response-Metadata"];
+//! assert_eq!(http["Response-Message"]["Status"], "200");
+//! assert_eq!(http["HTML-Metadata"]["Head"]["Title"], "Hi");
+//! ```
+
+use std::io::{BufRead, Write};
+
+use serde_json::{Map, Value};
+
+use crate::extract::{html_payload, outlinks};
+use crate::{html, http, BufferedBody, Error, HttpHead, Record, RecordType};
+use crate::{WarcReader, WarcWriter, WarcinfoBuilder};
+
+use crate::header::WarcHeader;
+
+/// The outcome of a `Wat` pass.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WatReport {
+    /// The number of records read.
+    pub records: usize,
+    /// The number of metadata records written, not counting the warcinfo record.
+    pub converted: usize,
+}
+
+/// A pass which describes captured pages in metadata records.
+#[derive(Clone, Debug, Default)]
+pub struct Wat {
+    warcinfo: WarcinfoBuilder,
+    filename: Option<String>,
+}
+
+impl Wat {
+    /// Create a pass which writes a metadata record for each response record.
+    pub fn new() -> Self {
+        Wat::default()
+    }
+
+    /// Set the fields of the warcinfo record written before the metadata records.
+    pub fn warcinfo(mut self, warcinfo: WarcinfoBuilder) -> Self {
+        self.warcinfo = warcinfo;
+
+        self
+    }
+
+    /// Set the name of the file the records are read from, given as the `Filename` of each
+    /// envelope's `Container`.
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filename = Some(filename.into());
+
+        self
+    }
+
+    /// Return the JSON envelope describing a record, read from the given offset of the input if
+    /// it is known.
+    pub fn envelope(&self, record: &Record<BufferedBody>, offset: Option<u64>) -> Value {
+        let mut container = Map::new();
+        if let Some(ref filename) = self.filename {
+            container.insert("Filename".to_owned(), Value::from(filename.as_str()));
+        }
+        if let Some(offset) = offset {
+            container.insert("Offset".to_owned(), Value::from(offset.to_string()));
+        }
+
+        let mut warc_headers = Map::new();
+        if let Ok(headers) = record.header_block(None) {
+            for (header, value) in headers.as_ref() {
+                warc_headers.insert(
+                    header.canonical_name(),
+                    Value::from(String::from_utf8_lossy(value)),
+                );
+            }
+        }
+
+        let mut payload = Map::new();
+        let content_type = record.header(WarcHeader::ContentType);
+        if let Some(ref content_type) = content_type {
+            payload.insert(
+                "Actual-Content-Type".to_owned(),
+                Value::from(content_type.as_ref()),
+            );
+        }
+        let is_http = content_type.is_some_and(|ct| http::is_http_message(&ct));
+        if is_http && *record.warc_type() == RecordType::Response {
+            if let Some(head) = record.http_headers() {
+                payload.insert(
+                    "HTTP-Response-Metadata".to_owned(),
+                    response_metadata(record, &head),
+                );
+            }
+        }
+
+        let mut envelope = Map::new();
+        envelope.insert("Format".to_owned(), Value::from("WARC"));
+        envelope.insert(
+            "Actual-Content-Length".to_owned(),
+            Value::from(record.content_length().to_string()),
+        );
+        if let Some(digest) = record.header(WarcHeader::BlockDigest) {
+            envelope.insert("Block-Digest".to_owned(), Value::from(digest.as_ref()));
+        }
+        envelope.insert(
+            "WARC-Header-Metadata".to_owned(),
+            Value::Object(warc_headers),
+        );
+        envelope.insert("Payload-Metadata".to_owned(), Value::Object(payload));
+
+        let mut object = Map::new();
+        object.insert("Container".to_owned(), Value::Object(container));
+        object.insert("Envelope".to_owned(), Value::Object(envelope));
+
+        Value::Object(object)
+    }
+
+    /// Convert a response record to a metadata record holding its envelope, read from the given
+    /// offset of the input if it is known.
+    ///
+    /// `None` is returned if the record is not a response record.
+    pub fn convert(
+        &self,
+        record: &Record<BufferedBody>,
+        offset: Option<u64>,
+    ) -> Option<Record<BufferedBody>> {
+        if *record.warc_type() != RecordType::Response {
+            return None;
+        }
+
+        let mut metadata = Record::<BufferedBody>::default();
+        metadata.set_warc_type(RecordType::Metadata);
+        metadata.set_date(*record.date());
+        if let Some(target_uri) = record.header(WarcHeader::TargetURI) {
+            metadata
+                .set_header(WarcHeader::TargetURI, target_uri)
+                .ok()?;
+        }
+        metadata
+            .set_header(WarcHeader::RefersTo, record.warc_id())
+            .ok()?;
+        metadata
+            .set_header(WarcHeader::ContentType, "application/json")
+            .ok()?;
+        metadata.replace_body(self.envelope(record, offset).to_string());
+
+        Some(metadata)
+    }
+
+    /// Read every record from the reader, and write a metadata record for each response record.
+    ///
+    /// A warcinfo record is written first. The offset of each record is its offset in the input
+    /// as read, after any decompression.
+    pub fn run<R: BufRead, W: Write>(
+        &self,
+        reader: WarcReader<R>,
+        writer: &mut WarcWriter<W>,
+    ) -> Result<WatReport, Error> {
+        writer
+            .write_warcinfo(&self.warcinfo)
+            .map_err(|_| Error::WriteData)?;
+
+        let mut report = WatReport::default();
+        for item in reader.iter_positioned_records() {
+            let (position, record) = item?;
+            report.records += 1;
+            if let Some(metadata) = self.convert(&record, Some(position.offset)) {
+                writer.write(&metadata).map_err(|_| Error::WriteData)?;
+                report.converted += 1;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Describe the HTTP response held by a record, and the HTML page it carries if it is one.
+fn response_metadata(record: &Record<BufferedBody>, head: &HttpHead) -> Value {
+    let mut parts = head.start_line.splitn(3, ' ');
+    let mut message = Map::new();
+    for key in &["Version", "Status", "Reason"] {
+        if let Some(part) = parts.next() {
+            message.insert((*key).to_owned(), Value::from(part));
+        }
+    }
+
+    let mut headers = Map::new();
+    for (name, value) in &head.headers {
+        match headers.get_mut(name) {
+            Some(Value::Array(values)) => values.push(Value::from(value.as_str())),
+            Some(previous) => {
+                *previous = Value::Array(vec![previous.take(), Value::from(value.as_str())])
+            }
+            None => {
+                headers.insert(name.clone(), Value::from(value.as_str()));
+            }
+        }
+    }
+
+    let mut metadata = Map::new();
+    metadata.insert("Response-Message".to_owned(), Value::Object(message));
+    metadata.insert("Headers".to_owned(), Value::Object(headers));
+    if let Some((head, entity)) = http::split_head(record.body()) {
+        metadata.insert(
+            "Headers-Length".to_owned(),
+            Value::from(head.len().to_string()),
+        );
+        metadata.insert(
+            "Entity-Length".to_owned(),
+            Value::from(entity.len().to_string()),
+        );
+    }
+    if let Some(document) = html_payload(record, true) {
+        metadata.insert("HTML-Metadata".to_owned(), html_metadata(record, &document));
+    }
+
+    Value::Object(metadata)
+}
+
+/// Describe the title, meta tags and links of an HTML page.
+fn html_metadata(record: &Record<BufferedBody>, document: &str) -> Value {
+    let tags = html::tags(document);
+
+    let mut head = Map::new();
+    if let Some(title) = html::title(document, &tags) {
+        head.insert("Title".to_owned(), Value::from(title));
+    }
+    let metas: Vec<Value> = tags
+        .iter()
+        .filter(|tag| tag.name == "meta")
+        .map(|tag| {
+            let attributes = tag
+                .attributes
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+                .collect();
+            Value::Object(attributes)
+        })
+        .collect();
+    if !metas.is_empty() {
+        head.insert("Metas".to_owned(), Value::Array(metas));
+    }
+
+    let mut metadata = Map::new();
+    metadata.insert("Head".to_owned(), Value::Object(head));
+    metadata.insert("Links".to_owned(), outlinks(record, &tags));
+
+    Value::Object(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use serde_json::{json, Value};
+
+    use super::{Wat, WatReport};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    #[test]
+    fn metadata_records() {
+        let mut page = Record::<BufferedBody>::with_body(
+            &b"HTTP/1.1 200 OK\r\n\
+              Content-Type: text/html\r\n\
+              Set-Cookie: a=1\r\n\
+              Set-Cookie: b=2\r\n\
+              \r\n\
+              <html><head><title>Page</title>\
+              <meta name=\"description\" content=\"About\"></head>\
+              <body><a href=\"/next\">next</a></body></html>"[..],
+        );
+        page.set_warc_type(RecordType::Response);
+        page.set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        page.set_header(WarcHeader::TargetURI, "http://example.com/a")
+            .unwrap();
+
+        let wat = Wat::new().filename("example.warc");
+        let envelope = wat.envelope(&page, Some(42));
+        assert_eq!(
+            envelope["Container"],
+            json!({"Filename": "example.warc", "Offset": "42"})
+        );
+        let warc_headers = &envelope["Envelope"]["WARC-Header-Metadata"];
+        assert_eq!(warc_headers["WARC-Type"], "response");
+        assert_eq!(warc_headers["WARC-Target-URI"], "http://example.com/a");
+        let http = &envelope["Envelope"]["Payload-Metadata"]["HTTP-Response-Metadata"];
+        assert_eq!(
+            http["Response-Message"],
+            json!({"Version": "HTTP/1.1", "Status": "200", "Reason": "OK"})
+        );
+        assert_eq!(http["Headers"]["Set-Cookie"], json!(["a=1", "b=2"]));
+        let html = &http["HTML-Metadata"];
+        assert_eq!(html["Head"]["Title"], "Page");
+        assert_eq!(
+            html["Head"]["Metas"],
+            json!([{"name": "description", "content": "About"}])
+        );
+        assert_eq!(
+            html["Links"],
+            json!([{"path": "A@/href", "url": "http://example.com/next"}])
+        );
+
+        let metadata = wat.convert(&page, None).unwrap();
+        assert_eq!(metadata.warc_type(), &RecordType::Metadata);
+        assert_eq!(
+            metadata.header(WarcHeader::RefersTo).unwrap(),
+            page.warc_id()
+        );
+        let body: Value = serde_json::from_slice(metadata.body()).unwrap();
+        assert_eq!(body["Envelope"]["Format"], "WARC");
+        assert!(wat
+            .convert(&Record::<BufferedBody>::default(), None)
+            .is_none());
+
+        let mut input = Vec::new();
+        let mut writer = WarcWriter::new(&mut input);
+        let offset = writer.write(&Record::<BufferedBody>::default()).unwrap();
+        writer.write(&page).unwrap();
+        drop(writer);
+        let mut output = Vec::new();
+        let mut writer = WarcWriter::new(&mut output);
+        let report = wat
+            .run(WarcReader::new(BufReader::new(&input[..])), &mut writer)
+            .unwrap();
+        drop(writer);
+        assert_eq!(
+            report,
+            WatReport {
+                records: 2,
+                converted: 1
+            }
+        );
+        let bodies: Vec<Value> = WarcReader::new(BufReader::new(&output[..]))
+            .iter_records()
+            .skip(1)
+            .map(|record| serde_json::from_slice(record.unwrap().body()).unwrap())
+            .collect();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["Container"]["Offset"], offset.to_string());
+        assert_eq!(
+            bodies[0]["Envelope"]["WARC-Header-Metadata"]["WARC-Record-ID"],
+            page.warc_id()
+        );
+    }
+
+    #[test]
+    fn non_html_response() {
+        let mut image = Record::<BufferedBody>::with_body(
+            &b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n\x89PNG"[..],
+        );
+        image.set_warc_type(RecordType::Response);
+        image
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        image
+            .set_header(WarcHeader::TargetURI, "http://example.com/a.png")
+            .unwrap();
+
+        let envelope = Wat::new().envelope(&image, None);
+        assert_eq!(envelope["Container"], json!({}));
+        assert_eq!(envelope["Envelope"]["Actual-Content-Length"], "48");
+        let payload = &envelope["Envelope"]["Payload-Metadata"];
+        assert_eq!(
+            payload["Actual-Content-Type"],
+            "application/http;msgtype=response"
+        );
+        assert_eq!(
+            payload["HTTP-Response-Metadata"],
+            json!({
+                "Response-Message": {"Version": "HTTP/1.1", "Status": "200", "Reason": "OK"},
+                "Headers": {"Content-Type": "image/png"},
+                "Headers-Length": "44",
+                "Entity-Length": "4",
+            })
+        );
+
+        let metadata = Wat::new().convert(&image, Some(7)).unwrap();
+        assert_eq!(
+            metadata.header(WarcHeader::TargetURI).unwrap(),
+            "http://example.com/a.png"
+        );
+        assert_eq!(metadata.date(), image.date());
+        assert_eq!(
+            metadata.header(WarcHeader::ContentType).unwrap(),
+            "application/json"
+        );
+        let body: Value = serde_json::from_slice(metadata.body()).unwrap();
+        assert_eq!(body, Wat::new().envelope(&image, Some(7)));
+    }
+
+    #[test]
+    fn non_response_record() {
+        let mut resource = Record::<BufferedBody>::with_body(&b"<title>Not parsed</title>"[..]);
+        resource.set_warc_type(RecordType::Resource);
+        resource
+            .set_header(WarcHeader::ContentType, "text/html")
+            .unwrap();
+        resource
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+
+        let wat = Wat::new().filename("example.warc");
+        assert!(wat.convert(&resource, Some(0)).is_none());
+
+        let envelope = wat.envelope(&resource, Some(0));
+        assert_eq!(
+            envelope["Container"],
+            json!({"Filename": "example.warc", "Offset": "0"})
+        );
+        assert_eq!(envelope["Envelope"]["Actual-Content-Length"], "25");
+        let warc_headers = &envelope["Envelope"]["WARC-Header-Metadata"];
+        assert_eq!(warc_headers["WARC-Type"], "resource");
+        assert_eq!(warc_headers["WARC-Target-URI"], "http://example.com/");
+        assert_eq!(warc_headers["Content-Length"], "25");
+        assert_eq!(
+            envelope["Envelope"]["Payload-Metadata"],
+            json!({"Actual-Content-Type": "text/html"})
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Records holding WebSocket message transcripts.
+//!
+//! A WebSocket connection is archived as a single resource record whose WARC-Target-URI uses the
+//! `ws` or `wss` scheme and whose body is a JSON-lines transcript with one message per line,
+//! wrapped here to fit:
+//!
+//! ```text
+//! {"direction":"sent","timestamp":"2020-01-01T00:00:00Z","opcode":"text","data":"hello"}
+//! {"direction":"received","timestamp":"2020-01-01T00:00:01Z","opcode":"binary","data":"AAE=",
+//!     "encoding":"base64"}
+//! ```
+//!
+//! Text payloads are stored as JSON strings; all other payloads are base64-encoded.
+
+use chrono::prelude::*;
+use data_encoding::BASE64;
+use serde::{Deserialize, Serialize};
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Error, Record, RecordType};
+
+/// The Content-Type of a WebSocket transcript record.
+pub const TRANSCRIPT_CONTENT_TYPE: &str = "application/x-websocket-transcript+jsonl";
+
+/// The side of the connection which sent a message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Sent by the client.
+    Sent,
+    /// Received from the server.
+    Received,
+}
+
+/// The kind of a WebSocket message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Opcode {
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+/// A single message of a WebSocket transcript.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub direction: Direction,
+    pub timestamp: DateTime<Utc>,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MessageLine {
+    direction: Direction,
+    timestamp: String,
+    opcode: Opcode,
+    data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+}
+
+impl Message {
+    /// Create a text message.
+    pub fn text<S: Into<String>>(direction: Direction, timestamp: DateTime<Utc>, text: S) -> Self {
+        Message {
+            direction,
+            timestamp,
+            opcode: Opcode::Text,
+            payload: text.into().into_bytes(),
+        }
+    }
+
+    /// Create a binary message.
+    pub fn binary<B: Into<Vec<u8>>>(
+        direction: Direction,
+        timestamp: DateTime<Utc>,
+        payload: B,
+    ) -> Self {
+        Message {
+            direction,
+            timestamp,
+            opcode: Opcode::Binary,
+            payload: payload.into(),
+        }
+    }
+
+    fn to_line(&self) -> Result<String, Error> {
+        let (data, encoding) = match (self.opcode, std::str::from_utf8(&self.payload)) {
+            (Opcode::Text, Ok(text)) => (text.to_owned(), None),
+            _ => (BASE64.encode(&self.payload), Some("base64".to_owned())),
+        };
+        let line = MessageLine {
+            direction: self.direction,
+            timestamp: self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            opcode: self.opcode,
+            data,
+            encoding,
+        };
+
+        serde_json::to_string(&line).map_err(|e| Error::MalformedBody(e.to_string()))
+    }
+
+    fn from_line(line: &str) -> Result<Self, Error> {
+        let line: MessageLine =
+            serde_json::from_str(line).map_err(|e| Error::MalformedBody(e.to_string()))?;
+        let timestamp = DateTime::parse_from_rfc3339(&line.timestamp)
+            .map_err(|e| Error::MalformedBody(format!("invalid timestamp: {}", e)))?
+            .with_timezone(&Utc);
+        let payload = match line.encoding.as_deref() {
+            None => line.data.into_bytes(),
+            Some("base64") => BASE64
+                .decode(line.data.as_bytes())
+                .map_err(|e| Error::MalformedBody(format!("invalid base64 data: {}", e)))?,
+            Some(other) => {
+                return Err(Error::MalformedBody(format!("unknown encoding: {}", other)))
+            }
+        };
+
+        Ok(Message {
+            direction: line.direction,
+            timestamp,
+            opcode: line.opcode,
+            payload,
+        })
+    }
+}
+
+/// The messages exchanged over a single WebSocket connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcript {
+    target_uri: String,
+    messages: Vec<Message>,
+}
+
+impl Transcript {
+    /// Create an empty transcript of a connection to the given `ws` or `wss` URI.
+    pub fn new<S: Into<String>>(target_uri: S) -> Self {
+        Transcript {
+            target_uri: target_uri.into(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Append a message to this transcript.
+    pub fn message(mut self, message: Message) -> Self {
+        self.messages.push(message);
+
+        self
+    }
+
+    /// Append a message to this transcript in place.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Return the target URI of the connection.
+    pub fn target_uri(&self) -> &str {
+        &self.target_uri
+    }
+
+    /// Return the messages of this transcript in order.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Build a resource record holding this transcript.
+    ///
+    /// The record is dated by its first message, or now if there are no messages.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a message cannot be serialized as a transcript line.
+    pub fn into_record(self) -> Result<Record<BufferedBody>, Error> {
+        let mut body = Vec::new();
+        for message in &self.messages {
+            body.extend_from_slice(message.to_line()?.as_bytes());
+            body.push(b'\n');
+        }
+
+        let mut record = Record::<BufferedBody>::default();
+        record.set_warc_type(RecordType::Resource);
+        if let Some(first) = self.messages.first() {
+            record.set_date(first.timestamp);
+        }
+        record
+            .set_header(WarcHeader::TargetURI, self.target_uri)
+            .unwrap();
+        record
+            .set_header(WarcHeader::ContentType, TRANSCRIPT_CONTENT_TYPE)
+            .unwrap();
+        record.replace_body(body);
+
+        Ok(record)
+    }
+
+    /// Read a transcript from a record.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the record is not a WebSocket transcript or if any line of the
+    /// transcript is not well-formed.
+    pub fn from_record(record: &Record<BufferedBody>) -> Result<Self, Error> {
+        if !is_transcript(record) {
+            return Err(Error::MalformedHeader(
+                WarcHeader::ContentType,
+                "not a WebSocket transcript".to_string(),
+            ));
+        }
+        let target_uri = record
+            .header(WarcHeader::TargetURI)
+            .ok_or(Error::MissingHeader(WarcHeader::TargetURI))?
+            .into_owned();
+        let body = std::str::from_utf8(record.body())
+            .map_err(|_| Error::MalformedBody("not a UTF-8 string".to_string()))?;
+        let messages = body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Message::from_line)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Transcript {
+            target_uri,
+            messages,
+        })
+    }
+}
+
+/// Return true if the record holds a WebSocket transcript.
+pub fn is_transcript<T: crate::record::BodyKind>(record: &Record<T>) -> bool {
+    record.warc_type() == &RecordType::Resource
+        && record
+            .header(WarcHeader::ContentType)
+            .map(|ct| {
+                ct.split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .eq_ignore_ascii_case(TRANSCRIPT_CONTENT_TYPE)
+            })
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_transcript, Direction, Message, Opcode, Transcript};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Error, Record, RecordType};
+
+    use chrono::prelude::*;
+
+    #[test]
+    fn round_trip() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let transcript = Transcript::new("wss://example.com/socket")
+            .message(Message::text(Direction::Sent, start, "hello \"world\""))
+            .message(Message::binary(
+                Direction::Received,
+                start + chrono::Duration::milliseconds(250),
+                vec![0, 1, 255],
+            ))
+            .message(Message {
+                direction: Direction::Sent,
+                timestamp: start + chrono::Duration::seconds(1),
+                opcode: Opcode::Close,
+                payload: vec![3, 232],
+            });
+
+        let record = transcript.clone().into_record().unwrap();
+        assert!(is_transcript(&record));
+        assert_eq!(record.warc_type(), &RecordType::Resource);
+        assert_eq!(record.date(), &start);
+        assert_eq!(
+            record.header(WarcHeader::TargetURI).unwrap(),
+            "wss://example.com/socket"
+        );
+        assert_eq!(
+            std::str::from_utf8(record.body()).unwrap().lines().next(),
+            Some(concat!(
+                r#"{"direction":"sent","timestamp":"2020-01-01T00:00:00Z","opcode":"text","#,
+                r#""data":"hello \"world\""}"#
+            ))
+        );
+
+        assert_eq!(Transcript::from_record(&record), Ok(transcript));
+    }
+
+    #[test]
+    fn invalid_transcripts() {
+        let record = Record::<BufferedBody>::default();
+        assert!(!is_transcript(&record));
+        assert!(Transcript::from_record(&record).is_err());
+
+        let mut record = Transcript::new("ws://example.com/").into_record().unwrap();
+        record.replace_body(&b"{\"direction\":\"sideways\"}\n"[..]);
+        match Transcript::from_record(&record) {
+            Err(Error::MalformedBody(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
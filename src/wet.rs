@@ -0,0 +1,279 @@
+//! Generation of WET files, which hold the text of captured pages, in the form published by
+//! Common Crawl.
+//!
+//! Each response record holding a successful HTTP response with an HTML or plain text payload is
+//! converted to a `conversion` record holding the text of the page. The conversion record keeps
+//! the WARC-Target-URI and WARC-Date of the response, and refers to it by WARC-Refers-To. The text
+//! of an HTML page has a line for each block of text, starting with the title.
+//!
+//! ```
+//! use warc::header::WarcHeader;
+//! use warc::wet::Wet;
+//! use warc::{BufferedBody, Record, RecordType};
+//!
+//! let mut response = Record::<BufferedBody>::with_body(
+//!     &b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<title>Hi</title><p>there</p>"[..],
+//! );
+//! response.set_warc_type(RecordType::Response);
+//! response
+//!     .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+//!     .unwrap();
+//!
+//! let conversion = Wet::new().convert(&response).unwrap();
+//! assert_eq!(conversion.warc_type(), &RecordType::Conversion);
+//! assert_eq!(conversion.body(), b"Hi\nthere");
+//! ```
+//!
+//! Text is only extracted from payloads without a content or transfer encoding, and is decoded
+//! as UTF-8.
+
+use std::io::{BufRead, Write};
+
+use crate::header::WarcHeader;
+use crate::{
+    html, BufferedBody, Error, Record, RecordType, WarcReader, WarcWriter, WarcinfoBuilder,
+};
+
+/// The outcome of a `Wet` pass.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WetReport {
+    /// The number of records read.
+    pub records: usize,
+    /// The number of conversion records written, not counting the warcinfo record.
+    pub converted: usize,
+}
+
+/// A pass which extracts the text of captured pages into conversion records.
+#[derive(Clone, Debug)]
+pub struct Wet {
+    warcinfo: WarcinfoBuilder,
+    #[cfg(feature = "digest")]
+    block_digest: Option<crate::digest::Algorithm>,
+}
+
+impl Default for Wet {
+    fn default() -> Self {
+        Wet::new()
+    }
+}
+
+impl Wet {
+    /// Create a pass which writes conversion records without digests.
+    pub fn new() -> Self {
+        Wet {
+            warcinfo: WarcinfoBuilder::new(),
+            #[cfg(feature = "digest")]
+            block_digest: None,
+        }
+    }
+
+    /// Set the fields of the warcinfo record written before the conversion records.
+    pub fn warcinfo(mut self, warcinfo: WarcinfoBuilder) -> Self {
+        self.warcinfo = warcinfo;
+
+        self
+    }
+
+    /// Give each conversion record a WARC-Block-Digest computed with the algorithm. Common Crawl
+    /// uses SHA-1.
+    #[cfg(feature = "digest")]
+    pub fn block_digest(mut self, algorithm: crate::digest::Algorithm) -> Self {
+        self.block_digest = Some(algorithm);
+
+        self
+    }
+
+    /// Convert a response record to a conversion record holding the text of its payload.
+    ///
+    /// `None` is returned unless the record is a response record holding an HTTP response with a
+    /// 2xx status, whose payload is HTML or plain text, has no content or transfer encoding, and
+    /// has some text.
+    pub fn convert(&self, record: &Record<BufferedBody>) -> Option<Record<BufferedBody>> {
+        if *record.warc_type() != RecordType::Response {
+            return None;
+        }
+        let head = record.http_headers()?;
+        if !head
+            .status()
+            .is_some_and(|status| (200..300).contains(&status))
+        {
+            return None;
+        }
+        let identity = |name: &str| {
+            head.header(name)
+                .is_none_or(|value| value.trim().eq_ignore_ascii_case("identity"))
+        };
+        if !identity("content-encoding") || !identity("transfer-encoding") {
+            return None;
+        }
+
+        let media_type = head
+            .header("content-type")
+            .and_then(|value| value.split(';').next())
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        let payload = String::from_utf8_lossy(record.http_payload()?);
+        let text = match media_type.as_str() {
+            "text/html" | "application/xhtml+xml" => html::text(&payload),
+            "text/plain" => payload.trim().to_owned(),
+            _ => return None,
+        };
+        if text.is_empty() {
+            return None;
+        }
+
+        let mut conversion = Record::<BufferedBody>::default();
+        conversion.set_warc_type(RecordType::Conversion);
+        conversion.set_date(*record.date());
+        if let Some(target_uri) = record.header(WarcHeader::TargetURI) {
+            conversion
+                .set_header(WarcHeader::TargetURI, target_uri)
+                .ok()?;
+        }
+        conversion
+            .set_header(WarcHeader::RefersTo, record.warc_id())
+            .ok()?;
+        #[cfg(feature = "digest")]
+        {
+            if let Some(algorithm) = self.block_digest {
+                conversion
+                    .set_header(
+                        WarcHeader::BlockDigest,
+                        algorithm.compute(text.as_bytes()).to_string(),
+                    )
+                    .ok()?;
+            }
+        }
+        conversion
+            .set_header(WarcHeader::ContentType, "text/plain")
+            .ok()?;
+        conversion.replace_body(text);
+
+        Some(conversion)
+    }
+
+    /// Read every record from the reader, and write a conversion record for each one which can
+    /// be converted.
+    ///
+    /// A warcinfo record is written first.
+    pub fn run<R: BufRead, W: Write>(
+        &self,
+        reader: WarcReader<R>,
+        writer: &mut WarcWriter<W>,
+    ) -> Result<WetReport, Error> {
+        writer
+            .write_warcinfo(&self.warcinfo)
+            .map_err(|_| Error::WriteData)?;
+
+        let mut report = WetReport::default();
+        for record in reader.iter_records() {
+            let record = record?;
+            report.records += 1;
+            if let Some(conversion) = self.convert(&record) {
+                writer.write(&conversion).map_err(|_| Error::WriteData)?;
+                report.converted += 1;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::{Wet, WetReport};
+    use crate::header::WarcHeader;
+    use crate::{BufferedBody, Record, RecordType, WarcReader, WarcWriter};
+
+    fn response(head: &str, payload: &str) -> Record<BufferedBody> {
+        let mut record =
+            Record::<BufferedBody>::with_body(format!("HTTP/1.1 {}\r\n\r\n{}", head, payload));
+        record.set_warc_type(RecordType::Response);
+        record
+            .set_header(WarcHeader::ContentType, "application/http;msgtype=response")
+            .unwrap();
+        record
+            .set_header(WarcHeader::TargetURI, "http://example.com/")
+            .unwrap();
+        record
+    }
+
+    #[test]
+    fn conversion_records() {
+        let page = response(
+            "200 OK\r\nContent-Type: text/html; charset=utf-8",
+            "<html><head><title>Page</title><style>p {}</style></head>\
+             <body><h1>Heading</h1><p>Some <i>text</i>.</p></body></html>",
+        );
+        let conversion = Wet::new().convert(&page).unwrap();
+        assert_eq!(conversion.warc_type(), &RecordType::Conversion);
+        assert_eq!(conversion.body(), b"Page\nHeading\nSome text.");
+        assert_eq!(
+            conversion.header(WarcHeader::RefersTo).unwrap(),
+            page.warc_id()
+        );
+        assert_eq!(
+            conversion.header(WarcHeader::TargetURI).unwrap(),
+            "http://example.com/"
+        );
+        assert_eq!(conversion.date(), page.date());
+        assert_eq!(
+            conversion.header(WarcHeader::ContentType).unwrap(),
+            "text/plain"
+        );
+
+        let skipped = [
+            response("404 Not Found\r\nContent-Type: text/html", "<p>gone</p>"),
+            response("200 OK\r\nContent-Type: image/png", "png"),
+            response(
+                "200 OK\r\nContent-Type: text/html\r\nContent-Encoding: gzip",
+                "\x1f",
+            ),
+            response("200 OK\r\nContent-Type: text/html", "<p> </p>"),
+        ];
+        for record in &skipped {
+            assert!(Wet::new().convert(record).is_none());
+        }
+
+        let mut input = Vec::new();
+        let mut writer = WarcWriter::new(&mut input);
+        writer.write(&page).unwrap();
+        writer.write(&skipped[0]).unwrap();
+        drop(writer);
+        let mut output = Vec::new();
+        let mut writer = WarcWriter::new(&mut output);
+        let report = Wet::new()
+            .run(WarcReader::new(BufReader::new(&input[..])), &mut writer)
+            .unwrap();
+        drop(writer);
+        assert_eq!(
+            report,
+            WetReport {
+                records: 2,
+                converted: 1
+            }
+        );
+        let types: Vec<_> = WarcReader::new(BufReader::new(&output[..]))
+            .iter_records()
+            .map(|record| record.unwrap().warc_type().clone())
+            .collect();
+        assert_eq!(types, vec![RecordType::WarcInfo, RecordType::Conversion]);
+
+        #[cfg(feature = "digest")]
+        {
+            use crate::digest::Algorithm;
+
+            let conversion = Wet::new()
+                .block_digest(Algorithm::Sha1)
+                .convert(&page)
+                .unwrap();
+            assert!(conversion
+                .header(WarcHeader::BlockDigest)
+                .unwrap()
+                .starts_with("sha1:"));
+        }
+    }
+}
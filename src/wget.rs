@@ -0,0 +1,152 @@
+use chrono::prelude::*;
+
+use crate::header::WarcHeader;
+use crate::{BufferedBody, Record, RecordType};
+
+const MANIFEST_URI: &str = "metadata://gnu.org/software/wget/warc/MANIFEST.txt";
+const ARGUMENTS_URI: &str = "metadata://gnu.org/software/wget/warc/wget_arguments.txt";
+const LOG_URI: &str = "metadata://gnu.org/software/wget/warc/wget.log";
+
+/// The order in which wget writes the headers of a record.
+///
+/// Headers not listed are written after the payload digest, ordered by name.
+pub(crate) const HEADER_ORDER: &[WarcHeader] = &[
+    WarcHeader::WarcType,
+    WarcHeader::TargetURI,
+    WarcHeader::Date,
+    WarcHeader::RecordID,
+    WarcHeader::WarcInfoID,
+    WarcHeader::ConcurrentTo,
+    WarcHeader::IPAddress,
+    WarcHeader::ContentType,
+    WarcHeader::Filename,
+    WarcHeader::BlockDigest,
+    WarcHeader::PayloadDigest,
+];
+
+/// The records wget writes at the end of a crawl.
+///
+/// These are a metadata record listing the WARC files written, followed by resource records
+/// holding the command line arguments and the log of the crawl, each concurrent to the manifest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WgetMetadata {
+    warc_files: Vec<String>,
+    arguments: Vec<String>,
+    log: Vec<u8>,
+    date: Option<DateTime<Utc>>,
+}
+
+impl WgetMetadata {
+    /// Create an empty set of crawl metadata.
+    pub fn new() -> Self {
+        WgetMetadata::default()
+    }
+
+    /// Add the name of a WARC file written by the crawl to the manifest.
+    pub fn warc_file<S: Into<String>>(mut self, filename: S) -> Self {
+        self.warc_files.push(filename.into());
+
+        self
+    }
+
+    /// Add a command line argument of the crawl, starting with the program name.
+    pub fn argument<S: Into<String>>(mut self, argument: S) -> Self {
+        self.arguments.push(argument.into());
+
+        self
+    }
+
+    /// Set the log of the crawl.
+    pub fn log<B: Into<Vec<u8>>>(mut self, log: B) -> Self {
+        self.log = log.into();
+
+        self
+    }
+
+    /// Set the date of the records. By default, the records are dated when they are built.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.date = Some(date);
+
+        self
+    }
+
+    /// Build the manifest, arguments, and log records, in the order wget writes them.
+    pub fn into_records(self, warcinfo_id: Option<&str>) -> Vec<Record<BufferedBody>> {
+        let date = self.date.unwrap_or_else(Utc::now);
+        let record = |record_type, uri: &str, body: Vec<u8>| {
+            let mut record = Record::<BufferedBody>::default();
+            record.set_warc_version("1.0");
+            record.set_warc_type(record_type);
+            record.set_date(date);
+            record.set_header(WarcHeader::TargetURI, uri).unwrap();
+            record
+                .set_header(WarcHeader::ContentType, "text/plain")
+                .unwrap();
+            if let Some(id) = warcinfo_id {
+                record.set_header(WarcHeader::WarcInfoID, id).unwrap();
+            }
+            record.replace_body(body);
+            record
+        };
+
+        let manifest_body: String = self
+            .warc_files
+            .iter()
+            .map(|filename| format!("{}\n", filename))
+            .collect();
+        let manifest = record(RecordType::Metadata, MANIFEST_URI, manifest_body.into());
+        let arguments_body = self
+            .arguments
+            .iter()
+            .map(|argument| format!("\"{}\"", argument))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut arguments = record(RecordType::Resource, ARGUMENTS_URI, arguments_body.into());
+        let mut log = record(RecordType::Resource, LOG_URI, self.log);
+        for resource in [&mut arguments, &mut log] {
+            resource
+                .set_header(WarcHeader::ConcurrentTo, manifest.warc_id())
+                .unwrap();
+        }
+
+        vec![manifest, arguments, log]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WgetMetadata;
+    use crate::header::WarcHeader;
+    use crate::RecordType;
+
+    #[test]
+    fn trailer_records() {
+        let records = WgetMetadata::new()
+            .warc_file("crawl.warc.gz")
+            .argument("wget")
+            .argument("--warc-file=crawl")
+            .log("done\n")
+            .into_records(Some("<urn:test:warcinfo>"));
+
+        let types: Vec<_> = records.iter().map(|r| r.warc_type().clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                RecordType::Metadata,
+                RecordType::Resource,
+                RecordType::Resource
+            ]
+        );
+        assert_eq!(records[0].body(), b"crawl.warc.gz\n");
+        assert_eq!(records[1].body(), b"\"wget\" \"--warc-file=crawl\"");
+        assert_eq!(records[2].body(), b"done\n");
+        assert_eq!(
+            records[2].header(WarcHeader::ConcurrentTo).unwrap(),
+            records[0].warc_id()
+        );
+        assert_eq!(
+            records[1].header(WarcHeader::WarcInfoID).unwrap(),
+            "<urn:test:warcinfo>"
+        );
+    }
+}